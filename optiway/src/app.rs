@@ -3,26 +3,30 @@ use std::{
     f32::consts::PI,
     fmt::Display,
     fs::{ self, File },
-    io::{ BufRead, BufReader, Read, Write },
+    io::Write,
     path::{ Path, PathBuf },
     process::{ Command, Stdio },
     sync::{ Arc, Mutex },
     thread,
+    time::{ SystemTime, UNIX_EPOCH },
 };
 
 use egui::{
     color_picker,
     emath,
     pos2,
+    Align2,
     CentralPanel,
     Color32,
     ColorImage,
     ComboBox,
+    FontId,
     Grid,
     Layout,
     ProgressBar,
     Rect,
     RichText,
+    Sense,
     Slider,
     Stroke,
     TextureHandle,
@@ -31,7 +35,55 @@ use egui::{
 use num_format::{ Locale, ToFormattedString };
 use rfd::FileDialog;
 
-use crate::{ md_icons::material_design_icons, setup_custom_fonts, setup_custom_styles };
+use crate::{
+    building_model::{
+        is_room,
+        BuildingModel,
+        BuildingModelError,
+        Connectivity,
+        EDGE_TYPE_NAMES,
+        GROUPS,
+    },
+    dismissal::{
+        optimize_offsets,
+        DismissalOffsets,
+        OffsetGroup,
+        OffsetSearch,
+        TimingModel,
+        TimingParams,
+        TimingReport,
+    },
+    compact_routes::{ CompactRoutes, BINARY_EXTENSION },
+    congestion::{ CongestionIndex, CongestionLayer, CongestionLayers },
+    consistency::{ check_building, BuildingData, Issue, Severity },
+    destinations::{ DestinationAssignment, DestinationModel, Outing },
+    directions::{ instructions, route_steps, Language },
+    evaluation::{ parse_path_distances, PathDistances },
+    fairness::{ detour_report, DetourLimit, DetourLimits, DetourReport, DetourStatistics },
+    graph::BuildingGraph,
+    md_icons::material_design_icons,
+    monte_carlo::{ simulate, ConfidenceInterval, Deviation, MonteCarloParams, MonteCarloReport },
+    optimization::{
+        checkpoint::{ Checkpoint, CheckpointHistory, CheckpointSummary },
+        optimize_routes,
+        stopping::{ ImprovementWindow, StopReason, StoppingCriteria },
+        OptimizationParams,
+        OptimizationStrategy,
+        RobustObjective,
+        RouteStore,
+    },
+    pareto::{ pareto_optimize, Objective, ParetoArchive, ParetoParams },
+    pinning::{ load_rules, matches_pattern, PinningReport, PinningRule },
+    robustness::{ self, PerturbationParams, RobustnessReport, Scenario },
+    room_swap::{ suggest_room_swaps, RoomSwapReport },
+    route_editor::RouteEditor,
+    route_sheet::{ write_route_sheets, RouteSheets },
+    route_validation::{ validate_routes, RouteIssue },
+    routing::{ routes_from_timetable, Routes, ShortestPaths, TRANSITION_NAMES },
+    setup_custom_fonts,
+    setup_custom_styles,
+    timetable::{ Day, Period, Timetable },
+};
 
 #[derive(Default, Clone, PartialEq, Eq)]
 enum PathDisplay {
@@ -49,6 +101,114 @@ impl Display for PathDisplay {
     }
 }
 
+/// How the floors are drawn on the central canvas.
+#[derive(Default, Clone, Copy, PartialEq, Eq)]
+enum FloorViewMode {
+    /// Isometric projection of every floor.
+    #[default]
+    Projection,
+    /// Top-down plan of the selected floor.
+    Plan,
+}
+
+/// View of the central canvas to fit to the canvas on the next frame.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum CanvasFit {
+    /// The nodes of the selected floor, or of every floor if all floors are selected.
+    Floor,
+    /// The route of the selected student.
+    Route,
+}
+
+/// Tool of the building editor used on the canvas.
+#[derive(Default, Clone, Copy, PartialEq, Eq)]
+enum BuildingTool {
+    /// Click a node to select it and drag it to move it.
+    #[default]
+    Select,
+    AddNode,
+    /// Click two nodes to join them.
+    Connect,
+    /// Click a node or an edge to delete it.
+    Delete,
+}
+
+/// The building model open in the building editor.
+struct BuildingEditorState {
+    model: BuildingModel,
+    /// The model as last loaded or saved.
+    saved: BuildingModel,
+    /// Earlier models, the latest last.
+    history: Vec<BuildingModel>,
+    directory: PathBuf,
+    connectivity: Connectivity,
+    tool: BuildingTool,
+    selected: Option<String>,
+    /// First node of the edge being joined with the connect tool.
+    connect_from: Option<String>,
+    /// Node being moved with the select tool.
+    drag: Option<String>,
+    new_node_name: String,
+    new_node_group: String,
+    new_edge_type: u8,
+    rename: String,
+    connect_to: String,
+}
+
+impl BuildingEditorState {
+    fn new(model: BuildingModel, directory: PathBuf) -> Self {
+        Self {
+            saved: model.clone(),
+            connectivity: model.connectivity(),
+            model,
+            history: vec![],
+            directory,
+            tool: Default::default(),
+            selected: None,
+            connect_from: None,
+            drag: None,
+            new_node_name: String::new(),
+            new_node_group: "A".to_owned(),
+            new_edge_type: 0,
+            rename: String::new(),
+            connect_to: String::new(),
+        }
+    }
+
+    /// Applies an edit to the model, keeping the model before it for undo.
+    fn edit(
+        &mut self,
+        edit: impl FnOnce(&mut BuildingModel) -> Result<(), BuildingModelError>
+    ) -> Result<(), BuildingModelError> {
+        let mut model = self.model.clone();
+        edit(&mut model)?;
+        if model != self.model {
+            self.history.push(std::mem::replace(&mut self.model, model));
+            self.connectivity = self.model.connectivity();
+        }
+        Ok(())
+    }
+
+    fn undo(&mut self) {
+        if let Some(model) = self.history.pop() {
+            self.model = model;
+            self.connectivity = self.model.connectivity();
+            if self.selected.as_ref().is_some_and(|name| self.model.node(name).is_none()) {
+                self.select(None);
+            }
+        }
+    }
+
+    fn is_modified(&self) -> bool {
+        self.model != self.saved
+    }
+
+    fn select(&mut self, name: Option<String>) {
+        self.rename = name.clone().unwrap_or_default();
+        self.selected = name;
+    }
+}
+
 #[derive(Default, Clone, PartialEq, Eq)]
 enum TimetableValidationStatus {
     #[default]
@@ -67,18 +227,19 @@ impl TimetableValidationStatus {
     }
 }
 
-#[derive(Default, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
-struct MainJSONOutputFile {
-    iter: [u64; 5],
-    indices: HashMap<u32, HashMap<usize, u128>>,
-    routes: HashMap<String, HashMap<u32, HashMap<usize, String>>>,
-}
+/// The latest checkpoint, also read by the C++ optimizer.
+const ROUTES_FILE_PATH: &str = "./bin/routes.json";
+const CHECKPOINT_DIRECTORY: &str = "./bin/checkpoints";
 
-#[derive(Default, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
-struct SubJSONOutputFile {
-    iter: u64,
-    indices: HashMap<u32, HashMap<usize, u128>>,
-    routes: HashMap<String, HashMap<u32, HashMap<usize, String>>>,
+/// Saves a checkpoint of `store` to `ROUTES_FILE_PATH` and to the checkpoint history, returning the
+/// summaries of the history.
+fn save_checkpoint(
+    store: &RouteStore,
+    params: OptimizationParams
+) -> std::io::Result<Vec<CheckpointSummary>> {
+    let checkpoint = store.checkpoint(params);
+    checkpoint.save(Path::new(ROUTES_FILE_PATH))?;
+    CheckpointHistory::new(CHECKPOINT_DIRECTORY).save(&checkpoint)
 }
 
 #[derive(Default, Clone, PartialEq, Eq)]
@@ -91,6 +252,83 @@ enum OptimizationStatus {
     Failed(String),
 }
 
+/// Width of the bars of the detour histograms, in distance units of `paths.txt`.
+const DETOUR_BUCKET_WIDTH: u32 = 50;
+/// Number of detour limit violations listed in the detour window.
+const MAX_DETOUR_VIOLATIONS_SHOWN: usize = 100;
+
+/// Number of room swap suggestions listed in the room reassignment window.
+const MAX_ROOM_SWAPS_SHOWN: usize = 100;
+
+/// Asset directory of the repository, which holds the source files of the building model.
+const BUILDING_ASSETS_DIRECTORY: &str = "../assets";
+
+/// Distance on screen within which the route editor picks a node or waypoint, in points.
+const ROUTE_EDITOR_PICK_RADIUS: f32 = 10.0;
+
+/// Width of the floor plan at zoom 1, in units of projection-coords.yaml.
+const PLAN_WIDTH: f32 = 2400.0;
+/// Point of projection-coords.yaml drawn at the centre of the floor plan before panning.
+const PLAN_CENTER: [f32; 2] = [1160.0, 100.0];
+/// Shortest edge on screen, in points, that is labelled with its distance on the floor plan.
+const PLAN_MIN_LABELLED_EDGE: f32 = 40.0;
+/// Space left around what is fitted to the central canvas, in points.
+const CANVAS_FIT_MARGIN: f32 = 40.0;
+const MIN_CANVAS_ZOOM: f32 = 0.5;
+const MAX_CANVAS_ZOOM: f32 = 20.0;
+
+#[derive(Default, Clone, PartialEq, Eq)]
+enum RoomSwapStatus {
+    #[default]
+    ParamInput,
+    Ready,
+    Searching(i32, String),
+    Failed(String),
+    Successful,
+}
+
+#[derive(Default, Clone, PartialEq, Eq)]
+enum DismissalStatus {
+    #[default]
+    ParamInput,
+    Ready,
+    OptimizationReady,
+    Searching(i32, String),
+    Evaluating,
+    Failed(String),
+    Successful,
+}
+
+#[derive(Default, Clone, PartialEq, Eq)]
+enum MonteCarloStatus {
+    #[default]
+    ParamInput,
+    Ready,
+    Simulating(i32, String),
+    Failed(String),
+    Successful,
+}
+
+#[derive(Default, Clone, PartialEq, Eq)]
+enum RobustnessStatus {
+    #[default]
+    ParamInput,
+    Ready,
+    Evaluating(String),
+    Failed(String),
+    Successful,
+}
+
+#[derive(Default, Clone, PartialEq, Eq)]
+enum ParetoStatus {
+    #[default]
+    ParamInput,
+    Ready,
+    Searching(i32, String),
+    Failed(String),
+    Successful,
+}
+
 #[derive(Default)]
 struct TimetableFileInfo {
     filename: String,
@@ -98,7 +336,7 @@ struct TimetableFileInfo {
     student_count: Arc<Mutex<Option<i32>>>,
     session_count: Arc<Mutex<Option<i32>>>,
     validation_status: Arc<Mutex<TimetableValidationStatus>>,
-    timetable: Arc<Mutex<Option<serde_json::Value>>>,
+    timetable: Arc<Mutex<Option<Timetable>>>,
 }
 
 #[derive(Default, Clone, PartialEq, Eq)]
@@ -127,22 +365,21 @@ enum CongestionStatus {
     Ready,
     Generating(i32, String),
     Failed(String),
-    GeneratingPI(i32, String),
     Successful,
 }
 impl CongestionStatus {
     fn is_generating(&self) -> bool {
-        if let CongestionStatus::Generating(_, _) = self {
-            true
-        } else {
-            matches!(self, CongestionStatus::GeneratingPI(_, _))
-        }
+        matches!(self, CongestionStatus::Generating(_, _))
     }
 }
 
-type Routes = HashMap<String, HashMap<u32, HashMap<usize, String>>>;
 type CongestionPoint = HashMap<u32, HashMap<usize, HashMap<String, u32>>>;
 type CongestionPath = HashMap<u32, HashMap<usize, HashMap<(String, String), u32>>>;
+/// Monte Carlo congestion of the shortest routes, and of the optimized routes if there are any.
+type MonteCarloReports = (MonteCarloReport, Option<MonteCarloReport>);
+/// Scenarios, and the robustness of the shortest routes and of the optimized routes if there are
+/// any.
+type RobustnessReports = (Vec<Scenario>, RobustnessReport, Option<RobustnessReport>);
 
 struct CongestionStatistics {
     point_count: HashMap<u32, HashMap<usize, Vec<u32>>>,
@@ -195,13 +432,15 @@ pub struct OptiWayApp {
     active_path_color: Color32,
     inactive_path_color: Color32,
     show_path_window: bool,
+    /// Language of the directions in the path window.
+    path_language: Language,
     show_json_validation: bool,
     timetable_file_info: TimetableFileInfo,
     student_number_search: String,
     path_generation_status: Arc<Mutex<PathGenerationStatus>>,
     show_path_gen_window: bool,
-    student_routes_shortest: Arc<Mutex<Option<Routes>>>,
-    student_routes_optimized: Arc<Mutex<Option<Routes>>>,
+    student_routes_shortest: Arc<Mutex<Option<Arc<CompactRoutes>>>>,
+    student_routes_optimized: Arc<Mutex<Option<Arc<CompactRoutes>>>>,
     show_timetable_window: bool,
     show_congestion_window: bool,
     congestion_status: Arc<Mutex<CongestionStatus>>,
@@ -213,29 +452,119 @@ pub struct OptiWayApp {
     maximum_congestion_opt: Arc<Mutex<u32>>,
     congestion_statistics: Arc<Mutex<CongestionStatistics>>,
     congestion_statistics_opt: Arc<Mutex<CongestionStatistics>>,
+    /// Congestion of the staff and the layer shown, shared with the optimization thread.
+    congestion_layers: Arc<Mutex<CongestionLayers>>,
+    /// File name and timetable of the imported staff timetable.
+    staff_timetable: Option<(String, Timetable)>,
+    staff_timetable_message: Option<String>,
+    /// File name and destinations of arrivals, lunch and dismissals, when imported.
+    destination_model: Option<(String, DestinationModel)>,
+    destination_message: Option<String>,
+    show_destination_window: bool,
     show_congestion: bool,
     congestion_filter: u32,
     show_congestion_path: bool,
     show_congestion_point: bool,
     show_pi_window: bool,
-    #[allow(unused)]
-    shortest_paths_json: HashMap<String, String>,
+    shortest_paths_json: ShortestPaths,
     shortest_paths_content: Arc<Mutex<String>>,
     show_pi_shortest: bool,
     performance_indices_shortest: Arc<Mutex<HashMap<u32, HashMap<usize, u128>>>>,
     performance_indices_optimized: Arc<Mutex<HashMap<u32, HashMap<usize, u128>>>>,
-    path_distances: Arc<Mutex<HashMap<String, HashMap<String, u32>>>>,
+    /// Route steps left out of the performance indices because paths.txt has no such edge.
+    uncounted_steps_shortest: Arc<Mutex<usize>>,
+    uncounted_steps_optimized: Arc<Mutex<usize>>,
+    path_distances: Arc<Mutex<PathDistances>>,
     optimization_status: Arc<Mutex<OptimizationStatus>>,
     show_optimization_window: bool,
     param_batch_size: u32,
     param_save_every: u32,
+    param_thread_count: usize,
+    param_stopping_criteria: StoppingCriteria,
+    param_strategy: OptimizationStrategy,
+    param_robust_objective: RobustObjective,
+    param_seed: u64,
     param_use_shortest_path: bool,
     param_day: u32,
     param_filename: String,
     param_filepath: PathBuf,
+    /// Why the last selected route file was not accepted.
+    route_file_message: Option<String>,
+    show_route_file_window: bool,
+    /// Name and issues of the last route file that was not accepted.
+    route_file_issues: Option<(String, Vec<RouteIssue>)>,
     current_iter: Arc<Mutex<[u64; 5]>>,
     current_period_iter: Arc<Mutex<[u64; 5]>>,
+    optimization_store: Option<Arc<RouteStore>>,
+    optimization_thread: Option<thread::JoinHandle<()>>,
+    checkpoint_summaries: Arc<Mutex<Vec<CheckpointSummary>>>,
     path_display: PathDisplay,
+    show_room_swap_window: bool,
+    room_swap_status: Arc<Mutex<RoomSwapStatus>>,
+    room_swap_report: Arc<Mutex<Option<RoomSwapReport>>>,
+    param_swap_candidates: usize,
+    show_dismissal_window: bool,
+    dismissal_status: Arc<Mutex<DismissalStatus>>,
+    dismissal_offsets: Arc<Mutex<DismissalOffsets>>,
+    /// Time-resolved congestion with simultaneous and staggered release.
+    dismissal_reports: Arc<Mutex<Option<(TimingReport, TimingReport)>>>,
+    param_walking_speed: f32,
+    param_slot_length: f32,
+    param_offset_group: OffsetGroup,
+    param_max_offset: f32,
+    show_monte_carlo_window: bool,
+    monte_carlo_status: Arc<Mutex<MonteCarloStatus>>,
+    monte_carlo_reports: Arc<Mutex<Option<MonteCarloReports>>>,
+    param_monte_carlo: MonteCarloParams,
+    monte_carlo_message: Option<String>,
+    show_robustness_window: bool,
+    robustness_status: Arc<Mutex<RobustnessStatus>>,
+    robustness_reports: Arc<Mutex<Option<RobustnessReports>>>,
+    /// Perturbations of the robustness evaluation and of the robust objectives of the optimizer.
+    param_perturbation: PerturbationParams,
+    building_graph: Arc<Mutex<BuildingGraph>>,
+    show_pareto_window: bool,
+    pareto_status: Arc<Mutex<ParetoStatus>>,
+    pareto_archive: Arc<Mutex<Option<ParetoArchive>>>,
+    selected_pareto_plan: Option<usize>,
+    pareto_x_axis: Objective,
+    pareto_y_axis: Objective,
+    param_penalty_levels: usize,
+    param_max_penalty: f64,
+    param_pareto_passes: usize,
+    param_detour_limits: DetourLimits,
+    show_detour_window: bool,
+    detour_report: Option<DetourReport>,
+    param_pinning_rules: Vec<PinningRule>,
+    /// Name of the file the pinning rules were loaded from.
+    param_pinning_file: String,
+    pinning_message: Option<String>,
+    /// Routes affected by each pinning rule when the optimization last started.
+    pinning_report: Option<PinningReport>,
+    show_pinning_window: bool,
+    show_route_editor_window: bool,
+    show_building_editor_window: bool,
+    building_editor: Option<BuildingEditorState>,
+    building_editor_message: Option<String>,
+    show_consistency_window: bool,
+    show_route_sheet_window: bool,
+    /// Pattern of the student numbers to export route sheets for, as in pinning rules.
+    route_sheet_students: String,
+    route_sheet_optimized: bool,
+    route_sheet_message: Option<String>,
+    /// Issues of the last consistency check, grouped by category.
+    consistency_issues: Option<Vec<Issue>>,
+    consistency_message: Option<String>,
+    route_editor: Option<RouteEditor>,
+    /// Index of the waypoint being dragged on the projection.
+    route_editor_drag: Option<usize>,
+    route_editor_message: Option<String>,
+    floor_view_mode: FloorViewMode,
+    plan_view: CanvasView,
+    projection_view: CanvasView,
+    canvas_fit: Option<CanvasFit>,
+    show_plan_node_labels: bool,
+    show_plan_distance_labels: bool,
 }
 
 impl Default for OptiWayApp {
@@ -257,6 +586,7 @@ impl Default for OptiWayApp {
             active_path_color: Color32::from_rgb(0xec, 0x6f, 0x27),
             inactive_path_color: Color32::from_gray(0x61),
             show_path_window: false,
+            path_language: Default::default(),
             show_json_validation: false,
             timetable_file_info: Default::default(),
             student_number_search: Default::default(),
@@ -319,6 +649,12 @@ impl Default for OptiWayApp {
             congestion_statistics_opt: Default::default(),
             maximum_congestion: Default::default(),
             maximum_congestion_opt: Default::default(),
+            congestion_layers: Default::default(),
+            staff_timetable: None,
+            staff_timetable_message: None,
+            destination_model: None,
+            destination_message: None,
+            show_destination_window: false,
             show_congestion: false,
             congestion_filter: 0,
             show_congestion_path: true,
@@ -347,41 +683,98 @@ impl Default for OptiWayApp {
                 }
                 Arc::new(Mutex::new(performance_indices))
             },
+            uncounted_steps_shortest: Default::default(),
+            uncounted_steps_optimized: Default::default(),
             show_pi_shortest: true,
             path_distances: Arc::new(
-                Mutex::new({
-                    let mut path_distances = HashMap::new();
-                    for line in include_str!("../assets/paths.txt").lines() {
-                        let line = line.to_owned();
-                        let mut line = line.split(' ');
-                        let room1 = line.next().unwrap().to_owned();
-                        let room2 = line.next().unwrap().to_owned();
-                        let distance = line.next().unwrap().parse::<u32>().unwrap();
-                        // insert if not present
-                        if !path_distances.contains_key(&room1) {
-                            path_distances.insert(room1.clone(), HashMap::new());
-                        }
-                        path_distances.get_mut(&room1).unwrap().insert(room2.clone(), distance);
-                        if !path_distances.contains_key(&room2) {
-                            path_distances.insert(room2.clone(), HashMap::new());
-                        }
-                        path_distances.get_mut(&room2).unwrap().insert(room1, distance);
-                    }
-                    path_distances
-                })
+                Mutex::new(parse_path_distances(include_str!("../assets/paths.txt")))
             ),
             optimization_status: Default::default(),
             show_optimization_window: false,
             param_batch_size: 100,
             param_save_every: 2500,
+            param_thread_count: default_thread_count(),
+            param_stopping_criteria: Default::default(),
+            param_strategy: Default::default(),
+            param_robust_objective: Default::default(),
+            param_seed: 0,
             param_use_shortest_path: true,
             param_day: 1,
             param_filename: Default::default(),
             param_filepath: Default::default(),
+            route_file_message: None,
+            show_route_file_window: false,
+            route_file_issues: None,
             shortest_paths_content: Default::default(),
             current_iter: Default::default(),
             current_period_iter: Default::default(),
+            optimization_store: None,
+            optimization_thread: None,
+            checkpoint_summaries: Arc::new(
+                Mutex::new(CheckpointHistory::new(CHECKPOINT_DIRECTORY).summaries())
+            ),
             path_display: Default::default(),
+            show_room_swap_window: false,
+            room_swap_status: Default::default(),
+            room_swap_report: Default::default(),
+            param_swap_candidates: 6,
+            show_dismissal_window: false,
+            dismissal_status: Default::default(),
+            dismissal_offsets: Default::default(),
+            dismissal_reports: Default::default(),
+            param_walking_speed: 1200.0,
+            param_slot_length: 0.5,
+            param_offset_group: Default::default(),
+            param_max_offset: 3.0,
+            show_monte_carlo_window: false,
+            monte_carlo_status: Default::default(),
+            monte_carlo_reports: Default::default(),
+            param_monte_carlo: Default::default(),
+            monte_carlo_message: None,
+            show_robustness_window: false,
+            robustness_status: Default::default(),
+            robustness_reports: Default::default(),
+            param_perturbation: Default::default(),
+            building_graph: Arc::new(
+                Mutex::new(BuildingGraph::parse(include_str!("../assets/paths.txt")))
+            ),
+            show_pareto_window: false,
+            pareto_status: Default::default(),
+            pareto_archive: Default::default(),
+            selected_pareto_plan: None,
+            pareto_x_axis: Objective::TotalDistance,
+            pareto_y_axis: Objective::PeakLoad,
+            param_penalty_levels: 8,
+            param_max_penalty: 2.0,
+            param_pareto_passes: 2,
+            param_detour_limits: Default::default(),
+            show_detour_window: false,
+            detour_report: None,
+            param_pinning_rules: Default::default(),
+            param_pinning_file: Default::default(),
+            pinning_message: None,
+            pinning_report: None,
+            show_pinning_window: false,
+            show_route_editor_window: false,
+            show_building_editor_window: false,
+            building_editor: None,
+            building_editor_message: None,
+            show_consistency_window: false,
+            show_route_sheet_window: false,
+            route_sheet_students: "*".to_owned(),
+            route_sheet_optimized: true,
+            route_sheet_message: None,
+            consistency_issues: None,
+            consistency_message: None,
+            route_editor: None,
+            route_editor_drag: None,
+            route_editor_message: None,
+            floor_view_mode: Default::default(),
+            plan_view: Default::default(),
+            projection_view: Default::default(),
+            canvas_fit: None,
+            show_plan_node_labels: true,
+            show_plan_distance_labels: true,
         }
     }
 }
@@ -447,12 +840,50 @@ impl OptiWayApp {
                     let path_generation_status_arc = self.path_generation_status.clone();
                     let student_paths_arc = self.student_routes_shortest.clone();
                     let shortest_paths_content_arc = self.shortest_paths_content.clone();
+                    // the routes to and from the destinations replace those to and from `G`
+                    let destinations = self.destination_model
+                        .as_ref()
+                        .zip(self.timetable_file_info.timetable.lock().unwrap().as_ref())
+                        .map(|((_, model), timetable)| {
+                            (model.assign(timetable), timetable.clone())
+                        });
+                    let shortest_paths = destinations
+                        .as_ref()
+                        .map(|_| self.shortest_paths_json.clone());
                     thread::spawn(move || {
                         *shortest_paths_content_arc.lock().unwrap() = run_floyd_algorithm_cpp(
                             filepath,
-                            path_generation_status_arc,
-                            student_paths_arc
+                            path_generation_status_arc.clone(),
+                            student_paths_arc.clone()
                         );
+                        let Some(((assignment, timetable), shortest_paths)) = destinations.zip(
+                            shortest_paths
+                        ) else {
+                            return;
+                        };
+                        let mut status = path_generation_status_arc.lock().unwrap();
+                        if *status != PathGenerationStatus::Successful {
+                            return;
+                        }
+                        *status = PathGenerationStatus::LoadingJSON;
+                        drop(status);
+                        let mut student_paths = student_paths_arc.lock().unwrap();
+                        if let Some(routes) = student_paths.as_ref() {
+                            let mut routes = routes.to_routes();
+                            assignment.route(&mut routes, &timetable, &shortest_paths);
+                            match CompactRoutes::from_routes(&routes) {
+                                Ok(routes) => {
+                                    *student_paths = Some(Arc::new(routes));
+                                }
+                                Err(error) => {
+                                    *path_generation_status_arc.lock().unwrap() =
+                                        PathGenerationStatus::Failed(error.to_string());
+                                    return;
+                                }
+                            }
+                        }
+                        *path_generation_status_arc.lock().unwrap() =
+                            PathGenerationStatus::Successful;
                     });
                     // let shortest_paths_json = self.shortest_paths_json.clone();
                     // thread::spawn(move || run_floyd_algorithm_rust(filepath, shortest_paths_json, path_generation_status_arc, student_paths_arc));
@@ -512,6 +943,205 @@ impl OptiWayApp {
         });
     }
 
+    /// Uses a checkpoint of the history as the route file and restores its parameters.
+    fn select_checkpoint(&mut self, summary: &CheckpointSummary) {
+        let history = CheckpointHistory::new(CHECKPOINT_DIRECTORY);
+        if let Ok(checkpoint) = history.load(summary) {
+            if let Some(params) = checkpoint.params {
+                self.param_batch_size = params.batch_size;
+                self.param_thread_count = params.thread_count;
+                self.param_detour_limits = params.detour_limits;
+                self.param_stopping_criteria = params.stopping;
+                self.param_strategy = params.strategy;
+                self.param_robust_objective = params.robust_objective;
+                self.param_perturbation = params.perturbation;
+                self.param_seed = params.seed;
+            }
+            self.param_pinning_rules = checkpoint.pinning_rules;
+            self.param_pinning_file = checkpoint_label(summary);
+            self.pinning_report = None;
+        }
+        self.param_use_shortest_path = false;
+        self.param_filepath = Path::new(CHECKPOINT_DIRECTORY).join(&summary.file_name);
+        self.param_filename = checkpoint_label(summary);
+    }
+
+    /// Reads a route file and checks its routes against the building graph and the imported
+    /// timetable. If the file cannot be used, shows its issues and returns why.
+    fn check_route_file(&mut self, file: &Path, file_name: &str) -> Result<Checkpoint, String> {
+        let checkpoint = Checkpoint::load(file).map_err(|error|
+            format!("Failed to load {}: {}", file_name, error)
+        )?;
+        let timetable = self.timetable_file_info.timetable.lock().unwrap();
+        let destinations = timetable
+            .as_ref()
+            .map_or_else(Default::default, |timetable| self.destination_assignment(timetable));
+        let issues = validate_routes(
+            &checkpoint.routes,
+            &self.building_graph.lock().unwrap(),
+            timetable.as_ref(),
+            &destinations
+        );
+        drop(timetable);
+        if issues.is_empty() {
+            self.route_file_issues = None;
+            return Ok(checkpoint);
+        }
+        let message = format!(
+            "{} does not match the building or the timetable ({} issues).",
+            file_name,
+            issues.len().to_formatted_string(&Locale::fr)
+        );
+        self.route_file_issues = Some((file_name.to_owned(), issues));
+        self.show_route_file_window = true;
+        Err(message)
+    }
+
+    fn show_route_sheet_window(&mut self, ctx: &egui::Context) {
+        let mut show_route_sheet_window = self.show_route_sheet_window;
+        let mut export = false;
+        let has_optimized_routes = self.student_routes_optimized.lock().unwrap().is_some();
+        Window::new("Route Sheets")
+            .open(&mut show_route_sheet_window)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("Students");
+                    ui.text_edit_singleline(&mut self.route_sheet_students).on_hover_text(
+                        "Student numbers, where * stands for any characters and ? for one character, such as 21* for a grade."
+                    );
+                });
+                ui.horizontal(|ui| {
+                    ui.add_enabled_ui(has_optimized_routes, |ui| {
+                        ui.radio_value(&mut self.route_sheet_optimized, true, "Optimized routes");
+                    });
+                    ui.radio_value(&mut self.route_sheet_optimized, false, "Shortest routes");
+                });
+                export = ui
+                    .button("Export…")
+                    .on_hover_text(
+                        "Write one HTML page per student and an index.html into a folder."
+                    )
+                    .clicked();
+                if let Some(message) = &self.route_sheet_message {
+                    ui.label(message);
+                }
+            });
+
+        if export {
+            if let Some(directory) = FileDialog::new().pick_folder() {
+                let optimized = self.route_sheet_optimized && has_optimized_routes;
+                let routes = if optimized {
+                    self.student_routes_optimized.lock().unwrap().clone()
+                } else {
+                    self.student_routes_shortest.lock().unwrap().clone()
+                };
+                let timetable = self.timetable_file_info.timetable.lock().unwrap();
+                self.route_sheet_message = Some(match (routes, timetable.as_ref()) {
+                    (Some(routes), Some(timetable)) => {
+                        let graph = self.building_graph.lock().unwrap();
+                        let sheets = RouteSheets::new(&graph, &self.projection_coords);
+                        let result = write_route_sheets(
+                            &directory,
+                            &sheets,
+                            timetable,
+                            &routes,
+                            &self.destination_assignment(timetable),
+                            if optimized { "Optimized routes" } else { "Shortest routes" },
+                            |student_number| {
+                                matches_pattern(&self.route_sheet_students, student_number)
+                            }
+                        );
+                        match result {
+                            Ok(0) => "No student matches.".to_owned(),
+                            Ok(count) =>
+                                format!(
+                                    "Wrote {} route sheets to {}.",
+                                    count.to_formatted_string(&Locale::fr),
+                                    directory.display()
+                                ),
+                            Err(error) => format!("Failed to write the route sheets: {}", error),
+                        }
+                    }
+                    _ => "Calculate routes first.".to_owned(),
+                });
+            }
+        }
+        self.show_route_sheet_window = show_route_sheet_window;
+    }
+
+    fn show_route_file_window(&mut self, ctx: &egui::Context) {
+        let mut show_route_file_window = self.show_route_file_window;
+        Window::new("Route File Issues")
+            .open(&mut show_route_file_window)
+            .show(ctx, |ui| {
+                let Some((file_name, issues)) = &self.route_file_issues else {
+                    ui.label("No issues.");
+                    return;
+                };
+                ui.label(
+                    format!(
+                        "{} was not used: {} routes or students do not match the building graph or the imported timetable.",
+                        file_name,
+                        issues.len().to_formatted_string(&Locale::fr)
+                    )
+                );
+                ui.separator();
+                let row_height = ui.text_style_height(&egui::TextStyle::Body);
+                egui::ScrollArea
+                    ::vertical()
+                    .auto_shrink([false, true])
+                    .show_rows(ui, row_height, issues.len(), |ui, rows| {
+                        for issue in &issues[rows] {
+                            ui.label(issue.to_string());
+                        }
+                    });
+            });
+        self.show_route_file_window = show_route_file_window;
+    }
+
+    /// Whether the thread of a paused run is still finishing its batch and saving a checkpoint,
+    /// which would overwrite the optimized routes.
+    fn is_optimization_stopping(&self) -> bool {
+        self.optimization_thread
+            .as_ref()
+            .is_some_and(|optimization_thread| !optimization_thread.is_finished())
+    }
+
+    /// Replaces the routes of a paused optimization with those of a checkpoint of the history.
+    /// The run must have stopped.
+    fn roll_back_to_checkpoint(&mut self, summary: &CheckpointSummary) {
+        if let Some(optimization_thread) = self.optimization_thread.take() {
+            optimization_thread.join().ok();
+        }
+        let checkpoint = match CheckpointHistory::new(CHECKPOINT_DIRECTORY).load(summary) {
+            Ok(checkpoint) => checkpoint,
+            Err(error) => {
+                *self.optimization_status.lock().unwrap() = OptimizationStatus::Failed(
+                    format!("Failed to load checkpoint: {}", error)
+                );
+                return;
+            }
+        };
+        let store = RouteStore::from_checkpoint(checkpoint);
+        let routes = match store_routes(&store) {
+            Ok(routes) => routes,
+            Err(error) => {
+                *self.optimization_status.lock().unwrap() = OptimizationStatus::Failed(
+                    format!("Failed to load checkpoint: {}", error)
+                );
+                return;
+            }
+        };
+        *self.current_iter.lock().unwrap() = store.iterations();
+        *self.performance_indices_optimized.lock().unwrap() = store.performance_indices();
+        *self.uncounted_steps_optimized.lock().unwrap() = CongestionIndex::from_routes(
+            &self.building_graph.lock().unwrap(),
+            &routes
+        ).uncounted_steps();
+        *self.student_routes_optimized.lock().unwrap() = Some(routes);
+        self.optimization_store = Some(Arc::new(store));
+    }
+
     fn show_optimization_window(
         &mut self,
         ctx: &egui::Context,
@@ -531,6 +1161,25 @@ impl OptiWayApp {
                             .step_by(100.0)
                             .text("Iterations per save")
                     );
+                    ui.add(
+                        Slider::new(&mut self.param_thread_count, 1..=default_thread_count() * 2)
+                            .text("Threads")
+                    );
+                    ComboBox::from_label("Strategy")
+                        .selected_text(self.param_strategy.to_string())
+                        .show_ui(ui, |ui| {
+                            for strategy in OptimizationStrategy::ALL {
+                                ui.selectable_value(
+                                    &mut self.param_strategy,
+                                    strategy,
+                                    strategy.to_string()
+                                );
+                            }
+                        });
+                    ui.horizontal(|ui| {
+                        ui.add(egui::DragValue::new(&mut self.param_seed).speed(1));
+                        ui.label("Random seed");
+                    });
                     // ComboBox::from_label("Day of Week")
                     //     .selected_text(convert_day_of_week(self.param_day))
                     //     .show_ui(ui, |ui| {
@@ -550,13 +1199,22 @@ impl OptiWayApp {
                                     .add_filter("JSON", &["json"])
                                     .pick_file();
                                 if let Some(file) = file {
-                                    self.param_filename = file
+                                    let file_name = file
                                         .file_name()
                                         .unwrap()
                                         .to_str()
                                         .unwrap()
                                         .to_owned();
-                                    self.param_filepath = file;
+                                    match self.check_route_file(&file, &file_name) {
+                                        Ok(_) => {
+                                            self.param_filename = file_name;
+                                            self.param_filepath = file;
+                                            self.route_file_message = None;
+                                        }
+                                        Err(message) => {
+                                            self.route_file_message = Some(message);
+                                        }
+                                    }
                                 }
                             }
                         });
@@ -570,207 +1228,414 @@ impl OptiWayApp {
                             );
                         }
                     });
+                    if let Some(message) = &self.route_file_message {
+                        if
+                            ui
+                                .link(
+                                    RichText::new(message).color(
+                                        Color32::from_rgb(0xe4, 0x37, 0x48)
+                                    )
+                                )
+                                .on_hover_text("Show the issues of the route file.")
+                                .clicked()
+                        {
+                            self.show_route_file_window = true;
+                        }
+                    }
+                    let checkpoint_summaries = self.checkpoint_summaries.lock().unwrap().clone();
+                    ui.add_enabled_ui(!checkpoint_summaries.is_empty(), |ui| {
+                        ComboBox::from_label("Resume from checkpoint")
+                            .selected_text("Select checkpoint")
+                            .show_ui(ui, |ui| {
+                                for summary in checkpoint_summaries.iter().rev() {
+                                    if
+                                        ui
+                                            .selectable_label(false, checkpoint_label(summary))
+                                            .clicked()
+                                    {
+                                        self.select_checkpoint(summary);
+                                    }
+                                }
+                            })
+                            .response.on_disabled_hover_text("No checkpoints saved yet.");
+                    });
+                    ui.separator();
+                    ui.heading("Fairness");
+                    detour_limits_ui(ui, &mut self.param_detour_limits);
+                    ui.separator();
+                    ui.heading("Pinning rules");
+                    ui.horizontal(|ui| {
+                        if ui.button("Load rules").clicked() {
+                            self.load_pinning_rules();
+                        }
+                        let has_rules = !self.param_pinning_rules.is_empty();
+                        if ui.add_enabled(has_rules, egui::Button::new("Show rules")).clicked() {
+                            self.show_pinning_window = true;
+                        }
+                        if ui.add_enabled(has_rules, egui::Button::new("Clear")).clicked() {
+                            self.param_pinning_rules.clear();
+                            self.param_pinning_file.clear();
+                            self.pinning_report = None;
+                        }
+                    });
+                    ui.label(
+                        if self.param_pinning_rules.is_empty() {
+                            "No rules.".to_owned()
+                        } else {
+                            format!(
+                                "{} rules from {}",
+                                self.param_pinning_rules.len(),
+                                self.param_pinning_file
+                            )
+                        }
+                    );
+                    if let Some(message) = &self.pinning_message {
+                        ui.label(RichText::new(message).color(Color32::from_rgb(0xe4, 0x37, 0x48)));
+                    }
+                    ui.separator();
+                    ui.heading("Robustness");
+                    ComboBox::from_label("Objective")
+                        .selected_text(self.param_robust_objective.to_string())
+                        .show_ui(ui, |ui| {
+                            for objective in RobustObjective::ALL {
+                                ui.selectable_value(
+                                    &mut self.param_robust_objective,
+                                    objective,
+                                    objective.to_string()
+                                );
+                            }
+                        })
+                        .response.on_hover_text(
+                            "Optimize the performance index of the timetable, or its mean or worst value over randomly perturbed timetables."
+                        );
+                    let is_robust = self.param_robust_objective != RobustObjective::Nominal;
+                    ui.add_enabled_ui(is_robust, |ui| {
+                        perturbation_ui(ui, &mut self.param_perturbation);
+                    });
+                    ui.separator();
+                    ui.heading("Stopping criteria");
+                    stopping_criteria_ui(ui, &mut self.param_stopping_criteria);
                     ui.separator();
                     ui.with_layout(Layout::top_down_justified(egui::Align::Center), |ui| {
                         if ui.button("Reset parameters").clicked() {
                             self.param_batch_size = 100;
+                            self.param_stopping_criteria = Default::default();
                             self.param_save_every = 2500;
+                            self.param_thread_count = default_thread_count();
+                            self.param_strategy = Default::default();
+                            self.param_robust_objective = Default::default();
+                            self.param_perturbation = Default::default();
+                            self.param_seed = 0;
                             self.param_use_shortest_path = true;
                             self.param_day = 1;
                             self.param_filename = Default::default();
                             self.param_filepath = Default::default();
+                            self.param_detour_limits = Default::default();
                         }
-                        if ui.button("Start").clicked() {
+                        let is_stopping = self.is_optimization_stopping();
+                        if is_stopping {
+                            ctx.request_repaint();
+                        }
+                        if
+                            ui
+                                .add_enabled(!is_stopping, egui::Button::new("Start"))
+                                .on_disabled_hover_text("The previous run is still stopping.")
+                                .clicked()
+                        {
                             self.show_pi_window = true;
                             self.show_pi_shortest = false;
+                            self.optimization_store = None;
                             *self.optimization_status.lock().unwrap() = OptimizationStatus::Ready;
                         }
                     });
                 }
                 OptimizationStatus::Ready => {
+                    // a paused run stops within one batch and saves a checkpoint, which must not
+                    // block the frame
+                    if self.is_optimization_stopping() {
+                        ui.with_layout(Layout::top_down_justified(egui::Align::Center), |ui| {
+                            ui.label(
+                                RichText::new(material_design_icons::MDI_COG_PAUSE).size(32.0)
+                            );
+                            ui.label("Waiting for the previous run to stop");
+                            ui.spinner();
+                        });
+                        ctx.request_repaint();
+                        return;
+                    }
+                    if let Some(optimization_thread) = self.optimization_thread.take() {
+                        optimization_thread.join().ok();
+                    }
                     *self.optimization_status.lock().unwrap() = OptimizationStatus::Calculating;
-                    let param_use_shortest_path = self.param_use_shortest_path;
-                    let param_batch_size = self.param_batch_size;
-                    let param_save_every = self.param_save_every;
-                    for day in 1..=5 {
-                        let param_day = day;
-                        let mut param_filepath = self.param_filepath.clone();
-                        let optimization_status_arc = self.optimization_status.clone();
-                        let shortest_paths_content_arc = self.shortest_paths_content.clone();
-                        let current_iter_arc = self.current_iter.clone();
-                        let performance_indices_shortest_json = serde_json
-                            ::to_string(&*self.performance_indices_shortest.lock().unwrap())
-                            .unwrap();
-                        let performance_indices_optimized_arc =
-                            self.performance_indices_optimized.clone();
-                        let performance_indices_shortest_arc =
-                            self.performance_indices_shortest.clone();
-                        let current_period_iter_arc = self.current_period_iter.clone();
-                        if param_use_shortest_path {
-                            self.param_filepath = fs::canonicalize("./bin/routes.json").unwrap();
-                            self.param_filename = "routes.json".to_owned();
-                            self.param_use_shortest_path = false;
-                        } else {
-                            let mut file = File::open(&param_filepath).unwrap();
-                            let mut content = String::new();
-                            file.read_to_string(&mut content).unwrap();
-                            let json: MainJSONOutputFile = serde_json::from_str(&content).unwrap();
-                            for day in 1..=5 {
-                                *self.current_iter
+                    // the scenarios are sampled on the optimization thread
+                    let robust_inputs = if self.param_robust_objective == RobustObjective::Nominal {
+                        None
+                    } else {
+                        let timetable = self.timetable_file_info.timetable.lock().unwrap().clone();
+                        let Some(timetable) = timetable else {
+                            *self.optimization_status.lock().unwrap() = OptimizationStatus::Failed(
+                                "Import a timetable to optimize against perturbations.".to_owned()
+                            );
+                            return;
+                        };
+                        let destinations = self.destination_assignment(&timetable);
+                        Some((timetable, destinations))
+                    };
+                    let store = match self.optimization_store.clone() {
+                        Some(store) => store,
+                        None => {
+                            let store = if self.param_use_shortest_path {
+                                let Some(student_routes) = self.student_routes_shortest
                                     .lock()
                                     .unwrap()
-                                    .get_mut((day - 1) as usize)
-                                    .unwrap() = *json.iter.get((day - 1) as usize).unwrap();
-                            }
-                            *self.performance_indices_optimized.lock().unwrap() = json.indices;
-                        }
-                        thread::spawn(move || {
-                            if param_use_shortest_path {
-                                let content =
-                                    "{\"iter\":[0,0,0,0,0],\"indices\":".to_owned() +
-                                    &performance_indices_shortest_json +
-                                    ",\"routes\":" +
-                                    &shortest_paths_content_arc.lock().unwrap().to_owned() +
-                                    "}";
-                                *performance_indices_optimized_arc.lock().unwrap() =
-                                    performance_indices_shortest_arc.lock().unwrap().clone();
-                                let mut file = File::create("./bin/routes.json").unwrap();
-                                file.write_all(content.as_bytes()).unwrap();
-                                param_filepath = fs::canonicalize("./bin/routes.json").unwrap();
-                            }
-                            let bin_dir = fs::canonicalize("./bin").unwrap();
-                            if
-                                let Ok(mut opt_command) = Command::new("./optimization.out")
-                                    .current_dir(bin_dir)
-                                    .stdout(Stdio::piped())
-                                    .stdin(Stdio::piped())
-                                    .args([
-                                        "-f",
-                                        param_filepath.to_str().unwrap(),
-                                        "-b",
-                                        &param_batch_size.to_string(),
-                                        "-s",
-                                        &(param_save_every / 5).to_string(),
-                                        "-d",
-                                        &param_day.to_string(),
-                                    ])
-                                    .spawn()
-                            {
-                                if let Some(stdout) = opt_command.stdout.take() {
-                                    let reader = BufReader::new(stdout);
-                                    reader
-                                        .lines()
-                                        .map_while(Result::ok)
-                                        .for_each(|line| {
-                                            let report: Vec<&str> = line.split(' ').collect();
-                                            if report[0] != "1" {
-                                                (*current_iter_arc.lock().unwrap())[
-                                                    (param_day - 1) as usize
-                                                ] = report[1].parse::<u64>().unwrap();
-                                                if
-                                                    optimization_status_arc
-                                                        .lock()
-                                                        .unwrap()
-                                                        .clone() == OptimizationStatus::AbortSignal
-                                                {
-                                                    opt_command
-                                                        .kill()
-                                                        .expect(
-                                                            "Failed to interrupt optimization algorithm"
-                                                        );
-                                                }
-                                                (*current_period_iter_arc.lock().unwrap())[
-                                                    (param_day - 1) as usize
-                                                ] = report[3].parse::<u64>().unwrap();
-                                                *performance_indices_optimized_arc
-                                                    .lock()
-                                                    .unwrap()
-                                                    .get_mut(&param_day)
-                                                    .unwrap()
-                                                    .get_mut(
-                                                        &(
-                                                            report[3]
-                                                                .parse::<u64>()
-                                                                .unwrap() as usize
-                                                        )
-                                                    )
-                                                    .unwrap() = report[4].parse::<u128>().unwrap();
-                                            }
-                                        });
-                                } else {
-                                    *optimization_status_arc.lock().unwrap() =
+                                    .clone() else {
+                                    *self.optimization_status.lock().unwrap() =
                                         OptimizationStatus::Failed(
-                                            "Optimization algorithm terminated unexpectedly".to_owned()
+                                            "No path data available".to_owned()
+                                        );
+                                    return;
+                                };
+                                let store = RouteStore::new(student_routes.to_routes(), [0; 5]);
+                                // routes edited by hand survive a new run from the shortest routes
+                                if
+                                    let Some(optimized) = self.student_routes_optimized
+                                        .lock()
+                                        .unwrap()
+                                        .clone()
+                                {
+                                    let graph = self.building_graph.lock().unwrap();
+                                    for (student_number, day, transition) in optimized
+                                        .edited_routes() {
+                                        let Some(index) = optimized.student_index(
+                                            &student_number
+                                        ) else {
+                                            continue;
+                                        };
+                                        store.set_route(
+                                            &graph,
+                                            &student_number,
+                                            day,
+                                            transition,
+                                            optimized.route_string(index, day, transition)
                                         );
+                                    }
                                 }
+                                store
                             } else {
-                                *optimization_status_arc.lock().unwrap() =
-                                    OptimizationStatus::Failed(
-                                        "Failed to start optimization algorithm".to_owned()
-                                    );
-                            }
-                        });
-                    }
-                }
-                OptimizationStatus::Calculating => {
-                    ui.with_layout(Layout::top_down_justified(egui::Align::Center), |ui| {
-                        ui.label(RichText::new(material_design_icons::MDI_COG).size(32.0));
-                        ui.label("Optimizing paths");
-                        ui.label(
-                            format!(
-                                "Total Iteration: {}",
-                                self.current_iter
-                                    .lock()
-                                    .unwrap()
-                                    .iter()
-                                    .sum::<u64>()
-                                    .to_formatted_string(&Locale::fr)
+                                let file = self.param_filepath.clone();
+                                let file_name = self.param_filename.clone();
+                                match self.check_route_file(&file, &file_name) {
+                                    Ok(checkpoint) => {
+                                        if self.param_pinning_rules.is_empty() {
+                                            self.param_pinning_rules =
+                                                checkpoint.pinning_rules.clone();
+                                            self.param_pinning_file = self.param_filename.clone();
+                                        }
+                                        RouteStore::from_checkpoint(checkpoint)
+                                    }
+                                    Err(message) => {
+                                        *self.optimization_status.lock().unwrap() =
+                                            OptimizationStatus::Failed(message);
+                                        return;
+                                    }
+                                }
+                            };
+                            self.param_filepath = PathBuf::from(ROUTES_FILE_PATH);
+                            self.param_filename = "routes.json".to_owned();
+                            self.param_use_shortest_path = false;
+                            let store = Arc::new(store);
+                            self.optimization_store = Some(store.clone());
+                            store
+                        }
+                    };
+                    if !self.param_pinning_rules.is_empty() || !store.pinning_rules().is_empty() {
+                        self.pinning_report = Some(
+                            store.apply_rules(
+                                &self.building_graph.lock().unwrap(),
+                                &self.shortest_paths_json,
+                                self.param_pinning_rules.clone()
+                            )
+                        );
+                        self.show_pinning_window = !self.param_pinning_rules.is_empty();
+                    }
+                    let params = OptimizationParams {
+                        batch_size: self.param_batch_size,
+                        thread_count: self.param_thread_count,
+                        detour_limits: self.param_detour_limits,
+                        seed: self.param_seed,
+                        stopping: self.param_stopping_criteria,
+                        strategy: self.param_strategy,
+                        robust_objective: self.param_robust_objective,
+                        perturbation: self.param_perturbation,
+                    };
+                    let param_save_every = self.param_save_every as u64;
+                    let shortest_paths = self.shortest_paths_json.clone();
+                    let building_graph = self.building_graph.lock().unwrap().clone();
+                    let optimization_status_arc = self.optimization_status.clone();
+                    let current_iter_arc = self.current_iter.clone();
+                    let current_period_iter_arc = self.current_period_iter.clone();
+                    let performance_indices_optimized_arc =
+                        self.performance_indices_optimized.clone();
+                    let uncounted_steps_optimized_arc = self.uncounted_steps_optimized.clone();
+                    let student_routes_optimized_arc = self.student_routes_optimized.clone();
+                    let checkpoint_summaries_arc = self.checkpoint_summaries.clone();
+                    let congestion_point_data_opt_arc = self.congestion_point_data_opt.clone();
+                    let congestion_path_data_opt_arc = self.congestion_path_data_opt.clone();
+                    let congestion_statistics_opt_arc = self.congestion_statistics_opt.clone();
+                    let max_congestion_opt_arc = self.maximum_congestion_opt.clone();
+                    let congestion_layers_arc = self.congestion_layers.clone();
+                    let mut rooms: Vec<String> = self.projection_coords.keys().cloned().collect();
+                    rooms.push("G".to_owned());
+                    self.optimization_thread = Some(
+                        thread::spawn(move || {
+                            let mut saved_iterations = store.iterations().iter().sum::<u64>();
+                            if let Some((timetable, destinations)) = robust_inputs {
+                                store.set_scenarios(
+                                    robustness::scenarios(
+                                        &timetable,
+                                        &destinations,
+                                        &shortest_paths,
+                                        params.perturbation
+                                    )
+                                );
+                            }
+                            // the congestion of the optimized routes follows the run from its start
+                            match store_routes(&store) {
+                                Ok(routes) => {
+                                    *student_routes_optimized_arc.lock().unwrap() = Some(routes);
+                                }
+                                Err(error) => {
+                                    *optimization_status_arc.lock().unwrap() =
+                                        OptimizationStatus::Failed(error.to_string());
+                                    return;
+                                }
+                            }
+                            optimize_routes(
+                                &store,
+                                &shortest_paths,
+                                &building_graph,
+                                params,
+                                |store| {
+                                    let iterations = store.iterations();
+                                    *current_iter_arc.lock().unwrap() = iterations;
+                                    *current_period_iter_arc.lock().unwrap() = store
+                                        .latest_transitions()
+                                        .map(|transition| transition as u64);
+                                    *performance_indices_optimized_arc.lock().unwrap() =
+                                        store.performance_indices();
+                                    let layers = congestion_layers_arc.lock().unwrap().clone();
+                                    store.with_congestion(|congestion| {
+                                        *uncounted_steps_optimized_arc.lock().unwrap() =
+                                            congestion.uncounted_steps();
+                                        let transitions = congestion.take_changed();
+                                        let congestion = layers.apply(congestion);
+                                        copy_congestion(
+                                            &congestion,
+                                            &building_graph,
+                                            &rooms,
+                                            &transitions,
+                                            &mut congestion_point_data_opt_arc.lock().unwrap(),
+                                            &mut congestion_path_data_opt_arc.lock().unwrap(),
+                                            &mut congestion_statistics_opt_arc.lock().unwrap()
+                                        );
+                                        *max_congestion_opt_arc.lock().unwrap() =
+                                            congestion.max_node_load();
+                                    });
+                                    if
+                                        iterations.iter().sum::<u64>() >=
+                                        saved_iterations + param_save_every
+                                    {
+                                        saved_iterations = iterations.iter().sum();
+                                        match save_checkpoint(store, params) {
+                                            Ok(summaries) => {
+                                                *checkpoint_summaries_arc.lock().unwrap() =
+                                                    summaries;
+                                            }
+                                            Err(error) => {
+                                                *optimization_status_arc.lock().unwrap() =
+                                                    OptimizationStatus::Failed(
+                                                        format!("Failed to save checkpoint: {}", error)
+                                                    );
+                                            }
+                                        }
+                                    }
+                                    *optimization_status_arc.lock().unwrap() ==
+                                        OptimizationStatus::Calculating
+                                }
+                            );
+                            let routes = store_routes(&store);
+                            let saved = save_checkpoint(&store, params);
+                            let mut optimization_status = optimization_status_arc.lock().unwrap();
+                            match routes {
+                                Ok(routes) => {
+                                    *student_routes_optimized_arc.lock().unwrap() = Some(routes);
+                                }
+                                Err(error) => {
+                                    *optimization_status = OptimizationStatus::Failed(
+                                        error.to_string()
+                                    );
+                                }
+                            }
+                            if let Err(error) = &saved {
+                                *optimization_status = OptimizationStatus::Failed(
+                                    format!("Failed to save checkpoint: {}", error)
+                                );
+                            } else if *optimization_status == OptimizationStatus::Calculating {
+                                // a stopping criterion was met or every transition has converged
+                                *optimization_status = OptimizationStatus::AbortSignal;
+                            }
+                            if let Ok(summaries) = saved {
+                                *checkpoint_summaries_arc.lock().unwrap() = summaries;
+                            }
+                        })
+                    );
+                }
+                OptimizationStatus::Calculating => {
+                    ui.with_layout(Layout::top_down_justified(egui::Align::Center), |ui| {
+                        ui.label(RichText::new(material_design_icons::MDI_COG).size(32.0));
+                        ui.label("Optimizing paths");
+                        ui.label(
+                            format!(
+                                "Total Iteration: {}",
+                                self.current_iter
+                                    .lock()
+                                    .unwrap()
+                                    .iter()
+                                    .sum::<u64>()
+                                    .to_formatted_string(&Locale::fr)
                             )
                         );
                         ui.spinner();
+                        if
+                            ui
+                                .button("Show current routes")
+                                .on_hover_text(
+                                    "Use the routes optimized so far without pausing."
+                                )
+                                .clicked()
+                        {
+                            let routes = self.optimization_store.as_deref().map(store_routes);
+                            if let Some(routes) = routes {
+                                match routes {
+                                    Ok(routes) => {
+                                        *self.student_routes_optimized.lock().unwrap() = Some(
+                                            routes
+                                        );
+                                    }
+                                    Err(error) => {
+                                        *self.optimization_status.lock().unwrap() =
+                                            OptimizationStatus::Failed(error.to_string());
+                                        return;
+                                    }
+                                }
+                                self.path_display = PathDisplay::Optimized;
+                                self.show_congestion_window = true;
+                                *self.congestion_status.lock().unwrap() = CongestionStatus::Ready;
+                            }
+                        }
                         if ui.button("Pause").clicked() {
                             *self.optimization_status.lock().unwrap() =
                                 OptimizationStatus::AbortSignal;
-                            // read from routes.json and load the json
-                            let mut file = File::open("./bin/routes.json").unwrap();
-                            let mut content = String::new();
-                            file.read_to_string(&mut content).unwrap();
-                            let mut json: MainJSONOutputFile = serde_json
-                                ::from_str(&content)
-                                .unwrap();
-                            let mut sub_jsons: [SubJSONOutputFile; 5] = Default::default();
-                            for i in 1..=5 {
-                                let mut file = File::open(
-                                    format!("./bin/routes.json_{}.json", i)
-                                ).unwrap();
-                                let mut content = String::new();
-                                file.read_to_string(&mut content).unwrap();
-                                sub_jsons[i - 1] = serde_json::from_str(&content).unwrap();
-                                json.iter[i - 1] = sub_jsons[i - 1].iter;
-                                *json.indices.get_mut(&(i as u32)).unwrap() = sub_jsons[
-                                    i - 1
-                                ].indices
-                                    .get(&(i as u32))
-                                    .unwrap()
-                                    .clone();
-                                for student_number in sub_jsons[i - 1].routes.keys() {
-                                    *json.routes
-                                        .get_mut(student_number)
-                                        .unwrap()
-                                        .get_mut(&(i as u32))
-                                        .unwrap() = sub_jsons[i - 1].routes
-                                        .get(student_number)
-                                        .unwrap()
-                                        .get(&(i as u32))
-                                        .unwrap()
-                                        .clone();
-                                }
-                            }
-                            let mut file = File::create("./bin/routes.json").unwrap();
-                            file.write_all(
-                                serde_json::to_string(&json).unwrap().as_bytes()
-                            ).unwrap();
-                            *self.student_routes_optimized.lock().unwrap() = Some(json.routes);
                         }
                     });
                 }
@@ -781,11 +1646,90 @@ impl OptiWayApp {
                                 .size(32.0)
                                 .color(Color32::from_rgb(0xff, 0xc1, 0x07))
                         );
-                        ui.label("Optimization paused");
+                        let stop_reason = self.optimization_store
+                            .as_ref()
+                            .and_then(|store| store.stop_reason());
+                        match stop_reason {
+                            Some(stop_reason) if stop_reason != StopReason::Interrupted => {
+                                ui.label("Optimization stopped");
+                                ui.label(stop_reason.to_string());
+                            }
+                            _ => {
+                                ui.label("Optimization paused");
+                            }
+                        }
                         ui.label("You may resume optimization at any time.");
                         if ui.button("Resume").clicked() {
                             *self.optimization_status.lock().unwrap() = OptimizationStatus::Ready;
                         }
+                        let checkpoint_summaries = self.checkpoint_summaries.lock().unwrap().clone();
+                        let is_stopping = self.is_optimization_stopping();
+                        if is_stopping {
+                            ctx.request_repaint();
+                        }
+                        let best_performance_index = checkpoint_summaries
+                            .iter()
+                            .map(|summary| summary.performance_index)
+                            .min();
+                        ui.collapsing("Checkpoints", |ui| {
+                            egui::ScrollArea
+                                ::vertical()
+                                .max_height(200.0)
+                                .show(ui, |ui| {
+                                    Grid::new("checkpoint_grid")
+                                        .striped(true)
+                                        .num_columns(4)
+                                        .show(ui, |ui| {
+                                            ui.label("Iteration");
+                                            ui.label("Total PI");
+                                            ui.label("Saved");
+                                            ui.label("");
+                                            ui.end_row();
+                                            for summary in checkpoint_summaries.iter().rev() {
+                                                ui.label(
+                                                    summary.iterations.to_formatted_string(
+                                                        &Locale::fr
+                                                    )
+                                                );
+                                                let performance_index = RichText::new(
+                                                    summary.performance_index.to_formatted_string(
+                                                        &Locale::fr
+                                                    )
+                                                );
+                                                ui.label(
+                                                    if
+                                                        Some(summary.performance_index) ==
+                                                        best_performance_index
+                                                    {
+                                                        performance_index.color(
+                                                            Color32::from_rgb(0x14, 0xae, 0x52)
+                                                        )
+                                                    } else {
+                                                        performance_index
+                                                    }
+                                                );
+                                                ui.label(format_age(summary.saved_at));
+                                                if
+                                                    ui
+                                                        .add_enabled(
+                                                            !is_stopping,
+                                                            egui::Button::new("Roll back")
+                                                        )
+                                                        .on_hover_text(
+                                                            "Continue from the routes of this checkpoint."
+                                                        )
+                                                        .on_disabled_hover_text(
+                                                            "The run is still stopping."
+                                                        )
+                                                        .clicked()
+                                                {
+                                                    self.roll_back_to_checkpoint(summary);
+                                                }
+                                                ui.end_row();
+                                            }
+                                        });
+                                });
+                        });
                         if ui.button("Close").clicked() {
                             self.show_optimization_window = false;
                             *self.optimization_status.lock().unwrap() =
@@ -811,6 +1755,299 @@ impl OptiWayApp {
         });
     }
 
+    fn show_pareto_window(&mut self, ctx: &egui::Context, current_pareto_status: ParetoStatus) {
+        Window::new("Multi-objective Optimization").show(ctx, |ui| {
+            match current_pareto_status {
+                ParetoStatus::ParamInput => {
+                    ui.heading("Parameters");
+                    ui.add(
+                        Slider::new(&mut self.param_penalty_levels, 1..=20).text(
+                            "Congestion penalty levels"
+                        )
+                    );
+                    ui.add(
+                        Slider::new(&mut self.param_max_penalty, 0.1..=10.0)
+                            .step_by(0.1)
+                            .text("Maximum congestion penalty")
+                    ).on_hover_text(
+                        "Relative increase in the cost of an edge for every 100 students already walking it."
+                    );
+                    ui.add(
+                        Slider::new(&mut self.param_pareto_passes, 1..=5).text(
+                            "Rerouting passes per level"
+                        )
+                    );
+                    ui.label(
+                        "Plans are generated from the shortest routes and compared on total walking distance, peak edge load and maximum detour. Only plans that no other plan beats on every objective are kept."
+                    );
+                    ui.separator();
+                    ui.heading("Fairness");
+                    detour_limits_ui(ui, &mut self.param_detour_limits);
+                    ui.separator();
+                    ui.with_layout(Layout::top_down_justified(egui::Align::Center), |ui| {
+                        if ui.button("Reset parameters").clicked() {
+                            self.param_penalty_levels = 8;
+                            self.param_max_penalty = 2.0;
+                            self.param_pareto_passes = 2;
+                            self.param_detour_limits = Default::default();
+                        }
+                        if ui.button("Start").clicked() {
+                            *self.pareto_status.lock().unwrap() = ParetoStatus::Ready;
+                        }
+                        if ui.button("Close").clicked() {
+                            self.show_pareto_window = false;
+                        }
+                    });
+                }
+                ParetoStatus::Ready => {
+                    *self.pareto_status.lock().unwrap() = ParetoStatus::Searching(
+                        0,
+                        "Generating route plans".to_owned()
+                    );
+                    self.selected_pareto_plan = None;
+                    let Some(student_routes) = self.student_routes_shortest
+                        .lock()
+                        .unwrap()
+                        .clone() else {
+                        *self.pareto_status.lock().unwrap() = ParetoStatus::Failed(
+                            "No path data available".to_owned()
+                        );
+                        return;
+                    };
+                    let pareto_status_arc = self.pareto_status.clone();
+                    let pareto_archive_arc = self.pareto_archive.clone();
+                    let shortest_paths = self.shortest_paths_json.clone();
+                    let building_graph = self.building_graph.lock().unwrap().clone();
+                    let params = ParetoParams {
+                        penalty_levels: self.param_penalty_levels,
+                        max_penalty: self.param_max_penalty,
+                        passes: self.param_pareto_passes,
+                        detour_limits: self.param_detour_limits,
+                    };
+                    thread::spawn(move || {
+                        let archive = pareto_optimize(
+                            &student_routes.to_routes(),
+                            &shortest_paths,
+                            &building_graph,
+                            params,
+                            |progress| {
+                                *pareto_status_arc.lock().unwrap() = ParetoStatus::Searching(
+                                    progress,
+                                    "Generating route plans".to_owned()
+                                );
+                            }
+                        );
+                        *pareto_archive_arc.lock().unwrap() = Some(archive);
+                        *pareto_status_arc.lock().unwrap() = ParetoStatus::Successful;
+                    });
+                }
+                ParetoStatus::Searching(progress, message) => {
+                    ui.with_layout(Layout::top_down_justified(egui::Align::Center), |ui| {
+                        ui.label(
+                            RichText::new(material_design_icons::MDI_CHART_SCATTER_PLOT).size(32.0)
+                        );
+                        ui.label(message);
+                        ui.add(ProgressBar::new((progress as f32) / 100.0));
+                    });
+                }
+                ParetoStatus::Failed(message) => {
+                    ui.with_layout(Layout::top_down_justified(egui::Align::Center), |ui| {
+                        ui.label(
+                            RichText::new(material_design_icons::MDI_CHART_SCATTER_PLOT)
+                                .size(32.0)
+                                .color(Color32::from_rgb(0xe4, 0x37, 0x48))
+                        );
+                        ui.label("Multi-objective optimization failed");
+                        ui.label(message);
+                        if ui.button("Close").clicked() {
+                            self.show_pareto_window = false;
+                            *self.pareto_status.lock().unwrap() = ParetoStatus::ParamInput;
+                        }
+                    });
+                }
+                ParetoStatus::Successful => {
+                    let archive = self.pareto_archive.lock().unwrap().clone().unwrap_or_default();
+                    let plans = archive.plans();
+                    ui.horizontal(|ui| {
+                        for (label, axis) in [
+                            ("X axis", &mut self.pareto_x_axis),
+                            ("Y axis", &mut self.pareto_y_axis),
+                        ] {
+                            ComboBox::from_label(label)
+                                .selected_text(format!("{}", axis))
+                                .show_ui(ui, |ui| {
+                                    for objective in [
+                                        Objective::TotalDistance,
+                                        Objective::PeakLoad,
+                                        Objective::MaxDetour,
+                                    ] {
+                                        ui.selectable_value(
+                                            axis,
+                                            objective,
+                                            format!("{}", objective)
+                                        );
+                                    }
+                                });
+                        }
+                    });
+                    let (x_axis, y_axis) = (self.pareto_x_axis, self.pareto_y_axis);
+                    let plan_points: Vec<[f64; 2]> = plans
+                        .iter()
+                        .map(|plan| [x_axis.value(&plan.objectives), y_axis.value(&plan.objectives)])
+                        .collect();
+                    let selected_point = self.selected_pareto_plan.and_then(|index|
+                        plan_points.get(index).copied()
+                    );
+                    let clicked_plan = egui::plot::Plot
+                        ::new("pareto_plot")
+                        .height(240.0)
+                        .width(420.0)
+                        .allow_scroll(false)
+                        .label_formatter(move |_, point| {
+                            format!("{}: {:.0}\n{}: {:.0}", x_axis, point.x, y_axis, point.y)
+                        })
+                        .show(ui, |plot_ui| {
+                            plot_ui.points(
+                                egui::plot::Points
+                                    ::new(plan_points.clone())
+                                    .radius(4.0)
+                                    .color(Color32::from_rgb(0xec, 0x6f, 0x27))
+                            );
+                            if let Some(selected_point) = selected_point {
+                                plot_ui.points(
+                                    egui::plot::Points
+                                        ::new(vec![selected_point])
+                                        .radius(7.0)
+                                        .filled(false)
+                                        .color(Color32::from_rgb(0x14, 0xae, 0x52))
+                                );
+                            }
+                            if !plot_ui.plot_clicked() {
+                                return None;
+                            }
+                            let pointer = plot_ui.screen_from_plot(plot_ui.pointer_coordinate()?);
+                            plan_points
+                                .iter()
+                                .map(|point| {
+                                    plot_ui
+                                        .screen_from_plot(egui::plot::PlotPoint::new(point[0], point[1]))
+                                        .distance(pointer)
+                                })
+                                .enumerate()
+                                .min_by(|a, b| a.1.total_cmp(&b.1))
+                                .filter(|(_, distance)| *distance < 12.0)
+                                .map(|(index, _)| index)
+                        }).inner;
+                    if clicked_plan.is_some() {
+                        self.selected_pareto_plan = clicked_plan;
+                    }
+                    ui.label("Click a point or a row to select a plan.");
+                    ui.separator();
+                    egui::ScrollArea
+                        ::vertical()
+                        .max_height(200.0)
+                        .show(ui, |ui| {
+                            Grid::new("pareto_plan_grid")
+                                .striped(true)
+                                .num_columns(6)
+                                .show(ui, |ui| {
+                                    ui.label("Penalty");
+                                    ui.label("Passes");
+                                    ui.label("Total distance");
+                                    ui.label("Peak edge load");
+                                    ui.label("Maximum detour");
+                                    ui.label("PI");
+                                    ui.end_row();
+                                    for (index, plan) in plans.iter().enumerate() {
+                                        if
+                                            ui
+                                                .selectable_label(
+                                                    self.selected_pareto_plan == Some(index),
+                                                    format!("{:.2}", plan.penalty)
+                                                )
+                                                .clicked()
+                                        {
+                                            self.selected_pareto_plan = Some(index);
+                                        }
+                                        ui.label(plan.passes.to_string());
+                                        ui.label(
+                                            plan.objectives.total_distance.to_formatted_string(
+                                                &Locale::fr
+                                            )
+                                        );
+                                        ui.label(plan.objectives.peak_load.to_string());
+                                        ui.label(plan.objectives.max_detour.to_string());
+                                        ui.label(
+                                            (plan.performance_index as u128).to_formatted_string(
+                                                &Locale::fr
+                                            )
+                                        );
+                                        ui.end_row();
+                                    }
+                                });
+                        });
+                    ui.separator();
+                    ui.with_layout(Layout::top_down_justified(egui::Align::Center), |ui| {
+                        let selected_plan = self.selected_pareto_plan.and_then(|index|
+                            plans.get(index)
+                        );
+                        ui.add_enabled_ui(selected_plan.is_some(), |ui| {
+                            if
+                                ui
+                                    .button("Use selected plan")
+                                    .on_hover_text(
+                                        "Replace the optimized routes with the selected plan."
+                                    )
+                                    .clicked()
+                            {
+                                let student_routes = self.student_routes_shortest
+                                    .lock()
+                                    .unwrap()
+                                    .as_ref()
+                                    .map(|student_routes| student_routes.to_routes())
+                                    .unwrap_or_default();
+                                let routes = match
+                                    CompactRoutes::from_routes(
+                                        &selected_plan.unwrap().apply(&student_routes)
+                                    )
+                                {
+                                    Ok(routes) => routes,
+                                    Err(error) => {
+                                        *self.pareto_status.lock().unwrap() = ParetoStatus::Failed(
+                                            error.to_string()
+                                        );
+                                        return;
+                                    }
+                                };
+                                let congestion = CongestionIndex::from_routes(
+                                    &self.building_graph.lock().unwrap(),
+                                    &routes
+                                );
+                                *self.performance_indices_optimized.lock().unwrap() =
+                                    congestion.performance_indices();
+                                *self.uncounted_steps_optimized.lock().unwrap() =
+                                    congestion.uncounted_steps();
+                                *self.student_routes_optimized.lock().unwrap() = Some(
+                                    Arc::new(routes)
+                                );
+                                self.path_display = PathDisplay::Optimized;
+                                self.show_congestion_window = true;
+                                *self.congestion_status.lock().unwrap() = CongestionStatus::Ready;
+                            }
+                        });
+                        if ui.button("Start over").clicked() {
+                            *self.pareto_status.lock().unwrap() = ParetoStatus::ParamInput;
+                        }
+                        if ui.button("Close").clicked() {
+                            self.show_pareto_window = false;
+                            *self.pareto_status.lock().unwrap() = ParetoStatus::ParamInput;
+                        }
+                    });
+                }
+            }
+        });
+    }
+
     fn show_congestion_window(
         &mut self,
         ctx: &egui::Context,
@@ -886,7 +2123,6 @@ impl OptiWayApp {
                     let max_congestion_opt_arc = self.maximum_congestion_opt.clone();
                     let congestion_statistics_arc = self.congestion_statistics.clone();
                     let congestion_statistics_opt_arc = self.congestion_statistics_opt.clone();
-                    let path_distances_arc = self.path_distances.clone();
                     let mut rooms: Vec<String> = self.projection_coords
                         .clone()
                         .keys()
@@ -894,6 +2130,7 @@ impl OptiWayApp {
                         .collect();
                     let performance_indices_shortest_arc =
                         self.performance_indices_shortest.clone();
+                    let uncounted_steps_shortest_arc = self.uncounted_steps_shortest.clone();
                     rooms.push("G".to_owned());
                     let student_routes = self.student_routes_shortest.lock().unwrap().clone();
                     if student_routes.is_none() {
@@ -904,447 +2141,50 @@ impl OptiWayApp {
                     }
                     let student_routes = student_routes.unwrap();
                     let student_routes_opt = self.student_routes_optimized.lock().unwrap().clone();
+                    let building_graph = self.building_graph.lock().unwrap().clone();
+                    let layers = self.congestion_layers.lock().unwrap().clone();
                     thread::spawn(move || {
                         if let Some(student_routes) = student_routes_opt {
-                            for day in 1..=5 {
-                                for period in 0..=11 {
-                                    for room in &rooms {
-                                        congestion_point_data_opt_arc
-                                            .lock()
-                                            .unwrap()
-                                            .get_mut(&day)
-                                            .unwrap()
-                                            .get_mut(&period)
-                                            .unwrap()
-                                            .insert(room.to_owned(), 0);
-                                    }
-                                }
-                            }
-                            for student in &student_routes {
-                                for day in 1..=5 {
-                                    for period in 0..=11 {
-                                        let rooms = student_routes
-                                            .get(student.0)
-                                            .unwrap()
-                                            .get(&day)
-                                            .unwrap()
-                                            .get(&period)
-                                            .unwrap()
-                                            .split(' ')
-                                            .collect::<Vec<&str>>();
-                                        let mut previous_room = "";
-                                        for room in rooms {
-                                            if room.is_empty() || room == "G" {
-                                                continue;
-                                            }
-                                            if !previous_room.is_empty() {
-                                                let contains = congestion_path_data_opt_arc
-                                                    .lock()
-                                                    .unwrap()
-                                                    .get(&day)
-                                                    .unwrap()
-                                                    .get(&period)
-                                                    .unwrap()
-                                                    .contains_key(
-                                                        &(previous_room.to_owned(), room.to_owned())
-                                                    );
-                                                if contains {
-                                                    *congestion_path_data_opt_arc
-                                                        .lock()
-                                                        .unwrap()
-                                                        .get_mut(&day)
-                                                        .unwrap()
-                                                        .get_mut(&period)
-                                                        .unwrap()
-                                                        .get_mut(
-                                                            &(
-                                                                previous_room.to_owned(),
-                                                                room.to_owned(),
-                                                            )
-                                                        )
-                                                        .unwrap() += 1;
-                                                } else {
-                                                    congestion_path_data_opt_arc
-                                                        .lock()
-                                                        .unwrap()
-                                                        .get_mut(&day)
-                                                        .unwrap()
-                                                        .get_mut(&period)
-                                                        .unwrap()
-                                                        .insert(
-                                                            (
-                                                                previous_room.to_owned(),
-                                                                room.to_owned(),
-                                                            ),
-                                                            1
-                                                        );
-                                                }
-                                                let contains = congestion_path_data_opt_arc
-                                                    .lock()
-                                                    .unwrap()
-                                                    .get(&day)
-                                                    .unwrap()
-                                                    .get(&period)
-                                                    .unwrap()
-                                                    .contains_key(
-                                                        &(room.to_owned(), previous_room.to_owned())
-                                                    );
-                                                if contains {
-                                                    *congestion_path_data_opt_arc
-                                                        .lock()
-                                                        .unwrap()
-                                                        .get_mut(&day)
-                                                        .unwrap()
-                                                        .get_mut(&period)
-                                                        .unwrap()
-                                                        .get_mut(
-                                                            &(
-                                                                room.to_owned(),
-                                                                previous_room.to_owned(),
-                                                            )
-                                                        )
-                                                        .unwrap() += 1;
-                                                } else {
-                                                    congestion_path_data_opt_arc
-                                                        .lock()
-                                                        .unwrap()
-                                                        .get_mut(&day)
-                                                        .unwrap()
-                                                        .get_mut(&period)
-                                                        .unwrap()
-                                                        .insert(
-                                                            (
-                                                                room.to_owned(),
-                                                                previous_room.to_owned(),
-                                                            ),
-                                                            1
-                                                        );
-                                                }
-                                            }
-                                            previous_room = room;
-                                            *congestion_point_data_opt_arc
-                                                .lock()
-                                                .unwrap()
-                                                .get_mut(&day)
-                                                .unwrap()
-                                                .get_mut(&period)
-                                                .unwrap()
-                                                .get_mut(room)
-                                                .unwrap() += 1;
-                                            let cur_max_congestion = *max_congestion_opt_arc
-                                                .lock()
-                                                .unwrap();
-                                            *max_congestion_opt_arc.lock().unwrap() =
-                                                cur_max_congestion.max(
-                                                    *congestion_point_data_opt_arc
-                                                        .lock()
-                                                        .unwrap()
-                                                        .get_mut(&day)
-                                                        .unwrap()
-                                                        .get_mut(&period)
-                                                        .unwrap()
-                                                        .get_mut(room)
-                                                        .unwrap()
-                                                );
-                                        }
-                                    }
-                                }
-                            }
-                            for day in 1..=5 {
-                                for period in 0..=11 {
-                                    for room in &rooms {
-                                        let room_congestion = *congestion_point_data_opt_arc
-                                            .lock()
-                                            .unwrap()
-                                            .get_mut(&day)
-                                            .unwrap()
-                                            .get_mut(&period)
-                                            .unwrap()
-                                            .get_mut(room)
-                                            .unwrap();
-                                        congestion_statistics_opt_arc
-                                            .lock()
-                                            .unwrap()
-                                            .point_count.get_mut(&day)
-                                            .unwrap()
-                                            .get_mut(&period)
-                                            .unwrap()[congestion_range_index(room_congestion)] += 1;
-                                    }
-                                }
-                            }
-                            for day in 1..=5 {
-                                for period in 0..=11 {
-                                    congestion_path_data_opt_arc
-                                        .lock()
-                                        .unwrap()
-                                        .get_mut(&day)
-                                        .unwrap()
-                                        .get_mut(&period)
-                                        .unwrap()
-                                        .iter_mut()
-                                        .for_each(|(_, congestion)| {
-                                            congestion_statistics_opt_arc
-                                                .lock()
-                                                .unwrap()
-                                                .path_count.get_mut(&day)
-                                                .unwrap()
-                                                .get_mut(&period)
-                                                .unwrap()[congestion_range_index(*congestion)] += 1;
-                                        });
-                                }
-                            }
-                        }
-                        for day in 1..=5 {
-                            for period in 0..=11 {
-                                for room in &rooms {
-                                    congestion_point_data_arc
-                                        .lock()
-                                        .unwrap()
-                                        .get_mut(&day)
-                                        .unwrap()
-                                        .get_mut(&period)
-                                        .unwrap()
-                                        .insert(room.to_owned(), 0);
-                                }
-                            }
-                        }
-                        for student in &student_routes {
-                            for day in 1..=5 {
-                                for period in 0..=11 {
-                                    let rooms = student_routes
-                                        .get(student.0)
-                                        .unwrap()
-                                        .get(&day)
-                                        .unwrap()
-                                        .get(&period)
-                                        .unwrap()
-                                        .split(' ')
-                                        .collect::<Vec<&str>>();
-                                    let mut previous_room = "";
-                                    for room in rooms {
-                                        if room.is_empty() || room == "G" {
-                                            continue;
-                                        }
-                                        if !previous_room.is_empty() {
-                                            let contains = congestion_path_data_arc
-                                                .lock()
-                                                .unwrap()
-                                                .get(&day)
-                                                .unwrap()
-                                                .get(&period)
-                                                .unwrap()
-                                                .contains_key(
-                                                    &(previous_room.to_owned(), room.to_owned())
-                                                );
-                                            if contains {
-                                                *congestion_path_data_arc
-                                                    .lock()
-                                                    .unwrap()
-                                                    .get_mut(&day)
-                                                    .unwrap()
-                                                    .get_mut(&period)
-                                                    .unwrap()
-                                                    .get_mut(
-                                                        &(previous_room.to_owned(), room.to_owned())
-                                                    )
-                                                    .unwrap() += 1;
-                                            } else {
-                                                congestion_path_data_arc
-                                                    .lock()
-                                                    .unwrap()
-                                                    .get_mut(&day)
-                                                    .unwrap()
-                                                    .get_mut(&period)
-                                                    .unwrap()
-                                                    .insert(
-                                                        (previous_room.to_owned(), room.to_owned()),
-                                                        1
-                                                    );
-                                            }
-                                            let contains = congestion_path_data_arc
-                                                .lock()
-                                                .unwrap()
-                                                .get(&day)
-                                                .unwrap()
-                                                .get(&period)
-                                                .unwrap()
-                                                .contains_key(
-                                                    &(room.to_owned(), previous_room.to_owned())
-                                                );
-                                            if contains {
-                                                *congestion_path_data_arc
-                                                    .lock()
-                                                    .unwrap()
-                                                    .get_mut(&day)
-                                                    .unwrap()
-                                                    .get_mut(&period)
-                                                    .unwrap()
-                                                    .get_mut(
-                                                        &(room.to_owned(), previous_room.to_owned())
-                                                    )
-                                                    .unwrap() += 1;
-                                            } else {
-                                                congestion_path_data_arc
-                                                    .lock()
-                                                    .unwrap()
-                                                    .get_mut(&day)
-                                                    .unwrap()
-                                                    .get_mut(&period)
-                                                    .unwrap()
-                                                    .insert(
-                                                        (room.to_owned(), previous_room.to_owned()),
-                                                        1
-                                                    );
-                                            }
-                                        }
-                                        previous_room = room;
-                                        *congestion_point_data_arc
-                                            .lock()
-                                            .unwrap()
-                                            .get_mut(&day)
-                                            .unwrap()
-                                            .get_mut(&period)
-                                            .unwrap()
-                                            .get_mut(room)
-                                            .unwrap() += 1;
-                                        let cur_max_congestion = *max_congestion_arc
-                                            .lock()
-                                            .unwrap();
-                                        *max_congestion_arc.lock().unwrap() =
-                                            cur_max_congestion.max(
-                                                *congestion_point_data_arc
-                                                    .lock()
-                                                    .unwrap()
-                                                    .get_mut(&day)
-                                                    .unwrap()
-                                                    .get_mut(&period)
-                                                    .unwrap()
-                                                    .get_mut(room)
-                                                    .unwrap()
-                                            );
-                                    }
-                                }
-                            }
+                            let mut congestion = CongestionIndex::from_routes(
+                                &building_graph,
+                                &student_routes
+                            );
+                            let transitions = congestion.take_changed();
+                            let congestion = layers.apply(&congestion);
+                            copy_congestion(
+                                &congestion,
+                                &building_graph,
+                                &rooms,
+                                &transitions,
+                                &mut congestion_point_data_opt_arc.lock().unwrap(),
+                                &mut congestion_path_data_opt_arc.lock().unwrap(),
+                                &mut congestion_statistics_opt_arc.lock().unwrap()
+                            );
+                            *max_congestion_opt_arc.lock().unwrap() = congestion.max_node_load();
                         }
-                        for day in 1..=5 {
-                            for period in 0..=11 {
-                                for room in &rooms {
-                                    let room_congestion = *congestion_point_data_arc
-                                        .lock()
-                                        .unwrap()
-                                        .get_mut(&day)
-                                        .unwrap()
-                                        .get_mut(&period)
-                                        .unwrap()
-                                        .get_mut(room)
-                                        .unwrap();
-                                    congestion_statistics_arc
-                                        .lock()
-                                        .unwrap()
-                                        .point_count.get_mut(&day)
-                                        .unwrap()
-                                        .get_mut(&period)
-                                        .unwrap()[congestion_range_index(room_congestion)] += 1;
-                                }
-                            }
-                        }
-                        for day in 1..=5 {
-                            for period in 0..=11 {
-                                congestion_path_data_arc
-                                    .lock()
-                                    .unwrap()
-                                    .get_mut(&day)
-                                    .unwrap()
-                                    .get_mut(&period)
-                                    .unwrap()
-                                    .iter_mut()
-                                    .for_each(|(_, congestion)| {
-                                        congestion_statistics_arc
-                                            .lock()
-                                            .unwrap()
-                                            .path_count.get_mut(&day)
-                                            .unwrap()
-                                            .get_mut(&period)
-                                            .unwrap()[congestion_range_index(*congestion)] += 1;
-                                    });
-                            }
-                        }
-                        *congestion_status_arc.lock().unwrap() = CongestionStatus::GeneratingPI(
-                            0,
-                            "Calculating performance indices".to_owned()
+                        let mut congestion = CongestionIndex::from_routes(
+                            &building_graph,
+                            &student_routes
                         );
-                        let mut performance_indices_shortest = HashMap::new();
-                        let path_distances = path_distances_arc.lock().unwrap().clone();
-                        for day in 1..=5 {
-                            performance_indices_shortest.insert(day, HashMap::new());
-                            for period in 0..=11 {
-                                let mut index = 0f64;
-                                for student in &student_routes {
-                                    let rooms = student_routes
-                                        .get(student.0)
-                                        .unwrap()
-                                        .get(&day)
-                                        .unwrap()
-                                        .get(&period)
-                                        .unwrap()
-                                        .split(' ')
-                                        .collect::<Vec<&str>>();
-                                    let mut previous_room = "";
-                                    for room in rooms {
-                                        if room.is_empty() || room == "G" {
-                                            continue;
-                                        }
-                                        if !previous_room.is_empty() {
-                                            let path_distance = *path_distances
-                                                .get(previous_room)
-                                                .unwrap()
-                                                .get(room)
-                                                .unwrap_or_else(|| {
-                                                    println!(
-                                                        "{:?}",
-                                                        path_distances.get(previous_room).unwrap()
-                                                    );
-                                                    panic!(
-                                                        "Path distance not found: {} -> {}",
-                                                        previous_room,
-                                                        room
-                                                    )
-                                                }) as f64;
-                                            let path_congestion = *congestion_path_data_arc
-                                                .lock()
-                                                .unwrap()
-                                                .get(&day)
-                                                .unwrap()
-                                                .get(&period)
-                                                .unwrap()
-                                                .get(&(previous_room.to_owned(), room.to_owned()))
-                                                .unwrap() as f64;
-                                            index +=
-                                                path_distance *
-                                                (2.0 + ((path_congestion - 300.0) / 200.0).tanh());
-                                        }
-                                        previous_room = room;
-                                    }
-                                }
-                                performance_indices_shortest
-                                    .get_mut(&day)
-                                    .unwrap()
-                                    .insert(period, index as u128);
-                            }
-                        }
-                        // println!("{}", serde_json::to_string(&performance_indices_shortest).unwrap());
+                        let transitions = congestion.take_changed();
+                        let shown_congestion = layers.apply(&congestion);
+                        copy_congestion(
+                            &shown_congestion,
+                            &building_graph,
+                            &rooms,
+                            &transitions,
+                            &mut congestion_point_data_arc.lock().unwrap(),
+                            &mut congestion_path_data_arc.lock().unwrap(),
+                            &mut congestion_statistics_arc.lock().unwrap()
+                        );
+                        *max_congestion_arc.lock().unwrap() = shown_congestion.max_node_load();
                         *performance_indices_shortest_arc.lock().unwrap() =
-                            performance_indices_shortest;
+                            congestion.performance_indices();
+                        *uncounted_steps_shortest_arc.lock().unwrap() =
+                            congestion.uncounted_steps();
                         *congestion_status_arc.lock().unwrap() = CongestionStatus::Successful;
                     });
                 }
-                CongestionStatus::GeneratingPI(_, message) => {
-                    ui.with_layout(Layout::top_down_justified(egui::Align::Center), |ui| {
-                        ui.label(RichText::new(material_design_icons::MDI_TIMER).size(32.0));
-                        ui.label(message);
-                        ui.spinner();
-                    });
-                }
                 CongestionStatus::Generating(_, message) => {
                     ui.with_layout(Layout::top_down_justified(egui::Align::Center), |ui| {
                         ui.label(
@@ -1394,283 +2234,2915 @@ impl OptiWayApp {
         });
     }
 
-    fn show_json_validation_window(
-        &mut self,
-        ctx: &egui::Context,
-        current_validation_status: TimetableValidationStatus
-    ) {
-        Window::new("Timetable Validation").show(ctx, |ui| {
-            ui.heading("Metadata");
-            Grid::new("timetable_validation_grid")
-                .num_columns(2)
-                .show(ui, |ui| {
-                    ui.label("Filename");
-                    ui.label(self.timetable_file_info.filename.to_string());
-                    ui.end_row();
+    /// Destinations of the students of `timetable`, or `G` for everyone without destinations.
+    fn destination_assignment(&self, timetable: &Timetable) -> DestinationAssignment {
+        self.destination_model
+            .as_ref()
+            .map_or_else(Default::default, |(_, model)| model.assign(timetable))
+    }
 
-                    ui.label("Student count");
-                    if
-                        let Some(student_count) = *self.timetable_file_info.student_count
-                            .lock()
-                            .unwrap()
-                    {
-                        ui.label(format!("{}", student_count));
-                    } else {
-                        ui.label("—");
-                    }
-                    ui.end_row();
+    /// Shortest routes of every student of `timetable`, to and from their destinations.
+    fn shortest_routes(&self, timetable: &Timetable) -> Routes {
+        let mut routes = routes_from_timetable(timetable, &self.shortest_paths_json);
+        self.destination_assignment(timetable).route(
+            &mut routes,
+            timetable,
+            &self.shortest_paths_json
+        );
+        routes
+    }
 
-                    ui.label("Session count");
-                    if
-                        let Some(session_count) = *self.timetable_file_info.session_count
-                            .lock()
-                            .unwrap()
-                    {
-                        ui.label(format!("{}", session_count));
-                    } else {
-                        ui.label("—");
+    /// Routes every member of staff of a timetable file along the shortest paths and indexes their
+    /// congestion for the staff and combined layers.
+    fn load_staff_timetable(&mut self, file: &Path, file_name: &str) {
+        let timetable = Timetable::load(file, |_| ()).and_then(|timetable| {
+            timetable.check_rooms(|room| self.projection_coords.contains_key(room))?;
+            Ok(timetable)
+        });
+        let timetable = match timetable {
+            Ok(timetable) => timetable,
+            Err(error) => {
+                self.staff_timetable_message = Some(
+                    format!("{} was not imported: {}", file_name, error)
+                );
+                return;
+            }
+        };
+        self.staff_timetable = Some((file_name.to_owned(), timetable));
+        self.staff_timetable_message = None;
+        self.index_staff_congestion();
+    }
+
+    fn index_staff_congestion(&mut self) {
+        let Some((_, timetable)) = &self.staff_timetable else {
+            return;
+        };
+        let routes = match CompactRoutes::from_routes(&self.shortest_routes(timetable)) {
+            Ok(routes) => routes,
+            Err(error) => {
+                self.staff_timetable_message = Some(
+                    format!("The staff routes were not indexed: {}", error)
+                );
+                self.congestion_layers.lock().unwrap().staff = None;
+                self.refresh_congestion_layer();
+                return;
+            }
+        };
+        let congestion = CongestionIndex::from_routes(
+            &self.building_graph.lock().unwrap(),
+            &routes
+        );
+        self.congestion_layers.lock().unwrap().staff = Some(Arc::new(congestion));
+        self.refresh_congestion_layer();
+    }
+
+    /// Joins the destinations of a YAML file to the building and sends the students and the staff
+    /// to them in the shortest routes. The optimized routes are kept as they are.
+    fn load_destinations(&mut self, file: &Path, file_name: &str) {
+        let building_paths = include_str!("../assets/paths.txt");
+        let model = match DestinationModel::load(file, &BuildingGraph::parse(building_paths)) {
+            Ok(model) => model,
+            Err(error) => {
+                self.destination_message = Some(
+                    format!("{} was not imported: {}", file_name, error)
+                );
+                return;
+            }
+        };
+        let paths = format!("{}{}", building_paths, model.paths_txt());
+        let graph = BuildingGraph::parse(&paths);
+        let mut shortest_paths: ShortestPaths = serde_json
+            ::from_str(include_str!("../assets/shortest_paths.json"))
+            .unwrap();
+        shortest_paths.extend(model.shortest_paths(&graph));
+        let mut projection_coords: HashMap<String, [i32; 3]> = serde_yaml
+            ::from_str(include_str!("../assets/projection-coords-flatten.yaml"))
+            .unwrap();
+        projection_coords.extend(model.coords().map(|(name, coords)| (name.to_owned(), coords)));
+        *self.building_graph.lock().unwrap() = graph;
+        *self.path_distances.lock().unwrap() = parse_path_distances(&paths);
+        self.shortest_paths_json = shortest_paths;
+        self.projection_coords = projection_coords;
+        self.destination_model = Some((file_name.to_owned(), model));
+        self.destination_message = None;
+
+        let shortest_routes = self.student_routes_shortest.lock().unwrap().clone();
+        if let (Some(timetable), Some(routes)) = (
+            self.timetable_file_info.timetable.lock().unwrap().as_ref(),
+            shortest_routes,
+        ) {
+            let mut routes = routes.to_routes();
+            self.destination_assignment(timetable).route(
+                &mut routes,
+                timetable,
+                &self.shortest_paths_json
+            );
+            match CompactRoutes::from_routes(&routes) {
+                Ok(routes) => {
+                    *self.student_routes_shortest.lock().unwrap() = Some(Arc::new(routes));
+                }
+                Err(error) => {
+                    self.destination_message = Some(
+                        format!("The routes to {} were not updated: {}", file_name, error)
+                    );
+                }
+            }
+        }
+        self.index_staff_congestion();
+        let mut congestion_status = self.congestion_status.lock().unwrap();
+        if *congestion_status == CongestionStatus::Successful {
+            *congestion_status = CongestionStatus::Ready;
+        }
+    }
+
+    fn show_destination_window(&mut self, ctx: &egui::Context) {
+        let mut show_destination_window = self.show_destination_window;
+        Window::new("Destinations")
+            .open(&mut show_destination_window)
+            .show(ctx, |ui| {
+                let timetable = self.timetable_file_info.timetable.lock().unwrap();
+                let Some(timetable) = timetable.as_ref() else {
+                    ui.label("Import a timetable to see where the students go.");
+                    return;
+                };
+                let assignment = self.destination_assignment(timetable);
+                ui.horizontal(|ui| {
+                    for day in Day::all() {
+                        ui.selectable_value(&mut self.selected_day, day.number(), day.name());
                     }
-                    ui.end_row();
                 });
-            ui.separator();
-            match current_validation_status {
-                TimetableValidationStatus::Ready => {
-                    *self.timetable_file_info.validation_status.clone().lock().unwrap() =
-                        TimetableValidationStatus::Validating(0, "Ready to validate".to_owned());
-                    let filepath = self.timetable_file_info.filepath.clone();
-                    let projection_coords = self.projection_coords.clone();
-                    let validation_status_arc = self.timetable_file_info.validation_status.clone();
-                    let student_count_arc = self.timetable_file_info.student_count.clone();
-                    let session_count_arc = self.timetable_file_info.session_count.clone();
-                    let timetable_arc = self.timetable_file_info.timetable.clone();
-                    let student_list_arc = self.student_list.clone();
+                let Some(day) = Day::new(self.selected_day) else {
+                    return;
+                };
+                Grid::new("destination_grid")
+                    .striped(true)
+                    .show(ui, |ui| {
+                        ui.label("Destination");
+                        for outing in Outing::ALL {
+                            ui.label(outing.to_string());
+                        }
+                        ui.end_row();
+                        for (name, counts) in assignment.counts(day) {
+                            ui.label(name);
+                            for count in counts {
+                                ui.label(count.to_formatted_string(&Locale::fr));
+                            }
+                            ui.end_row();
+                        }
+                    });
+            });
+        self.show_destination_window = show_destination_window;
+    }
+
+    /// Copies the congestion of the shortest and optimized routes again in the layer now selected.
+    /// While the routes are being optimized, the congestion of the optimization is used.
+    fn refresh_congestion_layer(&mut self) {
+        if *self.congestion_status.lock().unwrap() != CongestionStatus::Successful {
+            return;
+        }
+        let graph = self.building_graph.lock().unwrap().clone();
+        let layers = self.congestion_layers.lock().unwrap().clone();
+        let mut rooms: Vec<String> = self.projection_coords.keys().cloned().collect();
+        rooms.push("G".to_owned());
+        let transitions: Vec<(u32, usize)> = (1..=5)
+            .flat_map(|day| (0..=11).map(move |period| (day, period)))
+            .collect();
+
+        let shortest_routes = self.student_routes_shortest.lock().unwrap().clone();
+        if let Some(routes) = shortest_routes {
+            let congestion = CongestionIndex::from_routes(&graph, &routes);
+            let congestion = layers.apply(&congestion);
+            copy_congestion(
+                &congestion,
+                &graph,
+                &rooms,
+                &transitions,
+                &mut self.congestion_point_data.lock().unwrap(),
+                &mut self.congestion_path_data.lock().unwrap(),
+                &mut self.congestion_statistics.lock().unwrap()
+            );
+            *self.maximum_congestion.lock().unwrap() = congestion.max_node_load();
+        }
+        let is_optimizing =
+            *self.optimization_status.lock().unwrap() == OptimizationStatus::Calculating;
+        let optimized_congestion = self.optimization_store
+            .as_ref()
+            .filter(|_| is_optimizing)
+            .and_then(|store| store.with_congestion(|congestion| congestion.clone()))
+            .or_else(|| {
+                self.student_routes_optimized
+                    .lock()
+                    .unwrap()
+                    .as_ref()
+                    .map(|routes| CongestionIndex::from_routes(&graph, routes))
+            });
+        if let Some(congestion) = optimized_congestion {
+            let congestion = layers.apply(&congestion);
+            copy_congestion(
+                &congestion,
+                &graph,
+                &rooms,
+                &transitions,
+                &mut self.congestion_point_data_opt.lock().unwrap(),
+                &mut self.congestion_path_data_opt.lock().unwrap(),
+                &mut self.congestion_statistics_opt.lock().unwrap()
+            );
+            *self.maximum_congestion_opt.lock().unwrap() = congestion.max_node_load();
+        }
+    }
+
+    fn show_room_swap_window(&mut self, ctx: &egui::Context, current_room_swap_status: RoomSwapStatus) {
+        Window::new("Room Reassignment").show(ctx, |ui| {
+            match current_room_swap_status {
+                RoomSwapStatus::ParamInput => {
+                    ui.heading("Parameters");
+                    ui.add(
+                        Slider::new(&mut self.param_swap_candidates, 1..=20).text(
+                            "Candidate rooms per session"
+                        )
+                    );
+                    ui.label(
+                        "Each session is tried in its nearest rooms. A room is only used when it is free in that period, or by swapping with the session held there."
+                    );
+                    ui.separator();
+                    ui.with_layout(Layout::top_down_justified(egui::Align::Center), |ui| {
+                        if ui.button("Start").clicked() {
+                            *self.room_swap_status.lock().unwrap() = RoomSwapStatus::Ready;
+                        }
+                        if ui.button("Close").clicked() {
+                            self.show_room_swap_window = false;
+                        }
+                    });
+                }
+                RoomSwapStatus::Ready => {
+                    *self.room_swap_status.lock().unwrap() = RoomSwapStatus::Searching(
+                        0,
+                        "Searching for room swaps".to_owned()
+                    );
+                    let room_swap_status_arc = self.room_swap_status.clone();
+                    let room_swap_report_arc = self.room_swap_report.clone();
+                    let timetable = self.timetable_file_info.timetable.lock().unwrap().clone();
+                    let shortest_paths = self.shortest_paths_json.clone();
+                    let path_distances = self.path_distances.lock().unwrap().clone();
+                    let candidate_count = self.param_swap_candidates;
                     thread::spawn(move || {
-                        let mut rooms: Vec<String> = projection_coords
-                            .keys()
-                            .map(|k| k.to_owned())
-                            .collect();
-                        *validation_status_arc.lock().unwrap() =
-                            TimetableValidationStatus::Validating(
-                                0,
-                                "Validating student numbers...".to_owned()
+                        let Some(timetable) = timetable else {
+                            *room_swap_status_arc.lock().unwrap() = RoomSwapStatus::Failed(
+                                "No valid timetable available".to_owned()
                             );
-                        rooms.push("G".into());
-                        let mut timetable_file = std::fs::File::open(filepath).unwrap();
-                        let mut timetable_json = String::new();
-                        if timetable_file.read_to_string(&mut timetable_json).is_err() {
-                            *validation_status_arc.lock().unwrap() =
-                                TimetableValidationStatus::Failed(
-                                    "Failed to read timetable file".to_owned()
-                                );
                             return;
-                        }
-                        let timetable: serde_json::Value = match
-                            serde_json::from_str(&timetable_json)
-                        {
-                            Ok(t) => t,
-                            Err(e) => {
-                                *validation_status_arc.lock().unwrap() =
-                                    TimetableValidationStatus::Failed(
-                                        format!("Invalid JSON format in timetable file: {}", e)
-                                    );
-                                return;
-                            }
                         };
-                        let mut student_count = 0;
-
-                        if timetable.as_object().is_none() {
-                            *validation_status_arc.lock().unwrap() =
-                                TimetableValidationStatus::Failed(
-                                    "Invalid timetable file format: the JSON file is not a map".to_owned()
+                        let report = suggest_room_swaps(
+                            &timetable,
+                            &shortest_paths,
+                            &path_distances,
+                            candidate_count,
+                            |progress| {
+                                *room_swap_status_arc.lock().unwrap() = RoomSwapStatus::Searching(
+                                    progress,
+                                    "Searching for room swaps".to_owned()
+                                );
+                            }
+                        );
+                        let report = match report {
+                            Ok(report) => report,
+                            Err(error) => {
+                                *room_swap_status_arc.lock().unwrap() = RoomSwapStatus::Failed(
+                                    error.to_string()
                                 );
-                            return;
-                        }
-                        for student_key in timetable.as_object().unwrap().keys() {
-                            if
-                                student_key.chars().all(char::is_numeric) &&
-                                4 <= student_key.len() &&
-                                student_key.len() <= 5
-                            {
-                                student_count += 1;
-                                *student_count_arc.lock().unwrap() = Some(student_count);
-                            } else {
-                                *validation_status_arc.lock().unwrap() =
-                                    TimetableValidationStatus::Failed(
-                                        format!("Invalid student number: \"{}\"", student_key)
-                                    );
                                 return;
                             }
+                        };
+                        *room_swap_report_arc.lock().unwrap() = Some(report);
+                        *room_swap_status_arc.lock().unwrap() = RoomSwapStatus::Successful;
+                    });
+                }
+                RoomSwapStatus::Searching(progress, message) => {
+                    ui.with_layout(Layout::top_down_justified(egui::Align::Center), |ui| {
+                        ui.label(RichText::new(material_design_icons::MDI_DOOR_OPEN).size(32.0));
+                        ui.label(message);
+                        ui.add(ProgressBar::new((progress as f32) / 100.0));
+                    });
+                }
+                RoomSwapStatus::Failed(message) => {
+                    ui.with_layout(Layout::top_down_justified(egui::Align::Center), |ui| {
+                        ui.label(
+                            RichText::new(material_design_icons::MDI_DOOR_CLOSED_CANCEL)
+                                .size(32.0)
+                                .color(Color32::from_rgb(0xe4, 0x37, 0x48))
+                        );
+                        ui.label("Room reassignment failed");
+                        ui.label(message);
+                        if ui.button("Close").clicked() {
+                            self.show_room_swap_window = false;
+                            *self.room_swap_status.lock().unwrap() = RoomSwapStatus::ParamInput;
                         }
+                    });
+                }
+                RoomSwapStatus::Successful => {
+                    let report = self.room_swap_report.lock().unwrap().clone().unwrap_or_default();
+                    Grid::new("room_swap_summary_grid")
+                        .num_columns(2)
+                        .show(ui, |ui| {
+                            ui.label("Baseline PI");
+                            ui.label((report.baseline_pi as u128).to_formatted_string(&Locale::fr));
+                            ui.end_row();
 
-                        *validation_status_arc.lock().unwrap() =
-                            TimetableValidationStatus::Validating(
-                                10,
-                                "Validating days of the week...".to_owned()
-                            );
-                        for (student_key, week_timetable) in timetable.as_object().unwrap() {
-                            if week_timetable.as_object().is_none() {
-                                *validation_status_arc.lock().unwrap() =
-                                    TimetableValidationStatus::Failed(
-                                        format!("Invalid timetable file format: student {}'s timetable is not a map", student_key)
-                                    );
-                                return;
-                            }
-                            let mut days_of_week = [false; 5];
-                            for day_key in week_timetable.as_object().unwrap().keys() {
-                                if
-                                    day_key.chars().all(char::is_numeric) &&
-                                    day_key.parse::<i32>().unwrap() <= 5 &&
-                                    day_key.parse::<i32>().unwrap() >= 1
-                                {
-                                    days_of_week[
-                                        (day_key.parse::<i32>().unwrap() as usize) - 1
-                                    ] = true;
-                                } else {
-                                    *validation_status_arc.lock().unwrap() =
-                                        TimetableValidationStatus::Failed(
-                                            format!(
-                                                "Student {} has an invalid day of week: \"{}\"",
-                                                student_key,
-                                                day_key
+                            ui.label("Patched PI");
+                            ui.label((report.patched_pi as u128).to_formatted_string(&Locale::fr));
+                            ui.end_row();
+
+                            ui.label("Suggestions");
+                            ui.label(report.suggestions.len().to_string());
+                            ui.end_row();
+
+                            ui.label("Applied to patched timetable");
+                            ui.label(report.applied_count.to_string());
+                            ui.end_row();
+                        });
+                    ui.separator();
+                    ui.label(
+                        format!("Top {} suggestions", report.suggestions.len().min(MAX_ROOM_SWAPS_SHOWN))
+                    );
+                    egui::ScrollArea
+                        ::vertical()
+                        .max_height(300.0)
+                        .show(ui, |ui| {
+                            Grid::new("room_swap_grid")
+                                .striped(true)
+                                .num_columns(5)
+                                .show(ui, |ui| {
+                                    ui.label("Rank");
+                                    ui.label("Day");
+                                    ui.label("Period");
+                                    ui.label("Change");
+                                    ui.label("PI gain");
+                                    ui.end_row();
+                                    for (rank, suggestion) in report.suggestions
+                                        .iter()
+                                        .take(MAX_ROOM_SWAPS_SHOWN)
+                                        .enumerate() {
+                                        ui.label((rank + 1).to_string());
+                                        ui.label(convert_day_of_week(suggestion.day.number()));
+                                        ui.label(format!("P{}", suggestion.period));
+                                        if suggestion.target_student_count == 0 {
+                                            ui.label(
+                                                format!(
+                                                    "{} → {} ({} students)",
+                                                    suggestion.room,
+                                                    suggestion.target_room,
+                                                    suggestion.student_count
+                                                )
+                                            );
+                                        } else {
+                                            ui.label(
+                                                format!(
+                                                    "{} ⇄ {} ({} and {} students)",
+                                                    suggestion.room,
+                                                    suggestion.target_room,
+                                                    suggestion.student_count,
+                                                    suggestion.target_student_count
+                                                )
+                                            );
+                                        }
+                                        ui.label(
+                                            (suggestion.gain as u128).to_formatted_string(
+                                                &Locale::fr
                                             )
                                         );
-                                    return;
-                                }
-                            }
-                            if days_of_week.contains(&false) {
-                                *validation_status_arc.lock().unwrap() =
-                                    TimetableValidationStatus::Failed(
-                                        format!(
-                                            "Student {} has an incomplete timetable: missing day {}",
-                                            student_key,
-                                            days_of_week
-                                                .iter()
-                                                .enumerate()
-                                                .filter(|(_, &b)| !b)
-                                                .map(|(i, _)| i + 1)
-                                                .collect::<Vec<usize>>()
-                                                .iter()
-                                                .map(|i| i.to_string())
-                                                .collect::<Vec<String>>()
-                                                .join(", ")
-                                        )
-                                    );
-                                return;
+                                        ui.end_row();
+                                    }
+                                });
+                        });
+                    ui.separator();
+                    ui.with_layout(Layout::top_down_justified(egui::Align::Center), |ui| {
+                        if ui.button("Export patched timetable").clicked() {
+                            let file = FileDialog::new().add_filter("JSON", &["json"]).save_file();
+                            if let Some(file) = file {
+                                let mut file = File::create(file).unwrap();
+                                let json = serde_json
+                                    ::to_string(&report.patched_timetable)
+                                    .unwrap();
+                                file.write_all(json.as_bytes()).unwrap();
                             }
                         }
+                        if ui.button("Close").clicked() {
+                            self.show_room_swap_window = false;
+                            *self.room_swap_status.lock().unwrap() = RoomSwapStatus::ParamInput;
+                        }
+                    });
+                }
+            }
+        });
+    }
 
+    fn show_dismissal_window(&mut self, ctx: &egui::Context, current_dismissal_status: DismissalStatus) {
+        Window::new("Staggered Dismissal").show(ctx, |ui| {
+            match current_dismissal_status {
+                DismissalStatus::ParamInput => {
+                    ui.heading("Parameters");
+                    ui.add(
+                        Slider::new(&mut self.param_walking_speed, 500.0..=3000.0)
+                            .step_by(100.0)
+                            .text("Walking speed (units per minute)")
+                    );
+                    ui.add(
+                        Slider::new(&mut self.param_slot_length, 0.25..=2.0)
+                            .step_by(0.25)
+                            .text("Time slot length (minutes)")
+                    );
+                    ui.label(format!("Routes evaluated: {}", self.path_display));
+                    ui.separator();
+                    ui.heading("Release offsets");
+                    ui.label("Offsets are in minutes; negative offsets release students earlier.");
+                    {
+                        let mut offsets = self.dismissal_offsets.lock().unwrap();
+                        offsets.add_cohorts(self.student_list.lock().unwrap().iter());
+                        fn offset_drag_value(value: &mut f32) -> egui::DragValue<'_> {
+                            egui::DragValue
+                                ::new(value)
+                                .speed(0.1)
+                                .clamp_range(-10.0..=10.0)
+                                .suffix(" min")
+                        }
+                        egui::CollapsingHeader::new("Cohort").show(ui, |ui| {
+                            Grid::new("dismissal_cohort_grid")
+                                .num_columns(2)
+                                .show(ui, |ui| {
+                                    for (cohort, offset) in offsets.cohort.iter_mut() {
+                                        ui.label(format!("{}xxx", cohort));
+                                        ui.add(offset_drag_value(offset));
+                                        ui.end_row();
+                                    }
+                                });
+                        });
+                        egui::CollapsingHeader::new("Block").show(ui, |ui| {
+                            Grid::new("dismissal_block_grid")
+                                .num_columns(2)
+                                .show(ui, |ui| {
+                                    for (block, offset) in offsets.block.iter_mut() {
+                                        ui.label(format!("{} block", block));
+                                        ui.add(offset_drag_value(offset));
+                                        ui.end_row();
+                                    }
+                                });
+                        });
+                        egui::CollapsingHeader::new("Floor").show(ui, |ui| {
+                            Grid::new("dismissal_floor_grid")
+                                .num_columns(2)
+                                .show(ui, |ui| {
+                                    for (floor, offset) in offsets.floor.iter_mut() {
+                                        ui.label(format!("{}F", floor));
+                                        ui.add(offset_drag_value(offset));
+                                        ui.end_row();
+                                    }
+                                });
+                        });
+                        if ui.button("Reset offsets").clicked() {
+                            offsets.reset();
+                        }
+                    }
+                    ui.separator();
+                    ui.heading("Offset search");
+                    ComboBox::from_label("Offset by")
+                        .selected_text(format!("{}", self.param_offset_group))
+                        .show_ui(ui, |ui| {
+                            for group in [OffsetGroup::Cohort, OffsetGroup::Block, OffsetGroup::Floor] {
+                                ui.selectable_value(
+                                    &mut self.param_offset_group,
+                                    group,
+                                    format!("{}", group)
+                                );
+                            }
+                        });
+                    ui.add(
+                        Slider::new(&mut self.param_max_offset, 0.5..=5.0)
+                            .step_by(0.5)
+                            .text("Maximum offset (minutes)")
+                    );
+                    ui.separator();
+                    ui.with_layout(Layout::top_down_justified(egui::Align::Center), |ui| {
+                        if ui.button("Evaluate").clicked() {
+                            *self.dismissal_status.lock().unwrap() = DismissalStatus::Ready;
+                        }
+                        if
+                            ui
+                                .button("Search offsets")
+                                .on_hover_text(
+                                    "Search the offsets of the selected groups to minimise the peak congestion of each transition."
+                                )
+                                .clicked()
+                        {
+                            *self.dismissal_status.lock().unwrap() =
+                                DismissalStatus::OptimizationReady;
+                        }
+                        if ui.button("Close").clicked() {
+                            self.show_dismissal_window = false;
+                        }
+                    });
+                }
+                DismissalStatus::Ready | DismissalStatus::OptimizationReady => {
+                    let optimize = current_dismissal_status == DismissalStatus::OptimizationReady;
+                    *self.dismissal_status.lock().unwrap() = if optimize {
+                        DismissalStatus::Searching(0, "Searching release offsets".to_owned())
+                    } else {
+                        DismissalStatus::Evaluating
+                    };
+                    let student_routes = if self.path_display == PathDisplay::Optimized {
+                        self.student_routes_optimized.lock().unwrap().clone()
+                    } else {
+                        self.student_routes_shortest.lock().unwrap().clone()
+                    };
+                    let Some(student_routes) = student_routes else {
+                        *self.dismissal_status.lock().unwrap() = DismissalStatus::Failed(
+                            "No path data available".to_owned()
+                        );
+                        return;
+                    };
+                    let dismissal_status_arc = self.dismissal_status.clone();
+                    let dismissal_offsets_arc = self.dismissal_offsets.clone();
+                    let dismissal_reports_arc = self.dismissal_reports.clone();
+                    let path_distances = self.path_distances.lock().unwrap().clone();
+                    let params = TimingParams {
+                        walking_speed: self.param_walking_speed,
+                        slot_length: self.param_slot_length,
+                    };
+                    let search = OffsetSearch {
+                        group: self.param_offset_group,
+                        max_offset: self.param_max_offset,
+                        step: 0.5,
+                    };
+                    thread::spawn(move || {
+                        let model = match
+                            TimingModel::new(&student_routes.to_routes(), &path_distances, params)
+                        {
+                            Ok(model) => model,
+                            Err(error) => {
+                                *dismissal_status_arc.lock().unwrap() = DismissalStatus::Failed(
+                                    error.to_string()
+                                );
+                                return;
+                            }
+                        };
+                        if optimize {
+                            let initial_offsets = dismissal_offsets_arc.lock().unwrap().clone();
+                            let offsets = optimize_offsets(
+                                &model,
+                                &initial_offsets,
+                                search,
+                                |progress| {
+                                    *dismissal_status_arc.lock().unwrap() =
+                                        DismissalStatus::Searching(
+                                            progress,
+                                            "Searching release offsets".to_owned()
+                                        );
+                                }
+                            );
+                            *dismissal_offsets_arc.lock().unwrap() = offsets;
+                            *dismissal_status_arc.lock().unwrap() = DismissalStatus::Evaluating;
+                        }
+                        let offsets = dismissal_offsets_arc.lock().unwrap().clone();
+                        let baseline = model.evaluate(&DismissalOffsets::default());
+                        let staggered = model.evaluate(&offsets);
+                        *dismissal_reports_arc.lock().unwrap() = Some((baseline, staggered));
+                        *dismissal_status_arc.lock().unwrap() = DismissalStatus::Successful;
+                    });
+                }
+                DismissalStatus::Searching(progress, message) => {
+                    ui.with_layout(Layout::top_down_justified(egui::Align::Center), |ui| {
+                        ui.label(RichText::new(material_design_icons::MDI_CLOCK_FAST).size(32.0));
+                        ui.label(message);
+                        ui.add(ProgressBar::new((progress as f32) / 100.0));
+                    });
+                }
+                DismissalStatus::Evaluating => {
+                    ui.with_layout(Layout::top_down_justified(egui::Align::Center), |ui| {
+                        ui.label(RichText::new(material_design_icons::MDI_CLOCK_FAST).size(32.0));
+                        ui.label("Evaluating time-resolved congestion");
+                        ui.spinner();
+                    });
+                }
+                DismissalStatus::Failed(message) => {
+                    ui.with_layout(Layout::top_down_justified(egui::Align::Center), |ui| {
+                        ui.label(
+                            RichText::new(material_design_icons::MDI_CLOCK_ALERT)
+                                .size(32.0)
+                                .color(Color32::from_rgb(0xe4, 0x37, 0x48))
+                        );
+                        ui.label("Dismissal evaluation failed");
+                        ui.label(message);
+                        if ui.button("Close").clicked() {
+                            self.show_dismissal_window = false;
+                            *self.dismissal_status.lock().unwrap() = DismissalStatus::ParamInput;
+                        }
+                    });
+                }
+                DismissalStatus::Successful => {
+                    let Some((baseline, staggered)) = self.dismissal_reports
+                        .lock()
+                        .unwrap()
+                        .clone() else {
+                        return;
+                    };
+                    ui.heading("Summary");
+                    Grid::new("dismissal_summary_grid")
+                        .striped(true)
+                        .num_columns(3)
+                        .show(ui, |ui| {
+                            ui.label("");
+                            ui.label("Simultaneous");
+                            ui.label("Staggered");
+                            ui.end_row();
+
+                            ui.label("Peak congestion");
+                            ui.label(baseline.peak_congestion().to_string());
+                            ui.label(staggered.peak_congestion().to_string());
+                            ui.end_row();
+
+                            ui.label("Sum of transition peaks");
+                            ui.label(baseline.peak_congestion_sum().to_formatted_string(&Locale::fr));
+                            ui.label(staggered.peak_congestion_sum().to_formatted_string(&Locale::fr));
+                            ui.end_row();
+
+                            ui.label("Time-resolved PI");
+                            ui.label(
+                                (baseline.performance_index() as u128).to_formatted_string(&Locale::fr)
+                            );
+                            ui.label(
+                                (staggered.performance_index() as u128).to_formatted_string(
+                                    &Locale::fr
+                                )
+                            );
+                            ui.end_row();
+                        });
+                    ui.separator();
+                    ui.heading(
+                        format!(
+                            "{}, {}",
+                            convert_day_of_week(self.selected_day),
+                            convert_periods(self.selected_period)
+                        )
+                    );
+                    ui.label("Highest edge load in each time slot");
+                    egui::ScrollArea
+                        ::vertical()
+                        .max_height(240.0)
+                        .show(ui, |ui| {
+                            ui.columns(2, |columns| {
+                                for (column, (title, report)) in columns
+                                    .iter_mut()
+                                    .zip([
+                                        ("Simultaneous", &baseline),
+                                        ("Staggered", &staggered),
+                                    ]) {
+                                    column.label(title);
+                                    let timing = &report.transitions[
+                                        &(self.selected_day, self.selected_period)
+                                    ];
+                                    Grid::new(format!("dismissal_slot_grid_{}", title))
+                                        .striped(true)
+                                        .num_columns(2)
+                                        .show(column, |ui| {
+                                            for (slot, peak) in timing.slot_peaks
+                                                .iter()
+                                                .enumerate() {
+                                                ui.label(
+                                                    format!(
+                                                        "{:+.2} min",
+                                                        timing.start +
+                                                            (slot as f32) * self.param_slot_length
+                                                    )
+                                                );
+                                                ui.label(
+                                                    RichText::new(peak.to_string()).color(
+                                                        congestion_color_scale(*peak)
+                                                    )
+                                                );
+                                                ui.end_row();
+                                            }
+                                        });
+                                }
+                            });
+                        });
+                    ui.separator();
+                    ui.with_layout(Layout::top_down_justified(egui::Align::Center), |ui| {
+                        if ui.button("Adjust offsets").clicked() {
+                            *self.dismissal_status.lock().unwrap() = DismissalStatus::ParamInput;
+                        }
+                        if ui.button("Close").clicked() {
+                            self.show_dismissal_window = false;
+                            *self.dismissal_status.lock().unwrap() = DismissalStatus::ParamInput;
+                        }
+                    });
+                }
+            }
+        });
+    }
+
+    fn show_monte_carlo_window(
+        &mut self,
+        ctx: &egui::Context,
+        current_monte_carlo_status: MonteCarloStatus
+    ) {
+        Window::new("Monte Carlo Congestion").show(ctx, |ui| {
+            match current_monte_carlo_status {
+                MonteCarloStatus::ParamInput => {
+                    ui.heading("Parameters");
+                    let params = &mut self.param_monte_carlo;
+                    ui.add(
+                        Slider::new(&mut params.compliance, 0.0..=1.0)
+                            .step_by(0.05)
+                            .text("Compliance probability")
+                    ).on_hover_text(
+                        "Probability that a student follows the planned route in a transition."
+                    );
+                    ui.label("Otherwise the student walks");
+                    for deviation in [Deviation::Shortest, Deviation::NearShortest] {
+                        ui.radio_value(&mut params.deviation, deviation, deviation.to_string());
+                    }
+                    ui.add_enabled(
+                        params.deviation == Deviation::NearShortest,
+                        Slider::new(&mut params.slack, 0.0..=1.0)
+                            .step_by(0.05)
+                            .text("Near-shortest slack")
+                    ).on_hover_text(
+                        "Largest extra distance of a near-shortest route, as a fraction of the shortest distance."
+                    );
+                    ui.add(Slider::new(&mut params.runs, 10..=1000).text("Runs"));
+                    ui.horizontal(|ui| {
+                        ui.label("Seed");
+                        ui.add(egui::DragValue::new(&mut params.seed));
+                    });
+                    ui.label(
+                        "Both the shortest and the optimized routes are simulated with the same parameters."
+                    );
+                    ui.separator();
+                    ui.with_layout(Layout::top_down_justified(egui::Align::Center), |ui| {
+                        if ui.button("Simulate").clicked() {
+                            *self.monte_carlo_status.lock().unwrap() = MonteCarloStatus::Ready;
+                        }
+                        if ui.button("Close").clicked() {
+                            self.show_monte_carlo_window = false;
+                        }
+                    });
+                }
+                MonteCarloStatus::Ready => {
+                    *self.monte_carlo_status.lock().unwrap() = MonteCarloStatus::Simulating(
+                        0,
+                        "Simulating the shortest routes".to_owned()
+                    );
+                    let Some(shortest_routes) = self.student_routes_shortest
+                        .lock()
+                        .unwrap()
+                        .clone() else {
+                        *self.monte_carlo_status.lock().unwrap() = MonteCarloStatus::Failed(
+                            "No path data available".to_owned()
+                        );
+                        return;
+                    };
+                    let optimized_routes = self.student_routes_optimized.lock().unwrap().clone();
+                    let monte_carlo_status_arc = self.monte_carlo_status.clone();
+                    let monte_carlo_reports_arc = self.monte_carlo_reports.clone();
+                    let graph = self.building_graph.lock().unwrap().clone();
+                    let shortest_paths = self.shortest_paths_json.clone();
+                    let params = self.param_monte_carlo;
+                    self.monte_carlo_message = None;
+                    thread::spawn(move || {
+                        // the optimized routes take the second half of the progress bar
+                        let plan_count = if optimized_routes.is_some() { 2 } else { 1 };
+                        let progress = |plan: i32, message: &'static str| {
+                            let status_arc = monte_carlo_status_arc.clone();
+                            move |progress: i32| {
+                                *status_arc.lock().unwrap() = MonteCarloStatus::Simulating(
+                                    (plan * 100 + progress) / plan_count,
+                                    message.to_owned()
+                                );
+                            }
+                        };
+                        let shortest = simulate(
+                            &graph,
+                            &shortest_paths,
+                            &shortest_routes,
+                            params,
+                            progress(0, "Simulating the shortest routes")
+                        );
+                        let optimized = optimized_routes.map(|routes| {
+                            simulate(
+                                &graph,
+                                &shortest_paths,
+                                &routes,
+                                params,
+                                progress(1, "Simulating the optimized routes")
+                            )
+                        });
+                        *monte_carlo_reports_arc.lock().unwrap() = Some((shortest, optimized));
+                        *monte_carlo_status_arc.lock().unwrap() = MonteCarloStatus::Successful;
+                    });
+                }
+                MonteCarloStatus::Simulating(progress, message) => {
+                    ui.with_layout(Layout::top_down_justified(egui::Align::Center), |ui| {
+                        ui.label(
+                            RichText::new(material_design_icons::MDI_DICE_MULTIPLE).size(32.0)
+                        );
+                        ui.label(message);
+                        ui.add(ProgressBar::new((progress as f32) / 100.0));
+                    });
+                }
+                MonteCarloStatus::Failed(message) => {
+                    ui.with_layout(Layout::top_down_justified(egui::Align::Center), |ui| {
+                        ui.label(
+                            RichText::new(material_design_icons::MDI_DICE_MULTIPLE)
+                                .size(32.0)
+                                .color(Color32::from_rgb(0xe4, 0x37, 0x48))
+                        );
+                        ui.label("Monte Carlo simulation failed");
+                        ui.label(message);
+                        if ui.button("Close").clicked() {
+                            self.show_monte_carlo_window = false;
+                            *self.monte_carlo_status.lock().unwrap() = MonteCarloStatus::ParamInput;
+                        }
+                    });
+                }
+                MonteCarloStatus::Successful => {
+                    let reports = self.monte_carlo_reports.lock().unwrap();
+                    let Some((shortest, optimized)) = reports.as_ref() else {
+                        return;
+                    };
+                    let plans: Vec<(&str, &MonteCarloReport)> = [
+                        Some(("Shortest", shortest)),
+                        optimized.as_ref().map(|optimized| ("Optimized", optimized)),
+                    ]
+                        .into_iter()
+                        .flatten()
+                        .collect();
+                    let interval_label = |interval: ConfidenceInterval| {
+                        format!(
+                            "{} – {}",
+                            (interval.low.max(0.0) as u128).to_formatted_string(&Locale::fr),
+                            (interval.high as u128).to_formatted_string(&Locale::fr)
+                        )
+                    };
+                    ui.label(
+                        format!(
+                            "{} runs, {:.0}% compliance, otherwise {}",
+                            shortest.params.runs,
+                            shortest.params.compliance * 100.0,
+                            shortest.params.deviation.to_string().to_lowercase()
+                        )
+                    );
+                    ui.heading("Week");
+                    Grid::new("monte_carlo_summary_grid")
+                        .striped(true)
+                        .num_columns(plans.len() + 1)
+                        .show(ui, |ui| {
+                            ui.label("");
+                            for (title, _) in &plans {
+                                ui.label(*title);
+                            }
+                            ui.end_row();
+                            ui.label("Planned PI");
+                            for (_, report) in &plans {
+                                let performance_index = report.planned_performance_index() as u128;
+                                ui.label(performance_index.to_formatted_string(&Locale::fr));
+                            }
+                            ui.end_row();
+                            ui.label("Mean PI");
+                            for (_, report) in &plans {
+                                let performance_index = report.performance_index().mean as u128;
+                                ui.label(performance_index.to_formatted_string(&Locale::fr));
+                            }
+                            ui.end_row();
+                            ui.label("95% confidence interval");
+                            for (_, report) in &plans {
+                                ui.label(interval_label(report.performance_index()));
+                            }
+                            ui.end_row();
+                        });
+                    ui.separator();
+                    ui.heading(
+                        format!(
+                            "{}, {}",
+                            convert_day_of_week(self.selected_day),
+                            convert_periods(self.selected_period)
+                        )
+                    );
+                    let graph = self.building_graph.lock().unwrap();
+                    let edge_ends = graph.edge_ends();
+                    ui.columns(plans.len(), |columns| {
+                        for (column, (title, report)) in columns.iter_mut().zip(&plans) {
+                            column.label(RichText::new(*title).strong());
+                            let Some(samples) = report.transition(
+                                self.selected_day,
+                                self.selected_period
+                            ) else {
+                                continue;
+                            };
+                            let interval = samples.performance_index();
+                            column.label(
+                                format!(
+                                    "PI {} planned, {} mean",
+                                    (samples.planned_performance_index as u128).to_formatted_string(
+                                        &Locale::fr
+                                    ),
+                                    (interval.mean as u128).to_formatted_string(&Locale::fr)
+                                )
+                            );
+                            column.label(
+                                format!("95% confidence interval {}", interval_label(interval))
+                            );
+                            egui::ScrollArea
+                                ::vertical()
+                                .id_source(format!("monte_carlo_edges_{}", title))
+                                .max_height(240.0)
+                                .show(column, |ui| {
+                                    Grid::new(format!("monte_carlo_edge_grid_{}", title))
+                                        .striped(true)
+                                        .num_columns(3)
+                                        .show(ui, |ui| {
+                                            ui.label("Edge");
+                                            ui.label("Mean");
+                                            ui.label("95th pct.");
+                                            ui.end_row();
+                                            for (edge, mean, percentile_95) in samples.edges() {
+                                                let (node1, node2) = edge_ends[edge];
+                                                ui.label(
+                                                    format!(
+                                                        "{} – {}",
+                                                        graph.node_name(node1),
+                                                        graph.node_name(node2)
+                                                    )
+                                                );
+                                                ui.label(format!("{:.1}", mean));
+                                                ui.label(
+                                                    RichText::new(percentile_95.to_string()).color(
+                                                        congestion_color_scale(percentile_95)
+                                                    )
+                                                );
+                                                ui.end_row();
+                                            }
+                                        });
+                                });
+                        }
+                    });
+                    ui.separator();
+                    let mut export = None;
+                    ui.with_layout(Layout::top_down_justified(egui::Align::Center), |ui| {
+                        for (title, report) in &plans {
+                            let label = format!("Export {} routes as CSV", title.to_lowercase());
+                            if ui.button(label).clicked() {
+                                export = Some(report.csv(&graph));
+                            }
+                        }
+                        if let Some(message) = &self.monte_carlo_message {
+                            ui.label(message);
+                        }
+                        if ui.button("Adjust parameters").clicked() {
+                            *self.monte_carlo_status.lock().unwrap() = MonteCarloStatus::ParamInput;
+                        }
+                        if ui.button("Close").clicked() {
+                            self.show_monte_carlo_window = false;
+                            *self.monte_carlo_status.lock().unwrap() = MonteCarloStatus::ParamInput;
+                        }
+                    });
+                    let file = export.and_then(|csv| {
+                        FileDialog::new()
+                            .add_filter("CSV", &["csv"])
+                            .save_file()
+                            .map(|file| (file, csv))
+                    });
+                    if let Some((file, csv)) = file {
+                        self.monte_carlo_message = Some(match fs::write(&file, csv) {
+                            Ok(()) => format!("Wrote {}.", file.display()),
+                            Err(error) => format!("Failed to write {}: {}", file.display(), error),
+                        });
+                    }
+                }
+            }
+        });
+    }
+
+    fn show_robustness_window(
+        &mut self,
+        ctx: &egui::Context,
+        current_robustness_status: RobustnessStatus
+    ) {
+        Window::new("Robustness").show(ctx, |ui| {
+            match current_robustness_status {
+                RobustnessStatus::ParamInput => {
+                    ui.heading("Perturbations");
+                    perturbation_ui(ui, &mut self.param_perturbation);
+                    ui.label(
+                        "Both the shortest and the optimized routes are scored against the same scenarios. The robust objectives of the optimizer use these parameters too."
+                    );
+                    ui.separator();
+                    ui.with_layout(Layout::top_down_justified(egui::Align::Center), |ui| {
+                        if ui.button("Evaluate").clicked() {
+                            *self.robustness_status.lock().unwrap() = RobustnessStatus::Ready;
+                        }
+                        if ui.button("Close").clicked() {
+                            self.show_robustness_window = false;
+                        }
+                    });
+                }
+                RobustnessStatus::Ready => {
+                    *self.robustness_status.lock().unwrap() = RobustnessStatus::Evaluating(
+                        "Sampling scenarios".to_owned()
+                    );
+                    let Some(timetable) = self.timetable_file_info.timetable
+                        .lock()
+                        .unwrap()
+                        .clone() else {
+                        *self.robustness_status.lock().unwrap() = RobustnessStatus::Failed(
+                            "No timetable imported".to_owned()
+                        );
+                        return;
+                    };
+                    let Some(shortest_routes) = self.student_routes_shortest
+                        .lock()
+                        .unwrap()
+                        .clone() else {
+                        *self.robustness_status.lock().unwrap() = RobustnessStatus::Failed(
+                            "No path data available".to_owned()
+                        );
+                        return;
+                    };
+                    let optimized_routes = self.student_routes_optimized.lock().unwrap().clone();
+                    let destinations = self.destination_assignment(&timetable);
+                    let robustness_status_arc = self.robustness_status.clone();
+                    let robustness_reports_arc = self.robustness_reports.clone();
+                    let graph = self.building_graph.lock().unwrap().clone();
+                    let shortest_paths = self.shortest_paths_json.clone();
+                    let params = self.param_perturbation;
+                    thread::spawn(move || {
+                        let scenarios = robustness::scenarios(
+                            &timetable,
+                            &destinations,
+                            &shortest_paths,
+                            params
+                        );
+                        *robustness_status_arc.lock().unwrap() = RobustnessStatus::Evaluating(
+                            "Scoring the routes".to_owned()
+                        );
+                        let shortest = robustness::evaluate(&graph, &shortest_routes, &scenarios);
+                        let optimized = optimized_routes.map(|routes| {
+                            robustness::evaluate(&graph, &routes, &scenarios)
+                        });
+                        *robustness_reports_arc.lock().unwrap() = Some((
+                            scenarios,
+                            shortest,
+                            optimized,
+                        ));
+                        *robustness_status_arc.lock().unwrap() = RobustnessStatus::Successful;
+                    });
+                }
+                RobustnessStatus::Evaluating(message) => {
+                    ui.with_layout(Layout::top_down_justified(egui::Align::Center), |ui| {
+                        ui.label(
+                            RichText::new(material_design_icons::MDI_SHIELD_HALF_FULL).size(32.0)
+                        );
+                        ui.label(message);
+                        ui.spinner();
+                    });
+                }
+                RobustnessStatus::Failed(message) => {
+                    ui.with_layout(Layout::top_down_justified(egui::Align::Center), |ui| {
+                        ui.label(
+                            RichText::new(material_design_icons::MDI_SHIELD_HALF_FULL)
+                                .size(32.0)
+                                .color(Color32::from_rgb(0xe4, 0x37, 0x48))
+                        );
+                        ui.label("Robustness evaluation failed");
+                        ui.label(message);
+                        if ui.button("Close").clicked() {
+                            self.show_robustness_window = false;
+                            *self.robustness_status.lock().unwrap() = RobustnessStatus::ParamInput;
+                        }
+                    });
+                }
+                RobustnessStatus::Successful => {
+                    let reports = self.robustness_reports.lock().unwrap();
+                    let Some((scenarios, shortest, optimized)) = reports.as_ref() else {
+                        return;
+                    };
+                    let plans: Vec<(&str, &RobustnessReport)> = [
+                        Some(("Shortest", shortest)),
+                        optimized.as_ref().map(|optimized| ("Optimized", optimized)),
+                    ]
+                        .into_iter()
+                        .flatten()
+                        .collect();
+                    let pi_label = |performance_index: f64| {
+                        (performance_index as u128).to_formatted_string(&Locale::fr)
+                    };
+                    let interval_label = |interval: ConfidenceInterval| {
+                        format!(
+                            "{} – {}",
+                            pi_label(interval.low.max(0.0)),
+                            pi_label(interval.high)
+                        )
+                    };
+                    let scenario_count = scenarios.len().max(1) as f64;
+                    let mean = |count: fn(&Scenario) -> usize| {
+                        (scenarios.iter().map(count).sum::<usize>() as f64) / scenario_count
+                    };
+                    ui.label(
+                        format!(
+                            "{} scenarios with {:.0} absences, {:.0} room changes and {:.0} changed routes on average",
+                            scenarios.len(),
+                            mean(|scenario| scenario.absences),
+                            mean(|scenario| scenario.room_changes),
+                            mean(Scenario::changed_route_count)
+                        )
+                    );
+                    ui.heading("Week");
+                    Grid::new("robustness_summary_grid")
+                        .striped(true)
+                        .num_columns(plans.len() + 1)
+                        .show(ui, |ui| {
+                            ui.label("");
+                            for (title, _) in &plans {
+                                ui.label(*title);
+                            }
+                            ui.end_row();
+                            ui.label("PI as given");
+                            for (_, report) in &plans {
+                                ui.label(pi_label(report.nominal_performance_index()));
+                            }
+                            ui.end_row();
+                            ui.label("Mean PI");
+                            for (_, report) in &plans {
+                                ui.label(pi_label(report.performance_index().mean));
+                            }
+                            ui.end_row();
+                            ui.label("95% confidence interval");
+                            for (_, report) in &plans {
+                                ui.label(interval_label(report.performance_index()));
+                            }
+                            ui.end_row();
+                            ui.label("Worst PI");
+                            for (_, report) in &plans {
+                                ui.label(pi_label(report.worst_performance_index()));
+                            }
+                            ui.end_row();
+                        });
+                    ui.separator();
+                    ui.heading(
+                        format!(
+                            "{}, {}",
+                            convert_day_of_week(self.selected_day),
+                            convert_periods(self.selected_period)
+                        )
+                    );
+                    let transitions: Vec<_> = plans
+                        .iter()
+                        .filter_map(|(_, report)| {
+                            report.transition(self.selected_day, self.selected_period)
+                        })
+                        .collect();
+                    Grid::new("robustness_transition_grid")
+                        .striped(true)
+                        .num_columns(plans.len() + 1)
+                        .show(ui, |ui| {
+                            ui.label("");
+                            for (title, _) in &plans {
+                                ui.label(*title);
+                            }
+                            ui.end_row();
+                            ui.label("PI as given");
+                            for (performance_index, _, _) in &transitions {
+                                ui.label(pi_label(*performance_index));
+                            }
+                            ui.end_row();
+                            ui.label("Mean PI");
+                            for (_, interval, _) in &transitions {
+                                ui.label(pi_label(interval.mean));
+                            }
+                            ui.end_row();
+                            ui.label("95% confidence interval");
+                            for (_, interval, _) in &transitions {
+                                ui.label(interval_label(*interval));
+                            }
+                            ui.end_row();
+                            ui.label("Worst PI");
+                            for (_, _, worst) in &transitions {
+                                ui.label(pi_label(*worst));
+                            }
+                            ui.end_row();
+                        });
+                    ui.separator();
+                    ui.with_layout(Layout::top_down_justified(egui::Align::Center), |ui| {
+                        if ui.button("Adjust parameters").clicked() {
+                            *self.robustness_status.lock().unwrap() = RobustnessStatus::ParamInput;
+                        }
+                        if ui.button("Close").clicked() {
+                            self.show_robustness_window = false;
+                            *self.robustness_status.lock().unwrap() = RobustnessStatus::ParamInput;
+                        }
+                    });
+                }
+            }
+        });
+    }
+
+    fn show_detour_window(&mut self, ctx: &egui::Context) {
+        let mut show_detour_window = self.show_detour_window;
+        Window::new("Detour Distribution")
+            .open(&mut show_detour_window)
+            .show(ctx, |ui| {
+                ui.heading("Limits");
+                detour_limits_ui(ui, &mut self.param_detour_limits);
+                if ui.button("Recalculate").clicked() {
+                    self.detour_report = None;
+                }
+                if self.detour_report.is_none() {
+                    let student_routes = self.student_routes_optimized.lock().unwrap();
+                    let Some(student_routes) = student_routes.as_ref() else {
+                        ui.label("No optimized routes available.");
+                        return;
+                    };
+                    let report = detour_report(
+                        &student_routes.to_routes(),
+                        &self.shortest_paths_json,
+                        &self.path_distances.lock().unwrap(),
+                        self.param_detour_limits,
+                        DETOUR_BUCKET_WIDTH
+                    );
+                    match report {
+                        Ok(report) => {
+                            self.detour_report = Some(report);
+                        }
+                        Err(error) => {
+                            ui.label(
+                                RichText::new(error.to_string()).color(
+                                    Color32::from_rgb(0xe4, 0x37, 0x48)
+                                )
+                            );
+                            return;
+                        }
+                    }
+                }
+                let report = self.detour_report.as_ref().unwrap();
+                ui.separator();
+                ui.heading("Optimized routes");
+                Grid::new("detour_statistics_grid")
+                    .striped(true)
+                    .num_columns(3)
+                    .show(ui, |ui| {
+                        let rows = |statistics: &DetourStatistics| {
+                            [
+                                ("Routes or days", statistics.count.to_string()),
+                                ("With a detour", statistics.detoured.to_string()),
+                                ("Mean detour", format!("{:.1}", statistics.mean)),
+                                ("95th percentile", statistics.percentile_95.to_string()),
+                                ("Maximum detour", statistics.max.to_string()),
+                                (
+                                    "Maximum relative detour",
+                                    format!("{:.1}%", statistics.max_relative * 100.0),
+                                ),
+                                ("Exceeding the limits", statistics.violations.to_string()),
+                            ]
+                        };
+                        ui.label("");
+                        ui.label("Per transition");
+                        ui.label("Per day");
+                        ui.end_row();
+                        for ((label, transition_value), (_, day_value)) in rows(
+                            &report.transition
+                        )
+                            .into_iter()
+                            .zip(rows(&report.day)) {
+                            ui.label(label);
+                            ui.label(transition_value);
+                            ui.label(day_value);
+                            ui.end_row();
+                        }
+                    });
+                ui.separator();
+                ui.label(
+                    format!(
+                        "Number of detours in steps of {} (routes without a detour are not shown)",
+                        report.bucket_width
+                    )
+                );
+                ui.columns(2, |columns| {
+                    for (column, (title, statistics)) in columns
+                        .iter_mut()
+                        .zip([
+                            ("Per transition", &report.transition),
+                            ("Per day", &report.day),
+                        ]) {
+                        column.label(title);
+                        let bars = statistics.histogram
+                            .iter()
+                            .enumerate()
+                            .map(|(bucket, count)| {
+                                egui::plot::Bar
+                                    ::new(
+                                        ((bucket as f64) + 0.5) * (report.bucket_width as f64),
+                                        *count as f64
+                                    )
+                                    .width(report.bucket_width as f64)
+                            })
+                            .collect();
+                        egui::plot::Plot
+                            ::new(format!("detour_histogram_{}", title))
+                            .height(160.0)
+                            .allow_scroll(false)
+                            .show(column, |plot_ui| {
+                                plot_ui.bar_chart(
+                                    egui::plot::BarChart
+                                        ::new(bars)
+                                        .color(Color32::from_rgb(0xec, 0x6f, 0x27))
+                                );
+                            });
+                    }
+                });
+                if !report.violations.is_empty() {
+                    ui.separator();
+                    ui.label(
+                        format!(
+                            "Largest {} detours exceeding the limits",
+                            report.violations.len().min(MAX_DETOUR_VIOLATIONS_SHOWN)
+                        )
+                    );
+                    egui::ScrollArea
+                        ::vertical()
+                        .max_height(200.0)
+                        .show(ui, |ui| {
+                            Grid::new("detour_violation_grid")
+                                .striped(true)
+                                .num_columns(5)
+                                .show(ui, |ui| {
+                                    ui.label("Student");
+                                    ui.label("Day");
+                                    ui.label("Period");
+                                    ui.label("Detour");
+                                    ui.label("Shortest distance");
+                                    ui.end_row();
+                                    for violation in report.violations
+                                        .iter()
+                                        .take(MAX_DETOUR_VIOLATIONS_SHOWN) {
+                                        ui.label(&violation.student_number);
+                                        ui.label(convert_day_of_week(violation.day));
+                                        ui.label(
+                                            violation.transition
+                                                .map(convert_periods)
+                                                .unwrap_or("Whole day".to_owned())
+                                        );
+                                        ui.label(violation.detour.to_string());
+                                        ui.label(violation.shortest_distance.to_string());
+                                        ui.end_row();
+                                    }
+                                });
+                        });
+                }
+            });
+        self.show_detour_window = show_detour_window;
+    }
+
+    fn load_pinning_rules(&mut self) {
+        let Some(file) = FileDialog::new().add_filter("YAML", &["yaml", "yml"]).pick_file() else {
+            return;
+        };
+        match load_rules(&file, &self.building_graph.lock().unwrap()) {
+            Ok(rules) => {
+                self.param_pinning_rules = rules;
+                self.param_pinning_file = file
+                    .file_name()
+                    .map_or_else(String::new, |name| name.to_string_lossy().into_owned());
+                self.pinning_message = None;
+                self.pinning_report = None;
+            }
+            Err(error) => {
+                self.pinning_message = Some(error.to_string());
+            }
+        }
+    }
+
+    fn show_pinning_window(&mut self, ctx: &egui::Context) {
+        let mut show_pinning_window = self.show_pinning_window;
+        Window::new("Pinning Rules")
+            .open(&mut show_pinning_window)
+            .show(ctx, |ui| {
+                let report = self.pinning_report
+                    .as_ref()
+                    .filter(|report| report.len() == self.param_pinning_rules.len());
+                if report.is_none() {
+                    ui.label("The rules are applied when the optimization starts.");
+                }
+                egui::ScrollArea
+                    ::vertical()
+                    .max_height(400.0)
+                    .show(ui, |ui| {
+                        Grid::new("pinning_rules_grid")
+                            .striped(true)
+                            .num_columns(6)
+                            .show(ui, |ui| {
+                                ui.label("Rule");
+                                ui.label("Routes");
+                                ui.label("Action");
+                                if report.is_some() {
+                                    ui.label("Matched");
+                                    ui.label("Changed");
+                                    ui.label("Unsatisfiable");
+                                }
+                                ui.end_row();
+                                for (index, rule) in self.param_pinning_rules.iter().enumerate() {
+                                    ui.label(&rule.name);
+                                    ui.label(rule.scope());
+                                    ui.label(rule.action());
+                                    if let Some(outcome) = report.map(|report| &report[index]) {
+                                        ui.label(outcome.matched.to_formatted_string(&Locale::fr));
+                                        ui.label(outcome.changed.to_formatted_string(&Locale::fr));
+                                        if outcome.unsatisfiable > 0 {
+                                            ui.label(
+                                                RichText::new(
+                                                    outcome.unsatisfiable.to_formatted_string(
+                                                        &Locale::fr
+                                                    )
+                                                ).color(Color32::from_rgb(0xe4, 0x37, 0x48))
+                                            ).on_hover_text(
+                                                "No path keeps to the rules, so these routes are left as they are."
+                                            );
+                                        } else {
+                                            ui.label("0");
+                                        }
+                                    }
+                                    ui.end_row();
+                                }
+                            });
+                    });
+            });
+        self.show_pinning_window = show_pinning_window;
+    }
+
+    /// Opens the route editor on the selected transition of the selected student, starting from
+    /// the optimized route if there is one.
+    fn open_route_editor(&mut self) {
+        self.show_route_editor_window = true;
+        self.route_editor = None;
+        self.route_editor_drag = None;
+        self.route_editor_message = None;
+        let Some(student_number) = self.selected_student.clone() else {
+            self.route_editor_message = Some("Select a student first.".to_owned());
+            return;
+        };
+        let student_routes = self.student_routes_optimized
+            .lock()
+            .unwrap()
+            .clone()
+            .or_else(|| self.student_routes_shortest.lock().unwrap().clone());
+        let Some(student_routes) = student_routes else {
+            self.route_editor_message = Some("No path data available".to_owned());
+            return;
+        };
+        let route = student_routes
+            .student_index(&student_number)
+            .map(|student_index| {
+                student_routes.route_string(student_index, self.selected_day, self.selected_period)
+            })
+            .unwrap_or_default();
+        match
+            RouteEditor::new(
+                &self.building_graph.lock().unwrap(),
+                &student_number,
+                self.selected_day,
+                self.selected_period,
+                &route
+            )
+        {
+            Ok(editor) => {
+                self.route_editor = Some(editor);
+            }
+            Err(error) => {
+                self.route_editor_message = Some(error.to_string());
+            }
+        }
+    }
+
+    /// Whether the route of a student in a transition of the optimized routes is edited by hand.
+    fn is_route_edited(&self, student_number: &str, day: u32, transition: usize) -> bool {
+        self.student_routes_optimized
+            .lock()
+            .unwrap()
+            .as_ref()
+            .is_some_and(|student_routes| {
+                student_routes
+                    .student_index(student_number)
+                    .is_some_and(|student_index| {
+                        student_routes.is_edited(student_index, day, transition)
+                    })
+            })
+    }
+
+    /// Saves the route of the editor into the optimized routes, creating them from the shortest
+    /// routes if there are none yet, and updates the congestion of the optimized routes.
+    fn save_edited_route(&mut self) {
+        let Some(editor) = &self.route_editor else {
+            return;
+        };
+        let graph = self.building_graph.clone();
+        let graph = graph.lock().unwrap();
+        let route = match editor.route_string(&graph) {
+            Ok(route) => route,
+            Err(error) => {
+                self.route_editor_message = Some(error.to_string());
+                return;
+            }
+        };
+        let student_number = editor.student_number.clone();
+        let (day, transition) = (editor.day, editor.transition);
+        let student_routes = self.student_routes_optimized
+            .lock()
+            .unwrap()
+            .clone()
+            .or_else(|| self.student_routes_shortest.lock().unwrap().clone());
+        let Some(mut student_routes) = student_routes else {
+            self.route_editor_message = Some("No path data available".to_owned());
+            return;
+        };
+        let Some(student_index) = student_routes.student_index(&student_number) else {
+            self.route_editor_message = Some(
+                format!("Student {} has no routes.", student_number)
+            );
+            return;
+        };
+        let edited_routes = Arc::make_mut(&mut student_routes);
+        let set = edited_routes.set_route(student_index, day, transition, route.split(' '));
+        if let Err(error) = set {
+            self.route_editor_message = Some(error.to_string());
+            return;
+        }
+        edited_routes.set_edited(student_index, day, transition, true);
+
+        // the congestion index of a store that has been optimized follows the edit
+        let updated = self.optimization_store.as_ref().and_then(|store| {
+            store.set_route(&graph, &student_number, day, transition, route);
+            store.with_congestion(|congestion| {
+                let mut rooms: Vec<String> = self.projection_coords.keys().cloned().collect();
+                rooms.push("G".to_owned());
+                let transitions = congestion.take_changed();
+                let performance_indices = congestion.performance_indices();
+                *self.uncounted_steps_optimized.lock().unwrap() = congestion.uncounted_steps();
+                let layers = self.congestion_layers.lock().unwrap();
+                let congestion = layers.apply(congestion);
+                copy_congestion(
+                    &congestion,
+                    &graph,
+                    &rooms,
+                    &transitions,
+                    &mut self.congestion_point_data_opt.lock().unwrap(),
+                    &mut self.congestion_path_data_opt.lock().unwrap(),
+                    &mut self.congestion_statistics_opt.lock().unwrap()
+                );
+                *self.maximum_congestion_opt.lock().unwrap() = congestion.max_node_load();
+                *self.performance_indices_optimized.lock().unwrap() = performance_indices;
+            })
+        });
+        if updated.is_none() {
+            let congestion = CongestionIndex::from_routes(&graph, &student_routes);
+            *self.performance_indices_optimized.lock().unwrap() = congestion.performance_indices();
+            *self.uncounted_steps_optimized.lock().unwrap() = congestion.uncounted_steps();
+            let mut congestion_status = self.congestion_status.lock().unwrap();
+            if *congestion_status == CongestionStatus::Successful {
+                *congestion_status = CongestionStatus::Ready;
+            }
+        }
+        *self.student_routes_optimized.lock().unwrap() = Some(student_routes);
+        self.path_display = PathDisplay::Optimized;
+        self.route_editor_message = None;
+    }
+
+    /// Hands the route of the editor back to the optimizer, which may change it in its next run.
+    fn release_edited_route(&mut self) {
+        let Some(editor) = &self.route_editor else {
+            return;
+        };
+        if let Some(student_routes) = self.student_routes_optimized.lock().unwrap().as_mut() {
+            if let Some(student_index) = student_routes.student_index(&editor.student_number) {
+                Arc::make_mut(student_routes).set_edited(
+                    student_index,
+                    editor.day,
+                    editor.transition,
+                    false
+                );
+            }
+        }
+        if let Some(store) = &self.optimization_store {
+            store.release_route(&editor.student_number, editor.day, editor.transition);
+        }
+    }
+
+    fn show_route_editor_window(&mut self, ctx: &egui::Context, is_optimizing: bool) {
+        let mut show_route_editor_window = self.show_route_editor_window;
+        let mut save = false;
+        let mut release = false;
+        let is_edited = self.route_editor
+            .as_ref()
+            .is_some_and(|editor| {
+                self.is_route_edited(&editor.student_number, editor.day, editor.transition)
+            });
+        let graph = self.building_graph.clone();
+        let graph = graph.lock().unwrap();
+        Window::new("Route Editor")
+            .open(&mut show_route_editor_window)
+            .show(ctx, |ui| {
+                if let Some(message) = &self.route_editor_message {
+                    ui.label(RichText::new(message).color(Color32::from_rgb(0xe4, 0x37, 0x48)));
+                }
+                let Some(editor) = self.route_editor.as_mut() else {
+                    return;
+                };
+                ui.label(
+                    format!(
+                        "Student {}, {}, {}",
+                        editor.student_number,
+                        convert_day_of_week(editor.day),
+                        convert_periods(editor.transition)
+                    )
+                );
+                if is_edited {
+                    ui.label("This route is edited by hand, so the optimizer leaves it untouched.");
+                }
+                ui.label(
+                    "Click a node on the projection to add a waypoint before the destination. Drag a waypoint to move it, or right-click it to remove it."
+                );
+                ui.separator();
+                let path = editor.path(&graph);
+                match &path {
+                    Ok(path) => {
+                        ui.label(
+                            RichText::new(
+                                format!(
+                                    "Valid route through {} nodes with a distance of {}",
+                                    path.len(),
+                                    graph.route_distance(path)
+                                )
+                            ).color(Color32::from_rgb(0x14, 0xae, 0x52))
+                        );
+                    }
+                    Err(error) => {
+                        ui.label(
+                            RichText::new(error.to_string()).color(
+                                Color32::from_rgb(0xe4, 0x37, 0x48)
+                            )
+                        );
+                    }
+                }
+                ui.heading("Waypoints");
+                let mut removed = None;
+                egui::ScrollArea
+                    ::vertical()
+                    .max_height(200.0)
+                    .show(ui, |ui| {
+                        for (index, &node) in editor.waypoints().iter().enumerate() {
+                            ui.horizontal(|ui| {
+                                ui.label(format!("{}. {}", index + 1, graph.node_name(node)));
+                                if editor.is_movable(index) && ui.small_button("✖").clicked() {
+                                    removed = Some(index);
+                                }
+                            });
+                        }
+                    });
+                if let Some(index) = removed {
+                    editor.remove_waypoint(index);
+                }
+                ui.separator();
+                ui.horizontal(|ui| {
+                    if ui.add_enabled(editor.can_undo(), egui::Button::new("Undo")).clicked() {
+                        editor.undo();
+                    }
+                    if
+                        ui
+                            .button("Clear waypoints")
+                            .on_hover_text(
+                                "Follow the shortest path between the ends of the route."
+                            )
+                            .clicked()
+                    {
+                        editor.clear();
+                    }
+                    if ui.button("Reset").clicked() {
+                        editor.reset();
+                    }
+                });
+                ui.horizontal(|ui| {
+                    save = ui
+                        .add_enabled(path.is_ok() && !is_optimizing, egui::Button::new("Save"))
+                        .on_disabled_hover_text("Pause the optimization and fix the route first.")
+                        .clicked();
+                    release = ui
+                        .add_enabled(
+                            is_edited && !is_optimizing,
+                            egui::Button::new("Hand back to optimizer")
+                        )
+                        .on_hover_text("The next optimization run may change the route again.")
+                        .clicked();
+                });
+            });
+        drop(graph);
+        if save {
+            self.save_edited_route();
+        }
+        if release {
+            self.release_edited_route();
+        }
+        if !show_route_editor_window {
+            self.route_editor = None;
+            self.route_editor_drag = None;
+        }
+        self.show_route_editor_window = show_route_editor_window;
+    }
+
+    /// The node drawn closest to `position` on the canvas among the nodes of the selected floor,
+    /// if it is close enough to be picked.
+    fn canvas_node_at(&self, geometry: &CanvasGeometry, position: emath::Pos2) -> Option<&str> {
+        self.projection_coords
+            .iter()
+            .filter(|(_, coords)| geometry.is_on_floor(coords))
+            .map(|(name, coords)| (name, geometry.screen_pos(coords).distance(position)))
+            .filter(|(_, distance)| *distance <= ROUTE_EDITOR_PICK_RADIUS)
+            .min_by(|(_, distance1), (_, distance2)| distance1.total_cmp(distance2))
+            .map(|(name, _)| name.as_str())
+    }
+
+    /// Edits the route of the route editor with the pointer, and draws it over the canvas.
+    fn edit_route_on_canvas(
+        &mut self,
+        ui: &egui::Ui,
+        response: &egui::Response,
+        geometry: &CanvasGeometry
+    ) {
+        let graph = self.building_graph.clone();
+        let graph = graph.lock().unwrap();
+        let pointer = response.interact_pointer_pos().or(response.hover_pos());
+        let node_at_pointer = pointer
+            .and_then(|pointer| self.canvas_node_at(geometry, pointer))
+            .and_then(|name| graph.node_id(name));
+        let Some(editor) = self.route_editor.as_mut() else {
+            return;
+        };
+        let waypoint_positions: Vec<Option<emath::Pos2>> = editor
+            .waypoints()
+            .iter()
+            .map(|node| {
+                self.projection_coords
+                    .get(graph.node_name(*node))
+                    .filter(|coords| geometry.shows_other_floors() || geometry.is_on_floor(coords))
+                    .map(|coords| geometry.screen_pos(coords))
+            })
+            .collect();
+        let waypoint_at = |position: emath::Pos2| {
+            waypoint_positions
+                .iter()
+                .enumerate()
+                .filter_map(|(index, waypoint)| {
+                    waypoint.map(|waypoint| (index, waypoint.distance(position)))
+                })
+                .filter(|(_, distance)| *distance <= ROUTE_EDITOR_PICK_RADIUS)
+                .min_by(|(_, distance1), (_, distance2)| distance1.total_cmp(distance2))
+                .map(|(index, _)| index)
+        };
+
+        if response.drag_started() {
+            self.route_editor_drag = ui
+                .input(|input| input.pointer.press_origin())
+                .and_then(waypoint_at)
+                .filter(|index| editor.is_movable(*index));
+        }
+        if response.drag_released() {
+            if let (Some(index), Some(node)) = (self.route_editor_drag.take(), node_at_pointer) {
+                editor.move_waypoint(index, node);
+            }
+        } else if response.clicked() {
+            if let Some(node) = node_at_pointer {
+                editor.add_waypoint(node);
+            }
+        } else if response.secondary_clicked() {
+            if let Some(index) = pointer.and_then(waypoint_at) {
+                editor.remove_waypoint(index);
+            }
+        }
+
+        let painter = ui.painter_at(geometry.rect());
+        let editor_color = Color32::from_rgb(0xec, 0x6f, 0x27);
+        match editor.path(&graph) {
+            Ok(path) => {
+                let coords: Vec<&[i32; 3]> = path
+                    .iter()
+                    .filter_map(|node| self.projection_coords.get(graph.node_name(*node)))
+                    .collect();
+                for pair in coords.windows(2) {
+                    let is_active = geometry.is_segment_active(pair[0], pair[1]);
+                    if !is_active && !geometry.shows_other_floors() {
+                        continue;
+                    }
+                    painter.line_segment(
+                        [geometry.screen_pos(pair[0]), geometry.screen_pos(pair[1])],
+                        Stroke::new(4.0, if is_active {
+                            editor_color
+                        } else {
+                            self.inactive_path_color
+                        })
+                    );
+                }
+            }
+            Err(_) => {
+                // the waypoints that cannot be joined are shown as they are
+                let positions: Vec<emath::Pos2> = waypoint_positions
+                    .iter()
+                    .flatten()
+                    .copied()
+                    .collect();
+                for pair in positions.windows(2) {
+                    painter.line_segment(
+                        [pair[0], pair[1]],
+                        Stroke::new(2.0, Color32::from_rgb(0xe4, 0x37, 0x48))
+                    );
+                }
+            }
+        }
+        let last = waypoint_positions.len() - 1;
+        for (index, position) in waypoint_positions.iter().enumerate() {
+            let Some(position) = position else {
+                continue;
+            };
+            let fill = match index {
+                0 => Color32::from_rgb(0x14, 0xae, 0x52),
+                _ if index == last => Color32::from_rgb(0xe4, 0x37, 0x48),
+                _ => Color32::WHITE,
+            };
+            painter.circle(*position, 6.0, fill, Stroke::new(2.0, editor_color));
+        }
+        if let Some(pointer) = pointer {
+            if let Some(node) = node_at_pointer {
+                if let Some(coords) = self.projection_coords.get(graph.node_name(node)) {
+                    painter.circle_stroke(
+                        geometry.screen_pos(coords),
+                        ROUTE_EDITOR_PICK_RADIUS,
+                        Stroke::new(1.0, editor_color)
+                    );
+                }
+            }
+            if self.route_editor_drag.is_some() {
+                painter.circle_filled(pointer, 5.0, editor_color);
+            }
+        }
+    }
+
+    fn show_building_editor_window(&mut self, ctx: &egui::Context) {
+        let mut show_building_editor_window = self.show_building_editor_window;
+        let mut open = false;
+        let mut save = false;
+        let mut save_as = false;
+        let mut go_to_node = None;
+        Window::new("Building Editor")
+            .open(&mut show_building_editor_window)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    open = ui
+                        .button("Open…")
+                        .on_hover_text(
+                            "Open an asset directory with projection-coords.yaml and paths.yaml."
+                        )
+                        .clicked();
+                    if let Some(editor) = &mut self.building_editor {
+                        save = ui
+                            .add_enabled(editor.is_modified(), egui::Button::new("Save"))
+                            .clicked();
+                        save_as = ui.button("Save as…").clicked();
+                        if
+                            ui
+                                .add_enabled(!editor.history.is_empty(), egui::Button::new("Undo"))
+                                .clicked()
+                        {
+                            editor.undo();
+                        }
+                    }
+                });
+                if let Some(message) = &self.building_editor_message {
+                    ui.label(message);
+                }
+                let Some(editor) = self.building_editor.as_mut() else {
+                    ui.label("Open the asset directory of the building to edit it.");
+                    return;
+                };
+                ui.label(
+                    format!(
+                        "{}{}",
+                        editor.directory.display(),
+                        if editor.is_modified() { " (modified)" } else { "" }
+                    )
+                );
+                ui.label(
+                    format!(
+                        "{} nodes, {} edges, {} entrances",
+                        editor.model.nodes().len(),
+                        editor.model.edges().len(),
+                        editor.model.entrances().len()
+                    )
+                );
+                ui.separator();
+
+                ui.horizontal(|ui| {
+                    for (tool, name) in [
+                        (BuildingTool::Select, "Select"),
+                        (BuildingTool::AddNode, "Add node"),
+                        (BuildingTool::Connect, "Connect"),
+                        (BuildingTool::Delete, "Delete"),
+                    ] {
+                        if ui.selectable_label(editor.tool == tool, name).clicked() {
+                            editor.tool = tool;
+                            editor.connect_from = None;
+                        }
+                    }
+                });
+                match editor.tool {
+                    BuildingTool::Select => {
+                        ui.label(
+                            "Click a node to select it. Drag it to move it on the selected floor."
+                        );
+                    }
+                    BuildingTool::AddNode => {
+                        ui.label("Click the canvas to add a node on the selected floor.");
+                        ui.horizontal(|ui| {
+                            ui.label("Name");
+                            ui.add(
+                                egui::TextEdit::singleline(&mut editor.new_node_name).desired_width(
+                                    80.0
+                                )
+                            );
+                            ComboBox::from_label("Block")
+                                .selected_text(editor.new_node_group.as_str())
+                                .show_ui(ui, |ui| {
+                                    for group in GROUPS {
+                                        ui.selectable_value(
+                                            &mut editor.new_node_group,
+                                            group.to_owned(),
+                                            group
+                                        );
+                                    }
+                                });
+                        });
+                    }
+                    BuildingTool::Connect => {
+                        ui.label(
+                            "Click two nodes to join them. Select all floors on the projection to join nodes on different floors."
+                        );
+                        edge_type_combo_box(ui, "new_edge_type", &mut editor.new_edge_type);
+                    }
+                    BuildingTool::Delete => {
+                        ui.label("Click a node or an edge to delete it.");
+                    }
+                }
+                ui.separator();
+
+                let selected = editor.selected
+                    .clone()
+                    .and_then(|name| editor.model.node(&name).cloned());
+                if let Some(node) = selected {
+                    let name = node.name.clone();
+                    let mut result = Ok(());
+                    ui.heading(&name);
+                    Grid::new("building_node_grid")
+                        .num_columns(2)
+                        .show(ui, |ui| {
+                            ui.label("Name");
+                            ui.horizontal(|ui| {
+                                ui.add(
+                                    egui::TextEdit::singleline(&mut editor.rename).desired_width(
+                                        80.0
+                                    )
+                                );
+                                if ui.button("Rename").clicked() {
+                                    let new_name = editor.rename.trim().to_owned();
+                                    result = editor.edit(|model| {
+                                        model.rename_node(&name, &new_name)
+                                    });
+                                    if result.is_ok() {
+                                        editor.select(Some(new_name));
+                                    }
+                                }
+                            });
+                            ui.end_row();
+
+                            ui.label("Floor");
+                            ui.label(&node.floor);
+                            ui.end_row();
+
+                            ui.label("Block");
+                            let mut group = node.group.clone();
+                            ComboBox::from_id_source("building_node_group")
+                                .selected_text(group.as_str())
+                                .show_ui(ui, |ui| {
+                                    for option in GROUPS {
+                                        ui.selectable_value(&mut group, option.to_owned(), option);
+                                    }
+                                });
+                            if group != node.group {
+                                result = editor.edit(|model| model.set_group(&name, &group));
+                            }
+                            ui.end_row();
+
+                            ui.label("Position");
+                            let [mut x, mut y, _] = node.coords;
+                            ui.horizontal(|ui| {
+                                ui.add(egui::DragValue::new(&mut x).prefix("x: "));
+                                ui.add(egui::DragValue::new(&mut y).prefix("y: "));
+                            });
+                            if [x, y] != [node.coords[0], node.coords[1]] {
+                                result = editor.edit(|model| model.move_node(&name, [x, y]));
+                            }
+                            ui.end_row();
+
+                            ui.label("Entrance");
+                            let mut is_entrance = editor.model.entrances().contains(&name);
+                            if
+                                ui
+                                    .checkbox(&mut is_entrance, "Joined to the ground")
+                                    .changed()
+                            {
+                                result = editor.edit(|model| {
+                                    model.set_entrance(&name, is_entrance);
+                                    Ok(())
+                                });
+                            }
+                            ui.end_row();
+                        });
+
+                    ui.label("Edges");
+                    let edges: Vec<(usize, (String, u8, u32, u32))> = editor.model
+                        .edges()
+                        .iter()
+                        .enumerate()
+                        .filter(|(_, edge)| edge.nodes.contains(&name))
+                        .map(|(index, edge)| {
+                            let other = edge.nodes.iter().find(|node| **node != name);
+                            let other = other.cloned().unwrap_or_default();
+                            let auto_distance = editor.model.auto_distance(
+                                &edge.nodes[0],
+                                &edge.nodes[1],
+                                edge.edge_type
+                            );
+                            (index, (other, edge.edge_type, edge.distance, auto_distance))
+                        })
+                        .collect();
+                    Grid::new("building_edge_grid")
+                        .num_columns(5)
+                        .striped(true)
+                        .show(ui, |ui| {
+                            for (index, (other, edge_type, distance, auto_distance)) in edges {
+                                ui.label(&other);
+                                let (mut new_type, mut new_distance) = (edge_type, distance);
+                                edge_type_combo_box(
+                                    ui,
+                                    format!("building_edge_type_{}", index),
+                                    &mut new_type
+                                );
+                                ui.add(egui::DragValue::new(&mut new_distance));
+                                if
+                                    ui
+                                        .add_enabled(
+                                            distance != auto_distance,
+                                            egui::Button::new("Auto")
+                                        )
+                                        .on_hover_text(
+                                            format!(
+                                                "Use the distance of the coordinates, {}.",
+                                                auto_distance
+                                            )
+                                        )
+                                        .clicked()
+                                {
+                                    new_distance = auto_distance;
+                                }
+                                if (new_type, new_distance) != (edge_type, distance) {
+                                    result = editor.edit(|model| {
+                                        model.set_edge(index, new_type, new_distance);
+                                        Ok(())
+                                    });
+                                }
+                                if ui.small_button("✖").clicked() {
+                                    result = editor.edit(|model| {
+                                        model.remove_edge(index);
+                                        Ok(())
+                                    });
+                                }
+                                ui.end_row();
+                            }
+                        });
+                    ui.horizontal(|ui| {
+                        ui.add(
+                            egui::TextEdit::singleline(&mut editor.connect_to).desired_width(80.0)
+                        );
+                        if
+                            ui
+                                .button("Connect")
+                                .on_hover_text(
+                                    "Join the selected node to the named node, with the edge type of the connect tool."
+                                )
+                                .clicked()
+                        {
+                            let (to, edge_type) = (
+                                editor.connect_to.trim().to_owned(),
+                                editor.new_edge_type,
+                            );
+                            result = editor.edit(|model| {
+                                model.connect(&name, &to, edge_type, None)
+                            });
+                        }
+                    });
+                    if ui.button("Delete node").clicked() {
+                        result = editor.edit(|model| model.delete_node(&name));
+                        if result.is_ok() {
+                            editor.select(None);
+                        }
+                    }
+                    if let Err(error) = result {
+                        self.building_editor_message = Some(error.to_string());
+                    }
+                    ui.separator();
+                }
+
+                ui.heading("Connectivity");
+                let connectivity = &editor.connectivity;
+                if connectivity.components.len() <= 1 {
+                    ui.label(
+                        RichText::new("Every node is connected.").color(
+                            Color32::from_rgb(0x14, 0xae, 0x52)
+                        )
+                    );
+                } else {
+                    ui.label(
+                        RichText::new(
+                            format!(
+                                "The building falls into {} components. {} rooms cannot be reached:",
+                                connectivity.components.len(),
+                                connectivity.unreachable_rooms.len()
+                            )
+                        ).color(Color32::from_rgb(0xe4, 0x37, 0x48))
+                    );
+                    egui::ScrollArea
+                        ::vertical()
+                        .max_height(100.0)
+                        .show(ui, |ui| {
+                            ui.horizontal_wrapped(|ui| {
+                                for room in &connectivity.unreachable_rooms {
+                                    if ui.small_button(room).clicked() {
+                                        go_to_node = Some(room.clone());
+                                    }
+                                }
+                            });
+                        });
+                }
+                ui.separator();
+                ui.label(
+                    "The app reads paths.txt and projection-coords-flatten.yaml from optiway/assets when it is built, so copy them there to use the saved building."
+                );
+            });
+
+        if open {
+            let directory = FileDialog::new()
+                .set_directory(BUILDING_ASSETS_DIRECTORY)
+                .pick_folder();
+            if let Some(directory) = directory {
+                match BuildingModel::load(&directory) {
+                    Ok(model) => {
+                        self.building_editor = Some(BuildingEditorState::new(model, directory));
+                        self.building_editor_message = None;
+                    }
+                    Err(error) => {
+                        self.building_editor_message = Some(error.to_string());
+                    }
+                }
+            }
+        }
+        let directory = if save_as {
+            FileDialog::new().set_directory(BUILDING_ASSETS_DIRECTORY).pick_folder()
+        } else if save {
+            self.building_editor.as_ref().map(|editor| editor.directory.clone())
+        } else {
+            None
+        };
+        if let (Some(directory), Some(editor)) = (directory, self.building_editor.as_mut()) {
+            match editor.model.save(&directory) {
+                Ok(()) => {
+                    self.building_editor_message = Some(
+                        format!("Saved to {}.", directory.display())
+                    );
+                    editor.saved = editor.model.clone();
+                    editor.directory = directory;
+                }
+                Err(error) => {
+                    self.building_editor_message = Some(
+                        format!("Failed to save the building: {}", error)
+                    );
+                }
+            }
+        }
+        if let Some(name) = go_to_node {
+            self.select_building_node(&name);
+        }
+        self.show_building_editor_window = show_building_editor_window;
+    }
+
+    fn show_consistency_window(&mut self, ctx: &egui::Context) {
+        let mut show_consistency_window = self.show_consistency_window;
+        // whether to check the building in the editor rather than the app's
+        let mut check = None;
+        Window::new("Building Consistency")
+            .open(&mut show_consistency_window)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    if
+                        ui
+                            .button("Check app data")
+                            .on_hover_text(
+                                "Check the building the app was built with and the source files in ../assets."
+                            )
+                            .clicked()
+                    {
+                        check = Some(false);
+                    }
+                    if
+                        ui
+                            .add_enabled(
+                                self.building_editor.is_some(),
+                                egui::Button::new("Check edited building")
+                            )
+                            .on_hover_text(
+                                "Check the building in the editor against the precomputed paths the app was built with."
+                            )
+                            .on_disabled_hover_text("Open a building in the building editor first.")
+                            .clicked()
+                    {
+                        check = Some(true);
+                    }
+                });
+                if let Some(message) = &self.consistency_message {
+                    ui.label(message);
+                }
+                let Some(issues) = &self.consistency_issues else {
+                    return;
+                };
+                let errors = issues
+                    .iter()
+                    .filter(|issue| issue.severity() == Severity::Error)
+                    .count();
+                ui.label(format!("{} errors, {} warnings", errors, issues.len() - errors));
+                ui.separator();
+                let color = |severity| {
+                    match severity {
+                        Severity::Error => Color32::from_rgb(0xe4, 0x37, 0x48),
+                        Severity::Warning => Color32::from_rgb(0xff, 0xc1, 0x07),
+                    }
+                };
+                egui::ScrollArea
+                    ::vertical()
+                    .auto_shrink([false, true])
+                    .show(ui, |ui| {
+                        for category in issues.chunk_by(|a, b| a.category() == b.category()) {
+                            let severity = category
+                                .iter()
+                                .map(|issue| issue.severity())
+                                .min()
+                                .unwrap();
+                            let heading = format!(
+                                "{} ({})",
+                                category[0].category(),
+                                category.len()
+                            );
+                            egui::CollapsingHeader
+                                ::new(RichText::new(heading).color(color(severity)))
+                                .show(ui, |ui| {
+                                    for issue in category {
+                                        ui.label(
+                                            RichText::new(issue.to_string()).color(
+                                                color(issue.severity())
+                                            )
+                                        );
+                                    }
+                                });
+                        }
+                    });
+            });
+
+        if let Some(edited) = check {
+            let data = match (edited, &self.building_editor) {
+                (true, Some(editor)) => {
+                    self.consistency_message = Some(
+                        format!("Checked the building in {}.", editor.directory.display())
+                    );
+                    BuildingData {
+                        coords: editor.model.flattened_coords(),
+                        paths_txt: editor.model.paths_txt(),
+                        shortest_paths: Some(self.shortest_paths_json.clone()),
+                        model: Some(editor.model.clone()),
+                    }
+                }
+                _ => {
+                    let model = BuildingModel::load(Path::new(BUILDING_ASSETS_DIRECTORY));
+                    self.consistency_message = Some(match &model {
+                        Ok(_) =>
+                            format!(
+                                "Checked the app's building and the source files in {}.",
+                                BUILDING_ASSETS_DIRECTORY
+                            ),
+                        Err(error) =>
+                            format!(
+                                "Checked the app's building only, the source files in {} cannot be read: {}",
+                                BUILDING_ASSETS_DIRECTORY,
+                                error
+                            ),
+                    });
+                    BuildingData {
+                        coords: self.projection_coords.clone(),
+                        paths_txt: include_str!("../assets/paths.txt").to_owned(),
+                        shortest_paths: Some(self.shortest_paths_json.clone()),
+                        model: model.ok(),
+                    }
+                }
+            };
+            self.consistency_issues = Some(check_building(&data));
+        }
+        self.show_consistency_window = show_consistency_window;
+    }
+
+    /// Selects a node in the building editor, and the floor it is on.
+    fn select_building_node(&mut self, name: &str) {
+        let Some(editor) = self.building_editor.as_mut() else {
+            return;
+        };
+        let Some(node) = editor.model.node(name) else {
+            return;
+        };
+        let floor = (node.coords[2] / 50 + 2) as usize;
+        editor.select(Some(name.to_owned()));
+        if (2..=8).contains(&floor) {
+            self.selected_floor = [false; 9];
+            self.selected_floor[floor] = true;
+            self.selected_floor_index = floor;
+        }
+    }
+
+    /// Edits the building model with the tool of the building editor, and draws the model over the
+    /// canvas.
+    fn edit_building_on_canvas(
+        &mut self,
+        ui: &egui::Ui,
+        response: &egui::Response,
+        geometry: &CanvasGeometry
+    ) {
+        let Some(editor) = self.building_editor.as_mut() else {
+            return;
+        };
+        let pointer = response.interact_pointer_pos().or(response.hover_pos());
+        let node_at = |model: &BuildingModel, position: emath::Pos2| {
+            model
+                .nodes()
+                .iter()
+                .filter(|node| geometry.is_on_floor(&node.coords))
+                .map(|node| (node, geometry.screen_pos(&node.coords).distance(position)))
+                .filter(|(_, distance)| *distance <= ROUTE_EDITOR_PICK_RADIUS)
+                .min_by(|(_, distance1), (_, distance2)| distance1.total_cmp(distance2))
+                .map(|(node, _)| node.name.clone())
+        };
+        let edge_at = |model: &BuildingModel, position: emath::Pos2| {
+            model
+                .edges()
+                .iter()
+                .enumerate()
+                .filter_map(|(index, edge)| {
+                    let node1 = model.node(&edge.nodes[0])?;
+                    let node2 = model.node(&edge.nodes[1])?;
+                    geometry
+                        .is_segment_active(&node1.coords, &node2.coords)
+                        .then(|| {
+                            let from = geometry.screen_pos(&node1.coords);
+                            let to = geometry.screen_pos(&node2.coords);
+                            (index, segment_distance(position, from, to))
+                        })
+                })
+                .filter(|(_, distance)| *distance <= ROUTE_EDITOR_PICK_RADIUS)
+                .min_by(|(_, distance1), (_, distance2)| distance1.total_cmp(distance2))
+                .map(|(index, _)| index)
+        };
+        let node_at_pointer = pointer.and_then(|pointer| node_at(&editor.model, pointer));
+        let coords_at_pointer = pointer.and_then(|pointer| geometry.coords_at(pointer));
+        let floor = geometry
+            .floor_z()
+            .map(|z| floor_name(&[0, 0, z]))
+            .filter(|floor| editor.model.floors().contains(floor));
+
+        let mut result = Ok(());
+        match editor.tool {
+            BuildingTool::Select => {
+                if response.drag_started() && coords_at_pointer.is_some() {
+                    let dragged = ui
+                        .input(|input| input.pointer.press_origin())
+                        .and_then(|origin| node_at(&editor.model, origin));
+                    if let Some(name) = dragged {
+                        editor.history.push(editor.model.clone());
+                        editor.select(Some(name.clone()));
+                        editor.drag = Some(name);
+                    }
+                }
+                if response.drag_released() {
+                    // a node dropped where it was picked up leaves nothing to undo
+                    let is_unmoved = editor.history.last() == Some(&editor.model);
+                    if editor.drag.take().is_some() && is_unmoved {
+                        editor.history.pop();
+                    }
+                } else if let (Some(name), Some(coords)) = (&editor.drag, coords_at_pointer) {
+                    result = editor.model.move_node(name, coords);
+                } else if response.clicked() {
+                    editor.select(node_at_pointer.clone());
+                }
+            }
+            BuildingTool::AddNode => {
+                if response.clicked() && node_at_pointer.is_none() {
+                    if let (Some(floor), Some(coords)) = (floor, coords_at_pointer) {
+                        let name = editor.new_node_name.trim().to_owned();
+                        let group = editor.new_node_group.clone();
+                        result = editor.edit(|model| model.add_node(&name, &floor, &group, coords));
+                        if result.is_ok() {
+                            editor.new_node_name = next_node_name(&name);
+                            editor.select(Some(name));
+                        }
+                    } else {
+                        self.building_editor_message = Some(
+                            "Select a floor to add nodes to.".to_owned()
+                        );
+                    }
+                }
+            }
+            BuildingTool::Connect => {
+                if response.clicked() {
+                    match (editor.connect_from.take(), node_at_pointer.clone()) {
+                        (None, to) => {
+                            editor.connect_from = to;
+                        }
+                        (Some(from), Some(to)) => {
+                            let edge_type = editor.new_edge_type;
+                            result = editor.edit(|model| {
+                                model.connect(&from, &to, edge_type, None)
+                            });
+                            editor.select(Some(to));
+                        }
+                        (Some(_), None) => {}
+                    }
+                }
+            }
+            BuildingTool::Delete => {
+                if response.clicked() {
+                    if let Some(name) = &node_at_pointer {
+                        result = editor.edit(|model| model.delete_node(name));
+                        if editor.selected.as_ref() == Some(name) {
+                            editor.select(None);
+                        }
+                    } else if
+                        let Some(index) = pointer.and_then(|pointer| {
+                            edge_at(&editor.model, pointer)
+                        })
+                    {
+                        result = editor.edit(|model| {
+                            model.remove_edge(index);
+                            Ok(())
+                        });
+                    }
+                }
+            }
+        }
+        if let Err(error) = result {
+            self.building_editor_message = Some(error.to_string());
+        }
+
+        let painter = ui.painter_at(geometry.rect());
+        let model = &editor.model;
+        let visuals = ui.visuals();
+        let is_plan = !geometry.shows_other_floors();
+        for edge in model.edges() {
+            let nodes = edge.nodes.each_ref().map(|node| model.node(node));
+            let [Some(node1), Some(node2)] = nodes else {
+                continue;
+            };
+            if !geometry.is_segment_active(&node1.coords, &node2.coords) {
+                continue;
+            }
+            let from = geometry.screen_pos(&node1.coords);
+            let to = geometry.screen_pos(&node2.coords);
+            let color = match edge.edge_type {
+                0 => Color32::from_gray(0x9e),
+                1 => Color32::from_rgb(0x00, 0x7a, 0xf5),
+                _ => Color32::from_rgb(0xff, 0xc1, 0x07),
+            };
+            painter.line_segment([from, to], Stroke::new(3.0, color));
+            if
+                is_plan &&
+                self.show_plan_distance_labels &&
+                from.distance(to) >= PLAN_MIN_LABELLED_EDGE
+            {
+                let auto_distance = model.auto_distance(
+                    &edge.nodes[0],
+                    &edge.nodes[1],
+                    edge.edge_type
+                );
+                let is_automatic = edge.distance == auto_distance;
+                painter.text(
+                    from.lerp(to, 0.5),
+                    Align2::CENTER_CENTER,
+                    edge.distance.to_string(),
+                    FontId::proportional(10.0),
+                    if is_automatic {
+                        visuals.weak_text_color()
+                    } else {
+                        Color32::from_rgb(0xec, 0x6f, 0x27)
+                    }
+                );
+            }
+        }
+        let highlight = Color32::from_rgb(0xec, 0x6f, 0x27);
+        for node in model.nodes().iter().filter(|node| geometry.is_on_floor(&node.coords)) {
+            let position = geometry.screen_pos(&node.coords);
+            let color = if editor.connectivity.unreachable_rooms.contains(&node.name) {
+                Color32::from_rgb(0xe4, 0x37, 0x48)
+            } else if model.entrances().contains(&node.name) {
+                Color32::from_rgb(0x14, 0xae, 0x52)
+            } else if is_room(node) {
+                visuals.text_color()
+            } else {
+                Color32::from_gray(0x9e)
+            };
+            painter.circle_filled(position, 4.0, color);
+            let is_selected = editor.selected.as_ref() == Some(&node.name);
+            if is_selected || editor.connect_from.as_ref() == Some(&node.name) {
+                painter.circle_stroke(position, 8.0, Stroke::new(2.0, highlight));
+            }
+            if (is_plan && self.show_plan_node_labels) || is_selected {
+                painter.text(
+                    position - emath::vec2(0.0, 6.0),
+                    Align2::CENTER_BOTTOM,
+                    &node.name,
+                    FontId::proportional(11.0),
+                    visuals.text_color()
+                );
+            }
+        }
+        if let Some(pointer) = pointer {
+            if let Some(node) = node_at_pointer.and_then(|name| model.node(&name)) {
+                painter.circle_stroke(
+                    geometry.screen_pos(&node.coords),
+                    ROUTE_EDITOR_PICK_RADIUS,
+                    Stroke::new(1.0, highlight)
+                );
+            }
+            if let Some(from) = editor.connect_from.as_ref().and_then(|name| model.node(name)) {
+                painter.line_segment(
+                    [geometry.screen_pos(&from.coords), pointer],
+                    Stroke::new(2.0, highlight)
+                );
+            }
+        }
+    }
+
+    fn canvas_view(&self) -> CanvasView {
+        match self.floor_view_mode {
+            FloorViewMode::Projection => self.projection_view,
+            FloorViewMode::Plan => self.plan_view,
+        }
+    }
+
+    fn canvas_view_mut(&mut self) -> &mut CanvasView {
+        match self.floor_view_mode {
+            FloorViewMode::Projection => &mut self.projection_view,
+            FloorViewMode::Plan => &mut self.plan_view,
+        }
+    }
+
+    /// Geometry of the central canvas at `rect` for the floor view and the selected floor.
+    fn canvas_geometry(&self, rect: Rect, view: &CanvasView) -> CanvasGeometry {
+        let floor_z = if self.selected_floor_index == 0 {
+            0
+        } else {
+            ((self.selected_floor_index - 2) * 50) as i32
+        };
+        match self.floor_view_mode {
+            FloorViewMode::Projection => {
+                let drawing = view.transform_rect(rect);
+                CanvasGeometry::Projection {
+                    rect,
+                    drawing,
+                    scale: drawing.width() / 2243.0,
+                    floor_z: (self.selected_floor_index != 0).then_some(floor_z),
+                }
+            }
+            FloorViewMode::Plan => CanvasGeometry::plan(rect, view, floor_z),
+        }
+    }
+
+    /// Draws the route of the selected student over the canvas.
+    fn paint_route(
+        &self,
+        painter: &egui::Painter,
+        geometry: &CanvasGeometry,
+        segments: &[&[i32; 3]]
+    ) {
+        let path_color = |is_active: bool| {
+            if is_active { self.active_path_color } else { self.inactive_path_color }
+        };
+        for (i, point) in segments.iter().enumerate() {
+            let position = geometry.screen_pos(point);
+            if i != 0 {
+                let is_active = geometry.is_segment_active(segments[i - 1], point);
+                if is_active || geometry.shows_other_floors() {
+                    painter.line_segment(
+                        [geometry.screen_pos(segments[i - 1]), position],
+                        Stroke::new(4.0, path_color(is_active))
+                    );
+                }
+            }
+            let is_active = if i == 0 || !geometry.shows_other_floors() {
+                geometry.is_on_floor(point)
+            } else {
+                geometry.is_segment_active(segments[i - 1], point)
+            };
+            if is_active || geometry.shows_other_floors() {
+                painter.circle_filled(position, 4.0, path_color(is_active));
+            }
+        }
+        if geometry.shows_other_floors() {
+            return;
+        }
+        // the floors the route comes from and goes to are written next to its stairs
+        for (i, point) in segments.iter().enumerate() {
+            if !geometry.is_on_floor(point) {
+                continue;
+            }
+            let from = (i > 0 && !geometry.is_on_floor(segments[i - 1])).then(|| segments[i - 1]);
+            let to = segments.get(i + 1).filter(|next| !geometry.is_on_floor(next));
+            let text = match (from, to) {
+                (Some(from), Some(to)) => format!("{} → {}", floor_name(from), floor_name(to)),
+                (Some(from), None) => format!("from {}", floor_name(from)),
+                (None, Some(to)) => format!("to {}", floor_name(to)),
+                (None, None) => continue,
+            };
+            let position = geometry.screen_pos(point);
+            painter.circle_stroke(position, 8.0, Stroke::new(2.0, self.active_path_color));
+            painter.text(
+                position + emath::vec2(12.0, 0.0),
+                Align2::LEFT_CENTER,
+                text,
+                FontId::proportional(13.0),
+                self.active_path_color
+            );
+        }
+    }
+
+    /// Draws the congestion of the selected transition over the canvas.
+    fn paint_congestion(&self, painter: &egui::Painter, geometry: &CanvasGeometry) {
+        let is_optimized =
+            self.path_display == PathDisplay::Optimized &&
+            self.student_routes_optimized.lock().unwrap().is_some();
+        if self.show_congestion_path {
+            let path_data = if is_optimized {
+                self.congestion_path_data_opt.lock().unwrap()
+            } else {
+                self.congestion_path_data.lock().unwrap()
+            };
+            for ((node1, node2), congestion) in path_data
+                .get(&self.selected_day)
+                .unwrap()
+                .get(&self.selected_period)
+                .unwrap() {
+                if node1 == "G" || node2 == "G" || congestion < &self.congestion_filter {
+                    continue;
+                }
+                let node1_pos = self.projection_coords[node1];
+                let node2_pos = self.projection_coords[node2];
+                if geometry.is_segment_active(&node1_pos, &node2_pos) {
+                    painter.line_segment(
+                        [geometry.screen_pos(&node1_pos), geometry.screen_pos(&node2_pos)],
+                        Stroke::new(4.0, congestion_color_scale(*congestion))
+                    );
+                }
+            }
+        }
+        if self.show_congestion_point {
+            let point_data = if is_optimized {
+                self.congestion_point_data_opt.lock().unwrap()
+            } else {
+                self.congestion_point_data.lock().unwrap()
+            };
+            for (room, congestion) in point_data
+                .get(&self.selected_day)
+                .unwrap()
+                .get(&self.selected_period)
+                .unwrap() {
+                if room == "G" || room.is_empty() {
+                    continue;
+                }
+                let coords = self.projection_coords.get(room).unwrap();
+                if geometry.is_on_floor(coords) && *congestion >= self.congestion_filter {
+                    painter.circle_filled(
+                        geometry.screen_pos(coords),
+                        4.0,
+                        congestion_color_scale(*congestion)
+                    );
+                }
+            }
+        }
+    }
+
+    /// Draws the edges and nodes of the building graph on the selected floor.
+    fn paint_floor_plan(&self, painter: &egui::Painter, geometry: &CanvasGeometry) {
+        let graph = self.building_graph.lock().unwrap();
+        let edge_color = Color32::from_gray(0x61);
+        painter.rect_filled(geometry.rect(), 0.0, painter.ctx().style().visuals.extreme_bg_color);
+        for (node, coords) in self.floor_plan_nodes(&graph, geometry) {
+            for edge in graph.edges(node) {
+                let Some(to_coords) = self.projection_coords.get(graph.node_name(edge.to)) else {
+                    continue;
+                };
+                if edge.to > node && geometry.is_on_floor(to_coords) {
+                    painter.line_segment(
+                        [geometry.screen_pos(coords), geometry.screen_pos(to_coords)],
+                        Stroke::new(2.0, edge_color)
+                    );
+                }
+            }
+        }
+        for (node, coords) in self.floor_plan_nodes(&graph, geometry) {
+            let position = geometry.screen_pos(coords);
+            let is_stairs = graph
+                .edges(node)
+                .iter()
+                .filter_map(|edge| self.projection_coords.get(graph.node_name(edge.to)))
+                .any(|to_coords| !geometry.is_on_floor(to_coords));
+            if is_stairs {
+                painter.rect_filled(
+                    Rect::from_center_size(position, emath::vec2(8.0, 8.0)),
+                    0.0,
+                    edge_color
+                );
+            } else {
+                painter.circle_filled(position, 3.0, edge_color);
+            }
+        }
+    }
+
+    /// Writes the names of the nodes and the distances of the edges on the selected floor.
+    fn paint_floor_plan_labels(&self, painter: &egui::Painter, geometry: &CanvasGeometry) {
+        let graph = self.building_graph.lock().unwrap();
+        let visuals = painter.ctx().style().visuals.clone();
+        if self.show_plan_distance_labels {
+            for (node, coords) in self.floor_plan_nodes(&graph, geometry) {
+                for edge in graph.edges(node) {
+                    let to_name = graph.node_name(edge.to);
+                    let Some(to_coords) = self.projection_coords.get(to_name) else {
+                        continue;
+                    };
+                    if edge.to < node || !geometry.is_on_floor(to_coords) {
+                        continue;
+                    }
+                    let (from, to) = (geometry.screen_pos(coords), geometry.screen_pos(to_coords));
+                    if from.distance(to) >= PLAN_MIN_LABELLED_EDGE {
+                        painter.text(
+                            from.lerp(to, 0.5),
+                            Align2::CENTER_CENTER,
+                            edge.distance.to_string(),
+                            FontId::proportional(10.0),
+                            visuals.weak_text_color()
+                        );
+                    }
+                }
+            }
+        }
+        if self.show_plan_node_labels {
+            for (node, coords) in self.floor_plan_nodes(&graph, geometry) {
+                painter.text(
+                    geometry.screen_pos(coords) - emath::vec2(0.0, 6.0),
+                    Align2::CENTER_BOTTOM,
+                    graph.node_name(node),
+                    FontId::proportional(11.0),
+                    visuals.text_color()
+                );
+            }
+        }
+    }
+
+    /// Graph nodes on the selected floor with their coordinates.
+    fn floor_plan_nodes<'a>(
+        &'a self,
+        graph: &'a BuildingGraph,
+        geometry: &'a CanvasGeometry
+    ) -> impl Iterator<Item = (usize, &'a [i32; 3])> + 'a {
+        (0..graph.node_count()).filter_map(move |node| {
+            self.projection_coords
+                .get(graph.node_name(node))
+                .filter(|coords| geometry.is_on_floor(coords))
+                .map(|coords| (node, coords))
+        })
+    }
+
+    fn show_json_validation_window(
+        &mut self,
+        ctx: &egui::Context,
+        current_validation_status: TimetableValidationStatus
+    ) {
+        Window::new("Timetable Validation").show(ctx, |ui| {
+            ui.heading("Metadata");
+            Grid::new("timetable_validation_grid")
+                .num_columns(2)
+                .show(ui, |ui| {
+                    ui.label("Filename");
+                    ui.label(self.timetable_file_info.filename.to_string());
+                    ui.end_row();
+
+                    ui.label("Student count");
+                    if
+                        let Some(student_count) = *self.timetable_file_info.student_count
+                            .lock()
+                            .unwrap()
+                    {
+                        ui.label(format!("{}", student_count));
+                    } else {
+                        ui.label("—");
+                    }
+                    ui.end_row();
+
+                    ui.label("Session count");
+                    if
+                        let Some(session_count) = *self.timetable_file_info.session_count
+                            .lock()
+                            .unwrap()
+                    {
+                        ui.label(format!("{}", session_count));
+                    } else {
+                        ui.label("—");
+                    }
+                    ui.end_row();
+                });
+            ui.separator();
+            match current_validation_status {
+                TimetableValidationStatus::Ready => {
+                    *self.timetable_file_info.validation_status.clone().lock().unwrap() =
+                        TimetableValidationStatus::Validating(0, "Ready to validate".to_owned());
+                    let filepath = self.timetable_file_info.filepath.clone();
+                    let projection_coords = self.projection_coords.clone();
+                    let validation_status_arc = self.timetable_file_info.validation_status.clone();
+                    let student_count_arc = self.timetable_file_info.student_count.clone();
+                    let session_count_arc = self.timetable_file_info.session_count.clone();
+                    let timetable_arc = self.timetable_file_info.timetable.clone();
+                    let student_list_arc = self.student_list.clone();
+                    thread::spawn(move || {
                         *validation_status_arc.lock().unwrap() =
                             TimetableValidationStatus::Validating(
-                                20,
-                                "Validating periods...".to_owned()
+                                0,
+                                "Reading timetable...".to_owned()
                             );
-                        for (student_key, week_timetable) in timetable.as_object().unwrap() {
-                            for (day_key, day_timetable) in week_timetable.as_object().unwrap() {
-                                if day_timetable.as_object().is_none() {
-                                    *validation_status_arc.lock().unwrap() =
-                                        TimetableValidationStatus::Failed(
-                                            format!(
-                                                "Invalid timetable file format: student {}'s timetable on day {} is not a map",
-                                                student_key,
-                                                day_key
-                                            )
-                                        );
-                                    return;
-                                }
-                                let mut periods = [false; 10];
-                                for period_key in day_timetable.as_object().unwrap().keys() {
-                                    if
-                                        period_key.chars().all(char::is_numeric) &&
-                                        period_key.parse::<i32>().unwrap() <= 10 &&
-                                        period_key.parse::<i32>().unwrap() >= 1
-                                    {
-                                        periods[
-                                            (period_key.parse::<i32>().unwrap() as usize) - 1
-                                        ] = true;
-                                    } else {
-                                        *validation_status_arc.lock().unwrap() =
-                                            TimetableValidationStatus::Failed(
-                                                format!(
-                                                    "Student {} has an invalid period on day {}: \"{}\"",
-                                                    student_key,
-                                                    day_key,
-                                                    period_key
-                                                )
-                                            );
-                                        return;
-                                    }
-                                }
-                                if periods.contains(&false) {
-                                    *validation_status_arc.lock().unwrap() =
-                                        TimetableValidationStatus::Failed(
-                                            format!(
-                                                "Student {} has an incomplete timetable on day {}: missing periods {}",
-                                                student_key,
-                                                day_key,
-                                                periods
-                                                    .iter()
-                                                    .enumerate()
-                                                    .filter(|(_, &b)| !b)
-                                                    .map(|(i, _)| i + 1)
-                                                    .collect::<Vec<usize>>()
-                                                    .iter()
-                                                    .map(|i| i.to_string())
-                                                    .collect::<Vec<String>>()
-                                                    .join(", ")
-                                            )
-                                        );
-                                    return;
-                                }
+                        let timetable = match
+                            Timetable::load(&filepath, |progress| {
+                                *validation_status_arc.lock().unwrap() =
+                                    TimetableValidationStatus::Validating(
+                                        (progress * 90.0) as i32,
+                                        "Reading timetable...".to_owned()
+                                    );
+                            })
+                        {
+                            Ok(timetable) => timetable,
+                            Err(error) => {
+                                *validation_status_arc.lock().unwrap() =
+                                    TimetableValidationStatus::Failed(error.to_string());
+                                return;
                             }
-                        }
+                        };
+                        *student_count_arc.lock().unwrap() = Some(
+                            timetable.student_count() as i32
+                        );
+                        *session_count_arc.lock().unwrap() = Some(
+                            timetable.session_count() as i32
+                        );
 
                         *validation_status_arc.lock().unwrap() =
                             TimetableValidationStatus::Validating(
-                                20,
+                                90,
                                 "Validating classrooms...".to_owned()
                             );
-                        let mut sessions = 0;
-                        for (student_key, week_timetable) in timetable.as_object().unwrap() {
-                            for (day_key, day_timetable) in week_timetable.as_object().unwrap() {
-                                for (period_key, room) in day_timetable.as_object().unwrap() {
-                                    if !rooms.contains(&room.as_str().unwrap().to_owned()) {
-                                        *validation_status_arc.lock().unwrap() =
-                                            TimetableValidationStatus::Failed(
-                                                format!(
-                                                    "Student {} has an invalid classroom on day {} period {}: {}",
-                                                    student_key,
-                                                    day_key,
-                                                    period_key,
-                                                    room
-                                                )
-                                            );
-                                        return;
-                                    }
-                                    sessions += 1;
-                                    if sessions % 1000 == 0 {
-                                        *session_count_arc.lock().unwrap() = Some(sessions);
-                                        *validation_status_arc.lock().unwrap() =
-                                            TimetableValidationStatus::Validating(
-                                                20 +
-                                                    (
-                                                        (((sessions as f32) /
-                                                            ((student_count * 10 * 5) as f32)) *
-                                                            80.0) as i32
-                                                    ),
-                                                "Validating classrooms...".to_owned()
-                                            );
-                                    }
-                                }
-                            }
+                        if
+                            let Err(error) = timetable.check_rooms(|room| {
+                                projection_coords.contains_key(room)
+                            })
+                        {
+                            *validation_status_arc.lock().unwrap() =
+                                TimetableValidationStatus::Failed(error.to_string());
+                            return;
                         }
-                        *timetable_arc.lock().unwrap() = Some(timetable.clone());
 
                         *student_list_arc.lock().unwrap() = timetable
-                            .as_object()
-                            .unwrap()
-                            .keys()
-                            .map(|k| k.to_owned())
+                            .student_numbers()
+                            .map(|student_number| student_number.to_owned())
                             .collect();
+                        *timetable_arc.lock().unwrap() = Some(timetable);
 
                         *validation_status_arc.lock().unwrap() =
                             TimetableValidationStatus::Successful;
@@ -1742,25 +5214,17 @@ impl OptiWayApp {
                                 ui.end_row();
 
                                 let timetable = self.timetable_file_info.timetable.lock().unwrap();
+                                let week_timetable = timetable
+                                    .as_ref()
+                                    .and_then(|timetable| timetable.student(student));
 
-                                for period in 1..=10 {
+                                for period in Period::all() {
                                     ui.label(format!("Period {}", period));
-                                    for weekday in 1..=5 {
-                                        let mut session = timetable
-                                            .as_ref()
-                                            .unwrap()
-                                            .get(student)
-                                            .unwrap()
-                                            .get(weekday.to_string())
-                                            .unwrap()
-                                            .get(period.to_string())
-                                            .unwrap()
-                                            .as_str()
-                                            .unwrap();
-                                        if session == "G" {
-                                            session = " ";
-                                        }
-                                        ui.label(session);
+                                    for day in Day::all() {
+                                        let session = week_timetable.map_or("—", |week_timetable| {
+                                            week_timetable.room(day, period)
+                                        });
+                                        ui.label(if session == "G" { " " } else { session });
                                     }
                                     ui.end_row();
                                 }
@@ -1796,11 +5260,24 @@ impl OptiWayApp {
                 });
                 ui.separator();
                 ui.label("Performance Indices Overview");
-                let pi_matrix = if self.show_pi_shortest {
-                    performance_indices_shortest
+                let (pi_matrix, uncounted_steps) = if self.show_pi_shortest {
+                    (performance_indices_shortest, *self.uncounted_steps_shortest.lock().unwrap())
                 } else {
-                    performance_indices_optimized
+                    (
+                        performance_indices_optimized,
+                        *self.uncounted_steps_optimized.lock().unwrap(),
+                    )
                 };
+                if uncounted_steps > 0 {
+                    ui.label(
+                        RichText::new(
+                            format!(
+                                "{} route steps have no edge in paths.txt and are not counted.",
+                                uncounted_steps.to_formatted_string(&Locale::fr)
+                            )
+                        ).color(Color32::from_rgb(0xe4, 0x37, 0x48))
+                    );
+                }
                 let hightlights = *self.current_period_iter.lock().unwrap();
                 let optimization_status = self.optimization_status.lock().unwrap().clone();
                 Grid::new("shortest_pi_grid")
@@ -1871,7 +5348,7 @@ impl OptiWayApp {
 fn run_floyd_algorithm_cpp(
     filepath: PathBuf,
     path_generation_status_arc: Arc<Mutex<PathGenerationStatus>>,
-    student_paths_arc: Arc<Mutex<Option<Routes>>>
+    student_paths_arc: Arc<Mutex<Option<Arc<CompactRoutes>>>>
 ) -> String {
     let bin_dir = fs::canonicalize("./bin").unwrap();
     if let Ok(binding) = fs::canonicalize(filepath) {
@@ -1898,13 +5375,13 @@ fn run_floyd_algorithm_cpp(
                     );
                     return "".to_owned();
                 };
-                let Ok(routes) = serde_json::from_str::<Routes>(&file_content) else {
+                let Ok(routes) = serde_json::from_str::<CompactRoutes>(&file_content) else {
                     *path_generation_status_arc.lock().unwrap() = PathGenerationStatus::Failed(
                         "Failed to parse result file [routes.json].".to_owned()
                     );
                     return "".to_owned();
                 };
-                *student_paths_arc.lock().unwrap() = Some(routes);
+                *student_paths_arc.lock().unwrap() = Some(Arc::new(routes));
                 *path_generation_status_arc.lock().unwrap() = PathGenerationStatus::Successful;
                 return file_content;
             } else {
@@ -1933,113 +5410,28 @@ fn run_floyd_algorithm_rust(
     filepath: PathBuf,
     shortest_paths_json: HashMap<String, String>,
     path_generation_status_arc: Arc<Mutex<PathGenerationStatus>>,
-    student_paths_arc: Arc<Mutex<Option<Routes>>>
+    student_paths_arc: Arc<Mutex<Option<Arc<CompactRoutes>>>>
 ) {
-    type Timetable = HashMap<String, HashMap<u32, HashMap<usize, String>>>;
-    let mut result: Routes = HashMap::new();
-    let Ok(timetable_file) = File::open(filepath) else {
-        *path_generation_status_arc.lock().unwrap() = PathGenerationStatus::Failed(
-            "Failed to find timetable file.".to_owned()
-        );
-        return;
-    };
-    let Ok(timetable_json) = serde_json::from_reader::<_, Timetable>(timetable_file) else {
-        *path_generation_status_arc.lock().unwrap() = PathGenerationStatus::Failed(
-            "Failed to parse timetable file. Please make sure the file is in JSON format.".to_owned()
-        );
-        return;
+    let timetable = match Timetable::load(&filepath, |_| ()) {
+        Ok(timetable) => timetable,
+        Err(error) => {
+            *path_generation_status_arc.lock().unwrap() = PathGenerationStatus::Failed(
+                error.to_string()
+            );
+            return;
+        }
     };
-    for student_number in timetable_json.keys() {
-        let mut student_hashmap: HashMap<u32, HashMap<usize, String>> = HashMap::new();
-        let mut student_timetable = timetable_json.get(student_number).unwrap().clone();
-        for day in 1..=5 {
-            let mut day_hashmap: HashMap<usize, String> = HashMap::new();
-            student_timetable.entry(day).or_insert_with(HashMap::new);
-            day_hashmap.insert(0, {
-                let first_room = student_timetable.get(&day).unwrap().get(&1).unwrap().to_owned();
-                if first_room == "G" {
-                    "".to_owned()
-                } else {
-                    shortest_paths_json.get(format!("G{}", first_room).as_str()).unwrap().to_owned()
-                }
-            });
-            for period in 1..=5 {
-                day_hashmap.insert(period, {
-                    let room_a = student_timetable
-                        .get(&day)
-                        .unwrap()
-                        .get(&period)
-                        .unwrap()
-                        .to_owned();
-                    let room_b = student_timetable
-                        .get(&day)
-                        .unwrap()
-                        .get(&(period + 1))
-                        .unwrap()
-                        .to_owned();
-                    if room_a == room_b {
-                        "".to_owned()
-                    } else {
-                        shortest_paths_json
-                            .get(format!("{}{}", room_a, room_b).as_str())
-                            .unwrap()
-                            .to_owned()
-                    }
-                });
-            }
-            day_hashmap.insert(6, {
-                let first_room = student_timetable.get(&day).unwrap().get(&6).unwrap().to_owned();
-                if first_room == "G" {
-                    "".to_owned()
-                } else {
-                    shortest_paths_json.get(format!("{}G", first_room).as_str()).unwrap().to_owned()
-                }
-            });
-            day_hashmap.insert(7, {
-                let first_room = student_timetable.get(&day).unwrap().get(&7).unwrap().to_owned();
-                if first_room == "G" {
-                    "".to_owned()
-                } else {
-                    shortest_paths_json.get(format!("G{}", first_room).as_str()).unwrap().to_owned()
-                }
-            });
-            for period in 7..=9 {
-                day_hashmap.insert(period + 1, {
-                    let room_a = student_timetable
-                        .get(&day)
-                        .unwrap()
-                        .get(&period)
-                        .unwrap()
-                        .to_owned();
-                    let room_b = student_timetable
-                        .get(&day)
-                        .unwrap()
-                        .get(&(period + 1))
-                        .unwrap()
-                        .to_owned();
-                    if room_a == room_b {
-                        "".to_owned()
-                    } else {
-                        shortest_paths_json
-                            .get(format!("{}{}", room_a, room_b).as_str())
-                            .unwrap()
-                            .to_owned()
-                    }
-                });
-            }
-            day_hashmap.insert(11, {
-                let first_room = student_timetable.get(&day).unwrap().get(&10).unwrap().to_owned();
-                if first_room == "G" {
-                    "".to_owned()
-                } else {
-                    shortest_paths_json.get(format!("{}G", first_room).as_str()).unwrap().to_owned()
-                }
-            });
-            student_hashmap.insert(day, day_hashmap);
+    let routes = routes_from_timetable(&timetable, &shortest_paths_json);
+    let routes = match CompactRoutes::from_routes(&routes) {
+        Ok(routes) => routes,
+        Err(error) => {
+            *path_generation_status_arc.lock().unwrap() = PathGenerationStatus::Failed(
+                error.to_string()
+            );
+            return;
         }
-        result.insert(student_number.to_owned(), student_hashmap);
-    }
-    *student_paths_arc.lock().unwrap() = Some(result);
+    };
+    *student_paths_arc.lock().unwrap() = Some(Arc::new(routes));
     *path_generation_status_arc.lock().unwrap() = PathGenerationStatus::Successful;
 }
 
@@ -2056,6 +5448,11 @@ impl eframe::App for OptiWayApp {
         let current_congestion_status = self.congestion_status.lock().unwrap().clone();
         let current_path_status = self.path_generation_status.lock().unwrap().clone();
         let current_optimization_status = self.optimization_status.lock().unwrap().clone();
+        let current_room_swap_status = self.room_swap_status.lock().unwrap().clone();
+        let current_dismissal_status = self.dismissal_status.lock().unwrap().clone();
+        let current_pareto_status = self.pareto_status.lock().unwrap().clone();
+        let current_monte_carlo_status = self.monte_carlo_status.lock().unwrap().clone();
+        let current_robustness_status = self.robustness_status.lock().unwrap().clone();
         egui::TopBottomPanel::top("top_panel").show(ctx, |ui| {
             egui::menu::bar(ui, |ui| {
                 ui.label("OptiWay");
@@ -2096,6 +5493,12 @@ impl eframe::App for OptiWayApp {
                         ui.label("Evaluating congestion");
                     } else if current_optimization_status == OptimizationStatus::Calculating {
                         ui.label("Optimizing routes");
+                    } else if let RoomSwapStatus::Searching(_, _) = current_room_swap_status {
+                        ui.label("Searching for room swaps");
+                    } else if let DismissalStatus::Searching(_, _) = current_dismissal_status {
+                        ui.label("Searching release offsets");
+                    } else if let ParetoStatus::Searching(_, _) = current_pareto_status {
+                        ui.label("Generating route plans");
                     } else {
                         ui.label("Ready");
                     }
@@ -2125,6 +5528,72 @@ impl eframe::App for OptiWayApp {
                             self.selected_student = None;
                         }
                     }
+                    if
+                        ui
+                            .button("Import staff timetable")
+                            .on_hover_text(
+                                "Optional. Route the staff along the shortest paths and count them in the congestion."
+                            )
+                            .clicked()
+                    {
+                        let file = FileDialog::new().add_filter("JSON", &["json"]).pick_file();
+                        if let Some(file) = file {
+                            let file_name = file
+                                .file_name()
+                                .unwrap()
+                                .to_string_lossy()
+                                .into_owned();
+                            self.load_staff_timetable(&file, &file_name);
+                        }
+                    }
+                    if let Some(message) = &self.staff_timetable_message {
+                        ui.label(RichText::new(message).color(Color32::from_rgb(0xe4, 0x37, 0x48)));
+                    } else if let Some((file_name, timetable)) = &self.staff_timetable {
+                        ui.label(format!("{}: {} staff", file_name, timetable.student_count()));
+                    }
+                    ui.add_enabled_ui(
+                        current_optimization_status != OptimizationStatus::Calculating,
+                        |ui| {
+                            if
+                                ui
+                                    .button("Import destinations")
+                                    .on_hover_text(
+                                        "Optional. Send the students somewhere other than the ground on arrival, at lunch and on dismissal."
+                                    )
+                                    .on_disabled_hover_text("Wait for the optimization to stop.")
+                                    .clicked()
+                            {
+                                let file = FileDialog::new()
+                                    .add_filter("YAML", &["yaml", "yml"])
+                                    .pick_file();
+                                if let Some(file) = file {
+                                    let file_name = file
+                                        .file_name()
+                                        .unwrap()
+                                        .to_string_lossy()
+                                        .into_owned();
+                                    self.load_destinations(&file, &file_name);
+                                }
+                            }
+                        }
+                    );
+                    if let Some(message) = &self.destination_message {
+                        ui.label(RichText::new(message).color(Color32::from_rgb(0xe4, 0x37, 0x48)));
+                    } else if let Some((file_name, model)) = &self.destination_model {
+                        ui.horizontal(|ui| {
+                            ui.label(
+                                format!(
+                                    "{}: {} destinations, {} cohorts",
+                                    file_name,
+                                    model.destinations.len(),
+                                    model.cohorts.len()
+                                )
+                            );
+                            if ui.small_button("Show").clicked() {
+                                self.show_destination_window = true;
+                            }
+                        });
+                    }
                     ui.add_enabled_ui(
                         current_validation_status == TimetableValidationStatus::Successful,
                         |ui| {
@@ -2171,6 +5640,17 @@ impl eframe::App for OptiWayApp {
                                 *self.optimization_status.lock().unwrap() =
                                     OptimizationStatus::ParamInput;
                             }
+                            if
+                                ui
+                                    .button("Multi-objective optimization")
+                                    .on_disabled_hover_text("Calculate congestion first.")
+                                    .on_hover_text(
+                                        "Trade walking distance against congestion and pick a route plan."
+                                    )
+                                    .clicked()
+                            {
+                                self.show_pareto_window = true;
+                            }
                         }
                     );
                     ui.separator();
@@ -2245,43 +5725,192 @@ impl eframe::App for OptiWayApp {
                     if ui.button("Show performance indices").clicked() {
                         self.show_pi_window = true;
                     }
+                    if
+                        ui
+                            .button("Edit route")
+                            .on_hover_text(
+                                "Edit the route of the selected student in the selected period by hand."
+                            )
+                            .clicked()
+                    {
+                        self.open_route_editor();
+                    }
+                    if
+                        ui
+                            .button("Edit building")
+                            .on_hover_text(
+                                "Edit the nodes and edges of the building model in its asset files."
+                            )
+                            .clicked()
+                    {
+                        self.show_building_editor_window = true;
+                    }
+                    if
+                        ui
+                            .button("Check building")
+                            .on_hover_text(
+                                "Check the building graph, its source files and the precomputed paths for inconsistencies."
+                            )
+                            .clicked()
+                    {
+                        self.show_consistency_window = true;
+                    }
+                    ui.separator();
+                    ui.heading("Analysis");
+                    ui.add_enabled_ui(
+                        current_validation_status == TimetableValidationStatus::Successful,
+                        |ui| {
+                            if
+                                ui
+                                    .button("Suggest room swaps")
+                                    .on_hover_text(
+                                        "Search for room changes that reduce the performance index of the shortest routes."
+                                    )
+                                    .on_disabled_hover_text("Import a timetable first.")
+                                    .clicked()
+                            {
+                                self.show_room_swap_window = true;
+                            }
+                        }
+                    );
+                    ui.add_enabled_ui(
+                        current_path_status == PathGenerationStatus::Successful,
+                        |ui| {
+                            if
+                                ui
+                                    .button("Staggered dismissal")
+                                    .on_hover_text(
+                                        "Evaluate time-resolved congestion when groups of students are released at different times."
+                                    )
+                                    .on_disabled_hover_text("Calculate routes first.")
+                                    .clicked()
+                            {
+                                self.show_dismissal_window = true;
+                            }
+                            if
+                                ui
+                                    .button("Monte Carlo congestion")
+                                    .on_hover_text(
+                                        "Simulate the congestion when some students do not follow their planned routes."
+                                    )
+                                    .on_disabled_hover_text("Calculate routes first.")
+                                    .clicked()
+                            {
+                                self.show_monte_carlo_window = true;
+                            }
+                            if
+                                ui
+                                    .button("Robustness")
+                                    .on_hover_text(
+                                        "Score the routes against timetables with random absences and room changes."
+                                    )
+                                    .on_disabled_hover_text("Calculate routes first.")
+                                    .clicked()
+                            {
+                                self.show_robustness_window = true;
+                            }
+                        }
+                    );
+                    ui.add_enabled_ui(self.student_routes_optimized.lock().unwrap().is_some(), |ui| {
+                        if
+                            ui
+                                .button("Detour distribution")
+                                .on_hover_text(
+                                    "Show how much further students walk on the optimized routes than on the shortest routes."
+                                )
+                                .on_disabled_hover_text("Optimize routes first.")
+                                .clicked()
+                        {
+                            self.show_detour_window = true;
+                            self.detour_report = None;
+                        }
+                    });
                     ui.separator();
                     ui.heading("Export");
                     if ui.button("Export shortest routes").clicked() {
-                        let file = FileDialog::new().add_filter("JSON", &["json"]).save_file();
+                        let file = FileDialog::new()
+                            .add_filter("JSON", &["json"])
+                            .add_filter("OptiWay routes", &[BINARY_EXTENSION])
+                            .save_file();
                         if let Some(file) = file {
-                            let mut file = File::create(file).unwrap();
                             let student_routes = self.student_routes_shortest.lock().unwrap();
-                            let student_routes = student_routes.as_ref().unwrap();
-                            let json = serde_json::to_string_pretty(&student_routes).unwrap();
-                            file.write_all(json.as_bytes()).unwrap();
+                            student_routes.as_ref().unwrap().save(&file).unwrap();
                         }
                     }
                     let enabled = self.student_routes_optimized.lock().unwrap().is_some();
                     ui.add_enabled_ui(enabled, |ui| {
                         if ui.button("Export optimized routes").clicked() {
-                            let file = FileDialog::new().add_filter("JSON", &["json"]).save_file();
+                            let file = FileDialog::new()
+                                .add_filter("JSON", &["json"])
+                                .add_filter("OptiWay routes", &[BINARY_EXTENSION])
+                                .save_file();
                             if let Some(file) = file {
-                                let mut file = File::create(file).unwrap();
                                 let student_routes = self.student_routes_optimized.lock().unwrap();
-                                let student_routes = student_routes.as_ref().unwrap();
-                                let json = serde_json::to_string_pretty(&student_routes).unwrap();
-                                file.write_all(json.as_bytes()).unwrap();
+                                student_routes.as_ref().unwrap().save(&file).unwrap();
                             }
                         }
                     });
+                    ui.add_enabled_ui(
+                        current_path_status == PathGenerationStatus::Successful,
+                        |ui| {
+                            if
+                                ui
+                                    .button("Export route sheets")
+                                    .on_hover_text(
+                                        "Write a printable page with the timetable and directions of every student."
+                                    )
+                                    .on_disabled_hover_text("Calculate routes first.")
+                                    .clicked()
+                            {
+                                self.show_route_sheet_window = true;
+                            }
+                        }
+                    );
                     ui.separator();
                     ui.heading("Floor view");
                     ui.horizontal(|ui| {
-                        if ui.toggle_value(&mut self.selected_floor[0], "All").clicked() {
-                            self.selected_floor_index = 0;
-                            for i in 1..=8 {
-                                self.selected_floor[i] = false;
-                            }
-                            if !self.selected_floor.contains(&true) {
-                                self.selected_floor[0] = true;
+                        if
+                            ui
+                                .selectable_label(
+                                    self.floor_view_mode == FloorViewMode::Projection,
+                                    "Projection"
+                                )
+                                .clicked()
+                        {
+                            self.floor_view_mode = FloorViewMode::Projection;
+                        }
+                        if
+                            ui
+                                .selectable_label(
+                                    self.floor_view_mode == FloorViewMode::Plan,
+                                    "Floor plan"
+                                )
+                                .on_hover_text(
+                                    "Top-down plan of the selected floor."
+                                )
+                                .clicked()
+                        {
+                            self.floor_view_mode = FloorViewMode::Plan;
+                            // the floor plan shows a single floor
+                            if self.selected_floor_index == 0 {
+                                self.selected_floor = [false; 9];
+                                self.selected_floor[2] = true;
+                                self.selected_floor_index = 2;
                             }
                         }
+                    });
+                    ui.horizontal(|ui| {
+                        ui.add_enabled_ui(self.floor_view_mode == FloorViewMode::Projection, |ui| {
+                            if ui.toggle_value(&mut self.selected_floor[0], "All").clicked() {
+                                self.selected_floor_index = 0;
+                                for i in 1..=8 {
+                                    self.selected_floor[i] = false;
+                                }
+                                if !self.selected_floor.contains(&true) {
+                                    self.selected_floor[0] = true;
+                                }
+                            }
+                        });
                         for i in 2..=8 {
                             if
                                 ui
@@ -2300,21 +5929,86 @@ impl eframe::App for OptiWayApp {
                             }
                         }
                     });
-                    ui.add(
-                        Slider::new(&mut self.inactive_brightness, 32..=255).text(
-                            "Inactive floor brightness"
-                        )
-                    );
+                    if self.floor_view_mode == FloorViewMode::Projection {
+                        ui.add(
+                            Slider::new(&mut self.inactive_brightness, 32..=255).text(
+                                "Inactive floor brightness"
+                            )
+                        );
+                    } else {
+                        ui.horizontal(|ui| {
+                            ui.checkbox(&mut self.show_plan_node_labels, "Node names");
+                            ui.checkbox(&mut self.show_plan_distance_labels, "Distances");
+                        });
+                    }
+                    ui.horizontal(|ui| {
+                        if
+                            ui
+                                .button("Fit floor")
+                                .on_hover_text("Zoom to the nodes of the selected floor.")
+                                .clicked()
+                        {
+                            self.canvas_fit = Some(CanvasFit::Floor);
+                        }
+                        if
+                            ui
+                                .add_enabled(
+                                    self.selected_student.is_some(),
+                                    egui::Button::new("Fit route")
+                                )
+                                .on_hover_text("Zoom to the route of the selected student.")
+                                .clicked()
+                        {
+                            self.canvas_fit = Some(CanvasFit::Route);
+                        }
+                        if ui.button("Reset view").clicked() {
+                            *self.canvas_view_mut() = Default::default();
+                        }
+                    });
                     ui.separator();
                     ui.horizontal(|ui| {
                         if ui.selectable_label(!self.show_congestion, "Show paths").clicked() {
                             self.show_congestion = false;
                         }
-                        if ui.selectable_label(self.show_congestion, "Show congestion").clicked() {
-                            self.show_congestion = true;
+                        if ui.selectable_label(self.show_congestion, "Show congestion").clicked() {
+                            self.show_congestion = true;
+                        }
+                    });
+                    if self.show_congestion {
+                        let mut layers = self.congestion_layers.lock().unwrap().clone();
+                        let mut refresh = false;
+                        ui.horizontal(|ui| {
+                            for layer in CongestionLayer::ALL {
+                                if
+                                    ui
+                                        .selectable_label(layers.layer == layer, layer.to_string())
+                                        .clicked()
+                                {
+                                    layers.layer = layer;
+                                    refresh = true;
+                                }
+                            }
+                        });
+                        if layers.staff.is_some() {
+                            let response = ui
+                                .add(
+                                    Slider::new(&mut layers.staff_weight, 0.0..=5.0).text(
+                                        "Staff weight"
+                                    )
+                                )
+                                .on_hover_text(
+                                    "Number of students a member of staff counts as in the combined layer."
+                                );
+                            refresh |=
+                                response.drag_released() ||
+                                (response.changed() && !response.dragged());
+                        } else if layers.layer != CongestionLayer::Students {
+                            ui.label("Import a staff timetable to count the staff.");
+                        }
+                        *self.congestion_layers.lock().unwrap() = layers;
+                        if refresh {
+                            self.refresh_congestion_layer();
                         }
-                    });
-                    if self.show_congestion {
                         ui.checkbox(&mut self.show_congestion_path, "Show path congestion");
                         ui.checkbox(&mut self.show_congestion_point, "Show node congestion");
                         ui.heading("Legend");
@@ -2402,9 +6096,51 @@ impl eframe::App for OptiWayApp {
             if self.show_pi_window {
                 self.show_pi_window(ctx);
             }
+            if self.show_pinning_window {
+                self.show_pinning_window(ctx);
+            }
+            if self.show_destination_window {
+                self.show_destination_window(ctx);
+            }
+            if self.show_building_editor_window {
+                self.show_building_editor_window(ctx);
+            }
+            if self.show_consistency_window {
+                self.show_consistency_window(ctx);
+            }
+            if self.show_route_file_window {
+                self.show_route_file_window(ctx);
+            }
+            if self.show_route_sheet_window {
+                self.show_route_sheet_window(ctx);
+            }
+            if self.show_route_editor_window {
+                self.show_route_editor_window(
+                    ctx,
+                    current_optimization_status == OptimizationStatus::Calculating
+                );
+            }
             if self.show_optimization_window {
                 self.show_optimization_window(ctx, current_optimization_status);
             }
+            if self.show_room_swap_window {
+                self.show_room_swap_window(ctx, current_room_swap_status);
+            }
+            if self.show_dismissal_window {
+                self.show_dismissal_window(ctx, current_dismissal_status);
+            }
+            if self.show_monte_carlo_window {
+                self.show_monte_carlo_window(ctx, current_monte_carlo_status);
+            }
+            if self.show_robustness_window {
+                self.show_robustness_window(ctx, current_robustness_status);
+            }
+            if self.show_pareto_window {
+                self.show_pareto_window(ctx, current_pareto_status);
+            }
+            if self.show_detour_window {
+                self.show_detour_window(ctx);
+            }
 
             // Paths
 
@@ -2416,12 +6152,15 @@ impl eframe::App for OptiWayApp {
                     self.student_routes_shortest.lock().unwrap().clone()
                 };
                 if let Some(student_routes) = student_routes {
-                    student_routes[&student_number][&self.selected_day][&self.selected_period]
-                        .split(' ')
-                        .collect::<Vec<&str>>()
-                        .iter()
-                        .map(|s| (*s).to_owned())
-                        .collect::<Vec<String>>()
+                    student_routes
+                        .student_index(&student_number)
+                        .map(|student_index| {
+                            student_routes
+                                .route_names(student_index, self.selected_day, self.selected_period)
+                                .map(|node| node.to_owned())
+                                .collect()
+                        })
+                        .unwrap_or_default()
                 } else {
                     vec![]
                 }
@@ -2439,246 +6178,204 @@ impl eframe::App for OptiWayApp {
 
             // Paths window
             if self.show_path_window {
+                let is_edited =
+                    self.path_display == PathDisplay::Optimized &&
+                    self.selected_student.as_ref().is_some_and(|student_number| {
+                        self.is_route_edited(
+                            student_number,
+                            self.selected_day,
+                            self.selected_period
+                        )
+                    });
                 Window::new("Path")
                     .open(&mut self.show_path_window)
                     .show(ctx, |ui| {
-                        let mut path_string = String::new();
-                        for i in &path_list {
-                            path_string.push_str(i);
-                            path_string.push_str(" → ");
+                        ui.horizontal(|ui| {
+                            for language in Language::ALL {
+                                ui.selectable_value(
+                                    &mut self.path_language,
+                                    language,
+                                    language.name()
+                                );
+                            }
+                        });
+                        let graph = self.building_graph.lock().unwrap();
+                        let route = graph.route_ids(&path_list.join(" ")).unwrap_or_default();
+                        let steps = route_steps(&graph, &self.projection_coords, &route);
+                        let instructions = instructions(&steps, self.path_language);
+                        if instructions.is_empty() {
+                            ui.label("No route.");
+                        } else {
+                            Grid::new("directions_grid")
+                                .striped(true)
+                                .num_columns(3)
+                                .show(ui, |ui| {
+                                    for (index, instruction) in instructions.iter().enumerate() {
+                                        ui.label(format!("{}.", index + 1));
+                                        ui.label(&instruction.text);
+                                        if instruction.distance > 0 {
+                                            ui.label(instruction.distance.to_string());
+                                        }
+                                        ui.end_row();
+                                    }
+                                    ui.label("");
+                                    ui.label(
+                                        match self.path_language {
+                                            Language::English => "Total",
+                                            Language::Chinese => "总距离",
+                                        }
+                                    );
+                                    ui.label(
+                                        instructions
+                                            .iter()
+                                            .map(|instruction| instruction.distance)
+                                            .sum::<u32>()
+                                            .to_string()
+                                    );
+                                    ui.end_row();
+                                });
+                        }
+                        egui::CollapsingHeader::new("Nodes").show(ui, |ui| {
+                            ui.label(path_list.join(" → "));
+                        });
+                        if is_edited {
+                            ui.label("Edited by hand");
                         }
-                        path_string.pop();
-                        path_string.pop();
-                        path_string.pop();
-                        ui.label(path_string);
                     });
             }
 
-            // Import textures if uninitialized
-
-            let mut textures: Vec<TextureHandle> = Vec::new();
-            for i in 2..=8 {
-                let texture_cur: &TextureHandle = self.textures[i].get_or_insert_with(|| {
-                    ui.ctx().load_texture(
-                        format!("texture-floor-projection-{i}F"),
-                        load_image_from_path(
-                            Path::new(
-                                format!(
-                                    "assets/projection-transparent/projection_{i}F.png"
-                                ).as_str()
-                            )
-                        ).unwrap(),
-                        Default::default()
-                    )
-                });
-                textures.push(texture_cur.clone());
-            }
+            let is_plan = self.floor_view_mode == FloorViewMode::Plan;
             let desired_size = ui.available_size_before_wrap();
-            if desired_size.y < (desired_size.x / 2243.0) * (1221.0 + 350.0) {
-                ui.label("▲ There may not be enough space to display the floor plan.");
+            if
+                !is_plan &&
+                self.projection_view.is_default() &&
+                desired_size.y < (desired_size.x / 2243.0) * (1221.0 + 350.0)
+            {
+                ui.label(
+                    "▲ There may not be enough space to display the floor plan. Scroll to zoom out."
+                );
             }
-            let (_id, rect) = ui.allocate_space(desired_size);
-            let scale = rect.width() / 2243.0;
-
-            // Paint floor projections
+            let (rect, canvas_response) = ui.allocate_exact_size(
+                desired_size,
+                Sense::click_and_drag()
+            );
+            let painter = ui.painter_at(rect);
 
-            let current_floor_z = if self.selected_floor_index == 0 {
-                0
-            } else {
-                ((self.selected_floor_index - 2) * 50) as i32
-            };
+            if let Some(fit) = self.canvas_fit.take() {
+                let geometry = self.canvas_geometry(rect, &CanvasView::default());
+                let coords: Vec<&[i32; 3]> = match fit {
+                    CanvasFit::Floor =>
+                        self.projection_coords
+                            .values()
+                            .filter(|coords| geometry.is_on_floor(coords))
+                            .collect(),
+                    CanvasFit::Route =>
+                        segments
+                            .iter()
+                            .copied()
+                            .filter(|coords| {
+                                geometry.shows_other_floors() || geometry.is_on_floor(coords)
+                            })
+                            .collect(),
+                };
+                let points: Vec<emath::Pos2> = coords
+                    .iter()
+                    .map(|coords| geometry.screen_pos(coords))
+                    .collect();
+                let mut view = self.canvas_view();
+                view.fit(rect, &points);
+                // not `canvas_view_mut`, as the segments still borrow `self.projection_coords`
+                match self.floor_view_mode {
+                    FloorViewMode::Projection => self.projection_view = view,
+                    FloorViewMode::Plan => self.plan_view = view,
+                }
+            }
+            let geometry = self.canvas_geometry(rect, &self.canvas_view());
 
+            let is_editing_building =
+                self.show_building_editor_window && self.building_editor.is_some();
+            if is_plan && !is_editing_building {
+                self.paint_floor_plan(&painter, &geometry);
+            }
             if !self.show_congestion {
-                for (i, point) in segments.iter().enumerate() {
-                    if i != 0 {
-                        ui.painter().circle_filled(convert_pos(&rect, point, scale), 4.0, if
-                            self.selected_floor_index == 0 ||
-                            (current_floor_z >= point[2].min(segments[i - 1][2]) &&
-                                current_floor_z <= point[2].max(segments[i - 1][2]))
-                        {
-                            self.active_path_color
-                        } else {
-                            self.inactive_path_color
-                        });
-                        ui.painter().line_segment(
-                            [
-                                convert_pos(&rect, segments[i - 1], scale),
-                                convert_pos(&rect, point, scale),
-                            ],
-                            if
-                                self.selected_floor_index == 0 ||
-                                (current_floor_z >= point[2].min(segments[i - 1][2]) &&
-                                    current_floor_z <= point[2].max(segments[i - 1][2]))
-                            {
-                                Stroke::new(4.0, self.active_path_color)
-                            } else {
-                                Stroke::new(4.0, self.inactive_path_color)
-                            }
-                        );
-                    } else {
-                        ui.painter().circle_filled(convert_pos(&rect, point, scale), 4.0, if
-                            self.selected_floor_index == 0 ||
-                            current_floor_z == point[2].min(segments[i][2])
-                        {
-                            self.active_path_color
-                        } else {
-                            self.inactive_path_color
-                        });
-                    }
-                }
+                self.paint_route(&painter, &geometry, &segments);
             } else {
-                if self.show_congestion_path {
-                    if
-                        self.path_display == PathDisplay::Optimized &&
-                        self.student_routes_optimized.lock().unwrap().is_some()
-                    {
-                        for ((node1, node2), congestion) in self.congestion_path_data_opt
-                            .lock()
-                            .unwrap()
-                            .clone()
-                            .get(&self.selected_day)
-                            .unwrap()
-                            .get(&self.selected_period)
-                            .unwrap() {
-                            if node1 == "G" || node2 == "G" || congestion < &self.congestion_filter {
-                                continue;
-                            }
-                            let node1_pos = self.projection_coords[node1];
-                            let node2_pos = self.projection_coords[node2];
-                            if
-                                self.selected_floor_index == 0 ||
-                                (current_floor_z >= node1_pos[2].min(node2_pos[2]) &&
-                                    current_floor_z <= node1_pos[2].max(node2_pos[2]))
-                            {
-                                ui.painter().line_segment(
-                                    [
-                                        convert_pos(&rect, &node1_pos, scale),
-                                        convert_pos(&rect, &node2_pos, scale),
-                                    ],
-                                    Stroke::new(4.0, congestion_color_scale(*congestion))
-                                );
-                            }
-                        }
-                    } else {
-                        for ((node1, node2), congestion) in self.congestion_path_data
-                            .lock()
-                            .unwrap()
-                            .clone()
-                            .get(&self.selected_day)
-                            .unwrap()
-                            .get(&self.selected_period)
-                            .unwrap() {
-                            if node1 == "G" || node2 == "G" || congestion < &self.congestion_filter {
-                                continue;
-                            }
-                            let node1_pos = self.projection_coords[node1];
-                            let node2_pos = self.projection_coords[node2];
-                            if
-                                self.selected_floor_index == 0 ||
-                                (current_floor_z >= node1_pos[2].min(node2_pos[2]) &&
-                                    current_floor_z <= node1_pos[2].max(node2_pos[2]))
-                            {
-                                ui.painter().line_segment(
-                                    [
-                                        convert_pos(&rect, &node1_pos, scale),
-                                        convert_pos(&rect, &node2_pos, scale),
-                                    ],
-                                    Stroke::new(4.0, congestion_color_scale(*congestion))
-                                );
-                            }
-                        }
-                    }
+                self.paint_congestion(&painter, &geometry);
+            }
+
+            if let CanvasGeometry::Projection { drawing, scale, .. } = geometry {
+                // Import textures if uninitialized
+
+                let mut textures: Vec<TextureHandle> = Vec::new();
+                for i in 2..=8 {
+                    let texture_cur: &TextureHandle = self.textures[i].get_or_insert_with(|| {
+                        ui.ctx().load_texture(
+                            format!("texture-floor-projection-{i}F"),
+                            load_image_from_path(
+                                Path::new(
+                                    format!(
+                                        "assets/projection-transparent/projection_{i}F.png"
+                                    ).as_str()
+                                )
+                            ).unwrap(),
+                            Default::default()
+                        )
+                    });
+                    textures.push(texture_cur.clone());
                 }
-                if self.show_congestion_point {
-                    if
-                        self.path_display == PathDisplay::Optimized &&
-                        self.student_routes_optimized.lock().unwrap().is_some()
-                    {
-                        for (room, congestion) in self.congestion_point_data_opt
-                            .lock()
-                            .unwrap()
-                            .clone()
-                            .get(&self.selected_day)
-                            .unwrap()
-                            .get(&self.selected_period)
-                            .unwrap() {
-                            if room == "G" || room.is_empty() {
-                                continue;
-                            }
-                            let coords = self.projection_coords.get(room).unwrap();
-                            if
-                                (self.selected_floor_index == 0 || current_floor_z == coords[2]) &&
-                                *congestion >= self.congestion_filter
-                            {
-                                ui.painter().circle_filled(
-                                    convert_pos(&rect, coords, scale),
-                                    4.0,
-                                    congestion_color_scale(*congestion)
-                                );
-                            }
-                        }
-                    } else {
-                        for (room, congestion) in self.congestion_point_data
-                            .lock()
-                            .unwrap()
-                            .clone()
-                            .get(&self.selected_day)
-                            .unwrap()
-                            .get(&self.selected_period)
-                            .unwrap() {
-                            if room == "G" || room.is_empty() {
-                                continue;
-                            }
-                            let coords = self.projection_coords.get(room).unwrap();
-                            if
-                                (self.selected_floor_index == 0 || current_floor_z == coords[2]) &&
-                                *congestion >= self.congestion_filter
-                            {
-                                ui.painter().circle_filled(
-                                    convert_pos(&rect, coords, scale),
-                                    4.0,
-                                    congestion_color_scale(*congestion)
-                                );
-                            }
+
+                // Paint floor projections
+
+                for (i, texture) in textures.iter().enumerate().take(7) {
+                    let texture_rect = Rect::from_min_size(
+                        drawing.min,
+                        emath::vec2(drawing.width(), drawing.width() / texture.aspect_ratio())
+                    ).translate(emath::vec2(0.0, ((7 - i) as f32) * 50.0 * scale));
+
+                    painter.image(
+                        texture.into(),
+                        texture_rect,
+                        Rect::from_min_max(pos2(0.0, 0.0), pos2(1.0, 1.0)),
+                        if self.selected_floor[0] || self.selected_floor[i + 2] {
+                            Color32::WHITE
+                        } else {
+                            Color32::from_gray(self.inactive_brightness)
                         }
-                    }
+                    );
                 }
-            }
-
-            for (i, texture) in textures.iter().enumerate().take(7) {
-                let rect = Rect::from_min_size(
-                    rect.min,
-                    emath::vec2(rect.width(), rect.width() / texture.aspect_ratio())
-                ).translate(emath::vec2(0.0, ((7 - i) as f32) * 50.0 * scale));
+                // Special case: the floor is selected, so needs to be repainted last
+                if self.selected_floor_index != 0 {
+                    let texture = textures[self.selected_floor_index - 2].clone();
+                    let texture_rect = Rect::from_min_size(
+                        drawing.min,
+                        emath::vec2(drawing.width(), drawing.width() / texture.aspect_ratio())
+                    ).translate(
+                        emath::vec2(
+                            0.0,
+                            ((7 - (self.selected_floor_index - 2)) as f32) * 50.0 * scale
+                        )
+                    );
 
-                ui.painter().image(
-                    texture.into(),
-                    rect,
-                    Rect::from_min_max(pos2(0.0, 0.0), pos2(1.0, 1.0)),
-                    if self.selected_floor[0] || self.selected_floor[i + 2] {
+                    painter.image(
+                        (&texture).into(),
+                        texture_rect,
+                        Rect::from_min_max(pos2(0.0, 0.0), pos2(1.0, 1.0)),
                         Color32::WHITE
-                    } else {
-                        Color32::from_gray(self.inactive_brightness)
-                    }
-                );
+                    );
+                }
+            } else if !is_editing_building {
+                self.paint_floor_plan_labels(&painter, &geometry);
             }
-            // Special case: the floor is selected, so needs to be repainted last
-            if self.selected_floor_index != 0 {
-                let texture = textures[self.selected_floor_index - 2].clone();
-                let rect = Rect::from_min_size(
-                    rect.min,
-                    emath::vec2(rect.width(), rect.width() / texture.aspect_ratio())
-                ).translate(
-                    emath::vec2(0.0, ((7 - (self.selected_floor_index - 2)) as f32) * 50.0 * scale)
-                );
 
-                ui.painter().image(
-                    (&texture).into(),
-                    rect,
-                    Rect::from_min_max(pos2(0.0, 0.0), pos2(1.0, 1.0)),
-                    Color32::WHITE
-                );
+            if is_editing_building {
+                self.edit_building_on_canvas(ui, &canvas_response, &geometry);
+            } else if self.route_editor.is_some() {
+                self.edit_route_on_canvas(ui, &canvas_response, &geometry);
             }
+            let pan =
+                self.route_editor_drag.is_none() &&
+                self.building_editor.as_ref().is_none_or(|editor| editor.drag.is_none());
+            self.canvas_view_mut().pan_and_zoom(ui, &canvas_response, rect, pan);
         });
     }
 }
@@ -2709,38 +6406,224 @@ fn convert_pos(rect: &Rect, pos: &[i32; 3], scale: f32) -> emath::Pos2 {
     ).to_pos2()
 }
 
+/// Converts a point on screen back to the x and y coordinates in projection-coords.yaml of the
+/// point at height `z` that `convert_pos` draws there.
+fn convert_pos_inverse(rect: &Rect, pos: emath::Pos2, z: i32, scale: f32) -> [f32; 2] {
+    /// Projection angle of the floor plan (radians)
+    const ANGLE: f32 = PI / 6.0;
+
+    let sum = (pos.x - rect.left()) / (ANGLE.cos() * scale) - 25.0;
+    let difference =
+        ((pos.y - rect.top()) / scale - 50.0 - 350.0 - 25.0 * ANGLE.sin() + (z as f32)) /
+        ANGLE.sin();
+    [(sum + difference) / 2.0, (sum - difference) / 2.0]
+}
+
+/// Name of the floor of 3D coordinates in projection-coords.yaml, such as `5F`.
+fn floor_name(pos: &[i32; 3]) -> String {
+    format!("{}F", pos[2] / 50 + 2)
+}
+
+/// Zoom and pan of a canvas, kept between frames.
+#[derive(Clone, Copy)]
+struct CanvasView {
+    zoom: f32,
+    /// Displacement of the drawing from the centre of the canvas, in points.
+    offset: emath::Vec2,
+}
+
+impl Default for CanvasView {
+    fn default() -> Self {
+        Self { zoom: 1.0, offset: emath::Vec2::ZERO }
+    }
+}
+
+impl CanvasView {
+    fn is_default(&self) -> bool {
+        self.zoom == 1.0 && self.offset == emath::Vec2::ZERO
+    }
+
+    /// Where `rect` of the canvas is drawn with this view.
+    fn transform_rect(&self, rect: Rect) -> Rect {
+        let center = rect.center() + self.offset;
+        Rect::from_center_size(center, rect.size() * self.zoom)
+    }
+
+    /// Zooms and pans so that `points`, drawn at the default view, fill the canvas at `rect`.
+    fn fit(&mut self, rect: Rect, points: &[emath::Pos2]) {
+        if points.is_empty() {
+            return;
+        }
+        let bounds = Rect::from_points(points);
+        let available = (rect.size() - emath::Vec2::splat(2.0 * CANVAS_FIT_MARGIN)).max(
+            emath::Vec2::splat(1.0)
+        );
+        let zoom = (available.x / bounds.width())
+            .min(available.y / bounds.height())
+            .clamp(MIN_CANVAS_ZOOM, MAX_CANVAS_ZOOM);
+        self.zoom = zoom;
+        self.offset = (rect.center() - bounds.center()) * zoom;
+    }
+
+    /// Zooms about the pointer with the scroll wheel, and pans by dragging if `pan` is set.
+    fn pan_and_zoom(&mut self, ui: &egui::Ui, response: &egui::Response, rect: Rect, pan: bool) {
+        if pan && response.dragged() {
+            self.offset += response.drag_delta();
+        }
+        let Some(pointer) = response.hover_pos() else {
+            return;
+        };
+        let (scroll, zoom_delta) = ui.input(|input| (input.scroll_delta.y, input.zoom_delta()));
+        let zoom = (self.zoom * zoom_delta * (scroll / 200.0).exp()).clamp(
+            MIN_CANVAS_ZOOM,
+            MAX_CANVAS_ZOOM
+        );
+        if zoom != self.zoom {
+            // keeps the point under the pointer in place
+            let anchor = pointer - rect.center();
+            self.offset = anchor - (anchor - self.offset) * (zoom / self.zoom);
+            self.zoom = zoom;
+        }
+    }
+}
+
+/// Where the nodes of projection-coords.yaml are drawn on the central canvas.
+#[derive(Clone, Copy)]
+enum CanvasGeometry {
+    /// Isometric projection of every floor, as drawn by `convert_pos` into `drawing`, which is
+    /// the canvas at `rect` zoomed and panned.
+    Projection {
+        rect: Rect,
+        drawing: Rect,
+        scale: f32,
+        /// Height of the selected floor, or `None` if all floors are selected.
+        floor_z: Option<i32>,
+    },
+    /// Top-down plan of the floor at height `floor_z`, with `PLAN_CENTER` drawn at `origin`.
+    Plan {
+        rect: Rect,
+        origin: emath::Pos2,
+        scale: f32,
+        floor_z: i32,
+    },
+}
+
+impl CanvasGeometry {
+    fn plan(rect: Rect, view: &CanvasView, floor_z: i32) -> Self {
+        Self::Plan {
+            rect,
+            origin: rect.center() + view.offset,
+            scale: (rect.width() / PLAN_WIDTH) * view.zoom,
+            floor_z,
+        }
+    }
+
+    fn rect(&self) -> Rect {
+        match *self {
+            Self::Projection { rect, .. } | Self::Plan { rect, .. } => rect,
+        }
+    }
+
+    fn screen_pos(&self, pos: &[i32; 3]) -> emath::Pos2 {
+        match *self {
+            Self::Projection { drawing, scale, .. } => convert_pos(&drawing, pos, scale),
+            Self::Plan { origin, scale, .. } => {
+                origin +
+                    emath::vec2(
+                        (pos[0] as f32) - PLAN_CENTER[0],
+                        PLAN_CENTER[1] - (pos[1] as f32)
+                    ) *
+                        scale
+            }
+        }
+    }
+
+    /// Height of the selected floor, or `None` if all floors are selected.
+    fn floor_z(&self) -> Option<i32> {
+        match *self {
+            Self::Projection { floor_z, .. } => floor_z,
+            Self::Plan { floor_z, .. } => Some(floor_z),
+        }
+    }
+
+    /// The x and y coordinates in projection-coords.yaml of the point of the selected floor drawn
+    /// at `position`, if a floor is selected.
+    fn coords_at(&self, position: emath::Pos2) -> Option<[i32; 2]> {
+        let [x, y] = match *self {
+            Self::Projection { drawing, scale, floor_z, .. } => {
+                convert_pos_inverse(&drawing, position, floor_z?, scale)
+            }
+            Self::Plan { origin, scale, .. } => {
+                let offset = (position - origin) / scale;
+                [PLAN_CENTER[0] + offset.x, PLAN_CENTER[1] - offset.y]
+            }
+        };
+        Some([x.round() as i32, y.round() as i32])
+    }
+
+    /// Whether a node is on the selected floor, which every node is if all floors are selected.
+    fn is_on_floor(&self, pos: &[i32; 3]) -> bool {
+        match *self {
+            Self::Projection { floor_z, .. } => floor_z.is_none_or(|z| z == pos[2]),
+            Self::Plan { floor_z, .. } => floor_z == pos[2],
+        }
+    }
+
+    /// Whether the segment between two nodes is drawn as part of the selected floor.
+    fn is_segment_active(&self, pos1: &[i32; 3], pos2: &[i32; 3]) -> bool {
+        match *self {
+            Self::Projection { floor_z, .. } => {
+                floor_z.is_none_or(|z| z >= pos1[2].min(pos2[2]) && z <= pos1[2].max(pos2[2]))
+            }
+            Self::Plan { .. } => self.is_on_floor(pos1) && self.is_on_floor(pos2),
+        }
+    }
+
+    /// Whether what lies off the selected floor is drawn too, dimmed.
+    fn shows_other_floors(&self) -> bool {
+        matches!(self, Self::Projection { .. })
+    }
+}
+
+/// Distance from `point` to the segment between `from` and `to`.
+fn segment_distance(point: emath::Pos2, from: emath::Pos2, to: emath::Pos2) -> f32 {
+    let segment = to - from;
+    let t = if segment.length_sq() > 0.0 {
+        ((point - from).dot(segment) / segment.length_sq()).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    point.distance(from + segment * t)
+}
+
+/// The name after `name` in a series of nodes, such as `A205` after `A204` or `S2-4` after `S2-3`.
+fn next_node_name(name: &str) -> String {
+    let prefix = name.trim_end_matches(|c: char| c.is_ascii_digit());
+    let digits = &name[prefix.len()..];
+    match digits.parse::<u32>() {
+        Ok(number) => format!("{}{:0width$}", prefix, number + 1, width = digits.len()),
+        Err(_) => name.to_owned(),
+    }
+}
+
+fn edge_type_combo_box(ui: &mut egui::Ui, id_source: impl std::hash::Hash, edge_type: &mut u8) {
+    ComboBox::from_id_source(id_source)
+        .selected_text(EDGE_TYPE_NAMES[*edge_type as usize])
+        .show_ui(ui, |ui| {
+            for (option, name) in EDGE_TYPE_NAMES.iter().enumerate() {
+                ui.selectable_value(edge_type, option as u8, *name);
+            }
+        });
+}
+
 fn convert_day_of_week(day: u32) -> String {
-    (
-        match day {
-            1 => "Monday",
-            2 => "Tuesday",
-            3 => "Wednesday",
-            4 => "Thursday",
-            5 => "Friday",
-            _ => "Unknown",
-        }
-    ).into()
+    Day::new(day).map_or("Unknown", Day::name).into()
 }
 
 fn convert_periods(index: usize) -> String {
-    (
-        match index {
-            0 => "Before P1",
-            1 => "P1–P2",
-            2 => "P2–P3",
-            3 => "P3–P4",
-            4 => "P4–P5",
-            5 => "P5–P6",
-            6 => "P6–Lunch",
-            7 => "Lunch–P7",
-            8 => "P7–P8",
-            9 => "P8–P9",
-            10 => "P9–P10",
-            11 => "After P10",
-            _ => "Unknown",
-        }
-    ).into()
+    TRANSITION_NAMES.get(index).copied().unwrap_or("Unknown").into()
 }
+
 fn congestion_color_scale(congestion: u32) -> Color32 {
     match congestion {
         0 => Color32::from_rgb(0x61, 0x61, 0x61),
@@ -2753,6 +6636,65 @@ fn congestion_color_scale(congestion: u32) -> Color32 {
     }
 }
 
+/// Routes of an optimization store, with the routes edited by hand flagged.
+fn store_routes(store: &RouteStore) -> std::io::Result<Arc<CompactRoutes>> {
+    let mut routes = CompactRoutes::from_routes(&store.routes())?;
+    routes.mark_edited(&store.edited_routes());
+    Ok(Arc::new(routes))
+}
+
+/// Copies the congestion of `transitions` from `congestion` into the maps drawn on the projection,
+/// with the rooms of `rooms` present even if nobody passes them.
+fn copy_congestion(
+    congestion: &CongestionIndex,
+    graph: &BuildingGraph,
+    rooms: &[String],
+    transitions: &[(u32, usize)],
+    point_data: &mut CongestionPoint,
+    path_data: &mut CongestionPath,
+    statistics: &mut CongestionStatistics
+) {
+    for &(day, period) in transitions {
+        let Some(transition) = congestion.transition(day, period) else {
+            continue;
+        };
+        let points = point_data.entry(day).or_default().entry(period).or_default();
+        points.clear();
+        for room in rooms {
+            points.insert(room.to_owned(), 0);
+        }
+        for (node, &load) in transition.node_loads.iter().enumerate() {
+            if load > 0 {
+                points.insert(graph.node_name(node).to_owned(), load);
+            }
+        }
+        let paths = path_data.entry(day).or_default().entry(period).or_default();
+        paths.clear();
+        for (edge, &load) in transition.edge_loads.iter().enumerate() {
+            if load > 0 {
+                let (node1, node2) = congestion.edge_ends(edge);
+                let (room1, room2) = (graph.node_name(node1), graph.node_name(node2));
+                paths.insert((room1.to_owned(), room2.to_owned()), load);
+                paths.insert((room2.to_owned(), room1.to_owned()), load);
+            }
+        }
+        let point_count = statistics.point_count
+            .get_mut(&day)
+            .unwrap()
+            .get_mut(&period)
+            .unwrap();
+        point_count.fill(0);
+        for room_congestion in points.values() {
+            point_count[congestion_range_index(*room_congestion)] += 1;
+        }
+        let path_count = statistics.path_count.get_mut(&day).unwrap().get_mut(&period).unwrap();
+        path_count.fill(0);
+        for path_congestion in paths.values() {
+            path_count[congestion_range_index(*path_congestion)] += 1;
+        }
+    }
+}
+
 fn congestion_range_index(congestion: u32) -> usize {
     match congestion {
         0 => 0,
@@ -2764,3 +6706,149 @@ fn congestion_range_index(congestion: u32) -> usize {
         _ => 6,
     }
 }
+
+/// Checkboxes and values for the detour limits per transition and per day.
+fn detour_limits_ui(ui: &mut egui::Ui, limits: &mut DetourLimits) {
+    fn limit_row(ui: &mut egui::Ui, label: &str, limit: &mut DetourLimit, default_absolute: u32) {
+        ui.label(label);
+        let mut absolute_enabled = limit.absolute.is_some();
+        let mut absolute = limit.absolute.unwrap_or(default_absolute);
+        ui.checkbox(&mut absolute_enabled, "Distance");
+        ui.add_enabled(
+            absolute_enabled,
+            egui::DragValue::new(&mut absolute).speed(10).clamp_range(0..=10000)
+        );
+        limit.absolute = absolute_enabled.then_some(absolute);
+        let mut relative_enabled = limit.relative.is_some();
+        let mut relative_percent = limit.relative.map_or(50.0, |relative| relative * 100.0);
+        ui.checkbox(&mut relative_enabled, "Relative");
+        ui.add_enabled(
+            relative_enabled,
+            egui::DragValue::new(&mut relative_percent).speed(1).clamp_range(0..=500).suffix("%")
+        );
+        limit.relative = relative_enabled.then_some(relative_percent / 100.0);
+        ui.end_row();
+    }
+
+    Grid::new(ui.next_auto_id())
+        .num_columns(5)
+        .show(ui, |ui| {
+            limit_row(ui, "Per transition", &mut limits.transition, 300);
+            limit_row(ui, "Per day", &mut limits.day, 600);
+        });
+    ui.label(
+        "Detours are measured against the shortest route. A reroute is only made if it stays within all enabled limits."
+    );
+}
+
+/// Number of optimization threads used by default, one per CPU core.
+fn default_thread_count() -> usize {
+    thread::available_parallelism().map_or(4, |count| count.get())
+}
+
+fn checkpoint_label(summary: &CheckpointSummary) -> String {
+    format!(
+        "Iteration {} (PI {}), {}",
+        summary.iterations.to_formatted_string(&Locale::fr),
+        summary.performance_index.to_formatted_string(&Locale::fr),
+        format_age(summary.saved_at)
+    )
+}
+
+/// Time elapsed since `timestamp`, in milliseconds since the Unix epoch, e.g. "5 min ago".
+fn format_age(timestamp: u64) -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_millis() as u64);
+    let minutes = now.saturating_sub(timestamp) / 60_000;
+    match minutes {
+        0 => "just now".to_owned(),
+        1..=59 => format!("{} min ago", minutes),
+        60..=1439 => format!("{} h ago", minutes / 60),
+        _ => format!("{} d ago", minutes / 1440),
+    }
+}
+
+/// Sliders for how scenarios perturb the timetable.
+fn perturbation_ui(ui: &mut egui::Ui, params: &mut PerturbationParams) {
+    ui.add(
+        Slider::new(&mut params.absence_rate, 0.0..=0.5)
+            .step_by(0.01)
+            .text("Absence rate")
+    ).on_hover_text("Probability that a student is absent on a day.");
+    ui.add(
+        Slider::new(&mut params.room_change_rate, 0.0..=0.5)
+            .step_by(0.01)
+            .text("Room change rate")
+    ).on_hover_text("Probability that a class moves to another free room in a period.");
+    ui.add(Slider::new(&mut params.samples, 2..=64).text("Scenarios"));
+    ui.horizontal(|ui| {
+        ui.label("Seed");
+        ui.add(egui::DragValue::new(&mut params.seed));
+    });
+}
+
+/// Checkboxes and values for the stopping criteria of the route optimization.
+fn stopping_criteria_ui(ui: &mut egui::Ui, criteria: &mut StoppingCriteria) {
+    Grid::new(ui.next_auto_id())
+        .num_columns(3)
+        .show(ui, |ui| {
+            let mut max_iterations_enabled = criteria.max_iterations.is_some();
+            let mut max_iterations = criteria.max_iterations.unwrap_or(100_000);
+            ui.checkbox(&mut max_iterations_enabled, "Maximum iterations");
+            ui.add_enabled(
+                max_iterations_enabled,
+                egui::DragValue::new(&mut max_iterations).speed(100).clamp_range(1..=u64::MAX)
+            );
+            ui.end_row();
+            criteria.max_iterations = max_iterations_enabled.then_some(max_iterations);
+
+            let mut time_limit_enabled = criteria.time_limit.is_some();
+            let mut time_limit = criteria.time_limit.unwrap_or(600);
+            ui.checkbox(&mut time_limit_enabled, "Time limit");
+            ui.add_enabled(
+                time_limit_enabled,
+                egui::DragValue::new(&mut time_limit).speed(10).clamp_range(1..=86_400).suffix(" s")
+            );
+            ui.end_row();
+            criteria.time_limit = time_limit_enabled.then_some(time_limit);
+
+            let mut window_enabled = criteria.improvement_window.is_some();
+            let mut window = criteria.improvement_window.unwrap_or(ImprovementWindow {
+                iterations: 10_000,
+                min_improvement: 0.001,
+            });
+            let mut min_improvement_percent = window.min_improvement * 100.0;
+            ui.checkbox(&mut window_enabled, "Minimum improvement");
+            ui.add_enabled(
+                window_enabled,
+                egui::DragValue
+                    ::new(&mut min_improvement_percent)
+                    .speed(0.01)
+                    .clamp_range(0.0..=100.0)
+                    .suffix("%")
+            );
+            ui.add_enabled(
+                window_enabled,
+                egui::DragValue
+                    ::new(&mut window.iterations)
+                    .speed(100)
+                    .clamp_range(1..=u64::MAX)
+                    .prefix("over ")
+                    .suffix(" iterations")
+            );
+            ui.end_row();
+            window.min_improvement = min_improvement_percent / 100.0;
+            criteria.improvement_window = window_enabled.then_some(window);
+
+            let mut target_enabled = criteria.target_performance_index.is_some();
+            let mut target = criteria.target_performance_index.unwrap_or(50_000_000);
+            ui.checkbox(&mut target_enabled, "Target total PI");
+            ui.add_enabled(
+                target_enabled,
+                egui::DragValue::new(&mut target).speed(10_000).clamp_range(0..=u64::MAX)
+            );
+            ui.end_row();
+            criteria.target_performance_index = target_enabled.then_some(target);
+        });
+}
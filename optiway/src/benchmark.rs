@@ -0,0 +1,361 @@
+//! Benchmark of the optimization strategies over the bundled timetables.
+//!
+//! Every timetable is routed along the shortest routes first, which is the baseline, and then
+//! optimized from the shortest routes with every strategy and seed. The results are written as a
+//! CSV file for further analysis and as a Markdown table for reading. A timetable or run that
+//! fails is recorded as a failed result, and the benchmark goes on with the others.
+
+use std::{ collections::BTreeMap, fmt::Write, time::{ Duration, Instant } };
+
+use num_format::{ Locale, ToFormattedString };
+
+use crate::{
+    evaluation::{ edge_load, performance_index, MissingEdge, PathDistances },
+    graph::BuildingGraph,
+    optimization::{
+        optimize_routes,
+        stopping::{ StopReason, StoppingCriteria },
+        OptimizationParams,
+        OptimizationStrategy,
+        RouteStore,
+    },
+    routing::{ routes_from_timetable, Routes, ShortestPaths },
+    timetable::Timetable,
+};
+
+/// Names of the bundled timetable families, whose timetables are `{family}_0.json` to
+/// `{family}_4.json`.
+pub const TIMETABLE_FAMILIES: [&str; 3] = ["timetable", "timetable_with_gt", "timetable_with_p5"];
+pub const TIMETABLES_PER_FAMILY: usize = 5;
+
+#[derive(Clone)]
+pub struct BenchmarkSettings {
+    pub strategies: Vec<OptimizationStrategy>,
+    /// Every strategy is run once with each seed.
+    pub seeds: Vec<u64>,
+    pub batch_size: u32,
+    pub thread_count: usize,
+    pub stopping: StoppingCriteria,
+}
+
+#[derive(Clone)]
+pub struct BenchmarkResult {
+    pub timetable: String,
+    /// `None` for the shortest routes the optimization starts from.
+    pub strategy: Option<OptimizationStrategy>,
+    pub seed: Option<u64>,
+    pub performance_index: f64,
+    /// Performance index of the shortest routes of the same timetable.
+    pub baseline_performance_index: f64,
+    /// Largest number of students walking an edge in any transition.
+    pub max_congestion: u32,
+    pub runtime: Duration,
+    pub iterations: u64,
+    pub stop_reason: Option<StopReason>,
+    /// Why the timetable or run failed, in which case the measurements are zero.
+    pub error: Option<String>,
+}
+
+impl BenchmarkResult {
+    /// A timetable that could not be read or evaluated, or a run that could not be evaluated.
+    pub fn failed(
+        timetable: &str,
+        strategy: Option<OptimizationStrategy>,
+        seed: Option<u64>,
+        error: String
+    ) -> Self {
+        Self {
+            timetable: timetable.to_owned(),
+            strategy,
+            seed,
+            performance_index: 0.0,
+            baseline_performance_index: 0.0,
+            max_congestion: 0,
+            runtime: Duration::ZERO,
+            iterations: 0,
+            stop_reason: None,
+            error: Some(error),
+        }
+    }
+
+    pub fn strategy_name(&self) -> String {
+        self.strategy.map_or_else(|| "Shortest routes".to_owned(), |strategy| strategy.to_string())
+    }
+
+    /// Decrease of the performance index from the shortest routes, in percent.
+    pub fn improvement(&self) -> f64 {
+        if self.baseline_performance_index > 0.0 {
+            (100.0 * (self.baseline_performance_index - self.performance_index)) /
+                self.baseline_performance_index
+        } else {
+            0.0
+        }
+    }
+
+    fn family(&self) -> &str {
+        self.timetable.rsplit_once('_').map_or(&self.timetable, |(family, _)| family)
+    }
+}
+
+/// Total performance index and largest edge congestion of the week.
+fn evaluate(routes: &Routes, distances: &PathDistances) -> Result<(f64, u32), MissingEdge> {
+    let mut total_performance_index = 0.0;
+    let mut max_congestion = 0;
+    for day in 1..=5 {
+        for transition in 0..=11 {
+            let load = edge_load(routes, day, transition);
+            total_performance_index += performance_index(&load, distances)?;
+            max_congestion = max_congestion.max(load.values().copied().max().unwrap_or(0));
+        }
+    }
+    Ok((total_performance_index, max_congestion))
+}
+
+/// Runs the baseline and every strategy and seed of `settings` on one timetable. If the shortest
+/// routes cannot be evaluated, the baseline is the only, failed, result.
+pub fn benchmark_timetable(
+    name: &str,
+    timetable: &Timetable,
+    shortest_paths: &ShortestPaths,
+    graph: &BuildingGraph,
+    distances: &PathDistances,
+    settings: &BenchmarkSettings
+) -> Vec<BenchmarkResult> {
+    let started = Instant::now();
+    let shortest_routes = routes_from_timetable(timetable, shortest_paths);
+    let runtime = started.elapsed();
+    let (baseline_performance_index, max_congestion) = match evaluate(&shortest_routes, distances) {
+        Ok(evaluation) => evaluation,
+        Err(error) => {
+            return vec![BenchmarkResult::failed(name, None, None, error.to_string())];
+        }
+    };
+    let mut results = vec![BenchmarkResult {
+        timetable: name.to_owned(),
+        strategy: None,
+        seed: None,
+        performance_index: baseline_performance_index,
+        baseline_performance_index,
+        max_congestion,
+        runtime,
+        iterations: 0,
+        stop_reason: None,
+        error: None,
+    }];
+    for &strategy in &settings.strategies {
+        for &seed in &settings.seeds {
+            let store = RouteStore::new(shortest_routes.clone(), [0; 5]);
+            let params = OptimizationParams {
+                batch_size: settings.batch_size,
+                thread_count: settings.thread_count,
+                detour_limits: Default::default(),
+                seed,
+                stopping: settings.stopping,
+                strategy,
+                robust_objective: Default::default(),
+                perturbation: Default::default(),
+            };
+            let started = Instant::now();
+            let stop_reason = optimize_routes(&store, shortest_paths, graph, params, |_| true);
+            let runtime = started.elapsed();
+            let (performance_index, max_congestion) = match evaluate(&store.routes(), distances) {
+                Ok(evaluation) => evaluation,
+                Err(error) => {
+                    results.push(
+                        BenchmarkResult::failed(name, Some(strategy), Some(seed), error.to_string())
+                    );
+                    continue;
+                }
+            };
+            results.push(BenchmarkResult {
+                timetable: name.to_owned(),
+                strategy: Some(strategy),
+                seed: Some(seed),
+                performance_index,
+                baseline_performance_index,
+                max_congestion,
+                runtime,
+                iterations: store.iterations().iter().sum(),
+                stop_reason: Some(stop_reason),
+                error: None,
+            });
+        }
+    }
+    results
+}
+
+/// Quotes a CSV field if it contains a separator, a quote or a line break.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_owned()
+    }
+}
+
+pub fn results_csv(results: &[BenchmarkResult]) -> String {
+    let mut csv = String::from(
+        concat!(
+            "timetable,strategy,seed,performance_index,improvement_percent,max_congestion,",
+            "runtime_seconds,iterations,stop_reason,error\n"
+        )
+    );
+    for result in results {
+        writeln!(
+            csv,
+            "{},{},{},{:.0},{:.3},{},{:.3},{},{},{}",
+            result.timetable,
+            result.strategy_name(),
+            result.seed.map_or_else(String::new, |seed| seed.to_string()),
+            result.performance_index,
+            result.improvement(),
+            result.max_congestion,
+            result.runtime.as_secs_f64(),
+            result.iterations,
+            result.stop_reason.map_or_else(String::new, |reason| reason.to_string()),
+            csv_field(result.error.as_deref().unwrap_or_default())
+        ).unwrap();
+    }
+    csv
+}
+
+/// A table of every result, followed by the mean improvement and runtime of every strategy in
+/// every timetable family, leaving out failed results, and a list of the failures.
+pub fn results_markdown(results: &[BenchmarkResult]) -> String {
+    let mut markdown = String::from("# Optimization Benchmark\n\n## Results\n\n");
+    markdown.push_str(
+        concat!(
+            "| Timetable | Strategy | Seed | Total PI | Improvement | Max congestion | Runtime ",
+            "| Iterations | Stopped because |\n"
+        )
+    );
+    markdown.push_str("|---|---|--:|--:|--:|--:|--:|--:|---|\n");
+    for result in results.iter().filter(|result| result.error.is_none()) {
+        writeln!(
+            markdown,
+            "| {} | {} | {} | {} | {:.2}% | {} | {:.2} s | {} | {} |",
+            result.timetable,
+            result.strategy_name(),
+            result.seed.map_or_else(String::new, |seed| seed.to_string()),
+            (result.performance_index as u64).to_formatted_string(&Locale::fr),
+            result.improvement(),
+            result.max_congestion,
+            result.runtime.as_secs_f64(),
+            result.iterations.to_formatted_string(&Locale::fr),
+            result.stop_reason.map_or_else(String::new, |reason| reason.to_string())
+        ).unwrap();
+    }
+
+    // Family → (strategy, sum of improvements, sum of runtimes, runs), in the order of `results`.
+    let mut summary: BTreeMap<&str, Vec<(String, f64, f64, u32)>> = BTreeMap::new();
+    for result in results.iter().filter(|result| result.error.is_none()) {
+        let strategies = summary.entry(result.family()).or_default();
+        let strategy = result.strategy_name();
+        let index = strategies
+            .iter()
+            .position(|(name, ..)| *name == strategy)
+            .unwrap_or_else(|| {
+                strategies.push((strategy, 0.0, 0.0, 0));
+                strategies.len() - 1
+            });
+        strategies[index].1 += result.improvement();
+        strategies[index].2 += result.runtime.as_secs_f64();
+        strategies[index].3 += 1;
+    }
+    markdown.push_str("\n## Mean by Timetable Family\n\n");
+    markdown.push_str("| Family | Strategy | Runs | Improvement | Runtime |\n");
+    markdown.push_str("|---|---|--:|--:|--:|\n");
+    for (family, strategies) in summary {
+        for (strategy, improvement, runtime, runs) in strategies {
+            writeln!(
+                markdown,
+                "| {} | {} | {} | {:.2}% | {:.2} s |",
+                family,
+                strategy,
+                runs,
+                improvement / (runs as f64),
+                runtime / (runs as f64)
+            ).unwrap();
+        }
+    }
+
+    let failures: Vec<&BenchmarkResult> = results
+        .iter()
+        .filter(|result| result.error.is_some())
+        .collect();
+    if !failures.is_empty() {
+        markdown.push_str("\n## Failures\n\n");
+        markdown.push_str("| Timetable | Strategy | Seed | Error |\n");
+        markdown.push_str("|---|---|--:|---|\n");
+        for result in failures {
+            writeln!(
+                markdown,
+                "| {} | {} | {} | {} |",
+                result.timetable,
+                result.strategy_name(),
+                result.seed.map_or_else(String::new, |seed| seed.to_string()),
+                result.error.as_deref().unwrap_or_default().replace('|', "\\|")
+            ).unwrap();
+        }
+    }
+    markdown
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::evaluation::parse_path_distances;
+
+    use super::*;
+
+    #[test]
+    fn every_strategy_runs_on_a_bundled_timetable() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("../assets/timetable_0.json");
+        let timetable = Timetable::load(&path, |_| ()).unwrap();
+        let shortest_paths: ShortestPaths = serde_json
+            ::from_str(include_str!("../assets/shortest_paths.json"))
+            .unwrap();
+        let distances = parse_path_distances(include_str!("../assets/paths.txt"));
+        let graph = BuildingGraph::parse(include_str!("../assets/paths.txt"));
+        let settings = BenchmarkSettings {
+            strategies: OptimizationStrategy::ALL.to_vec(),
+            seeds: vec![0],
+            batch_size: 10,
+            thread_count: 2,
+            stopping: StoppingCriteria {
+                max_iterations: Some(600),
+                ..Default::default()
+            },
+        };
+        let results = benchmark_timetable(
+            "timetable_0",
+            &timetable,
+            &shortest_paths,
+            &graph,
+            &distances,
+            &settings
+        );
+
+        assert_eq!(results.len(), 1 + OptimizationStrategy::ALL.len());
+        for result in &results {
+            assert_eq!(result.error, None, "{}", result.strategy_name());
+            assert!(result.performance_index > 0.0, "{}", result.strategy_name());
+        }
+        assert!(results[1..].iter().all(|result| result.iterations > 0));
+        let markdown = results_markdown(&results);
+        assert!(!markdown.contains("## Failures"));
+        assert_eq!(results_csv(&results).lines().count(), results.len() + 1);
+    }
+
+    #[test]
+    fn failures_are_listed_separately() {
+        let results = vec![
+            BenchmarkResult::failed("timetable_1", None, None, "Cannot read, or parse".to_owned())
+        ];
+        assert!(results_csv(&results).ends_with(",\"Cannot read, or parse\"\n"));
+        let markdown = results_markdown(&results);
+        assert!(markdown.contains("## Failures"));
+        assert!(markdown.contains("| timetable_1 | Shortest routes |  | Cannot read, or parse |"));
+    }
+}
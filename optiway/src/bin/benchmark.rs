@@ -0,0 +1,164 @@
+//! Runs every optimization strategy on the bundled timetables and writes a comparison table.
+//!
+//! Usage: `cargo run --release --bin benchmark -- [options]`
+//!
+//! - `--timetables <directory>`: directory of the timetable files (default `../assets`)
+//! - `--output <directory>`: directory `benchmark.csv` and `benchmark.md` are written to
+//!   (default `./bin/benchmark`)
+//! - `--strategies <list>`: comma-separated strategies out of `greedy`, `annealing`, `tabu` and
+//!   `flow` (default all)
+//! - `--seeds <list>`: comma-separated seeds every strategy is run with (default `0`)
+//! - `--threads <count>`: worker threads of each run (default the number of CPUs)
+//! - `--batch-size <iterations>` (default 100)
+//! - `--time-limit <seconds>`: time limit of each run (default 600)
+
+use std::{ env, fs, path::PathBuf, process, thread };
+
+use optiway::{
+    benchmark::{
+        benchmark_timetable,
+        results_csv,
+        results_markdown,
+        BenchmarkResult,
+        BenchmarkSettings,
+        TIMETABLES_PER_FAMILY,
+        TIMETABLE_FAMILIES,
+    },
+    evaluation::parse_path_distances,
+    graph::BuildingGraph,
+    optimization::{ stopping::StoppingCriteria, OptimizationStrategy },
+    routing::ShortestPaths,
+    timetable::Timetable,
+};
+
+fn parse_strategy(name: &str) -> Option<OptimizationStrategy> {
+    match name {
+        "greedy" => Some(OptimizationStrategy::Greedy),
+        "annealing" => Some(OptimizationStrategy::SimulatedAnnealing),
+        "tabu" => Some(OptimizationStrategy::TabuSearch),
+        "flow" => Some(OptimizationStrategy::FlowRelaxation),
+        _ => None,
+    }
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("{}", message);
+    process::exit(1);
+}
+
+fn parse_list<T>(value: &str, parse: impl Fn(&str) -> Option<T>) -> Vec<T> {
+    value
+        .split(',')
+        .map(|item| parse(item.trim()).unwrap_or_else(|| fail(&format!("Invalid value: {}", item))))
+        .collect()
+}
+
+fn main() {
+    let mut timetable_directory = PathBuf::from("../assets");
+    let mut output_directory = PathBuf::from("./bin/benchmark");
+    let mut settings = BenchmarkSettings {
+        strategies: OptimizationStrategy::ALL.to_vec(),
+        seeds: vec![0],
+        batch_size: 100,
+        thread_count: thread::available_parallelism().map_or(1, |count| count.get()),
+        stopping: StoppingCriteria {
+            time_limit: Some(600),
+            ..Default::default()
+        },
+    };
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let value = args.next().unwrap_or_else(|| fail(&format!("Missing value for {}", arg)));
+        match arg.as_str() {
+            "--timetables" => {
+                timetable_directory = value.into();
+            }
+            "--output" => {
+                output_directory = value.into();
+            }
+            "--strategies" => {
+                settings.strategies = parse_list(&value, parse_strategy);
+            }
+            "--seeds" => {
+                settings.seeds = parse_list(&value, |seed| seed.parse().ok());
+            }
+            "--threads" => {
+                settings.thread_count = value
+                    .parse()
+                    .unwrap_or_else(|_| fail(&format!("Invalid thread count: {}", value)));
+            }
+            "--batch-size" => {
+                settings.batch_size = value
+                    .parse()
+                    .unwrap_or_else(|_| fail(&format!("Invalid batch size: {}", value)));
+            }
+            "--time-limit" => {
+                settings.stopping.time_limit = Some(
+                    value
+                        .parse()
+                        .unwrap_or_else(|_| fail(&format!("Invalid time limit: {}", value)))
+                );
+            }
+            _ => fail(&format!("Unknown option: {}", arg)),
+        }
+    }
+
+    let shortest_paths: ShortestPaths = serde_json
+        ::from_str(include_str!("../../assets/shortest_paths.json"))
+        .unwrap();
+    let distances = parse_path_distances(include_str!("../../assets/paths.txt"));
+    let graph = BuildingGraph::parse(include_str!("../../assets/paths.txt"));
+
+    let mut results = vec![];
+    for family in TIMETABLE_FAMILIES {
+        for index in 0..TIMETABLES_PER_FAMILY {
+            let name = format!("{}_{}", family, index);
+            let path = timetable_directory.join(format!("{}.json", name));
+            let timetable = match Timetable::load(&path, |_| ()) {
+                Ok(timetable) => timetable,
+                Err(error) => {
+                    let error = format!("Cannot read {}: {}", path.display(), error);
+                    eprintln!("{}", error);
+                    results.push(BenchmarkResult::failed(&name, None, None, error));
+                    continue;
+                }
+            };
+            eprintln!("Benchmarking {}", name);
+            let timetable_results = benchmark_timetable(
+                &name,
+                &timetable,
+                &shortest_paths,
+                &graph,
+                &distances,
+                &settings
+            );
+            for result in timetable_results {
+                match &result.error {
+                    Some(error) => eprintln!("  {}: failed: {}", result.strategy_name(), error),
+                    None =>
+                        eprintln!(
+                            "  {}: PI {:.0} ({:.2}%) in {:.2} s",
+                            result.strategy_name(),
+                            result.performance_index,
+                            result.improvement(),
+                            result.runtime.as_secs_f64()
+                        ),
+                }
+                results.push(result);
+            }
+        }
+    }
+
+    let write = |file_name: &str, content: String| {
+        let path = output_directory.join(file_name);
+        fs::write(&path, content).unwrap_or_else(|error|
+            fail(&format!("Cannot write {}: {}", path.display(), error))
+        );
+        eprintln!("Wrote {}", path.display());
+    };
+    fs::create_dir_all(&output_directory).unwrap_or_else(|error|
+        fail(&format!("Cannot create {}: {}", output_directory.display(), error))
+    );
+    write("benchmark.csv", results_csv(&results));
+    write("benchmark.md", results_markdown(&results));
+}
@@ -0,0 +1,70 @@
+//! Checks the building data for disconnected nodes, duplicate edges, inconsistent distances,
+//! differences between the source files and outdated precomputed paths.
+//!
+//! Usage: `cargo run --release --bin consistency -- [options]`
+//!
+//! - `--assets <directory>`: directory of `paths.txt`, `projection-coords-flatten.yaml` and
+//!   `shortest_paths.json` (default `./assets`)
+//! - `--model <directory>`: directory of `projection-coords.yaml` and `paths.yaml`, left out if
+//!   it has none (default `../assets`)
+//!
+//! Exits with status 1 if there is any error.
+
+use std::{ env, path::PathBuf, process };
+
+use optiway::consistency::{ check_building, BuildingData, Severity };
+
+fn fail(message: &str) -> ! {
+    eprintln!("{}", message);
+    process::exit(1);
+}
+
+fn main() {
+    let mut assets_directory = PathBuf::from("./assets");
+    let mut model_directory = PathBuf::from("../assets");
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let value = args.next().unwrap_or_else(|| fail(&format!("Missing value for {}", arg)));
+        match arg.as_str() {
+            "--assets" => {
+                assets_directory = value.into();
+            }
+            "--model" => {
+                model_directory = value.into();
+            }
+            _ => fail(&format!("Unknown option: {}", arg)),
+        }
+    }
+
+    let data = BuildingData::load(&assets_directory, Some(&model_directory)).unwrap_or_else(
+        |error| fail(&format!("Cannot read the building data: {}", error))
+    );
+    if data.model.is_none() {
+        eprintln!("No source files in {}, skipping their checks", model_directory.display());
+    }
+    if data.shortest_paths.is_none() {
+        eprintln!("No shortest_paths.json, skipping the precomputed paths");
+    }
+
+    let issues = check_building(&data);
+    let mut category = "";
+    for issue in &issues {
+        if issue.category() != category {
+            category = issue.category();
+            let count = issues
+                .iter()
+                .filter(|issue| issue.category() == category)
+                .count();
+            println!("{} ({})", category, count);
+        }
+        println!("  {}: {}", issue.severity(), issue);
+    }
+    let errors = issues
+        .iter()
+        .filter(|issue| issue.severity() == Severity::Error)
+        .count();
+    println!("{} errors, {} warnings", errors, issues.len() - errors);
+    if errors > 0 {
+        process::exit(1);
+    }
+}
@@ -0,0 +1,694 @@
+//! The building model as maintained in the asset files, for editing nodes and edges in the app.
+//!
+//! The model is read from and written to the formats of the asset directory:
+//!
+//! - `projection-coords.yaml`: node coordinates grouped by floor and by block (`X` for the
+//!   crossings between the blocks, `A` and `B` for the nodes of each block, stairs included);
+//! - `projection-coords-flatten.yaml`: the same coordinates by node name only;
+//! - `paths.yaml`: the edges, grouped by floor (`S` for every staircase) and by block;
+//! - `paths.txt`: the edges of `paths.yaml` followed by the entrances, the staircases joined to the
+//!   ground node `G`.
+//!
+//! The comments of the YAML files are replaced by the key descriptions of the originals when the
+//! model is saved.
+
+use std::{
+    collections::{ BTreeMap, HashMap, HashSet },
+    fmt::{ self, Display },
+    fs,
+    io,
+    path::Path,
+};
+
+use serde::Deserialize;
+
+/// Groups of nodes and edges within a floor, in the order they are written.
+pub const GROUPS: [&str; 3] = ["X", "A", "B"];
+
+/// Section of `paths.yaml` holding the edges of every staircase.
+const STAIRS_SECTION: &str = "S";
+
+/// Distance of the edges joining the entrances to the ground node in `paths.txt`.
+const ENTRANCE_DISTANCE: u32 = 999999;
+const ENTRANCE_EDGE_TYPE: u8 = 2;
+
+/// Distance of a staircase between two adjacent floors, used as the automatic distance of stairs.
+pub const STAIRS_DISTANCE_PER_FLOOR: u32 = 190;
+/// Ratio of the length of a bridge to the gap it spans between the blocks, as for every bridge of
+/// `paths.yaml`.
+pub const BRIDGE_DISTANCE_FACTOR: f64 = 1.4;
+/// Height of a floor in `projection-coords.yaml`.
+pub const FLOOR_HEIGHT: i32 = 50;
+
+const COORDS_HEADER: &str =
+    "# Keys:
+#   - X: Crossings between two blocks
+#     XNKM: The M-th crossing node on K block, N-th floor.
+#   - A: Nodes in the A block
+#   - B: Nodes in the B block
+#   - S: Stairs
+#     SN-M: The M-th stair node on N-th floor.
+# Values: [x, y, z]
+#   - +x: East
+#   - +y: North
+#   - +z: Up
+
+";
+
+const PATHS_HEADER: &str =
+    "# Path types:
+# 0: Normal path between two rooms / between a room and one end of a bridge
+# 1: Bridge between two buildings
+# 2: Normal staircases (e.g. S2-3)
+# 3: Spiral staircases between two floors only (e.g. S2-2)
+# 4: Other types of staircases (e.g. S4-6)
+
+# Keys:
+#   - X: Crossings between two blocks
+#     XNKM: The M-th crossing node on K block, N-th floor.
+#   - A: Nodes in the A block
+#   - B: Nodes in the B block
+#   - S: Stairs
+#     SN-M: The M-th stair node on N-th floor.
+
+# Format of paths.txt
+# [node 1] [node 2] [distance] [path type]
+# Note that the path is undirected.
+
+";
+
+/// Names of the edge types of `paths.txt`.
+pub const EDGE_TYPE_NAMES: [&str; 5] = [
+    "Normal",
+    "Bridge",
+    "Stairs",
+    "Spiral staircase",
+    "Other staircase",
+];
+
+#[derive(Debug)]
+pub enum BuildingModelError {
+    Io(io::Error),
+    /// A file that cannot be read as the model, with its name.
+    Format(String, String),
+    DuplicateNode(String),
+    UnknownNode(String),
+    /// A name that is empty, is `G`, or has characters other than letters, digits and `-`.
+    InvalidName(String),
+    UnknownFloor(String),
+    /// An edge from a node to itself.
+    Loop(String),
+    DuplicateEdge(String, String),
+}
+
+impl Display for BuildingModelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BuildingModelError::Io(error) => {
+                write!(f, "Failed to read the building model: {}", error)
+            }
+            BuildingModelError::Format(file, error) => write!(f, "Invalid {}: {}", file, error),
+            BuildingModelError::DuplicateNode(node) => write!(f, "Node {} already exists.", node),
+            BuildingModelError::UnknownNode(node) => write!(f, "There is no node {}.", node),
+            BuildingModelError::InvalidName(name) => {
+                write!(f, "\"{}\" cannot be the name of a node.", name)
+            }
+            BuildingModelError::UnknownFloor(floor) => write!(f, "There is no floor {}.", floor),
+            BuildingModelError::Loop(node) => {
+                write!(f, "Node {} cannot be joined to itself.", node)
+            }
+            BuildingModelError::DuplicateEdge(node1, node2) => {
+                write!(f, "{} and {} are already joined.", node1, node2)
+            }
+        }
+    }
+}
+
+impl std::error::Error for BuildingModelError {}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ModelNode {
+    pub name: String,
+    /// Floor as in `projection-coords.yaml`, such as `2F`.
+    pub floor: String,
+    /// `X`, `A` or `B`.
+    pub group: String,
+    pub coords: [i32; 3],
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ModelEdge {
+    pub nodes: [String; 2],
+    pub distance: u32,
+    /// Edge type as in `paths.txt`, indexing `EDGE_TYPE_NAMES`.
+    pub edge_type: u8,
+    /// Section of `paths.yaml`: the floor of the edge, or `S` for staircases.
+    pub section: String,
+    pub group: String,
+}
+
+impl ModelEdge {
+    pub fn joins(&self, node1: &str, node2: &str) -> bool {
+        (self.nodes[0] == node1 && self.nodes[1] == node2) ||
+            (self.nodes[0] == node2 && self.nodes[1] == node1)
+    }
+
+    pub fn is_stairs(&self) -> bool {
+        is_stairs_type(self.edge_type)
+    }
+}
+
+fn is_stairs_type(edge_type: u8) -> bool {
+    (2..=4).contains(&edge_type)
+}
+
+#[derive(Deserialize)]
+struct YamlEdge {
+    dist: u32,
+    nodes: [String; 2],
+    #[serde(rename = "type")]
+    edge_type: u8,
+}
+
+/// Components of the building graph, including the ground node.
+#[derive(Default, Clone)]
+pub struct Connectivity {
+    /// Node names of each component, the largest first.
+    pub components: Vec<Vec<String>>,
+    /// Rooms that cannot be reached from the ground, or from the largest component if the model
+    /// has no entrances.
+    pub unreachable_rooms: Vec<String>,
+}
+
+#[derive(Clone, Default, PartialEq, Eq, Debug)]
+pub struct BuildingModel {
+    /// Floors in the order of `projection-coords.yaml`.
+    floors: Vec<String>,
+    /// Nodes in the order of `projection-coords.yaml` within each floor and group.
+    nodes: Vec<ModelNode>,
+    /// Edges in the order of `paths.yaml` within each section and group.
+    edges: Vec<ModelEdge>,
+    /// Nodes joined to the ground node `G`.
+    entrances: Vec<String>,
+}
+
+impl BuildingModel {
+    /// Reads `projection-coords.yaml` and `paths.yaml` of an asset directory, and the entrances
+    /// from its `paths.txt` if there is one.
+    pub fn load(directory: &Path) -> Result<Self, BuildingModelError> {
+        let read = |file: &str| fs::read_to_string(directory.join(file));
+        let coords = read("projection-coords.yaml").map_err(BuildingModelError::Io)?;
+        let paths = read("paths.yaml").map_err(BuildingModelError::Io)?;
+        let paths_txt = match read("paths.txt") {
+            Ok(content) => Some(content),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => None,
+            Err(error) => {
+                return Err(BuildingModelError::Io(error));
+            }
+        };
+        Self::parse(&coords, &paths, paths_txt.as_deref())
+    }
+
+    pub fn parse(
+        coords: &str,
+        paths: &str,
+        paths_txt: Option<&str>
+    ) -> Result<Self, BuildingModelError> {
+        let format_error = |file: &str, error: serde_yaml::Error| {
+            BuildingModelError::Format(file.to_owned(), error.to_string())
+        };
+        let mut model = Self::default();
+
+        let coords: serde_yaml::Mapping = serde_yaml
+            ::from_str(coords)
+            .map_err(|error| format_error("projection-coords.yaml", error))?;
+        for (floor, groups) in coords {
+            let floor: String = serde_yaml
+                ::from_value(floor)
+                .map_err(|error| format_error("projection-coords.yaml", error))?;
+            let groups: BTreeMap<String, Option<serde_yaml::Mapping>> = serde_yaml
+                ::from_value(groups)
+                .map_err(|error| format_error("projection-coords.yaml", error))?;
+            check_groups("projection-coords.yaml", groups.keys())?;
+            for group in GROUPS {
+                for (name, coords) in groups.get(group).cloned().flatten().unwrap_or_default() {
+                    let name: String = serde_yaml
+                        ::from_value(name)
+                        .map_err(|error| format_error("projection-coords.yaml", error))?;
+                    let coords: [i32; 3] = serde_yaml
+                        ::from_value(coords)
+                        .map_err(|error| format_error("projection-coords.yaml", error))?;
+                    if model.node(&name).is_some() {
+                        return Err(BuildingModelError::DuplicateNode(name));
+                    }
+                    model.nodes.push(ModelNode {
+                        name,
+                        floor: floor.clone(),
+                        group: group.to_owned(),
+                        coords,
+                    });
+                }
+            }
+            model.floors.push(floor);
+        }
+
+        let paths: BTreeMap<String, BTreeMap<String, Option<Vec<YamlEdge>>>> = serde_yaml
+            ::from_str(paths)
+            .map_err(|error| format_error("paths.yaml", error))?;
+        for section in model.sections() {
+            let Some(groups) = paths.get(&section) else {
+                continue;
+            };
+            check_groups("paths.yaml", groups.keys())?;
+            for group in GROUPS {
+                for edge in groups.get(group).into_iter().flatten().flatten() {
+                    for node in &edge.nodes {
+                        if model.node(node).is_none() {
+                            return Err(BuildingModelError::UnknownNode(node.clone()));
+                        }
+                    }
+                    model.edges.push(ModelEdge {
+                        nodes: edge.nodes.clone(),
+                        distance: edge.dist,
+                        edge_type: edge.edge_type,
+                        section: section.clone(),
+                        group: group.to_owned(),
+                    });
+                }
+            }
+        }
+        if let Some(section) = paths.keys().find(|section| !model.sections().contains(section)) {
+            return Err(BuildingModelError::UnknownFloor(section.clone()));
+        }
+
+        for line in paths_txt.unwrap_or_default().lines() {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if let [node1, node2, _, _] = fields[..] {
+                if node2 == "G" && model.node(node1).is_some() {
+                    model.entrances.push(node1.to_owned());
+                } else if node1 == "G" && model.node(node2).is_some() {
+                    model.entrances.push(node2.to_owned());
+                }
+            }
+        }
+        Ok(model)
+    }
+
+    /// Writes every file of the model into an asset directory.
+    pub fn save(&self, directory: &Path) -> io::Result<()> {
+        fs::write(directory.join("projection-coords.yaml"), self.coords_yaml())?;
+        fs::write(directory.join("projection-coords-flatten.yaml"), self.flattened_coords_yaml())?;
+        fs::write(directory.join("paths.yaml"), self.paths_yaml())?;
+        fs::write(directory.join("paths.txt"), self.paths_txt())
+    }
+
+    /// Sections of `paths.yaml` in the order they are written.
+    fn sections(&self) -> Vec<String> {
+        std::iter::once(STAIRS_SECTION.to_owned()).chain(self.floors.iter().cloned()).collect()
+    }
+
+    pub fn coords_yaml(&self) -> String {
+        let mut content = COORDS_HEADER.to_owned();
+        for floor in &self.floors {
+            content.push_str(&format!("{}:\n", floor));
+            for group in GROUPS {
+                content.push_str(&format!("  {}:\n", group));
+                for node in self.nodes.iter().filter(|node| node.floor == *floor) {
+                    if node.group == group {
+                        let [x, y, z] = node.coords;
+                        content.push_str(&format!("    {}: [{}, {}, {}]\n", node.name, x, y, z));
+                    }
+                }
+            }
+        }
+        content
+    }
+
+    /// `projection-coords-flatten.yaml`, with the nodes sorted by name.
+    pub fn flattened_coords_yaml(&self) -> String {
+        let mut content = String::new();
+        for (name, [x, y, z]) in self.flattened_coords().into_iter().collect::<BTreeMap<_, _>>() {
+            content.push_str(&format!("{}:\n- {}\n- {}\n- {}\n", name, x, y, z));
+        }
+        content
+    }
+
+    pub fn paths_yaml(&self) -> String {
+        let mut content = PATHS_HEADER.to_owned();
+        for section in self.sections() {
+            content.push_str(&format!("{}:\n", section));
+            for group in GROUPS {
+                content.push_str(&format!("  {}:\n", group));
+                for edge in self.edges.iter().filter(|edge| edge.section == section) {
+                    if edge.group == group {
+                        content.push_str(
+                            &format!(
+                                "  - dist: {}\n    nodes:\n    - {}\n    - {}\n    type: {}\n",
+                                edge.distance,
+                                edge.nodes[0],
+                                edge.nodes[1],
+                                edge.edge_type
+                            )
+                        );
+                    }
+                }
+            }
+        }
+        content
+    }
+
+    pub fn paths_txt(&self) -> String {
+        let mut content = String::new();
+        for section in self.sections() {
+            for group in GROUPS {
+                for edge in &self.edges {
+                    if edge.section == section && edge.group == group {
+                        content.push_str(
+                            &format!(
+                                "{} {} {} {}\n",
+                                edge.nodes[0],
+                                edge.nodes[1],
+                                edge.distance,
+                                edge.edge_type
+                            )
+                        );
+                    }
+                }
+            }
+        }
+        for entrance in &self.entrances {
+            content.push_str(
+                &format!("{} G {} {}\n", entrance, ENTRANCE_DISTANCE, ENTRANCE_EDGE_TYPE)
+            );
+        }
+        content
+    }
+
+    pub fn flattened_coords(&self) -> HashMap<String, [i32; 3]> {
+        self.nodes
+            .iter()
+            .map(|node| (node.name.clone(), node.coords))
+            .collect()
+    }
+
+    pub fn floors(&self) -> &[String] {
+        &self.floors
+    }
+
+    pub fn nodes(&self) -> &[ModelNode] {
+        &self.nodes
+    }
+
+    pub fn node(&self, name: &str) -> Option<&ModelNode> {
+        self.nodes.iter().find(|node| node.name == name)
+    }
+
+    pub fn edges(&self) -> &[ModelEdge] {
+        &self.edges
+    }
+
+    /// Index of the edge joining two nodes.
+    pub fn edge_index(&self, node1: &str, node2: &str) -> Option<usize> {
+        self.edges.iter().position(|edge| edge.joins(node1, node2))
+    }
+
+    pub fn entrances(&self) -> &[String] {
+        &self.entrances
+    }
+
+    fn check_name(&self, name: &str) -> Result<(), BuildingModelError> {
+        let is_valid =
+            !name.is_empty() &&
+            name != "G" &&
+            name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-');
+        if !is_valid {
+            return Err(BuildingModelError::InvalidName(name.to_owned()));
+        }
+        if self.node(name).is_some() {
+            return Err(BuildingModelError::DuplicateNode(name.to_owned()));
+        }
+        Ok(())
+    }
+
+    fn node_index(&self, name: &str) -> Result<usize, BuildingModelError> {
+        self.nodes
+            .iter()
+            .position(|node| node.name == name)
+            .ok_or_else(|| BuildingModelError::UnknownNode(name.to_owned()))
+    }
+
+    /// Height of a floor in `projection-coords.yaml`.
+    fn floor_z(&self, floor: &str) -> Result<i32, BuildingModelError> {
+        floor
+            .strip_suffix('F')
+            .and_then(|number| number.parse::<i32>().ok())
+            .filter(|_| self.floors.iter().any(|known| known == floor))
+            .map(|number| (number - 2) * FLOOR_HEIGHT)
+            .ok_or_else(|| BuildingModelError::UnknownFloor(floor.to_owned()))
+    }
+
+    /// Adds a node at `x` and `y` on a floor.
+    pub fn add_node(
+        &mut self,
+        name: &str,
+        floor: &str,
+        group: &str,
+        [x, y]: [i32; 2]
+    ) -> Result<(), BuildingModelError> {
+        self.check_name(name)?;
+        let z = self.floor_z(floor)?;
+        self.nodes.push(ModelNode {
+            name: name.to_owned(),
+            floor: floor.to_owned(),
+            group: group.to_owned(),
+            coords: [x, y, z],
+        });
+        Ok(())
+    }
+
+    /// Moves a node within its floor. The distances of its edges that were automatic follow the
+    /// node, while distances set by hand are kept.
+    pub fn move_node(&mut self, name: &str, [x, y]: [i32; 2]) -> Result<(), BuildingModelError> {
+        let index = self.node_index(name)?;
+        let automatic: Vec<usize> = (0..self.edges.len())
+            .filter(|&edge| self.edges[edge].nodes.iter().any(|node| node == name))
+            .filter(|&edge| self.edges[edge].distance == self.auto_distance_of(edge))
+            .collect();
+        self.nodes[index].coords[0] = x;
+        self.nodes[index].coords[1] = y;
+        for edge in automatic {
+            self.edges[edge].distance = self.auto_distance_of(edge);
+        }
+        Ok(())
+    }
+
+    pub fn set_group(&mut self, name: &str, group: &str) -> Result<(), BuildingModelError> {
+        let index = self.node_index(name)?;
+        self.nodes[index].group = group.to_owned();
+        for edge in 0..self.edges.len() {
+            if self.edges[edge].nodes.iter().any(|node| node == name) {
+                self.edges[edge].group = self.edge_group(&self.edges[edge].nodes);
+            }
+        }
+        Ok(())
+    }
+
+    /// Renames a node in the coordinates, the edges and the entrances.
+    pub fn rename_node(&mut self, name: &str, new_name: &str) -> Result<(), BuildingModelError> {
+        let index = self.node_index(name)?;
+        self.check_name(new_name)?;
+        self.nodes[index].name = new_name.to_owned();
+        for node in self.edges.iter_mut().flat_map(|edge| edge.nodes.iter_mut()) {
+            if node == name {
+                *node = new_name.to_owned();
+            }
+        }
+        for entrance in &mut self.entrances {
+            if entrance == name {
+                *entrance = new_name.to_owned();
+            }
+        }
+        Ok(())
+    }
+
+    /// Deletes a node with its edges.
+    pub fn delete_node(&mut self, name: &str) -> Result<(), BuildingModelError> {
+        let index = self.node_index(name)?;
+        self.nodes.remove(index);
+        self.edges.retain(|edge| !edge.nodes.iter().any(|node| node == name));
+        self.entrances.retain(|entrance| entrance != name);
+        Ok(())
+    }
+
+    /// Joins two nodes, with the automatic distance unless `distance` is given.
+    pub fn connect(
+        &mut self,
+        node1: &str,
+        node2: &str,
+        edge_type: u8,
+        distance: Option<u32>
+    ) -> Result<(), BuildingModelError> {
+        let (index1, index2) = (self.node_index(node1)?, self.node_index(node2)?);
+        if index1 == index2 {
+            return Err(BuildingModelError::Loop(node1.to_owned()));
+        }
+        if self.edge_index(node1, node2).is_some() {
+            return Err(BuildingModelError::DuplicateEdge(node1.to_owned(), node2.to_owned()));
+        }
+        let nodes = [node1.to_owned(), node2.to_owned()];
+        let section = if is_stairs_type(edge_type) {
+            STAIRS_SECTION.to_owned()
+        } else {
+            self.nodes[index1].floor.clone()
+        };
+        self.edges.push(ModelEdge {
+            distance: distance.unwrap_or_else(|| self.auto_distance(node1, node2, edge_type)),
+            edge_type,
+            section,
+            group: self.edge_group(&nodes),
+            nodes,
+        });
+        Ok(())
+    }
+
+    /// Changes the type and distance of an edge, moving it to the staircase section or back if
+    /// needed.
+    pub fn set_edge(&mut self, index: usize, edge_type: u8, distance: u32) {
+        let floor = self.node(&self.edges[index].nodes[0]).map(|node| node.floor.clone());
+        let edge = &mut self.edges[index];
+        if is_stairs_type(edge_type) != edge.is_stairs() {
+            edge.section = if is_stairs_type(edge_type) {
+                STAIRS_SECTION.to_owned()
+            } else {
+                floor.unwrap_or_default()
+            };
+        }
+        edge.edge_type = edge_type;
+        edge.distance = distance;
+    }
+
+    pub fn remove_edge(&mut self, index: usize) {
+        self.edges.remove(index);
+    }
+
+    pub fn set_entrance(&mut self, name: &str, is_entrance: bool) {
+        self.entrances.retain(|entrance| entrance != name);
+        if is_entrance {
+            self.entrances.push(name.to_owned());
+        }
+    }
+
+    /// Group of `paths.yaml` for an edge: the block of its nodes, the block of the node that is
+    /// not a crossing, or `X` between the blocks.
+    fn edge_group(&self, nodes: &[String; 2]) -> String {
+        let group = |name: &str| {
+            self.node(name)
+                .map(|node| node.group.as_str())
+                .unwrap_or("X")
+        };
+        match (group(&nodes[0]), group(&nodes[1])) {
+            (group1, group2) if group1 == group2 => group1,
+            ("X", group) | (group, "X") => group,
+            _ => "X",
+        }.to_owned()
+    }
+
+    /// Distance between two nodes computed from their coordinates, as by `coordinate_distance`.
+    pub fn auto_distance(&self, node1: &str, node2: &str, edge_type: u8) -> u32 {
+        match (self.node(node1), self.node(node2)) {
+            (Some(node1), Some(node2)) => {
+                coordinate_distance(&node1.coords, &node2.coords, edge_type)
+            }
+            _ => 0,
+        }
+    }
+
+    fn auto_distance_of(&self, index: usize) -> u32 {
+        let edge = &self.edges[index];
+        self.auto_distance(&edge.nodes[0], &edge.nodes[1], edge.edge_type)
+    }
+
+    /// Components of the edges and entrances, and the rooms (nodes of the `A` and `B` blocks
+    /// other than stairs) cut off from the ground.
+    pub fn connectivity(&self) -> Connectivity {
+        let mut neighbours: HashMap<&str, Vec<&str>> = HashMap::new();
+        for edge in &self.edges {
+            neighbours.entry(&edge.nodes[0]).or_default().push(&edge.nodes[1]);
+            neighbours.entry(&edge.nodes[1]).or_default().push(&edge.nodes[0]);
+        }
+        for entrance in &self.entrances {
+            neighbours.entry(entrance).or_default().push("G");
+            neighbours.entry("G").or_default().push(entrance);
+        }
+        let mut seen: HashSet<&str> = HashSet::new();
+        let mut components: Vec<Vec<String>> = vec![];
+        let names = self.nodes
+            .iter()
+            .map(|node| node.name.as_str())
+            .chain(if self.entrances.is_empty() { None } else { Some("G") });
+        for name in names {
+            if !seen.insert(name) {
+                continue;
+            }
+            let mut component = vec![name.to_owned()];
+            let mut stack = vec![name];
+            while let Some(node) = stack.pop() {
+                for &next in neighbours.get(node).into_iter().flatten() {
+                    if seen.insert(next) {
+                        component.push(next.to_owned());
+                        stack.push(next);
+                    }
+                }
+            }
+            components.push(component);
+        }
+        components.sort_by_key(|component| std::cmp::Reverse(component.len()));
+
+        let main = components
+            .iter()
+            .find(|component| component.iter().any(|node| node == "G"))
+            .or(components.first());
+        let unreachable_rooms = self.nodes
+            .iter()
+            .filter(|node| is_room(node))
+            .filter(|node| main.is_some_and(|main| !main.contains(&node.name)))
+            .map(|node| node.name.clone())
+            .collect();
+        Connectivity { components, unreachable_rooms }
+    }
+}
+
+fn check_groups<'a>(
+    file: &str,
+    mut groups: impl Iterator<Item = &'a String>
+) -> Result<(), BuildingModelError> {
+    match groups.find(|group| !GROUPS.contains(&group.as_str())) {
+        Some(group) => {
+            Err(BuildingModelError::Format(file.to_owned(), format!("unknown group {}", group)))
+        }
+        None => Ok(()),
+    }
+}
+
+/// Distance of an edge computed from the coordinates of its nodes: the straight distance on the
+/// floor, longer by `BRIDGE_DISTANCE_FACTOR` for bridges, plus `STAIRS_DISTANCE_PER_FLOOR` per
+/// floor for staircases.
+pub fn coordinate_distance(coords1: &[i32; 3], coords2: &[i32; 3], edge_type: u8) -> u32 {
+    let [x1, y1, z1] = coords1.map(|value| value as f64);
+    let [x2, y2, z2] = coords2.map(|value| value as f64);
+    let mut on_floor = ((x1 - x2).powi(2) + (y1 - y2).powi(2)).sqrt();
+    if edge_type == 1 {
+        on_floor *= BRIDGE_DISTANCE_FACTOR;
+    }
+    let floors = if is_stairs_type(edge_type) {
+        ((z1 - z2).abs() / (FLOOR_HEIGHT as f64)) * (STAIRS_DISTANCE_PER_FLOOR as f64)
+    } else {
+        0.0
+    };
+    (on_floor + floors).round() as u32
+}
+
+/// Whether a node is a room rather than a crossing or staircase.
+pub fn is_room(node: &ModelNode) -> bool {
+    node.group != "X" && !node.name.starts_with('S')
+}
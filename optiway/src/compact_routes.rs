@@ -0,0 +1,577 @@
+//! Compact route storage with interned node ids.
+//!
+//! `Routes` keeps every route as a space-separated string in three levels of hash maps, which has
+//! to be split again whenever a route is drawn or counted. `CompactRoutes` interns the node names
+//! once and keeps the routes of all students as `NodeId` slices of a single arena, so that a route
+//! is looked up by index and the routes of every student can be walked without touching a string.
+//!
+//! Compact routes convert to and from the JSON format of `Routes` and have a binary format of their
+//! own, which is chosen by the `.owr` extension:
+//!
+//! - the magic bytes `OWRT` and the format version, as a `u32`
+//! - the node names and the student numbers, each as a `u32` count followed by `u16`-length
+//!   prefixed UTF-8 strings
+//! - the number of nodes of every route, as a `u16` for each student, day and transition in order
+//! - the nodes of all routes in the same order, as `u16` node ids
+//! - since version 2, the routes edited by hand, as a `u32` count followed by the `u32` position of
+//!   every such route in the order above
+//!
+//! All integers are little-endian. The JSON format has no place for the routes edited by hand, so
+//! they are only kept in the binary format.
+
+use std::{
+    collections::{ BTreeSet, HashMap },
+    fs::{ self, File },
+    io::{ self, BufReader },
+    path::Path,
+};
+
+use serde::{ ser::SerializeMap, Deserialize, Deserializer, Serialize, Serializer };
+
+use crate::{
+    optimization::checkpoint::write_atomically,
+    routing::{ EditedRoutes, Routes, TRANSITION_COUNT },
+    timetable::DAY_COUNT,
+};
+
+/// Extension of the binary route format.
+pub const BINARY_EXTENSION: &str = "owr";
+
+const MAGIC: &[u8; 4] = b"OWRT";
+const VERSION: u32 = 2;
+
+const ROUTES_PER_STUDENT: usize = DAY_COUNT * TRANSITION_COUNT;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Debug)]
+pub struct NodeId(u16);
+
+impl NodeId {
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+/// Routes of every student for every day (1–5) and transition (0–11).
+#[derive(Clone, Default, PartialEq, Eq, Debug)]
+pub struct CompactRoutes {
+    node_names: Vec<String>,
+    node_ids: HashMap<String, NodeId>,
+    /// Student numbers in ascending order.
+    students: Vec<String>,
+    student_indices: HashMap<String, usize>,
+    /// The nodes of route `i` are `arena[offsets[i]..offsets[i + 1]]`, where routes are ordered by
+    /// student, then day, then transition.
+    offsets: Vec<u32>,
+    arena: Vec<NodeId>,
+    /// Positions of the routes edited by hand, in the order of `offsets`.
+    edited: BTreeSet<usize>,
+}
+
+fn route_index(student_index: usize, day: u32, transition: usize) -> Option<usize> {
+    if (1..=DAY_COUNT as u32).contains(&day) && transition < TRANSITION_COUNT {
+        let day_index = (day - 1) as usize;
+        Some(student_index * ROUTES_PER_STUDENT + day_index * TRANSITION_COUNT + transition)
+    } else {
+        None
+    }
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Reads the binary route format from a byte slice.
+struct ByteReader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> ByteReader<'a> {
+    fn take(&mut self, length: usize) -> io::Result<&'a [u8]> {
+        if self.bytes.len() < length {
+            return Err(invalid_data("Unexpected end of route file"));
+        }
+        let (taken, rest) = self.bytes.split_at(length);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn u16(&mut self) -> io::Result<u16> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> io::Result<u32> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn strings(&mut self) -> io::Result<Vec<String>> {
+        let count = self.u32()? as usize;
+        let mut strings = Vec::with_capacity(count.min(self.bytes.len() / 2));
+        for _ in 0..count {
+            let length = self.u16()? as usize;
+            let string = std::str
+                ::from_utf8(self.take(length)?)
+                .map_err(|_| invalid_data("Invalid UTF-8 in route file"))?;
+            strings.push(string.to_owned());
+        }
+        Ok(strings)
+    }
+}
+
+fn write_strings(bytes: &mut Vec<u8>, strings: &[String]) -> io::Result<()> {
+    bytes.extend_from_slice(&(strings.len() as u32).to_le_bytes());
+    for string in strings {
+        let length = u16
+            ::try_from(string.len())
+            .map_err(|_| invalid_data("Name too long for the binary route format"))?;
+        bytes.extend_from_slice(&length.to_le_bytes());
+        bytes.extend_from_slice(string.as_bytes());
+    }
+    Ok(())
+}
+
+impl CompactRoutes {
+    /// Routes of days and transitions out of range are dropped, and missing routes are empty.
+    /// Fails if the routes have more distinct nodes than a `NodeId` can tell apart.
+    pub fn from_routes(routes: &Routes) -> io::Result<Self> {
+        let mut students: Vec<String> = routes.keys().cloned().collect();
+        students.sort();
+        let mut compact = Self {
+            student_indices: students
+                .iter()
+                .enumerate()
+                .map(|(index, student)| (student.clone(), index))
+                .collect(),
+            offsets: Vec::with_capacity(students.len() * ROUTES_PER_STUDENT + 1),
+            ..Default::default()
+        };
+        compact.offsets.push(0);
+        for student in &students {
+            let student_routes = &routes[student];
+            for day in 1..=DAY_COUNT as u32 {
+                for transition in 0..TRANSITION_COUNT {
+                    let route = student_routes
+                        .get(&day)
+                        .and_then(|day_routes| day_routes.get(&transition))
+                        .map_or("", String::as_str);
+                    for node in route.split(' ').filter(|node| !node.is_empty()) {
+                        let node = compact.intern(node)?;
+                        compact.arena.push(node);
+                    }
+                    compact.offsets.push(compact.arena.len() as u32);
+                }
+            }
+        }
+        compact.students = students;
+        Ok(compact)
+    }
+
+    /// Routes as space-separated strings, with every day and transition of every student.
+    pub fn to_routes(&self) -> Routes {
+        self.students
+            .iter()
+            .enumerate()
+            .map(|(student_index, student)| {
+                let days = (1..=DAY_COUNT as u32)
+                    .map(|day| {
+                        let transitions = (0..TRANSITION_COUNT)
+                            .map(|transition| {
+                                (transition, self.route_string(student_index, day, transition))
+                            })
+                            .collect();
+                        (day, transitions)
+                    })
+                    .collect();
+                (student.clone(), days)
+            })
+            .collect()
+    }
+
+    fn intern(&mut self, name: &str) -> io::Result<NodeId> {
+        if let Some(id) = self.node_ids.get(name) {
+            return Ok(*id);
+        }
+        let id = NodeId(
+            u16
+                ::try_from(self.node_names.len())
+                .map_err(|_| invalid_data("Too many distinct nodes in the routes"))?
+        );
+        self.node_names.push(name.to_owned());
+        self.node_ids.insert(name.to_owned(), id);
+        Ok(id)
+    }
+
+    pub fn node_id(&self, name: &str) -> Option<NodeId> {
+        self.node_ids.get(name).copied()
+    }
+
+    pub fn node_name(&self, node: NodeId) -> &str {
+        &self.node_names[node.index()]
+    }
+
+    /// Every node appearing in a route, with its name.
+    pub fn nodes(&self) -> impl Iterator<Item = (NodeId, &str)> {
+        self.node_names
+            .iter()
+            .enumerate()
+            .map(|(index, name)| (NodeId(index as u16), name.as_str()))
+    }
+
+    pub fn student_count(&self) -> usize {
+        self.students.len()
+    }
+
+    /// Student numbers in ascending order, whose positions are the student indices.
+    pub fn students(&self) -> impl Iterator<Item = &str> {
+        self.students.iter().map(String::as_str)
+    }
+
+    pub fn student_index(&self, student_number: &str) -> Option<usize> {
+        self.student_indices.get(student_number).copied()
+    }
+
+    /// Nodes of a route, which is empty if the student stays in the room or the day or transition
+    /// is out of range.
+    pub fn route(&self, student_index: usize, day: u32, transition: usize) -> &[NodeId] {
+        match route_index(student_index, day, transition) {
+            Some(index) if index < self.offsets.len() - 1 => {
+                &self.arena[self.offsets[index] as usize..self.offsets[index + 1] as usize]
+            }
+            _ => &[],
+        }
+    }
+
+    pub fn route_names(
+        &self,
+        student_index: usize,
+        day: u32,
+        transition: usize
+    ) -> impl Iterator<Item = &str> {
+        self.route(student_index, day, transition)
+            .iter()
+            .map(|node| self.node_name(*node))
+    }
+
+    /// The route as a space-separated string, as in `Routes`.
+    pub fn route_string(&self, student_index: usize, day: u32, transition: usize) -> String {
+        self.route_names(student_index, day, transition).collect::<Vec<&str>>().join(" ")
+    }
+
+    /// Replaces a route by the nodes of `names`, doing nothing if the day or transition is out of
+    /// range. The routes after it are moved along the arena. Fails, leaving the route as it is, if
+    /// a new node does not fit in a `NodeId`.
+    pub fn set_route<'a>(
+        &mut self,
+        student_index: usize,
+        day: u32,
+        transition: usize,
+        names: impl IntoIterator<Item = &'a str>
+    ) -> io::Result<()> {
+        let Some(index) = route_index(student_index, day, transition).filter(
+            |index| *index < self.offsets.len() - 1
+        ) else {
+            return Ok(());
+        };
+        let nodes: Vec<NodeId> = names
+            .into_iter()
+            .map(|name| self.intern(name))
+            .collect::<io::Result<_>>()?;
+        let (start, end) = (self.offsets[index] as usize, self.offsets[index + 1] as usize);
+        let change = (nodes.len() as i64) - ((end - start) as i64);
+        self.arena.splice(start..end, nodes);
+        for offset in &mut self.offsets[index + 1..] {
+            *offset = ((*offset as i64) + change) as u32;
+        }
+        Ok(())
+    }
+
+    pub fn is_edited(&self, student_index: usize, day: u32, transition: usize) -> bool {
+        route_index(student_index, day, transition).is_some_and(|index| {
+            self.edited.contains(&index)
+        })
+    }
+
+    /// Marks a route as edited by hand, or as not edited if `edited` is `false`.
+    pub fn set_edited(&mut self, student_index: usize, day: u32, transition: usize, edited: bool) {
+        let Some(index) = route_index(student_index, day, transition).filter(
+            |index| *index < self.offsets.len() - 1
+        ) else {
+            return;
+        };
+        if edited {
+            self.edited.insert(index);
+        } else {
+            self.edited.remove(&index);
+        }
+    }
+
+    /// The routes edited by hand, as (student number, day, transition).
+    pub fn edited_routes(&self) -> EditedRoutes {
+        self.edited
+            .iter()
+            .map(|index| {
+                let route = index % ROUTES_PER_STUDENT;
+                (
+                    self.students[index / ROUTES_PER_STUDENT].clone(),
+                    (route / TRANSITION_COUNT + 1) as u32,
+                    route % TRANSITION_COUNT,
+                )
+            })
+            .collect()
+    }
+
+    /// Marks the routes of `edited` as edited by hand, skipping unknown students.
+    pub fn mark_edited(&mut self, edited: &EditedRoutes) {
+        for (student_number, day, transition) in edited {
+            if let Some(student_index) = self.student_index(student_number) {
+                self.set_edited(student_index, *day, *transition, true);
+            }
+        }
+    }
+
+    pub fn to_binary(&self) -> io::Result<Vec<u8>> {
+        let mut bytes = Vec::with_capacity(
+            16 + self.offsets.len() * 2 + self.arena.len() * 2 + self.students.len() * 8
+        );
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&VERSION.to_le_bytes());
+        write_strings(&mut bytes, &self.node_names)?;
+        write_strings(&mut bytes, &self.students)?;
+        for route in self.offsets.windows(2) {
+            let length = u16
+                ::try_from(route[1] - route[0])
+                .map_err(|_| invalid_data("Route too long for the binary route format"))?;
+            bytes.extend_from_slice(&length.to_le_bytes());
+        }
+        for node in &self.arena {
+            bytes.extend_from_slice(&node.0.to_le_bytes());
+        }
+        bytes.extend_from_slice(&(self.edited.len() as u32).to_le_bytes());
+        for index in &self.edited {
+            bytes.extend_from_slice(&(*index as u32).to_le_bytes());
+        }
+        Ok(bytes)
+    }
+
+    pub fn from_binary(bytes: &[u8]) -> io::Result<Self> {
+        let mut reader = ByteReader { bytes };
+        if reader.take(MAGIC.len())? != MAGIC {
+            return Err(invalid_data("Not a route file"));
+        }
+        let version = reader.u32()?;
+        if version == 0 || version > VERSION {
+            return Err(invalid_data(&format!("Unsupported route file version {}", version)));
+        }
+        let node_names = reader.strings()?;
+        if node_names.len() > (u16::MAX as usize) + 1 {
+            return Err(invalid_data("Too many nodes in route file"));
+        }
+        let students = reader.strings()?;
+        let route_count = students.len() * ROUTES_PER_STUDENT;
+        if route_count * 2 > reader.bytes.len() {
+            return Err(invalid_data("Unexpected end of route file"));
+        }
+        let mut offsets = Vec::with_capacity(route_count + 1);
+        offsets.push(0u32);
+        for _ in 0..route_count {
+            let offset = offsets[offsets.len() - 1]
+                .checked_add(reader.u16()? as u32)
+                .ok_or_else(|| invalid_data("Too many route nodes in route file"))?;
+            offsets.push(offset);
+        }
+        let node_count = offsets[route_count] as usize;
+        if node_count * 2 > reader.bytes.len() {
+            return Err(invalid_data("Unexpected end of route file"));
+        }
+        let mut arena = Vec::with_capacity(node_count);
+        for _ in 0..node_count {
+            let node = reader.u16()?;
+            if node as usize >= node_names.len() {
+                return Err(invalid_data(&format!("Unknown node id {} in route file", node)));
+            }
+            arena.push(NodeId(node));
+        }
+        let mut edited = BTreeSet::new();
+        if version >= 2 {
+            for _ in 0..reader.u32()? {
+                let index = reader.u32()? as usize;
+                if index >= route_count {
+                    return Err(invalid_data("Unknown edited route in route file"));
+                }
+                edited.insert(index);
+            }
+        }
+        if !reader.bytes.is_empty() {
+            return Err(invalid_data("Unexpected data after the routes"));
+        }
+        let node_ids: HashMap<String, NodeId> = node_names
+            .iter()
+            .enumerate()
+            .map(|(index, name)| (name.clone(), NodeId(index as u16)))
+            .collect();
+        let student_indices: HashMap<String, usize> = students
+            .iter()
+            .enumerate()
+            .map(|(index, student)| (student.clone(), index))
+            .collect();
+        if node_ids.len() != node_names.len() || student_indices.len() != students.len() {
+            return Err(invalid_data("Duplicate node or student in route file"));
+        }
+        Ok(Self { node_names, node_ids, students, student_indices, offsets, arena, edited })
+    }
+
+    fn is_binary(path: &Path) -> bool {
+        path.extension().is_some_and(|extension| extension == BINARY_EXTENSION)
+    }
+
+    /// Reads a route file, in the binary format if it has the `.owr` extension and in the JSON
+    /// format of `Routes` otherwise.
+    pub fn load(path: &Path) -> io::Result<Self> {
+        if Self::is_binary(path) {
+            Self::from_binary(&fs::read(path)?)
+        } else {
+            Ok(serde_json::from_reader(BufReader::new(File::open(path)?))?)
+        }
+    }
+
+    /// Writes a route file in the format `load` reads it in.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if Self::is_binary(path) {
+            write_atomically(path, &self.to_binary()?)
+        } else {
+            write_atomically(path, &serde_json::to_vec_pretty(self)?)
+        }
+    }
+}
+
+/// Routes of one student or day, serialized as a map without building `Routes`.
+struct StudentRoutes<'a>(&'a CompactRoutes, usize);
+struct DayRoutes<'a>(&'a CompactRoutes, usize, u32);
+
+impl Serialize for DayRoutes<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let DayRoutes(routes, student_index, day) = *self;
+        let mut map = serializer.serialize_map(Some(TRANSITION_COUNT))?;
+        for transition in 0..TRANSITION_COUNT {
+            map.serialize_entry(
+                &transition,
+                &routes.route_string(student_index, day, transition)
+            )?;
+        }
+        map.end()
+    }
+}
+
+impl Serialize for StudentRoutes<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let StudentRoutes(routes, student_index) = *self;
+        let mut map = serializer.serialize_map(Some(DAY_COUNT))?;
+        for day in 1..=DAY_COUNT as u32 {
+            map.serialize_entry(&day, &DayRoutes(routes, student_index, day))?;
+        }
+        map.end()
+    }
+}
+
+/// Serializes to the JSON format of `Routes`.
+impl Serialize for CompactRoutes {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.students.len()))?;
+        for (student_index, student) in self.students.iter().enumerate() {
+            map.serialize_entry(student, &StudentRoutes(self, student_index))?;
+        }
+        map.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for CompactRoutes {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let routes = Routes::deserialize(deserializer)?;
+        Self::from_routes(&routes).map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROUTES_JSON: &str = r#"{
+        "21001": { "1": { "0": "G A1 A101", "3": "A101 A1 B1 B201" }, "5": { "11": "B201 B1 G" } },
+        "20417": { "2": { "4": "G A1 A102" } }
+    }"#;
+
+    fn compact_routes() -> CompactRoutes {
+        let mut routes: CompactRoutes = serde_json::from_str(ROUTES_JSON).unwrap();
+        let student_index = routes.student_index("21001").unwrap();
+        routes.set_edited(student_index, 1, 3, true);
+        routes
+    }
+
+    #[test]
+    fn json_and_binary_round_trip() {
+        let routes = compact_routes();
+        let student_index = routes.student_index("21001").unwrap();
+        assert_eq!(routes.route_string(student_index, 1, 3), "A101 A1 B1 B201");
+        assert_eq!(routes.route_string(student_index, 2, 4), "");
+        assert!(routes.is_edited(student_index, 1, 3));
+
+        let json = serde_json::to_string(&routes).unwrap();
+        let mut from_json: CompactRoutes = serde_json::from_str(&json).unwrap();
+        from_json.mark_edited(&routes.edited_routes());
+        assert_eq!(from_json, routes);
+
+        let from_binary = CompactRoutes::from_binary(&routes.to_binary().unwrap()).unwrap();
+        assert_eq!(from_binary, routes);
+        assert_eq!(from_binary.to_routes(), routes.to_routes());
+        assert_eq!(from_binary.edited_routes(), routes.edited_routes());
+    }
+
+    #[test]
+    fn rejects_truncated_binary() {
+        let bytes = compact_routes().to_binary().unwrap();
+        for length in 0..bytes.len() {
+            assert!(CompactRoutes::from_binary(&bytes[..length]).is_err(), "length {}", length);
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_binary() {
+        let bytes = compact_routes().to_binary().unwrap();
+
+        let mut wrong_magic = bytes.clone();
+        wrong_magic[0] = b'X';
+        assert!(CompactRoutes::from_binary(&wrong_magic).is_err());
+
+        let mut wrong_version = bytes.clone();
+        wrong_version[4..8].copy_from_slice(&(VERSION + 1).to_le_bytes());
+        assert!(CompactRoutes::from_binary(&wrong_version).is_err());
+
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        assert!(CompactRoutes::from_binary(&trailing).is_err());
+
+        // the last node of the last route points past the node names
+        let mut unknown_node = bytes.clone();
+        let node_position = bytes.len() - 4 - 4 - 2;
+        unknown_node[node_position..node_position + 2].copy_from_slice(&u16::MAX.to_le_bytes());
+        assert!(CompactRoutes::from_binary(&unknown_node).is_err());
+
+        let mut unknown_edited = bytes.clone();
+        let edited_position = bytes.len() - 4;
+        unknown_edited[edited_position..].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(CompactRoutes::from_binary(&unknown_edited).is_err());
+
+        // a student count far beyond the length of the file
+        let mut header = MAGIC.to_vec();
+        header.extend_from_slice(&VERSION.to_le_bytes());
+        header.extend_from_slice(&0u32.to_le_bytes());
+        header.extend_from_slice(&u32::MAX.to_le_bytes());
+        assert!(CompactRoutes::from_binary(&header).is_err());
+    }
+
+    #[test]
+    fn rejects_malformed_json() {
+        let wrong_type = r#"{ "21001": { "1": { "0": 3 } } }"#;
+        assert!(serde_json::from_str::<CompactRoutes>(wrong_type).is_err());
+        assert!(serde_json::from_str::<CompactRoutes>(&ROUTES_JSON[..40]).is_err());
+    }
+}
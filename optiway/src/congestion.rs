@@ -0,0 +1,418 @@
+//! Incremental congestion of every transition of the week.
+//!
+//! A `CongestionIndex` counts the students walking every edge and passing every node of the
+//! building graph in every transition, together with the performance index of the transition.
+//! Replacing a route only touches the edges and nodes of the old and the new route, so the index
+//! can follow the optimizer and the route editor as they change routes one at a time.
+//!
+//! The congestion of the staff is indexed separately, and `CongestionLayers` combines it with the
+//! congestion of the students for display.
+
+use std::{ borrow::Cow, collections::HashMap, fmt::{ self, Display }, sync::Arc };
+
+use crate::{
+    compact_routes::CompactRoutes,
+    evaluation::edge_total_performance,
+    graph::BuildingGraph,
+    routing::TRANSITION_COUNT,
+    timetable::DAY_COUNT,
+};
+
+#[derive(Clone)]
+pub struct TransitionCongestion {
+    /// Edge id → number of students walking the edge in either direction.
+    pub edge_loads: Vec<u32>,
+    /// Node id → number of students passing the node. The ground node is never counted.
+    pub node_loads: Vec<u32>,
+    pub performance_index: f64,
+}
+
+#[derive(Clone)]
+pub struct CongestionIndex {
+    /// Edge id → distance.
+    edge_distances: Vec<u32>,
+    /// Edge id → the nodes the edge connects.
+    edge_ends: Vec<(usize, usize)>,
+    /// Ordered by day, then transition.
+    transitions: Vec<TransitionCongestion>,
+    /// Whether a transition has changed since `take_changed` was last called.
+    changed: Vec<bool>,
+    /// Steps of routes between nodes that the graph does not have or does not connect, which are
+    /// left out of the loads and performance indices.
+    uncounted_steps: usize,
+}
+
+fn transition_index(day: u32, transition: usize) -> Option<usize> {
+    ((1..=DAY_COUNT as u32).contains(&day) && transition < TRANSITION_COUNT).then(|| {
+        ((day - 1) as usize) * TRANSITION_COUNT + transition
+    })
+}
+
+impl CongestionIndex {
+    /// An index in which nobody walks anywhere.
+    pub fn new(graph: &BuildingGraph) -> Self {
+        let mut edge_ends = vec![(0, 0); graph.edge_count()];
+        for node in 0..graph.node_count() {
+            for edge in graph.edges(node) {
+                edge_ends[edge.id] = (node.min(edge.to), node.max(edge.to));
+            }
+        }
+        let transition_count = DAY_COUNT * TRANSITION_COUNT;
+        Self {
+            edge_distances: graph.edge_distances(),
+            edge_ends,
+            transitions: vec![
+                TransitionCongestion {
+                    edge_loads: vec![0; graph.edge_count()],
+                    node_loads: vec![0; graph.node_count()],
+                    performance_index: 0.0,
+                };
+                transition_count
+            ],
+            changed: vec![true; transition_count],
+            uncounted_steps: 0,
+        }
+    }
+
+    /// Congestion of every route. Routes through nodes missing from the graph are not counted, and
+    /// their steps are added to the uncounted steps.
+    pub fn from_routes(graph: &BuildingGraph, routes: &CompactRoutes) -> Self {
+        let mut index = Self::new(graph);
+        let graph_ids: Vec<Option<usize>> = routes
+            .nodes()
+            .map(|(_, name)| graph.node_id(name))
+            .collect();
+        for student_index in 0..routes.student_count() {
+            for day in 1..=DAY_COUNT as u32 {
+                for transition in 0..TRANSITION_COUNT {
+                    let route = routes.route(student_index, day, transition);
+                    let nodes: Option<Vec<usize>> = route
+                        .iter()
+                        .map(|node| graph_ids[node.index()])
+                        .collect();
+                    match nodes {
+                        Some(nodes) => index.add_route(graph, day, transition, &nodes),
+                        None => {
+                            index.add_uncounted_steps(route.len().saturating_sub(1));
+                        }
+                    }
+                }
+            }
+        }
+        index
+    }
+
+    /// Adds `change` students walking `nodes`, keeping the performance index up to date.
+    fn change_route(
+        &mut self,
+        graph: &BuildingGraph,
+        day: u32,
+        transition: usize,
+        nodes: &[usize],
+        change: i64
+    ) {
+        let Some(index) = transition_index(day, transition) else {
+            return;
+        };
+        let missing_edges = nodes
+            .windows(2)
+            .filter(|pair| {
+                !graph.is_ground(pair[0]) &&
+                    !graph.is_ground(pair[1]) &&
+                    graph.edge_between(pair[0], pair[1]).is_none()
+            })
+            .count();
+        self.uncounted_steps = ((self.uncounted_steps as i64) + change * (missing_edges as i64))
+            .max(0) as usize;
+        let congestion = &mut self.transitions[index];
+        for edge in graph.counted_edges(nodes) {
+            let distance = self.edge_distances[edge.id] as f64;
+            let load = &mut congestion.edge_loads[edge.id];
+            congestion.performance_index -= edge_total_performance(distance, *load);
+            *load = ((*load as i64) + change).max(0) as u32;
+            congestion.performance_index += edge_total_performance(distance, *load);
+        }
+        for &node in nodes {
+            if !graph.is_ground(node) {
+                let load = &mut congestion.node_loads[node];
+                *load = ((*load as i64) + change).max(0) as u32;
+            }
+        }
+        self.changed[index] = true;
+    }
+
+    pub fn add_route(
+        &mut self,
+        graph: &BuildingGraph,
+        day: u32,
+        transition: usize,
+        nodes: &[usize]
+    ) {
+        self.change_route(graph, day, transition, nodes, 1);
+    }
+
+    pub fn remove_route(
+        &mut self,
+        graph: &BuildingGraph,
+        day: u32,
+        transition: usize,
+        nodes: &[usize]
+    ) {
+        self.change_route(graph, day, transition, nodes, -1);
+    }
+
+    /// Moves a student from `old_nodes` to `new_nodes`, in time proportional to their length.
+    pub fn replace_route(
+        &mut self,
+        graph: &BuildingGraph,
+        day: u32,
+        transition: usize,
+        old_nodes: &[usize],
+        new_nodes: &[usize]
+    ) {
+        self.remove_route(graph, day, transition, old_nodes);
+        self.add_route(graph, day, transition, new_nodes);
+    }
+
+    /// Counts steps of a route that cannot be added, such as a route through an unknown node.
+    pub fn add_uncounted_steps(&mut self, steps: usize) {
+        self.uncounted_steps += steps;
+    }
+
+    /// Steps of routes that are left out of the congestion because the graph has no such node or
+    /// edge. The performance indices are too low if there are any.
+    pub fn uncounted_steps(&self) -> usize {
+        self.uncounted_steps
+    }
+
+    /// Congestion of a transition, or `None` if the day or transition is out of range.
+    pub fn transition(&self, day: u32, transition: usize) -> Option<&TransitionCongestion> {
+        transition_index(day, transition).map(|index| &self.transitions[index])
+    }
+
+    /// The nodes an edge connects, the lower node id first.
+    pub fn edge_ends(&self, edge: usize) -> (usize, usize) {
+        self.edge_ends[edge]
+    }
+
+    /// Transitions changed since the last call, as (day, transition).
+    pub fn take_changed(&mut self) -> Vec<(u32, usize)> {
+        let mut changed = vec![];
+        for (index, is_changed) in self.changed.iter_mut().enumerate() {
+            if *is_changed {
+                changed.push((
+                    (index / TRANSITION_COUNT + 1) as u32,
+                    index % TRANSITION_COUNT,
+                ));
+                *is_changed = false;
+            }
+        }
+        changed
+    }
+
+    /// An index of the same graph where `f` maps the index and congestion of every transition to
+    /// its new congestion.
+    fn map_transitions(
+        &self,
+        f: impl Fn(usize, &TransitionCongestion) -> TransitionCongestion
+    ) -> CongestionIndex {
+        CongestionIndex {
+            edge_distances: self.edge_distances.clone(),
+            edge_ends: self.edge_ends.clone(),
+            transitions: self.transitions
+                .iter()
+                .enumerate()
+                .map(|(index, congestion)| f(index, congestion))
+                .collect(),
+            changed: vec![true; self.changed.len()],
+            uncounted_steps: self.uncounted_steps,
+        }
+    }
+
+    /// Congestion of a transition with every member of staff of `staff` counted as `staff_weight`
+    /// students, rounded on every edge and node.
+    fn combined(
+        &self,
+        students: &TransitionCongestion,
+        staff: &TransitionCongestion,
+        staff_weight: f64
+    ) -> TransitionCongestion {
+        let combine = |students: &[u32], staff: &[u32]| -> Vec<u32> {
+            students
+                .iter()
+                .zip(staff)
+                .map(|(students, staff)| students + ((*staff as f64) * staff_weight).round() as u32)
+                .collect()
+        };
+        let edge_loads = combine(&students.edge_loads, &staff.edge_loads);
+        let performance_index = edge_loads
+            .iter()
+            .zip(&self.edge_distances)
+            .map(|(load, distance)| edge_total_performance(*distance as f64, *load))
+            .sum();
+        TransitionCongestion {
+            edge_loads,
+            node_loads: combine(&students.node_loads, &staff.node_loads),
+            performance_index,
+        }
+    }
+
+    /// Largest number of people passing a node in any transition.
+    pub fn max_node_load(&self) -> u32 {
+        self.transitions
+            .iter()
+            .flat_map(|congestion| congestion.node_loads.iter().copied())
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Day → transition → performance index, in the format of `routes.json`.
+    pub fn performance_indices(&self) -> HashMap<u32, HashMap<usize, u128>> {
+        let mut performance_indices: HashMap<u32, HashMap<usize, u128>> = HashMap::new();
+        for (index, congestion) in self.transitions.iter().enumerate() {
+            performance_indices
+                .entry((index / TRANSITION_COUNT + 1) as u32)
+                .or_default()
+                .insert(index % TRANSITION_COUNT, congestion.performance_index as u128);
+        }
+        performance_indices
+    }
+}
+
+/// Whose movements the congestion counts.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum CongestionLayer {
+    #[default]
+    Students,
+    Staff,
+    /// Students and staff, with every member of staff weighted.
+    Combined,
+}
+
+impl CongestionLayer {
+    pub const ALL: [Self; 3] = [Self::Students, Self::Staff, Self::Combined];
+}
+
+impl Display for CongestionLayer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CongestionLayer::Students => write!(f, "Students"),
+            CongestionLayer::Staff => write!(f, "Staff"),
+            CongestionLayer::Combined => write!(f, "Combined"),
+        }
+    }
+}
+
+/// Congestion of the staff, and the layer of congestion to show.
+#[derive(Clone)]
+pub struct CongestionLayers {
+    /// Congestion of the shortest routes of the staff timetable, if one was imported.
+    pub staff: Option<Arc<CongestionIndex>>,
+    pub layer: CongestionLayer,
+    /// Number of students a member of staff counts as in the combined layer.
+    pub staff_weight: f64,
+}
+
+impl Default for CongestionLayers {
+    fn default() -> Self {
+        Self {
+            staff: None,
+            layer: CongestionLayer::default(),
+            staff_weight: 1.0,
+        }
+    }
+}
+
+impl CongestionLayers {
+    /// Congestion of the students of `students` in the layer. Without a staff timetable, nobody is
+    /// counted in the staff layer and the combined layer is the students'.
+    pub fn apply<'a>(&'a self, students: &'a CongestionIndex) -> Cow<'a, CongestionIndex> {
+        match (self.layer, &self.staff) {
+            (CongestionLayer::Students, _) | (CongestionLayer::Combined, None) => {
+                Cow::Borrowed(students)
+            }
+            (CongestionLayer::Staff, Some(staff)) => Cow::Borrowed(staff),
+            (CongestionLayer::Staff, None) => {
+                Cow::Owned(
+                    students.map_transitions(|_, congestion| TransitionCongestion {
+                        edge_loads: vec![0; congestion.edge_loads.len()],
+                        node_loads: vec![0; congestion.node_loads.len()],
+                        performance_index: 0.0,
+                    })
+                )
+            }
+            (CongestionLayer::Combined, Some(staff)) => {
+                Cow::Owned(
+                    students.map_transitions(|index, congestion| {
+                        students.combined(congestion, &staff.transitions[index], self.staff_weight)
+                    })
+                )
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::routing::Routes;
+
+    use super::*;
+
+    const PATHS: &str = "\
+A101 A1 50 0
+A1 B1 100 0
+B1 C1 100 0
+C1 C101 50 0
+A1 D1 110 0
+D1 C1 110 0
+A1 G 999999 2
+C1 G 999999 2
+";
+
+    fn index(graph: &BuildingGraph, routes: &Routes) -> CongestionIndex {
+        CongestionIndex::from_routes(graph, &CompactRoutes::from_routes(routes).unwrap())
+    }
+
+    #[test]
+    fn replacing_routes_matches_a_full_recompute() {
+        let graph = BuildingGraph::parse(PATHS);
+        let mut routes: Routes = (21001..=21030)
+            .map(|student| {
+                let day_routes = HashMap::from([
+                    (0, "G A1 A101".to_owned()),
+                    (1, "A101 A1 B1 C1 C101".to_owned()),
+                ]);
+                (student.to_string(), HashMap::from([(1, day_routes)]))
+            })
+            .collect();
+        let mut incremental = index(&graph, &routes);
+        let old_nodes = graph.route_ids("A101 A1 B1 C1 C101").unwrap();
+        let new_nodes = graph.route_ids("A101 A1 D1 C1 C101").unwrap();
+        for student in (21001..=21030).step_by(3) {
+            incremental.replace_route(&graph, 1, 1, &old_nodes, &new_nodes);
+            routes.get_mut(&student.to_string()).unwrap().get_mut(&1).unwrap().insert(
+                1,
+                "A101 A1 D1 C1 C101".to_owned()
+            );
+        }
+        let full = index(&graph, &routes);
+        for transition in 0..TRANSITION_COUNT {
+            let (incremental, full) = (
+                incremental.transition(1, transition).unwrap(),
+                full.transition(1, transition).unwrap(),
+            );
+            assert_eq!(incremental.edge_loads, full.edge_loads, "transition {}", transition);
+            assert_eq!(incremental.node_loads, full.node_loads, "transition {}", transition);
+            assert!(
+                (incremental.performance_index - full.performance_index).abs() <=
+                    1e-9 * full.performance_index,
+                "transition {}",
+                transition
+            );
+        }
+        let d1 = graph.node_id("D1").unwrap();
+        assert_eq!(full.transition(1, 1).unwrap().node_loads[d1], 10);
+    }
+}
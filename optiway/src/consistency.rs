@@ -0,0 +1,525 @@
+//! Consistency checks of the building data the app is built with.
+//!
+//! The checks cover `paths.txt`, `projection-coords-flatten.yaml` and `shortest_paths.json` of the
+//! app's assets and, when they are available, the `projection-coords.yaml` and `paths.yaml` those
+//! are generated from:
+//!
+//! - every node of `paths.txt` is reachable from the ground node `G`, has coordinates, and every
+//!   room with coordinates has edges;
+//! - no edge is listed twice, joins a node to itself, or is listed with two distances;
+//! - distances are close to the distances of the coordinates (see `coordinate_distance`);
+//! - `paths.txt` and `paths.yaml` have the same edges, and both coordinate files the same nodes;
+//! - every pair of rooms has a precomputed path, which follows the edges of `paths.txt` and is
+//!   as short as the shortest path of the graph.
+
+use std::{
+    collections::{ BTreeMap, BTreeSet, HashMap, HashSet },
+    fmt::{ self, Display },
+    fs,
+    io,
+    path::Path,
+};
+
+use crate::{
+    building_model::{ coordinate_distance, BuildingModel, BuildingModelError },
+    graph::BuildingGraph,
+    routing::ShortestPaths,
+};
+
+/// Relative difference between the distance of an edge and the distance of its coordinates above
+/// which the edge is reported.
+pub const DISTANCE_TOLERANCE: f64 = 0.25;
+/// Smallest difference, in distance units, between the distance of an edge and the distance of
+/// its coordinates that is reported, so that short edges are not reported for rounding.
+pub const MIN_DISTANCE_DIFFERENCE: u32 = 20;
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum Severity {
+    /// Data that breaks routes or their evaluation.
+    Error,
+    /// Data that is likely a mistake but still works.
+    Warning,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Issue {
+    /// A line of `paths.txt`, numbered from 1, that is not `node node distance type`.
+    MalformedLine(usize, String),
+    /// A component of `paths.txt` that cannot be reached from the ground node, or from the
+    /// largest component if there is no ground node.
+    Disconnected(Vec<String>),
+    /// A room with coordinates that has no edge in `paths.txt`.
+    IsolatedRoom(String),
+    /// A node of `paths.txt` without coordinates.
+    MissingCoordinates(String),
+    /// An edge from a node to itself.
+    Loop(String),
+    /// An edge listed more than once in `paths.txt` with the same distance.
+    DuplicateEdge(String, String),
+    /// An edge listed more than once in `paths.txt` with different distances.
+    AsymmetricDistance(String, String, u32, u32),
+    /// An edge whose distance is far from the distance of its coordinates, with both distances.
+    DistanceOutlier(String, String, u32, u32),
+    /// An edge of `paths.yaml` missing from `paths.txt`.
+    MissingFromText(String, String),
+    /// An edge of `paths.txt` other than an entrance that is missing from `paths.yaml`.
+    MissingFromYaml(String, String),
+    /// An edge with another distance or type in `paths.yaml` than in `paths.txt`.
+    EdgeMismatch(String, String),
+    /// A node whose coordinates differ between `projection-coords.yaml` and
+    /// `projection-coords-flatten.yaml`, or that is missing from one of them.
+    CoordinatesMismatch(String),
+    /// A pair of connected rooms without a precomputed path.
+    MissingPath(String, String),
+    /// A precomputed path that does not follow the edges of `paths.txt` between its rooms.
+    InvalidPath(String, String),
+    /// A precomputed path longer than the shortest path, with both distances.
+    StalePath(String, String, u64, u64),
+    /// A precomputed path whose key is not a pair of rooms of the graph.
+    ObsoletePath(String),
+}
+
+impl Issue {
+    pub fn severity(&self) -> Severity {
+        match self {
+            | Issue::DuplicateEdge(..)
+            | Issue::DistanceOutlier(..)
+            | Issue::MissingFromYaml(..)
+            | Issue::CoordinatesMismatch(..)
+            | Issue::ObsoletePath(..) => Severity::Warning,
+            _ => Severity::Error,
+        }
+    }
+
+    /// Heading the issue is listed under.
+    pub fn category(&self) -> &'static str {
+        match self {
+            Issue::MalformedLine(..) | Issue::Loop(..) | Issue::DuplicateEdge(..) => "Edges",
+            Issue::AsymmetricDistance(..) | Issue::DistanceOutlier(..) => "Distances",
+            | Issue::Disconnected(..)
+            | Issue::IsolatedRoom(..)
+            | Issue::MissingCoordinates(..) => "Connectivity",
+            | Issue::MissingFromText(..)
+            | Issue::MissingFromYaml(..)
+            | Issue::EdgeMismatch(..)
+            | Issue::CoordinatesMismatch(..) => "Source files",
+            | Issue::MissingPath(..)
+            | Issue::InvalidPath(..)
+            | Issue::StalePath(..)
+            | Issue::ObsoletePath(..) => "Precomputed paths",
+        }
+    }
+}
+
+impl Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Issue::MalformedLine(line, content) => {
+                write!(f, "Line {} of paths.txt is not an edge: \"{}\"", line, content)
+            }
+            Issue::Disconnected(nodes) => {
+                write!(f, "Cannot be reached from the rest of the building: {}", nodes.join(", "))
+            }
+            Issue::IsolatedRoom(room) => write!(f, "{} has coordinates but no edges", room),
+            Issue::MissingCoordinates(node) => write!(f, "{} has edges but no coordinates", node),
+            Issue::Loop(node) => write!(f, "{} is joined to itself", node),
+            Issue::DuplicateEdge(node1, node2) => {
+                write!(f, "{} – {} is listed more than once", node1, node2)
+            }
+            Issue::AsymmetricDistance(node1, node2, distance1, distance2) => {
+                write!(f, "{} – {} is listed as {} and as {}", node1, node2, distance1, distance2)
+            }
+            Issue::DistanceOutlier(node1, node2, distance, expected) => {
+                write!(
+                    f,
+                    "{} – {} has a distance of {}, but its coordinates give {}",
+                    node1,
+                    node2,
+                    distance,
+                    expected
+                )
+            }
+            Issue::MissingFromText(node1, node2) => {
+                write!(f, "{} – {} is in paths.yaml but not in paths.txt", node1, node2)
+            }
+            Issue::MissingFromYaml(node1, node2) => {
+                write!(f, "{} – {} is in paths.txt but not in paths.yaml", node1, node2)
+            }
+            Issue::EdgeMismatch(node1, node2) => {
+                write!(f, "{} – {} differs between paths.yaml and paths.txt", node1, node2)
+            }
+            Issue::CoordinatesMismatch(node) => {
+                write!(
+                    f,
+                    "{} differs between projection-coords.yaml and projection-coords-flatten.yaml",
+                    node
+                )
+            }
+            Issue::MissingPath(from, to) => {
+                write!(f, "No precomputed path from {} to {}", from, to)
+            }
+            Issue::InvalidPath(from, to) => {
+                write!(f, "The precomputed path from {} to {} does not follow the edges", from, to)
+            }
+            Issue::StalePath(from, to, distance, shortest) => {
+                write!(
+                    f,
+                    "The precomputed path from {} to {} has a distance of {}, but the shortest is {}",
+                    from,
+                    to,
+                    distance,
+                    shortest
+                )
+            }
+            Issue::ObsoletePath(key) => {
+                write!(f, "Precomputed path {} joins no pair of rooms", key)
+            }
+        }
+    }
+}
+
+/// The building data to check.
+pub struct BuildingData {
+    /// Contents of `projection-coords-flatten.yaml`.
+    pub coords: HashMap<String, [i32; 3]>,
+    /// Contents of `paths.txt`.
+    pub paths_txt: String,
+    pub shortest_paths: Option<ShortestPaths>,
+    /// The model of `projection-coords.yaml` and `paths.yaml`, if they are available.
+    pub model: Option<BuildingModel>,
+}
+
+impl BuildingData {
+    /// Reads the app's assets from `assets`, and the model from `model_directory` if it has one.
+    /// A missing `shortest_paths.json` or model is not an error; their checks are left out.
+    pub fn load(assets: &Path, model_directory: Option<&Path>) -> Result<Self, BuildingModelError> {
+        let read = |file: &str| fs::read_to_string(assets.join(file));
+        let coords = serde_yaml
+            ::from_str(&read("projection-coords-flatten.yaml").map_err(BuildingModelError::Io)?)
+            .map_err(|error| {
+                BuildingModelError::Format(
+                    "projection-coords-flatten.yaml".to_owned(),
+                    error.to_string()
+                )
+            })?;
+        let paths_txt = read("paths.txt").map_err(BuildingModelError::Io)?;
+        let shortest_paths = match read("shortest_paths.json") {
+            Ok(content) =>
+                Some(
+                    serde_json::from_str(&content).map_err(|error| {
+                        BuildingModelError::Format(
+                            "shortest_paths.json".to_owned(),
+                            error.to_string()
+                        )
+                    })?
+                ),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => None,
+            Err(error) => {
+                return Err(BuildingModelError::Io(error));
+            }
+        };
+        let model = match model_directory.map(BuildingModel::load) {
+            Some(Ok(model)) => Some(model),
+            Some(Err(BuildingModelError::Io(error))) if error.kind() == io::ErrorKind::NotFound => {
+                None
+            }
+            Some(Err(error)) => {
+                return Err(error);
+            }
+            None => None,
+        };
+        Ok(Self { coords, paths_txt, shortest_paths, model })
+    }
+}
+
+/// Whether a node is a room rather than a crossing, a staircase or the ground.
+fn is_room_name(name: &str) -> bool {
+    name != "G" && !name.starts_with('X') && !name.starts_with('S')
+}
+
+/// The two nodes of an edge in a fixed order.
+fn edge_key(node1: &str, node2: &str) -> (String, String) {
+    if node1 <= node2 {
+        (node1.to_owned(), node2.to_owned())
+    } else {
+        (node2.to_owned(), node1.to_owned())
+    }
+}
+
+/// Every issue of the building data, grouped by category.
+pub fn check_building(data: &BuildingData) -> Vec<Issue> {
+    let mut issues = vec![];
+
+    // edges of paths.txt by their nodes, with every distance and type they are listed with
+    let mut edges: BTreeMap<(String, String), Vec<(u32, u8)>> = BTreeMap::new();
+    for (index, line) in data.paths_txt.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let parsed = match fields[..] {
+            [node1, node2, distance, edge_type] => {
+                distance
+                    .parse::<u32>()
+                    .ok()
+                    .zip(edge_type.parse::<u8>().ok())
+                    .map(|edge| (node1, node2, edge))
+            }
+            _ => None,
+        };
+        let Some((node1, node2, edge)) = parsed else {
+            issues.push(Issue::MalformedLine(index + 1, line.to_owned()));
+            continue;
+        };
+        if node1 == node2 {
+            issues.push(Issue::Loop(node1.to_owned()));
+            continue;
+        }
+        edges.entry(edge_key(node1, node2)).or_default().push(edge);
+    }
+    for ((node1, node2), listed) in &edges {
+        let distances: BTreeSet<u32> = listed
+            .iter()
+            .map(|(distance, _)| *distance)
+            .collect();
+        if distances.len() > 1 {
+            let mut distances = distances.into_iter();
+            issues.push(
+                Issue::AsymmetricDistance(
+                    node1.clone(),
+                    node2.clone(),
+                    distances.next().unwrap(),
+                    distances.next().unwrap()
+                )
+            );
+        } else if listed.len() > 1 {
+            issues.push(Issue::DuplicateEdge(node1.clone(), node2.clone()));
+        }
+    }
+
+    let graph = BuildingGraph::parse(&data.paths_txt);
+    check_connectivity(data, &graph, &mut issues);
+
+    for ((node1, node2), listed) in &edges {
+        let (Some(coords1), Some(coords2)) = (data.coords.get(node1), data.coords.get(node2)) else {
+            continue;
+        };
+        let (distance, edge_type) = listed[0];
+        let expected = coordinate_distance(coords1, coords2, edge_type);
+        let difference = distance.abs_diff(expected);
+        if
+            difference >= MIN_DISTANCE_DIFFERENCE &&
+            (difference as f64) > DISTANCE_TOLERANCE * (expected as f64)
+        {
+            issues.push(Issue::DistanceOutlier(node1.clone(), node2.clone(), distance, expected));
+        }
+    }
+
+    if let Some(model) = &data.model {
+        check_sources(data, model, &edges, &mut issues);
+    }
+    if let Some(shortest_paths) = &data.shortest_paths {
+        check_shortest_paths(data, &graph, shortest_paths, &mut issues);
+    }
+
+    issues.sort_by_key(|issue| issue.category());
+    issues
+}
+
+fn check_connectivity(data: &BuildingData, graph: &BuildingGraph, issues: &mut Vec<Issue>) {
+    let mut component_of = vec![usize::MAX; graph.node_count()];
+    let mut components: Vec<Vec<usize>> = vec![];
+    for start in 0..graph.node_count() {
+        if component_of[start] != usize::MAX {
+            continue;
+        }
+        let mut component = vec![start];
+        component_of[start] = components.len();
+        let mut index = 0;
+        while index < component.len() {
+            for edge in graph.edges(component[index]) {
+                if component_of[edge.to] == usize::MAX {
+                    component_of[edge.to] = components.len();
+                    component.push(edge.to);
+                }
+            }
+            index += 1;
+        }
+        components.push(component);
+    }
+    let main = match graph.node_id("G") {
+        Some(ground) => Some(component_of[ground]),
+        None => (0..components.len()).max_by_key(|&component| components[component].len()),
+    };
+    for (index, component) in components.iter().enumerate() {
+        if Some(index) != main {
+            let mut nodes: Vec<String> = component
+                .iter()
+                .map(|&node| graph.node_name(node).to_owned())
+                .collect();
+            nodes.sort();
+            issues.push(Issue::Disconnected(nodes));
+        }
+    }
+
+    let mut rooms: Vec<&String> = data.coords
+        .keys()
+        .filter(|name| is_room_name(name) && graph.node_id(name).is_none())
+        .collect();
+    rooms.sort();
+    issues.extend(rooms.into_iter().map(|room| Issue::IsolatedRoom(room.clone())));
+    let mut missing: Vec<&str> = (0..graph.node_count())
+        .map(|node| graph.node_name(node))
+        .filter(|name| *name != "G" && !data.coords.contains_key(*name))
+        .collect();
+    missing.sort();
+    issues.extend(missing.into_iter().map(|node| Issue::MissingCoordinates(node.to_owned())));
+}
+
+/// Compares `paths.txt` and the flattened coordinates with the files they are generated from.
+fn check_sources(
+    data: &BuildingData,
+    model: &BuildingModel,
+    edges: &BTreeMap<(String, String), Vec<(u32, u8)>>,
+    issues: &mut Vec<Issue>
+) {
+    let mut model_edges: BTreeMap<(String, String), (u32, u8)> = BTreeMap::new();
+    for edge in model.edges() {
+        model_edges.insert(
+            edge_key(&edge.nodes[0], &edge.nodes[1]),
+            (edge.distance, edge.edge_type)
+        );
+    }
+    for ((node1, node2), edge) in &model_edges {
+        match edges.get(&(node1.clone(), node2.clone())) {
+            None => issues.push(Issue::MissingFromText(node1.clone(), node2.clone())),
+            Some(listed) if !listed.contains(edge) => {
+                issues.push(Issue::EdgeMismatch(node1.clone(), node2.clone()));
+            }
+            Some(_) => {}
+        }
+    }
+    for (node1, node2) in edges.keys() {
+        let is_entrance = node1 == "G" || node2 == "G";
+        if !is_entrance && !model_edges.contains_key(&(node1.clone(), node2.clone())) {
+            issues.push(Issue::MissingFromYaml(node1.clone(), node2.clone()));
+        }
+    }
+
+    let model_coords = model.flattened_coords();
+    let names: BTreeSet<&String> = model_coords.keys().chain(data.coords.keys()).collect();
+    for name in names {
+        if model_coords.get(name) != data.coords.get(name) {
+            issues.push(Issue::CoordinatesMismatch(name.clone()));
+        }
+    }
+}
+
+/// Checks the precomputed paths against the shortest paths of the graph. The keys of
+/// `shortest_paths.json` are the names of both ends written together.
+fn check_shortest_paths(
+    data: &BuildingData,
+    graph: &BuildingGraph,
+    shortest_paths: &ShortestPaths,
+    issues: &mut Vec<Issue>
+) {
+    let mut ends: Vec<usize> = (0..graph.node_count())
+        .filter(|&node| {
+            let name = graph.node_name(node);
+            graph.is_ground(node) || (is_room_name(name) && data.coords.contains_key(name))
+        })
+        .collect();
+    ends.sort_by_key(|&node| graph.node_name(node));
+    let mut keys: HashSet<String> = HashSet::new();
+    for &from in &ends {
+        let costs = graph.cheapest_costs(from, |_, edge| edge.distance as f64);
+        let from_name = graph.node_name(from);
+        for &to in &ends {
+            if to == from || !costs[to].is_finite() {
+                continue;
+            }
+            let to_name = graph.node_name(to);
+            let key = format!("{}{}", from_name, to_name);
+            let Some(path) = shortest_paths.get(&key) else {
+                issues.push(Issue::MissingPath(from_name.to_owned(), to_name.to_owned()));
+                continue;
+            };
+            keys.insert(key);
+            let nodes: Option<Vec<usize>> = path
+                .split_whitespace()
+                .map(|name| graph.node_id(name))
+                .collect();
+            let distance = nodes
+                .filter(|nodes| nodes.first() == Some(&from) && nodes.last() == Some(&to))
+                .and_then(|nodes| {
+                    nodes
+                        .windows(2)
+                        .map(|pair| graph.edge_between(pair[0], pair[1]))
+                        .map(|edge| edge.map(|edge| edge.distance as u64))
+                        .sum::<Option<u64>>()
+                });
+            match distance {
+                None => issues.push(Issue::InvalidPath(from_name.to_owned(), to_name.to_owned())),
+                Some(distance) if distance > (costs[to] as u64) => {
+                    issues.push(
+                        Issue::StalePath(
+                            from_name.to_owned(),
+                            to_name.to_owned(),
+                            distance,
+                            costs[to] as u64
+                        )
+                    );
+                }
+                Some(_) => {}
+            }
+        }
+    }
+    let mut obsolete: Vec<&String> = shortest_paths
+        .iter()
+        .filter(|(key, path)| !path.trim().is_empty() && !keys.contains(*key))
+        .map(|(key, _)| key)
+        .collect();
+    obsolete.sort();
+    issues.extend(obsolete.into_iter().map(|key| Issue::ObsoletePath(key.clone())));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unreachable_rooms_are_reported() {
+        let data = BuildingData {
+            coords: ["A101", "A1", "A102", "B101", "B1"]
+                .into_iter()
+                .enumerate()
+                .map(|(index, node)| (node.to_owned(), [(index as i32) * 50, 0, 0]))
+                .collect(),
+            paths_txt: "A101 A1 50 0\nA1 G 999999 2\nB101 B1 50 0\n".to_owned(),
+            shortest_paths: None,
+            model: None,
+        };
+        let connectivity: Vec<Issue> = check_building(&data)
+            .into_iter()
+            .filter(|issue| issue.category() == "Connectivity")
+            .collect();
+        assert_eq!(connectivity, [
+            Issue::Disconnected(vec!["B1".to_owned(), "B101".to_owned()]),
+            Issue::IsolatedRoom("A102".to_owned()),
+        ]);
+        assert_eq!(connectivity[0].severity(), Severity::Error);
+        assert_eq!(
+            connectivity[0].to_string(),
+            "Cannot be reached from the rest of the building: B1, B101"
+        );
+    }
+}
@@ -0,0 +1,486 @@
+//! Where students go before school, at lunch and after school.
+//!
+//! Without destinations, students arrive from, leave for lunch to and go home to the abstract
+//! ground node `G`, whose edges count towards neither distance nor congestion. Destinations are
+//! places such as the canteen, the library or the sports field. A destination is either a node of
+//! the building graph, or a new node joined to nodes of the building by walks of the given
+//! distances, drawn at the given coordinates of `projection-coords.yaml`. Cohorts of students go
+//! to the destinations with the given probabilities, and `G` stays a destination of its own:
+//!
+//! ```yaml
+//! seed: 1
+//! destinations:
+//!   - name: Canteen
+//!     coords: [-150, 900, 0]
+//!     links: { S2-1: 300, S2-4: 420 }
+//!   - name: B222 # the library, a room of the building
+//! cohorts:
+//!   - name: AS & A Level
+//!     students: ["20*", "21*"]
+//!     lunch: { Canteen: 0.6, B222: 0.3, G: 0.1 }
+//!     dismissal: { G: 0.8, Canteen: 0.2 }
+//!   - name: Everyone else
+//!     lunch: { Canteen: 1 }
+//! ```
+//!
+//! A cohort selects students by student number patterns as in pinning rules, and every student
+//! belongs to the first cohort matching them. `arrival` is where students come from before P1,
+//! `lunch` where they go after P6 and come back from before P7, and `dismissal` where they go
+//! after P10. A left-out outing is `G`. The probabilities are weights, scaled to add up to 1.
+//!
+//! Every day, the students of a cohort are shuffled by the seed and shared out to the
+//! destinations in proportion to the probabilities, so the number of students going to each
+//! destination is exact while who goes where changes from day to day.
+
+use std::{
+    collections::{ BTreeMap, HashMap },
+    fmt::{ self, Display, Write as _ },
+    fs,
+    io,
+    path::Path,
+};
+
+use crate::{
+    directions::{ node_kind, NodeKind },
+    graph::BuildingGraph,
+    optimization::random::Random,
+    pinning::matches_pattern,
+    routing::{
+        shortest_path,
+        transition_periods,
+        transition_rooms,
+        Routes,
+        ShortestPaths,
+        TRANSITION_COUNT,
+    },
+    timetable::{ Day, DayTimetable, Timetable, DAY_COUNT },
+};
+
+/// The abstract ground node, which is always a destination.
+const GROUND: &str = "G";
+/// Edge type of the walks to new destinations, as normal paths of `paths.txt`.
+const LINK_EDGE_TYPE: u8 = 0;
+
+#[derive(Clone, PartialEq, Debug, serde::Deserialize, serde::Serialize)]
+pub struct Destination {
+    pub name: String,
+    /// Coordinates of a new node. Left out for a node of the building graph.
+    #[serde(default)]
+    pub coords: Option<[i32; 3]>,
+    /// Node of the building graph → distance of the walk between it and a new node.
+    #[serde(default)]
+    pub links: BTreeMap<String, u32>,
+}
+
+impl Destination {
+    /// Whether the destination is a new node rather than a node of the building graph.
+    pub fn is_new(&self) -> bool {
+        !self.links.is_empty()
+    }
+}
+
+#[derive(Clone, PartialEq, Debug, serde::Deserialize, serde::Serialize)]
+pub struct Cohort {
+    pub name: String,
+    /// Student number patterns. Empty matches every student.
+    #[serde(default)]
+    pub students: Vec<String>,
+    /// Destination → probability, for each outing.
+    #[serde(default)]
+    pub arrival: BTreeMap<String, f64>,
+    #[serde(default)]
+    pub lunch: BTreeMap<String, f64>,
+    #[serde(default)]
+    pub dismissal: BTreeMap<String, f64>,
+}
+
+impl Cohort {
+    fn probabilities(&self, outing: Outing) -> &BTreeMap<String, f64> {
+        match outing {
+            Outing::Arrival => &self.arrival,
+            Outing::Lunch => &self.lunch,
+            Outing::Dismissal => &self.dismissal,
+        }
+    }
+
+    fn matches(&self, student_number: &str) -> bool {
+        self.students.is_empty() ||
+            self.students.iter().any(|pattern| matches_pattern(pattern, student_number))
+    }
+}
+
+/// Leaving or entering the building around the school day.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Outing {
+    Arrival,
+    Lunch,
+    Dismissal,
+}
+
+impl Outing {
+    pub const ALL: [Outing; 3] = [Outing::Arrival, Outing::Lunch, Outing::Dismissal];
+
+    /// The outing a transition enters or leaves the building in, if it does.
+    pub fn of_transition(transition: usize) -> Option<Outing> {
+        match transition_periods(transition) {
+            (None, Some(1)) => Some(Outing::Arrival),
+            (Some(6), None) | (None, Some(7)) => Some(Outing::Lunch),
+            (Some(_), None) => Some(Outing::Dismissal),
+            _ => None,
+        }
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+impl Display for Outing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Outing::Arrival => write!(f, "Arrival"),
+            Outing::Lunch => write!(f, "Lunch"),
+            Outing::Dismissal => write!(f, "Dismissal"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum DestinationError {
+    Io(io::Error),
+    Format(serde_yaml::Error),
+    /// A destination name that is empty, has spaces, is `G` or is given twice.
+    InvalidName(String),
+    /// A destination that is neither a node of the building graph nor joined to one.
+    UnknownNode(String),
+    /// A new destination with the name of a node of the building graph.
+    ExistingNode(String),
+    /// A new destination without coordinates.
+    MissingCoordinates(String),
+    /// A link of a destination to a node missing from the building graph.
+    UnknownLink(String, String),
+    /// A destination of a cohort that is not declared, with the name of the cohort.
+    UnknownDestination(String, String),
+    /// Probabilities of a cohort that are negative or add up to 0, with the outing.
+    InvalidProbabilities(String, Outing),
+}
+
+impl Display for DestinationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DestinationError::Io(error) => write!(f, "Failed to read destinations: {}", error),
+            DestinationError::Format(error) => write!(f, "Invalid destinations: {}", error),
+            DestinationError::InvalidName(name) => {
+                write!(f, "\"{}\" cannot be the name of a destination.", name)
+            }
+            DestinationError::UnknownNode(name) => {
+                write!(f, "Destination \"{}\" is not in the building graph and has no links.", name)
+            }
+            DestinationError::ExistingNode(name) => {
+                write!(f, "Destination \"{}\" is already a node and cannot have links.", name)
+            }
+            DestinationError::MissingCoordinates(name) => {
+                write!(f, "Destination \"{}\" has no coordinates.", name)
+            }
+            DestinationError::UnknownLink(name, node) => {
+                write!(f, "Destination \"{}\" is linked to {}, which is not a node.", name, node)
+            }
+            DestinationError::UnknownDestination(cohort, name) => {
+                write!(f, "Cohort \"{}\" goes to \"{}\", which is not a destination.", cohort, name)
+            }
+            DestinationError::InvalidProbabilities(cohort, outing) => {
+                write!(
+                    f,
+                    "The {} probabilities of cohort \"{}\" must be positive.",
+                    outing.to_string().to_lowercase(),
+                    cohort
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for DestinationError {}
+
+/// Destinations and cohorts of a YAML file.
+#[derive(Clone, PartialEq, Debug, Default, serde::Deserialize, serde::Serialize)]
+pub struct DestinationModel {
+    /// Seed of the shuffles that share students out to the destinations.
+    #[serde(default)]
+    pub seed: u64,
+    #[serde(default)]
+    pub destinations: Vec<Destination>,
+    #[serde(default)]
+    pub cohorts: Vec<Cohort>,
+}
+
+impl DestinationModel {
+    /// Reads the destinations of a YAML file and checks them against the building graph of
+    /// `paths.txt`, without the destinations.
+    pub fn load(path: &Path, graph: &BuildingGraph) -> Result<Self, DestinationError> {
+        let content = fs::read_to_string(path).map_err(DestinationError::Io)?;
+        let model: Self = serde_yaml::from_str(&content).map_err(DestinationError::Format)?;
+        model.check(graph)?;
+        Ok(model)
+    }
+
+    pub fn check(&self, graph: &BuildingGraph) -> Result<(), DestinationError> {
+        let mut names = vec![GROUND];
+        for destination in &self.destinations {
+            let name = destination.name.as_str();
+            if name.is_empty() || name.contains(char::is_whitespace) || names.contains(&name) {
+                return Err(DestinationError::InvalidName(name.to_owned()));
+            }
+            names.push(name);
+            let is_node = graph.node_id(name).is_some();
+            if !destination.is_new() {
+                if !is_node {
+                    return Err(DestinationError::UnknownNode(name.to_owned()));
+                }
+                continue;
+            }
+            if is_node {
+                return Err(DestinationError::ExistingNode(name.to_owned()));
+            }
+            if destination.coords.is_none() {
+                return Err(DestinationError::MissingCoordinates(name.to_owned()));
+            }
+            let unknown_link = destination.links.keys().find(|node| graph.node_id(node).is_none());
+            if let Some(node) = unknown_link {
+                return Err(DestinationError::UnknownLink(name.to_owned(), node.clone()));
+            }
+        }
+        for cohort in &self.cohorts {
+            for outing in Outing::ALL {
+                let probabilities = cohort.probabilities(outing);
+                let unknown = probabilities.keys().find(|name| !names.contains(&name.as_str()));
+                if let Some(name) = unknown {
+                    return Err(
+                        DestinationError::UnknownDestination(cohort.name.clone(), name.clone())
+                    );
+                }
+                let is_valid =
+                    probabilities.values().all(|probability| *probability >= 0.0) &&
+                    (probabilities.is_empty() || probabilities.values().sum::<f64>() > 0.0);
+                if !is_valid {
+                    return Err(DestinationError::InvalidProbabilities(cohort.name.clone(), outing));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Lines of `paths.txt` joining the new destinations to the building.
+    pub fn paths_txt(&self) -> String {
+        let mut lines = String::new();
+        for destination in &self.destinations {
+            for (node, distance) in &destination.links {
+                writeln!(lines, "{} {} {} {}", node, destination.name, distance, LINK_EDGE_TYPE)
+                    .unwrap();
+            }
+        }
+        lines
+    }
+
+    /// Coordinates of the new destinations.
+    pub fn coords(&self) -> impl Iterator<Item = (&str, [i32; 3])> {
+        self.destinations
+            .iter()
+            .filter(|destination| destination.is_new())
+            .filter_map(|destination| Some((destination.name.as_str(), destination.coords?)))
+    }
+
+    /// Shortest routes between every room of `graph` and every new destination, in both
+    /// directions. `graph` must include the destinations.
+    pub fn shortest_paths(&self, graph: &BuildingGraph) -> ShortestPaths {
+        let rooms: Vec<usize> = (0..graph.node_count())
+            .filter(|node| {
+                let name = graph.node_name(*node);
+                node_kind(name) == NodeKind::Room &&
+                    !self.destinations
+                        .iter()
+                        .any(|destination| destination.is_new() && destination.name == name)
+            })
+            .collect();
+        let mut shortest_paths = ShortestPaths::new();
+        for destination in self.destinations.iter().filter(|destination| destination.is_new()) {
+            let Some(end) = graph.node_id(&destination.name) else {
+                continue;
+            };
+            for &room in &rooms {
+                let path = graph.cheapest_path(room, end, |_, edge| edge.distance as f64);
+                let Some(path) = path else {
+                    continue;
+                };
+                let names: Vec<&str> = path
+                    .iter()
+                    .map(|node| graph.node_name(*node))
+                    .collect();
+                let room_name = graph.node_name(room);
+                shortest_paths.insert(
+                    format!("{}{}", room_name, destination.name),
+                    names.join(" ")
+                );
+                let reversed: Vec<&str> = names.into_iter().rev().collect();
+                shortest_paths.insert(
+                    format!("{}{}", destination.name, room_name),
+                    reversed.join(" ")
+                );
+            }
+        }
+        shortest_paths
+    }
+
+    /// Shares the students of `timetable` out to the destinations of their cohorts.
+    pub fn assign(&self, timetable: &Timetable) -> DestinationAssignment {
+        let mut names = vec![GROUND.to_owned()];
+        names.extend(self.destinations.iter().map(|destination| destination.name.clone()));
+        let name_index = |name: &str| names.iter().position(|known| known == name).unwrap_or(0);
+
+        let mut cohorts: Vec<Vec<&str>> = vec![vec![]; self.cohorts.len()];
+        for student_number in timetable.student_numbers() {
+            let cohort = self.cohorts.iter().position(|cohort| cohort.matches(student_number));
+            if let Some(cohort) = cohort {
+                cohorts[cohort].push(student_number);
+            }
+        }
+
+        let mut destinations: HashMap<String, [[usize; 3]; DAY_COUNT]> = timetable
+            .student_numbers()
+            .map(|student_number| (student_number.to_owned(), Default::default()))
+            .collect();
+        for (cohort, student_numbers) in self.cohorts.iter().zip(&cohorts) {
+            for day in Day::all() {
+                for outing in Outing::ALL {
+                    let probabilities = cohort.probabilities(outing);
+                    let total: f64 = probabilities.values().sum();
+                    if probabilities.is_empty() {
+                        continue;
+                    }
+                    let mut shuffled: Vec<(u64, &str)> = student_numbers
+                        .iter()
+                        .map(|student_number| {
+                            (self.shuffle_key(student_number, day, outing), *student_number)
+                        })
+                        .collect();
+                    shuffled.sort_unstable();
+                    // the first students in the shuffled order go to the first destination, and
+                    // so on
+                    let mut cumulative = 0.0;
+                    let mut start = 0;
+                    for (name, probability) in probabilities {
+                        cumulative += probability / total;
+                        let end = ((cumulative * (shuffled.len() as f64)).round() as usize).min(
+                            shuffled.len()
+                        );
+                        for (_, student_number) in &shuffled[start..end.max(start)] {
+                            destinations.get_mut(*student_number).unwrap()[
+                                day.number() as usize - 1
+                            ][outing.index()] = name_index(name);
+                        }
+                        start = end.max(start);
+                    }
+                }
+            }
+        }
+        DestinationAssignment { names, destinations }
+    }
+
+    fn shuffle_key(&self, student_number: &str, day: Day, outing: Outing) -> u64 {
+        let number: u64 = student_number.parse().unwrap_or(0);
+        let input = (number << 8) | ((day.number() as u64) << 4) | (outing.index() as u64);
+        Random::keyed(self.seed, input).next_u64()
+    }
+}
+
+/// The destination of every student for every outing of every day.
+#[derive(Clone, Default, Debug)]
+pub struct DestinationAssignment {
+    /// Destination names, `G` first.
+    names: Vec<String>,
+    /// Student number → day → outing → index of the destination. Students of no cohort go to
+    /// `G`, and so do students missing from the timetable.
+    destinations: HashMap<String, [[usize; 3]; DAY_COUNT]>,
+}
+
+impl DestinationAssignment {
+    pub fn destination(&self, student_number: &str, day: Day, outing: Outing) -> &str {
+        self.destinations
+            .get(student_number)
+            .and_then(|days| self.names.get(days[day.number() as usize - 1][outing.index()]))
+            .map_or(GROUND, String::as_str)
+    }
+
+    /// The rooms a student moves between in a transition, as `transition_rooms`, with the ground
+    /// node replaced by the destination of the student. A student in room `G` in the period
+    /// before or after the outing stays where they are.
+    pub fn transition_ends<'a>(
+        &'a self,
+        student_number: &str,
+        day: Day,
+        day_timetable: &'a DayTimetable,
+        transition: usize
+    ) -> Option<(&'a str, &'a str)> {
+        let Some(outing) = Outing::of_transition(transition) else {
+            return transition_rooms(student_number, day_timetable, transition);
+        };
+        // one end is the ground node, and the other a room other than `G`
+        let (from, to) = transition_rooms(student_number, day_timetable, transition)?;
+        let destination = self.destination(student_number, day, outing);
+        let ends = match transition_periods(transition) {
+            (None, _) => (destination, to),
+            _ => (from, destination),
+        };
+        Some(ends).filter(|(from, to)| from != to)
+    }
+
+    /// Replaces every route whose ends differ from the ends of its transition by the shortest
+    /// route between them. `shortest_paths` must include the destinations.
+    pub fn route(
+        &self,
+        routes: &mut Routes,
+        timetable: &Timetable,
+        shortest_paths: &ShortestPaths
+    ) {
+        for (student_number, week_timetable) in timetable.students() {
+            let Some(week_routes) = routes.get_mut(student_number) else {
+                continue;
+            };
+            for (day, day_timetable) in week_timetable.days() {
+                let day_routes = week_routes.entry(day.number()).or_default();
+                let transitions = (0..TRANSITION_COUNT).filter(|transition| {
+                    Outing::of_transition(*transition).is_some()
+                });
+                for transition in transitions {
+                    let ends = self.transition_ends(student_number, day, day_timetable, transition);
+                    let route = day_routes.entry(transition).or_default();
+                    let route_ends = route
+                        .split(' ')
+                        .next()
+                        .zip(route.split(' ').next_back())
+                        .filter(|_| !route.is_empty());
+                    if route_ends != ends {
+                        *route = ends.map_or_else(String::new, |(from, to)| {
+                            shortest_path(shortest_paths, from, to)
+                        });
+                    }
+                }
+            }
+        }
+    }
+
+    /// Number of students going to each destination for each outing of a day, as (destination,
+    /// [arrival, lunch, dismissal]), in the order of the destinations.
+    pub fn counts(&self, day: Day) -> Vec<(&str, [usize; 3])> {
+        let mut counts: Vec<(&str, [usize; 3])> = self.names
+            .iter()
+            .map(|name| (name.as_str(), [0; 3]))
+            .collect();
+        for days in self.destinations.values() {
+            for outing in Outing::ALL {
+                counts[days[day.number() as usize - 1][outing.index()]].1[outing.index()] += 1;
+            }
+        }
+        counts
+    }
+}
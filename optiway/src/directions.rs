@@ -0,0 +1,392 @@
+//! Directions along a route, in steps a student can follow.
+//!
+//! Consecutive edges of the same kind are merged into one step: a walk along the corridors of a
+//! floor, a flight of stairs (possibly over several floors), a bridge between the blocks, and
+//! entering or leaving the building through the ground node `G`. The floors of the nodes are
+//! taken from their coordinates, and the blocks from their names (see `paths.yaml`). Steps are
+//! written as instructions in English or Chinese.
+
+use std::{ collections::HashMap, fmt::{ self, Display } };
+
+use crate::graph::BuildingGraph;
+
+/// Edge type of bridges between the blocks.
+const BRIDGE_EDGE_TYPE: u8 = 1;
+/// Edge type of spiral staircases.
+const SPIRAL_EDGE_TYPE: u8 = 3;
+/// Height of a floor in projection-coords.yaml.
+const FLOOR_HEIGHT: i32 = 50;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum NodeKind {
+    Ground,
+    Room,
+    Stairs,
+    Crossing,
+}
+
+pub fn node_kind(name: &str) -> NodeKind {
+    if name == "G" {
+        NodeKind::Ground
+    } else if name.starts_with('S') {
+        NodeKind::Stairs
+    } else if name.starts_with('X') {
+        NodeKind::Crossing
+    } else {
+        NodeKind::Room
+    }
+}
+
+/// Block of a room or crossing, such as `'A'`.
+pub fn node_block(name: &str) -> Option<char> {
+    match node_kind(name) {
+        NodeKind::Room => name.chars().next(),
+        // XNKM: the M-th crossing on K block, N-th floor
+        NodeKind::Crossing => name.chars().nth(2),
+        NodeKind::Ground | NodeKind::Stairs => None,
+    }
+}
+
+/// Floor number of 3D coordinates in projection-coords.yaml, such as 5 for 5F.
+pub fn coords_floor(coords: &[i32; 3]) -> i32 {
+    coords[2] / FLOOR_HEIGHT + 2
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Step {
+    /// Entering the building at the given stair node.
+    Enter(String),
+    /// Walking along the corridors of a floor to the given node.
+    Walk {
+        to: String,
+        distance: u32,
+    },
+    /// Taking the stairs from the given stair node to another floor.
+    Stairs {
+        from: String,
+        to_floor: i32,
+        up: bool,
+        spiral: bool,
+        distance: u32,
+    },
+    /// Crossing a bridge to another block.
+    Bridge {
+        to_block: char,
+        floor: i32,
+        distance: u32,
+    },
+    /// Leaving the building at the given stair node.
+    Leave(String),
+}
+
+impl Step {
+    /// Distance walked in the step. Entering and leaving the building are not counted.
+    pub fn distance(&self) -> u32 {
+        match self {
+            Step::Walk { distance, .. } => *distance,
+            Step::Stairs { distance, .. } => *distance,
+            Step::Bridge { distance, .. } => *distance,
+            Step::Enter(_) | Step::Leave(_) => 0,
+        }
+    }
+
+    /// Merges `next` into the step if both are walks, or flights of stairs in the same direction.
+    fn merge(&mut self, next: &Step) -> bool {
+        match (self, next) {
+            (
+                Step::Walk { to, distance },
+                Step::Walk { to: next_to, distance: next_distance },
+            ) => {
+                *to = next_to.clone();
+                *distance += next_distance;
+                true
+            }
+            (
+                Step::Stairs { to_floor, up, spiral, distance, .. },
+                Step::Stairs {
+                    to_floor: next_floor,
+                    up: next_up,
+                    spiral: next_spiral,
+                    distance: next_distance,
+                    ..
+                },
+            ) if up == next_up => {
+                *to_floor = *next_floor;
+                *spiral |= next_spiral;
+                *distance += next_distance;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+impl Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.text(Language::English))
+    }
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum Language {
+    #[default]
+    English,
+    Chinese,
+}
+
+impl Language {
+    pub const ALL: [Language; 2] = [Language::English, Language::Chinese];
+
+    /// Name of the language in itself.
+    pub fn name(self) -> &'static str {
+        match self {
+            Language::English => "English",
+            Language::Chinese => "中文",
+        }
+    }
+
+    /// Joins two instructions into one sentence.
+    fn then(self, first: &str, second: &str) -> String {
+        match self {
+            Language::English => {
+                let mut chars = second.chars();
+                let second = chars
+                    .next()
+                    .map(|first| first.to_lowercase().chain(chars).collect::<String>())
+                    .unwrap_or_default();
+                format!("{}, then {}", first, second)
+            }
+            Language::Chinese => format!("{}，然后{}", first, second),
+        }
+    }
+}
+
+impl Step {
+    /// Instruction for the step.
+    pub fn text(&self, language: Language) -> String {
+        match (language, self) {
+            (Language::English, Step::Enter(stairs)) => {
+                format!("Enter the building at staircase {}", stairs)
+            }
+            (Language::English, Step::Walk { to, .. }) => {
+                match node_kind(to) {
+                    NodeKind::Stairs => format!("Walk to staircase {}", to),
+                    NodeKind::Crossing => "Walk to the bridge".to_owned(),
+                    NodeKind::Room | NodeKind::Ground => format!("Walk to {}", to),
+                }
+            }
+            (Language::English, Step::Stairs { from, to_floor, up, spiral, .. }) => {
+                format!(
+                    "Take {}staircase {} {} to {}F",
+                    if *spiral { "spiral " } else { "" },
+                    from,
+                    if *up { "up" } else { "down" },
+                    to_floor
+                )
+            }
+            (Language::English, Step::Bridge { to_block, floor, .. }) => {
+                format!("Cross the bridge to {} block on {}F", to_block, floor)
+            }
+            (Language::English, Step::Leave(stairs)) => {
+                format!("Leave the building at staircase {}", stairs)
+            }
+            (Language::Chinese, Step::Enter(stairs)) => format!("从 {} 楼梯进入教学楼", stairs),
+            (Language::Chinese, Step::Walk { to, .. }) => {
+                match node_kind(to) {
+                    NodeKind::Stairs => format!("步行至 {} 楼梯", to),
+                    NodeKind::Crossing => "步行至连廊".to_owned(),
+                    NodeKind::Room | NodeKind::Ground => format!("步行至 {}", to),
+                }
+            }
+            (Language::Chinese, Step::Stairs { from, to_floor, up, spiral, .. }) => {
+                format!(
+                    "经 {} {}楼梯{}至 {}F",
+                    from,
+                    if *spiral { "旋转" } else { "" },
+                    if *up { "上" } else { "下" },
+                    to_floor
+                )
+            }
+            (Language::Chinese, Step::Bridge { to_block, floor, .. }) => {
+                format!("在 {}F 经连廊前往 {} 座", floor, to_block)
+            }
+            (Language::Chinese, Step::Leave(stairs)) => format!("从 {} 楼梯离开教学楼", stairs),
+        }
+    }
+
+    /// Instruction for the step and the next step it leads to.
+    fn joined_text(&self, next: &Step, language: Language) -> String {
+        match (language, self, next) {
+            (
+                Language::English,
+                Step::Walk { to, .. },
+                Step::Stairs { to_floor, up, spiral, .. },
+            ) => {
+                format!(
+                    "Walk to {}staircase {} and go {} to {}F",
+                    if *spiral { "spiral " } else { "" },
+                    to,
+                    if *up { "up" } else { "down" },
+                    to_floor
+                )
+            }
+            (Language::English, Step::Walk { .. }, Step::Bridge { to_block, .. }) => {
+                format!("Walk to the bridge and cross to {} block", to_block)
+            }
+            (
+                Language::Chinese,
+                Step::Walk { to, .. },
+                Step::Stairs { to_floor, up, spiral, .. },
+            ) => {
+                format!(
+                    "步行至 {} {}楼梯，{}至 {}F",
+                    to,
+                    if *spiral { "旋转" } else { "" },
+                    if *up { "上" } else { "下" },
+                    to_floor
+                )
+            }
+            (Language::Chinese, Step::Walk { .. }, Step::Bridge { to_block, .. }) => {
+                format!("步行至连廊，过连廊前往 {} 座", to_block)
+            }
+            _ => language.then(&self.text(language), &next.text(language)),
+        }
+    }
+
+    /// Whether the step only leads to the stairs or the bridge taken in the next step.
+    fn leads_to_next(&self, next: &Step) -> bool {
+        match (self, next) {
+            (Step::Walk { to, .. }, Step::Stairs { from, .. }) => to == from,
+            (Step::Walk { to, .. }, Step::Bridge { .. }) => node_kind(to) == NodeKind::Crossing,
+            (Step::Bridge { .. }, Step::Stairs { .. }) => true,
+            _ => false,
+        }
+    }
+}
+
+/// One sentence of directions, with the distance walked in it.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Instruction {
+    pub text: String,
+    pub distance: u32,
+}
+
+/// Directions along `steps`. A step that only leads to the stairs or the bridge of the next step
+/// is joined with it into one sentence, such as "Cross the bridge to B block on 4F, then take
+/// spiral staircase S4-2 up to 5F".
+pub fn instructions(steps: &[Step], language: Language) -> Vec<Instruction> {
+    let mut instructions = vec![];
+    let mut index = 0;
+    while index < steps.len() {
+        let step = &steps[index];
+        let mut instruction = Instruction {
+            text: step.text(language),
+            distance: step.distance(),
+        };
+        if let Some(next) = steps.get(index + 1).filter(|next| step.leads_to_next(next)) {
+            instruction.text = step.joined_text(next, language);
+            instruction.distance += next.distance();
+            index += 1;
+        }
+        instructions.push(instruction);
+        index += 1;
+    }
+    instructions
+}
+
+/// Steps of a route given by graph node ids. Edges missing from the graph count as corridors of
+/// distance 0.
+pub fn route_steps(
+    graph: &BuildingGraph,
+    coords: &HashMap<String, [i32; 3]>,
+    route: &[usize]
+) -> Vec<Step> {
+    let floor = |node: usize| coords.get(graph.node_name(node)).map(coords_floor);
+    let mut steps: Vec<Step> = vec![];
+    for pair in route.windows(2) {
+        let (from, to) = (pair[0], pair[1]);
+        if graph.is_ground(from) {
+            steps.push(Step::Enter(graph.node_name(to).to_owned()));
+            continue;
+        }
+        if graph.is_ground(to) {
+            steps.push(Step::Leave(graph.node_name(from).to_owned()));
+            continue;
+        }
+        let edge = graph.edge_between(from, to);
+        let distance = edge.map_or(0, |edge| edge.distance);
+        let edge_type = edge.map_or(0, |edge| edge.edge_type);
+        let step = match (floor(from), floor(to)) {
+            (Some(from_floor), Some(to_floor)) if from_floor != to_floor => {
+                Step::Stairs {
+                    from: graph.node_name(from).to_owned(),
+                    to_floor,
+                    up: to_floor > from_floor,
+                    spiral: edge_type == SPIRAL_EDGE_TYPE,
+                    distance,
+                }
+            }
+            _ if edge_type == BRIDGE_EDGE_TYPE => {
+                Step::Bridge {
+                    to_block: node_block(graph.node_name(to)).unwrap_or('?'),
+                    floor: floor(to).unwrap_or_default(),
+                    distance,
+                }
+            }
+            _ => Step::Walk { to: graph.node_name(to).to_owned(), distance },
+        };
+        if !steps.last_mut().is_some_and(|last| last.merge(&step)) {
+            steps.push(step);
+        }
+    }
+    steps
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Rooms `A201` and `A401` next to a staircase from 2F to 4F, whose upper flight is a spiral
+    /// staircase.
+    const PATHS: &str = "\
+A201 S1-2 30 0
+S1-2 S1-3 60 2
+S1-3 S1-4 60 3
+S1-4 A401 40 0
+";
+
+    fn directions(route: &str, language: Language) -> Vec<Instruction> {
+        let graph = BuildingGraph::parse(PATHS);
+        let coords = HashMap::from(
+            [
+                ("A201", 2),
+                ("S1-2", 2),
+                ("S1-3", 3),
+                ("S1-4", 4),
+                ("A401", 4),
+            ].map(|(node, floor)| (node.to_owned(), [0, 0, (floor - 2) * FLOOR_HEIGHT]))
+        );
+        let steps = route_steps(&graph, &coords, &graph.route_ids(route).unwrap());
+        instructions(&steps, language)
+    }
+
+    fn instruction(text: &str, distance: u32) -> Instruction {
+        Instruction { text: text.to_owned(), distance }
+    }
+
+    #[test]
+    fn flights_of_stairs_are_one_instruction() {
+        assert_eq!(directions("A201 S1-2 S1-3 S1-4 A401", Language::English), [
+            instruction("Walk to spiral staircase S1-2 and go up to 4F", 150),
+            instruction("Walk to A401", 40),
+        ]);
+        assert_eq!(directions("A401 S1-4 S1-3 S1-2 A201", Language::English), [
+            instruction("Walk to spiral staircase S1-4 and go down to 2F", 160),
+            instruction("Walk to A201", 30),
+        ]);
+        assert_eq!(directions("A201 S1-2 S1-3 S1-4 A401", Language::Chinese), [
+            instruction("步行至 S1-2 旋转楼梯，上至 4F", 150),
+            instruction("步行至 A401", 40),
+        ]);
+    }
+}
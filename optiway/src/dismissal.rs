@@ -0,0 +1,400 @@
+//! Staggered dismissal: time-resolved congestion when groups of students are released at
+//! different times.
+//!
+//! Every student starts walking at the release offset of their group and walks their route at a
+//! constant speed. Time is divided into slots, and the load of an edge in a slot is the number of
+//! students on the edge at any moment of the slot. A student's performance index on an edge uses
+//! the highest load of the slots they spend on it, so the index reduces to the one in
+//! `TECHNICAL_NOTES.md` when the slots are long enough to cover the whole transition.
+
+use std::collections::{ BTreeMap, HashMap };
+
+use crate::{
+    evaluation::{
+        edge_key,
+        edge_performance,
+        known_path_distance,
+        route_legs,
+        MissingEdge,
+        PathDistances,
+    },
+    routing::Routes,
+};
+
+#[derive(Default, Clone, Copy, PartialEq, Eq)]
+pub enum OffsetGroup {
+    /// Students grouped by the first two digits of their student number.
+    Cohort,
+    /// Students grouped by the teaching block of the room they leave.
+    Block,
+    /// Students grouped by the floor of the room they leave.
+    #[default]
+    Floor,
+}
+
+impl std::fmt::Display for OffsetGroup {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OffsetGroup::Cohort => write!(f, "Cohort"),
+            OffsetGroup::Block => write!(f, "Block"),
+            OffsetGroup::Floor => write!(f, "Floor"),
+        }
+    }
+}
+
+/// Release offsets in minutes; negative offsets release students earlier. The offset of a
+/// student is the sum of the offsets of their cohort, block and floor.
+#[derive(Clone, PartialEq)]
+pub struct DismissalOffsets {
+    pub cohort: BTreeMap<u32, f32>,
+    pub block: BTreeMap<char, f32>,
+    pub floor: BTreeMap<u32, f32>,
+}
+
+impl Default for DismissalOffsets {
+    fn default() -> Self {
+        Self {
+            cohort: BTreeMap::new(),
+            block: BTreeMap::from([
+                ('A', 0.0),
+                ('B', 0.0),
+            ]),
+            floor: (2..=8).map(|floor| (floor, 0.0)).collect(),
+        }
+    }
+}
+
+/// Cohort of a student, e.g. `21` for `21447`.
+pub fn student_cohort(student_number: &str) -> u32 {
+    student_number.get(..2).and_then(|prefix| prefix.parse().ok()).unwrap_or(0)
+}
+
+/// Teaching block and floor of a room such as `B429`.
+pub fn room_block_floor(room: &str) -> Option<(char, u32)> {
+    let mut chars = room.chars();
+    let block = chars.next().filter(|block| *block == 'A' || *block == 'B')?;
+    let floor = chars.next()?.to_digit(10)?;
+    Some((block, floor))
+}
+
+impl DismissalOffsets {
+    /// Adds a zero offset for every cohort in `student_numbers` that has none yet.
+    pub fn add_cohorts<'a>(&mut self, student_numbers: impl Iterator<Item = &'a String>) {
+        for student_number in student_numbers {
+            self.cohort.entry(student_cohort(student_number)).or_insert(0.0);
+        }
+    }
+
+    pub fn offset(&self, student_number: &str, origin: &str) -> f32 {
+        let mut offset = self.cohort
+            .get(&student_cohort(student_number))
+            .copied()
+            .unwrap_or(0.0);
+        if let Some((block, floor)) = room_block_floor(origin) {
+            offset += self.block.get(&block).copied().unwrap_or(0.0);
+            offset += self.floor.get(&floor).copied().unwrap_or(0.0);
+        }
+        offset
+    }
+
+    pub fn reset(&mut self) {
+        self.cohort.values_mut().for_each(|offset| {
+            *offset = 0.0;
+        });
+        self.block.values_mut().for_each(|offset| {
+            *offset = 0.0;
+        });
+        self.floor.values_mut().for_each(|offset| {
+            *offset = 0.0;
+        });
+    }
+
+    fn group_offsets(&mut self, group: OffsetGroup) -> Vec<&mut f32> {
+        match group {
+            OffsetGroup::Cohort => self.cohort.values_mut().collect(),
+            OffsetGroup::Block => self.block.values_mut().collect(),
+            OffsetGroup::Floor => self.floor.values_mut().collect(),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct TimingParams {
+    /// Walking speed in distance units of `paths.txt` per minute.
+    pub walking_speed: f32,
+    /// Length of a time slot in minutes.
+    pub slot_length: f32,
+}
+
+#[derive(Clone, Default)]
+pub struct TransitionTiming {
+    /// Start of the first slot in minutes, relative to the unstaggered release time.
+    pub start: f32,
+    /// Highest edge load in each time slot.
+    pub slot_peaks: Vec<u32>,
+    pub peak_congestion: u32,
+    pub performance_index: f64,
+}
+
+#[derive(Clone, Default)]
+pub struct TimingReport {
+    /// (day, transition) → time-resolved congestion of the transition.
+    pub transitions: HashMap<(u32, usize), TransitionTiming>,
+}
+
+impl TimingReport {
+    pub fn peak_congestion(&self) -> u32 {
+        self.transitions
+            .values()
+            .map(|timing| timing.peak_congestion)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Sum of the peak congestion of every transition, the objective of the offset search.
+    pub fn peak_congestion_sum(&self) -> u64 {
+        self.transitions
+            .values()
+            .map(|timing| timing.peak_congestion as u64)
+            .sum()
+    }
+
+    pub fn performance_index(&self) -> f64 {
+        self.transitions
+            .values()
+            .map(|timing| timing.performance_index)
+            .sum()
+    }
+}
+
+/// A student's walk in one transition, with edges interned per transition.
+struct Walk {
+    student_number: String,
+    origin: String,
+    /// (edge index, distance) in walking order.
+    edges: Vec<(usize, f64)>,
+}
+
+/// Routes prepared for repeated evaluation with different offsets.
+pub struct TimingModel {
+    walks: HashMap<(u32, usize), (Vec<Walk>, usize)>,
+    params: TimingParams,
+}
+
+impl TimingModel {
+    /// Fails if a route walks an edge missing from `distances`.
+    pub fn new(
+        routes: &Routes,
+        distances: &PathDistances,
+        params: TimingParams
+    ) -> Result<Self, MissingEdge> {
+        let mut walks = HashMap::new();
+        for day in 1..=5 {
+            for transition in 0..=11 {
+                let mut edge_indices: HashMap<(String, String), usize> = HashMap::new();
+                let mut transition_walks = vec![];
+                for (student_number, student_routes) in routes {
+                    let Some(route) = student_routes
+                        .get(&day)
+                        .and_then(|day_routes| day_routes.get(&transition)) else {
+                        continue;
+                    };
+                    let legs: Vec<(&str, &str)> = route_legs(route).collect();
+                    let Some((origin, _)) = legs.first() else {
+                        continue;
+                    };
+                    let edges = legs
+                        .iter()
+                        .map(|(node1, node2)| {
+                            let edge_count = edge_indices.len();
+                            let index = *edge_indices
+                                .entry(edge_key(node1, node2))
+                                .or_insert(edge_count);
+                            let distance = known_path_distance(distances, node1, node2)?;
+                            Ok((index, distance as f64))
+                        })
+                        .collect::<Result<_, MissingEdge>>()?;
+                    transition_walks.push(Walk {
+                        student_number: student_number.to_owned(),
+                        origin: (*origin).to_owned(),
+                        edges,
+                    });
+                }
+                walks.insert((day, transition), (transition_walks, edge_indices.len()));
+            }
+        }
+        Ok(Self { walks, params })
+    }
+
+    fn evaluate_transition(&self, offsets: &DismissalOffsets, day: u32, transition: usize) -> TransitionTiming {
+        let (walks, edge_count) = &self.walks[&(day, transition)];
+        let walk_offsets: Vec<f32> = walks
+            .iter()
+            .map(|walk| offsets.offset(&walk.student_number, &walk.origin))
+            .collect();
+        let start = walk_offsets.iter().copied().fold(f32::INFINITY, f32::min);
+        if !start.is_finite() {
+            return TransitionTiming::default();
+        }
+        let slot_length = self.params.slot_length;
+        let slot = |time: f32| ((time - start) / slot_length).floor().max(0.0) as usize;
+
+        // (edge index, distance, first slot, last slot) of every edge walked by every student
+        let mut traversals = vec![];
+        let mut loads: Vec<Vec<u32>> = vec![vec![]; *edge_count];
+        for (walk, offset) in walks.iter().zip(walk_offsets) {
+            let mut time = offset;
+            for (edge, distance) in &walk.edges {
+                let end_time = time + (*distance as f32) / self.params.walking_speed;
+                let (first_slot, last_slot) = (slot(time), slot(end_time));
+                let load = &mut loads[*edge];
+                if load.len() <= last_slot {
+                    load.resize(last_slot + 1, 0);
+                }
+                for slot_load in &mut load[first_slot..=last_slot] {
+                    *slot_load += 1;
+                }
+                traversals.push((*edge, *distance, first_slot, last_slot));
+                time = end_time;
+            }
+        }
+
+        let slot_count = loads.iter().map(Vec::len).max().unwrap_or(0);
+        let mut slot_peaks = vec![0; slot_count];
+        for load in &loads {
+            for (slot_peak, slot_load) in slot_peaks.iter_mut().zip(load) {
+                *slot_peak = (*slot_peak).max(*slot_load);
+            }
+        }
+        let performance_index = traversals
+            .iter()
+            .map(|(edge, distance, first_slot, last_slot)| {
+                let congestion = loads[*edge][*first_slot..=*last_slot]
+                    .iter()
+                    .copied()
+                    .max()
+                    .unwrap_or(0);
+                edge_performance(*distance, congestion as f64)
+            })
+            .sum();
+        TransitionTiming {
+            start,
+            peak_congestion: slot_peaks.iter().copied().max().unwrap_or(0),
+            slot_peaks,
+            performance_index,
+        }
+    }
+
+    pub fn evaluate(&self, offsets: &DismissalOffsets) -> TimingReport {
+        let mut report = TimingReport::default();
+        for day in 1..=5 {
+            for transition in 0..=11 {
+                report.transitions.insert(
+                    (day, transition),
+                    self.evaluate_transition(offsets, day, transition)
+                );
+            }
+        }
+        report
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct OffsetSearch {
+    pub group: OffsetGroup,
+    /// Largest offset tried in either direction, in minutes.
+    pub max_offset: f32,
+    /// Offsets tried are multiples of this, in minutes.
+    pub step: f32,
+}
+
+/// Searches the offsets of one kind of group, one group at a time, minimising the sum of the peak
+/// congestion of all transitions. Ties are broken by the time-resolved performance index.
+///
+/// `progress` is called with a percentage as the search advances.
+pub fn optimize_offsets(
+    model: &TimingModel,
+    initial_offsets: &DismissalOffsets,
+    search: OffsetSearch,
+    mut progress: impl FnMut(i32)
+) -> DismissalOffsets {
+    /// Rounds of coordinate descent over all groups.
+    const ROUNDS: usize = 3;
+
+    let candidates: Vec<f32> = {
+        let step_count = (search.max_offset / search.step).floor() as i32;
+        (-step_count..=step_count).map(|i| (i as f32) * search.step).collect()
+    };
+    let objective = |offsets: &DismissalOffsets| {
+        let report = model.evaluate(offsets);
+        (report.peak_congestion_sum(), report.performance_index())
+    };
+    let is_better = |a: (u64, f64), b: (u64, f64)| a.0 < b.0 || (a.0 == b.0 && a.1 < b.1);
+
+    let mut best_offsets = initial_offsets.clone();
+    let mut best = objective(&best_offsets);
+    let group_count = best_offsets.group_offsets(search.group).len();
+    for round in 0..ROUNDS {
+        let mut improved = false;
+        for index in 0..group_count {
+            progress(
+                (((round * group_count + index) as f32) / ((ROUNDS * group_count) as f32) *
+                    100.0) as i32
+            );
+            for candidate in &candidates {
+                let mut offsets = best_offsets.clone();
+                *offsets.group_offsets(search.group)[index] = *candidate;
+                if offsets == best_offsets {
+                    continue;
+                }
+                let value = objective(&offsets);
+                if is_better(value, best) {
+                    best = value;
+                    best_offsets = offsets;
+                    improved = true;
+                }
+            }
+        }
+        if !improved {
+            break;
+        }
+    }
+    best_offsets
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::evaluation::parse_path_distances;
+
+    use super::*;
+
+    /// Two classes leaving `A201` and `B201` at the same time, whose routes meet on the corridor
+    /// from `X` to `Y`. Every edge takes one minute to walk.
+    fn model() -> TimingModel {
+        let distances = parse_path_distances("A201 X 60 0\nB201 X 60 0\nX Y 60 0\nY C201 60 0\n");
+        let routes: Routes = (21001..=21040)
+            .map(|student| {
+                let origin = if student <= 21020 { "A201" } else { "B201" };
+                let day_routes = HashMap::from([(1, format!("{} X Y C201", origin))]);
+                (student.to_string(), HashMap::from([(1, day_routes)]))
+            })
+            .collect();
+        let params = TimingParams { walking_speed: 60.0, slot_length: 1.0 };
+        TimingModel::new(&routes, &distances, params).unwrap()
+    }
+
+    #[test]
+    fn offset_search_staggers_the_blocks() {
+        let model = model();
+        let initial_offsets = DismissalOffsets::default();
+        assert_eq!(model.evaluate(&initial_offsets).peak_congestion(), 40);
+
+        let search = OffsetSearch { group: OffsetGroup::Block, max_offset: 5.0, step: 1.0 };
+        let offsets = optimize_offsets(&model, &initial_offsets, search, |_| ());
+        assert_eq!(model.evaluate(&offsets).peak_congestion(), 20);
+        let (offset_a, offset_b) = (offsets.block[&'A'], offsets.block[&'B']);
+        assert!((offset_a - offset_b).abs() >= 2.0, "{} {}", offset_a, offset_b);
+        assert!(offset_a.abs() <= 5.0 && offset_b.abs() <= 5.0);
+        assert_eq!(offsets.floor, initial_offsets.floor);
+    }
+}
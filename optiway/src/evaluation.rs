@@ -0,0 +1,168 @@
+//! Congestion and performance index evaluation shared by the analysis tools.
+//!
+//! See "Path Evaluation" in `TECHNICAL_NOTES.md` for the definition of the performance index.
+
+use std::{ collections::HashMap, fmt::{ self, Display } };
+
+use crate::routing::Routes;
+
+/// Distances between adjacent nodes, as read from `paths.txt`.
+pub type PathDistances = HashMap<String, HashMap<String, u32>>;
+
+/// Number of students walking each (undirected) edge during a single transition.
+///
+/// Keys are ordered so that the first node is lexicographically smaller.
+pub type EdgeLoad = HashMap<(String, String), u32>;
+
+pub fn parse_path_distances(content: &str) -> PathDistances {
+    let mut path_distances: PathDistances = HashMap::new();
+    for line in content.lines() {
+        let mut line = line.split(' ');
+        let (Some(room1), Some(room2), Some(distance)) = (line.next(), line.next(), line.next()) else {
+            continue;
+        };
+        let Ok(distance) = distance.parse::<u32>() else {
+            continue;
+        };
+        path_distances
+            .entry(room1.to_owned())
+            .or_default()
+            .insert(room2.to_owned(), distance);
+        path_distances
+            .entry(room2.to_owned())
+            .or_default()
+            .insert(room1.to_owned(), distance);
+    }
+    path_distances
+}
+
+pub fn edge_key(node1: &str, node2: &str) -> (String, String) {
+    if node1 <= node2 {
+        (node1.to_owned(), node2.to_owned())
+    } else {
+        (node2.to_owned(), node1.to_owned())
+    }
+}
+
+/// Pairs of consecutive nodes of a space-separated route, in walking order. Legs to or from the
+/// abstract ground node `G` are skipped, as `BuildingGraph::counted_edges` does, so a route
+/// through `G` is not joined up around it.
+pub fn route_legs(route: &str) -> impl Iterator<Item = (&str, &str)> {
+    let mut nodes = route.split(' ').filter(|node| !node.is_empty());
+    let mut previous_node = nodes.next();
+    nodes.filter_map(move |node| {
+        let leg = previous_node.filter(|previous_node| *previous_node != "G" && node != "G");
+        previous_node = Some(node);
+        leg.map(|previous_node| (previous_node, node))
+    })
+}
+
+/// Performance index of a single student walking an edge of `distance` shared with `congestion`
+/// students.
+pub fn edge_performance(distance: f64, congestion: f64) -> f64 {
+    distance * (2.0 + ((congestion - 300.0) / 200.0).tanh())
+}
+
+/// Performance index contributed by all `congestion` students walking the same edge.
+pub fn edge_total_performance(distance: f64, congestion: u32) -> f64 {
+    (congestion as f64) * edge_performance(distance, congestion as f64)
+}
+
+/// Edges walked by a route, in walking order.
+pub fn route_edges(route: &str) -> Vec<(String, String)> {
+    route_legs(route)
+        .map(|(node1, node2)| edge_key(node1, node2))
+        .collect()
+}
+
+pub fn add_route(load: &mut EdgeLoad, route: &str) {
+    for edge in route_edges(route) {
+        *load.entry(edge).or_insert(0) += 1;
+    }
+}
+
+pub fn remove_route(load: &mut EdgeLoad, route: &str) {
+    for edge in route_edges(route) {
+        if let Some(congestion) = load.get_mut(&edge) {
+            *congestion = congestion.saturating_sub(1);
+            if *congestion == 0 {
+                load.remove(&edge);
+            }
+        }
+    }
+}
+
+/// Edge loads of every student's route in a single transition.
+pub fn edge_load(routes: &Routes, day: u32, period: usize) -> EdgeLoad {
+    let mut load = EdgeLoad::new();
+    for student_routes in routes.values() {
+        if let Some(route) = student_routes.get(&day).and_then(|day_routes| day_routes.get(&period)) {
+            add_route(&mut load, route);
+        }
+    }
+    load
+}
+
+pub fn path_distance(distances: &PathDistances, node1: &str, node2: &str) -> Option<u32> {
+    distances.get(node1).and_then(|neighbours| neighbours.get(node2)).copied()
+}
+
+/// A route walks between two nodes that `paths.txt` does not connect, so the distance it walks is
+/// unknown.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct MissingEdge(pub String, pub String);
+
+impl Display for MissingEdge {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "No edge between {} and {} in paths.txt", self.0, self.1)
+    }
+}
+
+impl std::error::Error for MissingEdge {}
+
+/// Distance between two adjacent nodes, which must be connected in `distances`.
+pub fn known_path_distance(
+    distances: &PathDistances,
+    node1: &str,
+    node2: &str
+) -> Result<u32, MissingEdge> {
+    path_distance(distances, node1, node2).ok_or_else(|| {
+        MissingEdge(node1.to_owned(), node2.to_owned())
+    })
+}
+
+/// Total walking distance of a route.
+pub fn route_distance(distances: &PathDistances, route: &str) -> Result<u32, MissingEdge> {
+    route_legs(route)
+        .map(|(node1, node2)| known_path_distance(distances, node1, node2))
+        .sum()
+}
+
+/// Sum of the performance indices of all students in a transition with the given edge loads.
+pub fn performance_index(load: &EdgeLoad, distances: &PathDistances) -> Result<f64, MissingEdge> {
+    load.iter()
+        .map(|((node1, node2), congestion)| {
+            let distance = known_path_distance(distances, node1, node2)?;
+            Ok(edge_total_performance(distance as f64, *congestion))
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn legs_through_ground_are_skipped() {
+        let distances = parse_path_distances("A B 10 0\nB C 20 0\nC G 999999 2\nD G 999999 2\n");
+        assert_eq!(route_edges("A B C"), vec![edge_key("A", "B"), edge_key("B", "C")]);
+        assert_eq!(route_edges("B C G D"), vec![edge_key("B", "C")]);
+        assert_eq!(route_distance(&distances, "G C B A"), Ok(30));
+        // C and D are not adjacent, and walking through G must not make them so
+        assert_eq!(route_distance(&distances, "A B C G D"), Ok(30));
+        assert_eq!(
+            route_distance(&distances, "A C"),
+            Err(MissingEdge("A".to_owned(), "C".to_owned()))
+        );
+    }
+}
@@ -0,0 +1,209 @@
+//! Limits on the detours of individual students, and the distribution of detours in a route plan.
+//!
+//! The detour of a student in a transition is the extra distance walked compared to the shortest
+//! route between the same rooms. The detour of a student in a day is the sum over its transitions.
+
+use std::collections::HashMap;
+
+use crate::{
+    evaluation::{ route_distance, MissingEdge, PathDistances },
+    routing::{ Routes, ShortestPaths },
+};
+
+/// Maximum detour, either as a distance, as a fraction of the shortest distance, or both.
+#[derive(Clone, Copy, Default, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct DetourLimit {
+    pub absolute: Option<u32>,
+    pub relative: Option<f32>,
+}
+
+impl DetourLimit {
+    pub fn is_set(&self) -> bool {
+        self.absolute.is_some() || self.relative.is_some()
+    }
+
+    pub fn allows(&self, detour: u32, shortest_distance: u32) -> bool {
+        self.absolute.is_none_or(|absolute| detour <= absolute) &&
+            self.relative.is_none_or(|relative| {
+                (detour as f32) <= relative * (shortest_distance as f32)
+            })
+    }
+}
+
+#[derive(Clone, Copy, Default, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct DetourLimits {
+    pub transition: DetourLimit,
+    pub day: DetourLimit,
+}
+
+/// Shortest walking distance between the first and last nodes of `route`, or `None` if no
+/// shortest path between them is known.
+pub fn shortest_distance(
+    route: &str,
+    shortest_paths: &ShortestPaths,
+    distances: &PathDistances
+) -> Result<Option<u32>, MissingEdge> {
+    let mut nodes = route.split(' ').filter(|node| !node.is_empty());
+    let Some(first) = nodes.next() else {
+        return Ok(None);
+    };
+    let last = nodes.next_back().unwrap_or(first);
+    if first == last {
+        return Ok(Some(0));
+    }
+    shortest_paths
+        .get(&format!("{}{}", first, last))
+        .map(|shortest_route| route_distance(distances, shortest_route))
+        .transpose()
+}
+
+#[derive(Clone, Default)]
+pub struct DetourStatistics {
+    /// Number of non-empty routes (or days with at least one route).
+    pub count: usize,
+    /// Number of those with a positive detour.
+    pub detoured: usize,
+    pub mean: f64,
+    pub percentile_95: u32,
+    pub max: u32,
+    /// Largest detour as a fraction of the shortest distance.
+    pub max_relative: f32,
+    /// Number of those exceeding the limit.
+    pub violations: usize,
+    /// Detours grouped by `DetourReport::bucket_width`, by number of buckets above zero. Routes
+    /// without a detour are not counted.
+    pub histogram: Vec<usize>,
+}
+
+impl DetourStatistics {
+    fn new(mut detours: Vec<(u32, u32)>, limit: DetourLimit, bucket_width: u32) -> Self {
+        if detours.is_empty() {
+            return Self::default();
+        }
+        let mut statistics = Self {
+            count: detours.len(),
+            detoured: detours
+                .iter()
+                .filter(|(detour, _)| *detour > 0)
+                .count(),
+            mean: detours
+                .iter()
+                .map(|(detour, _)| *detour as f64)
+                .sum::<f64>() / (detours.len() as f64),
+            violations: detours
+                .iter()
+                .filter(|(detour, shortest)| !limit.allows(*detour, *shortest))
+                .count(),
+            max_relative: detours
+                .iter()
+                .filter(|(_, shortest)| *shortest > 0)
+                .map(|(detour, shortest)| (*detour as f32) / (*shortest as f32))
+                .fold(0.0, f32::max),
+            ..Default::default()
+        };
+        detours.sort();
+        statistics.max = detours.last().unwrap().0;
+        statistics.percentile_95 = detours[((detours.len() - 1) * 95) / 100].0;
+        for (detour, _) in detours {
+            if detour == 0 {
+                continue;
+            }
+            let bucket = ((detour - 1) / bucket_width) as usize;
+            if statistics.histogram.len() <= bucket {
+                statistics.histogram.resize(bucket + 1, 0);
+            }
+            statistics.histogram[bucket] += 1;
+        }
+        statistics
+    }
+}
+
+#[derive(Clone)]
+pub struct DetourRecord {
+    pub student_number: String,
+    pub day: u32,
+    /// `None` for the detour of the whole day.
+    pub transition: Option<usize>,
+    pub detour: u32,
+    pub shortest_distance: u32,
+}
+
+#[derive(Clone, Default)]
+pub struct DetourReport {
+    pub bucket_width: u32,
+    pub transition: DetourStatistics,
+    pub day: DetourStatistics,
+    /// Routes and days exceeding the limits, largest detour first.
+    pub violations: Vec<DetourRecord>,
+}
+
+pub fn detour_report(
+    routes: &Routes,
+    shortest_paths: &ShortestPaths,
+    distances: &PathDistances,
+    limits: DetourLimits,
+    bucket_width: u32
+) -> Result<DetourReport, MissingEdge> {
+    let mut transition_detours = vec![];
+    let mut day_detours: HashMap<(&str, u32), (u32, u32)> = HashMap::new();
+    let mut violations = vec![];
+    for (student_number, student_routes) in routes {
+        for (day, day_routes) in student_routes {
+            for (transition, route) in day_routes {
+                if route.is_empty() {
+                    continue;
+                }
+                let Some(shortest) = shortest_distance(route, shortest_paths, distances)? else {
+                    continue;
+                };
+                let detour = route_distance(distances, route)?.saturating_sub(shortest);
+                transition_detours.push((detour, shortest));
+                let day_detour = day_detours.entry((student_number, *day)).or_default();
+                day_detour.0 += detour;
+                day_detour.1 += shortest;
+                if !limits.transition.allows(detour, shortest) {
+                    violations.push(DetourRecord {
+                        student_number: student_number.to_owned(),
+                        day: *day,
+                        transition: Some(*transition),
+                        detour,
+                        shortest_distance: shortest,
+                    });
+                }
+            }
+        }
+    }
+    for ((student_number, day), (detour, shortest)) in &day_detours {
+        if !limits.day.allows(*detour, *shortest) {
+            violations.push(DetourRecord {
+                student_number: student_number.to_string(),
+                day: *day,
+                transition: None,
+                detour: *detour,
+                shortest_distance: *shortest,
+            });
+        }
+    }
+    violations.sort_by_key(|violation| std::cmp::Reverse(violation.detour));
+    Ok(DetourReport {
+        bucket_width,
+        transition: DetourStatistics::new(transition_detours, limits.transition, bucket_width),
+        day: DetourStatistics::new(day_detours.into_values().collect(), limits.day, bucket_width),
+        violations,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn histogram_buckets_include_their_upper_edge() {
+        let detours = [0, 1, 10, 11, 20, 21].map(|detour| (detour, 100)).to_vec();
+        let limit = DetourLimit { absolute: Some(10), relative: None };
+        let statistics = DetourStatistics::new(detours, limit, 10);
+        assert_eq!(statistics.histogram, [2, 2, 1]);
+        assert_eq!((statistics.count, statistics.detoured, statistics.violations), (6, 5, 3));
+        assert_eq!(statistics.max, 21);
+    }
+}
@@ -0,0 +1,261 @@
+//! The building graph of `paths.txt` with interned node ids, used to generate new routes.
+
+use std::{ cmp::Ordering, collections::{ BinaryHeap, HashMap } };
+
+use crate::routing::ShortestPaths;
+
+#[derive(Clone, Copy)]
+pub struct GraphEdge {
+    pub to: usize,
+    /// Index of the undirected edge, shared by both directions.
+    pub id: usize,
+    pub distance: u32,
+    /// Edge type as in `paths.txt`: normal, bridge, stairs, spiral staircase or other.
+    pub edge_type: u8,
+}
+
+#[derive(Clone, Default)]
+pub struct BuildingGraph {
+    names: Vec<String>,
+    ids: HashMap<String, usize>,
+    adjacency: Vec<Vec<GraphEdge>>,
+    edge_count: usize,
+    /// The abstract ground node `G`, whose edges count towards neither distance nor congestion.
+    ground: Option<usize>,
+}
+
+/// Entry of the Dijkstra queue, ordered so that `BinaryHeap` pops the lowest cost first.
+struct QueueEntry(f64, usize);
+
+impl PartialEq for QueueEntry {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for QueueEntry {}
+
+impl PartialOrd for QueueEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for QueueEntry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.0.total_cmp(&self.0).then_with(|| other.1.cmp(&self.1))
+    }
+}
+
+impl BuildingGraph {
+    pub fn parse(content: &str) -> Self {
+        let mut graph = Self::default();
+        for line in content.lines() {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let [node1, node2, distance, edge_type] = fields[..] else {
+                continue;
+            };
+            let (Ok(distance), Ok(edge_type)) = (distance.parse(), edge_type.parse()) else {
+                continue;
+            };
+            let (node1, node2) = (graph.intern(node1), graph.intern(node2));
+            let id = graph.edge_count;
+            graph.edge_count += 1;
+            graph.adjacency[node1].push(GraphEdge { to: node2, id, distance, edge_type });
+            graph.adjacency[node2].push(GraphEdge { to: node1, id, distance, edge_type });
+        }
+        graph.ground = graph.node_id("G");
+        graph
+    }
+
+    fn intern(&mut self, name: &str) -> usize {
+        if let Some(id) = self.ids.get(name) {
+            return *id;
+        }
+        let id = self.names.len();
+        self.names.push(name.to_owned());
+        self.ids.insert(name.to_owned(), id);
+        self.adjacency.push(vec![]);
+        id
+    }
+
+    pub fn node_id(&self, name: &str) -> Option<usize> {
+        self.ids.get(name).copied()
+    }
+
+    pub fn node_name(&self, id: usize) -> &str {
+        &self.names[id]
+    }
+
+    pub fn node_count(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn edge_count(&self) -> usize {
+        self.edge_count
+    }
+
+    pub fn edges(&self, node: usize) -> &[GraphEdge] {
+        &self.adjacency[node]
+    }
+
+    /// Edge id → distance.
+    pub fn edge_distances(&self) -> Vec<u32> {
+        let mut distances = vec![0; self.edge_count];
+        for edge in self.adjacency.iter().flatten() {
+            distances[edge.id] = edge.distance;
+        }
+        distances
+    }
+
+    /// Edge id → the nodes the edge connects, the smaller id first.
+    pub fn edge_ends(&self) -> Vec<(usize, usize)> {
+        let mut ends = vec![(0, 0); self.edge_count];
+        for (node, edges) in self.adjacency.iter().enumerate() {
+            for edge in edges {
+                ends[edge.id] = (node.min(edge.to), node.max(edge.to));
+            }
+        }
+        ends
+    }
+
+    pub fn is_ground(&self, node: usize) -> bool {
+        Some(node) == self.ground
+    }
+
+    /// Edges of a route that count towards distance and congestion, skipping the ground node.
+    pub fn counted_edges<'a>(
+        &'a self,
+        nodes: &'a [usize]
+    ) -> impl Iterator<Item = &'a GraphEdge> + 'a {
+        nodes.windows(2).filter_map(|pair| {
+            if self.is_ground(pair[0]) || self.is_ground(pair[1]) {
+                return None;
+            }
+            self.edge_between(pair[0], pair[1])
+        })
+    }
+
+    pub fn route_distance(&self, nodes: &[usize]) -> u32 {
+        self.counted_edges(nodes)
+            .map(|edge| edge.distance)
+            .sum()
+    }
+
+    /// Distance of the shortest route between the ends of a route, falling back to the distance of
+    /// the route itself if no shortest route is known.
+    pub fn shortest_distance(&self, nodes: &[usize], shortest_paths: &ShortestPaths) -> u32 {
+        let (Some(first), Some(last)) = (nodes.first(), nodes.last()) else {
+            return 0;
+        };
+        shortest_paths
+            .get(&format!("{}{}", self.node_name(*first), self.node_name(*last)))
+            .and_then(|shortest_route| self.route_ids(shortest_route))
+            .map(|shortest_nodes| self.route_distance(&shortest_nodes))
+            .unwrap_or_else(|| self.route_distance(nodes))
+    }
+
+    /// Adds a route to `loads`, the number of students walking each edge by edge id.
+    pub fn add_load(&self, loads: &mut [u32], nodes: &[usize]) {
+        for edge in self.counted_edges(nodes) {
+            loads[edge.id] += 1;
+        }
+    }
+
+    pub fn remove_load(&self, loads: &mut [u32], nodes: &[usize]) {
+        for edge in self.counted_edges(nodes) {
+            loads[edge.id] = loads[edge.id].saturating_sub(1);
+        }
+    }
+
+    pub fn edge_between(&self, node1: usize, node2: usize) -> Option<&GraphEdge> {
+        self.adjacency[node1].iter().find(|edge| edge.to == node2)
+    }
+
+    /// Node ids of a space-separated route, or `None` if it contains an unknown node.
+    pub fn route_ids(&self, route: &str) -> Option<Vec<usize>> {
+        route
+            .split(' ')
+            .filter(|node| !node.is_empty())
+            .map(|node| self.node_id(node))
+            .collect()
+    }
+
+    pub fn route_string(&self, route: &[usize]) -> String {
+        route
+            .iter()
+            .map(|node| self.node_name(*node))
+            .collect::<Vec<&str>>()
+            .join(" ")
+    }
+
+    /// Cheapest path from `start` to `end` where `cost` gives the cost of walking an edge from a
+    /// node. Edges with an infinite cost are never used. The path only visits the ground node as
+    /// its start or end: `G` stands for every entrance at once, and a path through it would jump
+    /// between entrances without walking.
+    pub fn cheapest_path(
+        &self,
+        start: usize,
+        end: usize,
+        cost: impl Fn(usize, &GraphEdge) -> f64
+    ) -> Option<Vec<usize>> {
+        let (costs, previous) = self.dijkstra(start, Some(end), |node, edge| {
+            if self.is_ground(edge.to) && edge.to != end {
+                f64::INFINITY
+            } else {
+                cost(node, edge)
+            }
+        });
+        if !costs[end].is_finite() {
+            return None;
+        }
+        let mut path = vec![end];
+        while *path.last().unwrap() != start {
+            path.push(previous[*path.last().unwrap()]);
+        }
+        path.reverse();
+        Some(path)
+    }
+
+    /// Cost of the cheapest path from `start` to every node, infinite for the nodes that cannot
+    /// be reached.
+    pub fn cheapest_costs(
+        &self,
+        start: usize,
+        cost: impl Fn(usize, &GraphEdge) -> f64
+    ) -> Vec<f64> {
+        self.dijkstra(start, None, cost).0
+    }
+
+    /// Costs of the cheapest paths from `start` and the node before each node on them, stopping
+    /// once `end` is reached.
+    fn dijkstra(
+        &self,
+        start: usize,
+        end: Option<usize>,
+        cost: impl Fn(usize, &GraphEdge) -> f64
+    ) -> (Vec<f64>, Vec<usize>) {
+        let mut costs = vec![f64::INFINITY; self.node_count()];
+        let mut previous = vec![usize::MAX; self.node_count()];
+        let mut queue = BinaryHeap::new();
+        costs[start] = 0.0;
+        queue.push(QueueEntry(0.0, start));
+        while let Some(QueueEntry(node_cost, node)) = queue.pop() {
+            if Some(node) == end {
+                break;
+            }
+            if node_cost > costs[node] {
+                continue;
+            }
+            for edge in &self.adjacency[node] {
+                let next_cost = node_cost + cost(node, edge);
+                if next_cost < costs[edge.to] {
+                    costs[edge.to] = next_cost;
+                    previous[edge.to] = node;
+                    queue.push(QueueEntry(next_cost, edge.to));
+                }
+            }
+        }
+        (costs, previous)
+    }
+}
@@ -4,4 +4,26 @@ mod app;
 pub use app::OptiWayApp;
 mod app_init;
 pub use app_init::{ setup_custom_fonts, setup_custom_styles };
+pub mod benchmark;
+pub mod building_model;
+pub mod compact_routes;
+pub mod congestion;
+pub mod consistency;
+pub mod destinations;
+pub mod directions;
+pub mod dismissal;
+pub mod evaluation;
+pub mod fairness;
+pub mod graph;
 pub mod md_icons;
+pub mod monte_carlo;
+pub mod optimization;
+pub mod pareto;
+pub mod pinning;
+pub mod robustness;
+pub mod room_swap;
+pub mod route_editor;
+pub mod route_sheet;
+pub mod route_validation;
+pub mod routing;
+pub mod timetable;
@@ -0,0 +1,434 @@
+//! Monte Carlo congestion: how the congestion of a route plan spreads when not every student
+//! follows it.
+//!
+//! In every run, every student follows their planned route in a transition with the compliance
+//! probability, and otherwise walks a route of their own between the same rooms: the shortest
+//! route, or a random near-shortest route. A random near-shortest route is a walk towards the end
+//! that picks uniformly among the next nodes from which the end can still be reached within the
+//! given slack over the shortest distance, and that never visits a node twice. The runs are
+//! repeated with seeds drawn in turn from a generator seeded with the seed of the parameters, so
+//! the same parameters always give the same report.
+
+use std::{ collections::HashMap, fmt::{ self, Display, Write as _ } };
+
+use crate::{
+    compact_routes::CompactRoutes,
+    evaluation::edge_total_performance,
+    graph::{ BuildingGraph, GraphEdge },
+    optimization::random::Random,
+    routing::{ shortest_path, ShortestPaths, TRANSITION_COUNT },
+    timetable::DAY_COUNT,
+};
+
+/// z-score of a two-sided 95% confidence interval.
+const Z_95: f64 = 1.96;
+
+/// The route a student walks when not following the plan.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum Deviation {
+    Shortest,
+    #[default]
+    NearShortest,
+}
+
+impl Display for Deviation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Deviation::Shortest => write!(f, "Shortest route"),
+            Deviation::NearShortest => write!(f, "Random near-shortest route"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct MonteCarloParams {
+    /// Probability that a student follows the planned route in a transition.
+    pub compliance: f64,
+    pub deviation: Deviation,
+    /// Largest extra distance of a near-shortest route, as a fraction of the shortest distance.
+    pub slack: f64,
+    pub runs: usize,
+    pub seed: u64,
+}
+
+impl Default for MonteCarloParams {
+    fn default() -> Self {
+        Self {
+            compliance: 0.8,
+            deviation: Default::default(),
+            slack: 0.2,
+            runs: 100,
+            seed: 0,
+        }
+    }
+}
+
+/// Mean of samples with the 95% confidence interval of the mean, using the normal approximation.
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub struct ConfidenceInterval {
+    pub mean: f64,
+    pub low: f64,
+    pub high: f64,
+}
+
+impl ConfidenceInterval {
+    pub(crate) fn new(samples: &[f64]) -> Self {
+        if samples.is_empty() {
+            return Self::default();
+        }
+        let count = samples.len() as f64;
+        let mean = samples.iter().sum::<f64>() / count;
+        let variance = if samples.len() > 1 {
+            samples
+                .iter()
+                .map(|sample| (sample - mean).powi(2))
+                .sum::<f64>() / (count - 1.0)
+        } else {
+            0.0
+        };
+        let margin = (Z_95 * variance.sqrt()) / count.sqrt();
+        Self { mean, low: mean - margin, high: mean + margin }
+    }
+}
+
+/// Congestion of a transition over the runs.
+#[derive(Clone, Default)]
+pub struct TransitionSamples {
+    /// Edge id → mean number of students walking the edge.
+    pub mean_loads: Vec<f64>,
+    /// Edge id → 95th percentile of the number of students walking the edge.
+    pub percentile_95_loads: Vec<u32>,
+    /// Performance index when every student follows the plan.
+    pub planned_performance_index: f64,
+    /// Performance index of every run.
+    pub performance_indices: Vec<f64>,
+}
+
+impl TransitionSamples {
+    pub fn performance_index(&self) -> ConfidenceInterval {
+        ConfidenceInterval::new(&self.performance_indices)
+    }
+
+    /// Edges walked in any run as (edge id, mean load, 95th percentile load), the most congested
+    /// first.
+    pub fn edges(&self) -> Vec<(usize, f64, u32)> {
+        let mut edges: Vec<(usize, f64, u32)> = self.mean_loads
+            .iter()
+            .zip(&self.percentile_95_loads)
+            .enumerate()
+            .filter(|(_, (mean, _))| **mean > 0.0)
+            .map(|(edge, (mean, percentile_95))| (edge, *mean, *percentile_95))
+            .collect();
+        edges.sort_by(|a, b| b.2.cmp(&a.2).then(b.1.total_cmp(&a.1)).then(a.0.cmp(&b.0)));
+        edges
+    }
+}
+
+pub struct MonteCarloReport {
+    pub params: MonteCarloParams,
+    /// Ordered by day, then transition.
+    transitions: Vec<TransitionSamples>,
+}
+
+impl MonteCarloReport {
+    pub fn transition(&self, day: u32, transition: usize) -> Option<&TransitionSamples> {
+        let index = ((day as usize).checked_sub(1)? * TRANSITION_COUNT) + transition;
+        self.transitions.get(index).filter(|_| transition < TRANSITION_COUNT)
+    }
+
+    pub fn planned_performance_index(&self) -> f64 {
+        self.transitions
+            .iter()
+            .map(|samples| samples.planned_performance_index)
+            .sum()
+    }
+
+    /// Performance index of the whole week.
+    pub fn performance_index(&self) -> ConfidenceInterval {
+        let totals: Vec<f64> = (0..self.params.runs)
+            .map(|run| {
+                self.transitions
+                    .iter()
+                    .map(|samples| samples.performance_indices[run])
+                    .sum()
+            })
+            .collect();
+        ConfidenceInterval::new(&totals)
+    }
+
+    /// The mean and 95th percentile load of every edge walked in every transition, as CSV.
+    pub fn csv(&self, graph: &BuildingGraph) -> String {
+        let edge_ends = graph.edge_ends();
+        let mut csv = String::from("day,transition,node1,node2,mean_load,percentile_95_load\n");
+        for (index, samples) in self.transitions.iter().enumerate() {
+            let day = index / TRANSITION_COUNT + 1;
+            let transition = index % TRANSITION_COUNT;
+            let mut edges = samples.edges();
+            edges.sort_by_key(|(edge, _, _)| *edge);
+            for (edge, mean, percentile_95) in edges {
+                let (node1, node2) = edge_ends[edge];
+                writeln!(
+                    csv,
+                    "{},{},{},{},{:.3},{}",
+                    day,
+                    transition,
+                    graph.node_name(node1),
+                    graph.node_name(node2),
+                    mean,
+                    percentile_95
+                ).unwrap();
+            }
+        }
+        csv
+    }
+}
+
+/// Cost of walking an edge for near-shortest routes. Edges of the ground node cost nothing, as in
+/// the performance index.
+fn walking_cost(node: usize, edge: &GraphEdge, graph: &BuildingGraph) -> f64 {
+    if graph.is_ground(node) || graph.is_ground(edge.to) { 0.0 } else { edge.distance as f64 }
+}
+
+/// The routes students walk instead of the planned ones, shared by all transitions.
+struct Deviations<'a> {
+    graph: &'a BuildingGraph,
+    shortest_paths: &'a ShortestPaths,
+    params: MonteCarloParams,
+    /// (start, end) → shortest route, `None` if none is known.
+    shortest_routes: HashMap<(usize, usize), Option<Vec<usize>>>,
+    /// End → node → walking cost of the cheapest route from the node to the end.
+    costs_to: HashMap<usize, Vec<f64>>,
+}
+
+impl Deviations<'_> {
+    fn shortest_route(&mut self, start: usize, end: usize) -> Option<&Vec<usize>> {
+        let graph = self.graph;
+        let shortest_paths = self.shortest_paths;
+        self.shortest_routes
+            .entry((start, end))
+            .or_insert_with(|| {
+                let route = shortest_path(
+                    shortest_paths,
+                    graph.node_name(start),
+                    graph.node_name(end)
+                );
+                graph.route_ids(&route).filter(|route| route.len() > 1)
+            })
+            .as_ref()
+    }
+
+    /// A route from the start to the end of `planned`, or `planned` itself if no route is found.
+    fn route(&mut self, planned: &[usize], random: &mut Random) -> Vec<usize> {
+        let (start, end) = (planned[0], planned[planned.len() - 1]);
+        let route = match self.params.deviation {
+            Deviation::Shortest => self.shortest_route(start, end).cloned(),
+            Deviation::NearShortest => self.near_shortest_route(start, end, random),
+        };
+        route.unwrap_or_else(|| planned.to_vec())
+    }
+
+    fn near_shortest_route(
+        &mut self,
+        start: usize,
+        end: usize,
+        random: &mut Random
+    ) -> Option<Vec<usize>> {
+        let graph = self.graph;
+        let costs = self.costs_to
+            .entry(end)
+            .or_insert_with(|| {
+                // the ground node can only be the start or the end of a route
+                graph.cheapest_costs(end, |node, edge| {
+                    if graph.is_ground(node) && node != end {
+                        f64::INFINITY
+                    } else {
+                        walking_cost(node, edge, graph)
+                    }
+                })
+            });
+        if !costs[start].is_finite() {
+            return None;
+        }
+        let budget = costs[start] * (1.0 + self.params.slack) + 1e-6;
+        let mut route = vec![start];
+        let mut walked = 0.0;
+        let mut candidates = vec![];
+        while route[route.len() - 1] != end {
+            let node = route[route.len() - 1];
+            candidates.clear();
+            for edge in graph.edges(node) {
+                let cost = walking_cost(node, edge, graph);
+                let is_open = !route.contains(&edge.to) &&
+                    (!graph.is_ground(edge.to) || edge.to == end);
+                if is_open && walked + cost + costs[edge.to] <= budget {
+                    candidates.push((edge.to, cost));
+                }
+            }
+            if candidates.is_empty() {
+                return self.shortest_route(start, end).cloned();
+            }
+            let (next, cost) = candidates[random.below(candidates.len())];
+            route.push(next);
+            walked += cost;
+        }
+        Some(route)
+    }
+}
+
+/// Runs the Monte Carlo simulation of `routes`. `progress` is called with the percentage of
+/// transitions done.
+pub fn simulate(
+    graph: &BuildingGraph,
+    shortest_paths: &ShortestPaths,
+    routes: &CompactRoutes,
+    params: MonteCarloParams,
+    progress: impl Fn(i32)
+) -> MonteCarloReport {
+    let graph_ids: Vec<Option<usize>> = routes
+        .nodes()
+        .map(|(_, name)| graph.node_id(name))
+        .collect();
+    let edge_distances = graph.edge_distances();
+    let performance_index = |loads: &[u32]| -> f64 {
+        loads
+            .iter()
+            .zip(&edge_distances)
+            .filter(|(load, _)| **load > 0)
+            .map(|(load, distance)| edge_total_performance(*distance as f64, *load))
+            .sum()
+    };
+    let mut deviations = Deviations {
+        graph,
+        shortest_paths,
+        params,
+        shortest_routes: HashMap::new(),
+        costs_to: HashMap::new(),
+    };
+    // the seeds of the runs are drawn from one generator, so that no two runs of nearby seeds
+    // share a seed
+    let mut seeds = Random::new(params.seed);
+    let run_seeds: Vec<u64> = (0..params.runs).map(|_| seeds.next_u64()).collect();
+
+    let mut transitions = Vec::with_capacity(DAY_COUNT * TRANSITION_COUNT);
+    for day in 1..=DAY_COUNT as u32 {
+        for transition in 0..TRANSITION_COUNT {
+            let planned_routes: Vec<Vec<usize>> = (0..routes.student_count())
+                .filter_map(|student_index| {
+                    routes
+                        .route(student_index, day, transition)
+                        .iter()
+                        .map(|node| graph_ids[node.index()])
+                        .collect::<Option<Vec<usize>>>()
+                })
+                .filter(|route| route.len() > 1)
+                .collect();
+
+            let mut planned_loads = vec![0; graph.edge_count()];
+            for route in &planned_routes {
+                graph.add_load(&mut planned_loads, route);
+            }
+            let mut run_loads: Vec<Vec<u32>> = Vec::with_capacity(params.runs);
+            let mut performance_indices = Vec::with_capacity(params.runs);
+            for run_seed in &run_seeds {
+                let mut random = Random::for_transition(*run_seed, day, transition);
+                let mut loads = vec![0; graph.edge_count()];
+                for route in &planned_routes {
+                    if random.next_f64() < params.compliance {
+                        graph.add_load(&mut loads, route);
+                    } else {
+                        let route = deviations.route(route, &mut random);
+                        graph.add_load(&mut loads, &route);
+                    }
+                }
+                performance_indices.push(performance_index(&loads));
+                run_loads.push(loads);
+            }
+
+            let mut samples = TransitionSamples {
+                mean_loads: vec![0.0; graph.edge_count()],
+                percentile_95_loads: vec![0; graph.edge_count()],
+                planned_performance_index: performance_index(&planned_loads),
+                performance_indices,
+            };
+            let mut edge_loads = Vec::with_capacity(params.runs);
+            for edge in 0..graph.edge_count() {
+                edge_loads.clear();
+                edge_loads.extend(run_loads.iter().map(|loads| loads[edge]));
+                if edge_loads.is_empty() {
+                    continue;
+                }
+                edge_loads.sort_unstable();
+                samples.mean_loads[edge] =
+                    (edge_loads.iter().sum::<u32>() as f64) / (edge_loads.len() as f64);
+                samples.percentile_95_loads[edge] =
+                    edge_loads[((edge_loads.len() - 1) * 95) / 100];
+            }
+            transitions.push(samples);
+            progress(((transitions.len() * 100) / (DAY_COUNT * TRANSITION_COUNT)) as i32);
+        }
+    }
+    MonteCarloReport { params, transitions }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::routing::Routes;
+
+    use super::*;
+
+    const PATHS: &str = "\
+A101 A1 50 0
+A1 B1 100 0
+B1 C1 100 0
+C1 C101 50 0
+A1 D1 110 0
+D1 C1 110 0
+";
+
+    /// Simulates 40 students walking from `A101` to `C101`, half of them through `D1` rather than
+    /// along the shortest route through `B1`.
+    fn simulate_compliance(compliance: f64) -> MonteCarloReport {
+        let graph = BuildingGraph::parse(PATHS);
+        let shortest_paths = ShortestPaths::from([
+            ("A101C101".to_owned(), "A101 A1 B1 C1 C101".to_owned()),
+        ]);
+        let routes: Routes = (21001..=21040)
+            .map(|student| {
+                let route = if student % 2 == 0 {
+                    "A101 A1 D1 C1 C101"
+                } else {
+                    "A101 A1 B1 C1 C101"
+                };
+                let day_routes = HashMap::from([(1, route.to_owned())]);
+                (student.to_string(), HashMap::from([(1, day_routes)]))
+            })
+            .collect();
+        let routes = CompactRoutes::from_routes(&routes).unwrap();
+        let params = MonteCarloParams {
+            compliance,
+            deviation: Deviation::Shortest,
+            runs: 20,
+            ..Default::default()
+        };
+        simulate(&graph, &shortest_paths, &routes, params, |_| ())
+    }
+
+    #[test]
+    fn full_compliance_has_no_spread() {
+        let report = simulate_compliance(1.0);
+        let performance_index = report.performance_index();
+        assert_eq!(performance_index.mean, report.planned_performance_index());
+        assert_eq!(performance_index.low, performance_index.mean);
+        assert_eq!(performance_index.high, performance_index.mean);
+        let samples = report.transition(1, 1).unwrap();
+        assert!(
+            samples.mean_loads
+                .iter()
+                .zip(&samples.percentile_95_loads)
+                .all(|(mean, percentile_95)| *mean == (*percentile_95 as f64))
+        );
+
+        let performance_index = simulate_compliance(0.5).performance_index();
+        assert!(performance_index.low < performance_index.high);
+    }
+}
@@ -0,0 +1,175 @@
+//! Simulated annealing.
+//!
+//! Every iteration picks a random student and proposes the cheapest path between the ends of their
+//! route where walking an edge costs the increase of its performance index, with every edge cost
+//! scaled by a random factor so that repeated proposals differ. A proposal that lowers the
+//! performance index of the transition is always taken, and one that raises it by Δ is taken with
+//! probability e^(−Δ/T). The temperature T starts at a fraction of the performance index of an
+//! average route and is lowered after every iteration. Once it is negligible, the best routes seen
+//! at the end of a batch are restored and the transition has converged.
+
+use super::{ OptimizationContext, RouteOptimizer, TransitionRoutes };
+
+/// Initial temperature, as a fraction of the performance index of an average route.
+const INITIAL_TEMPERATURE: f64 = 0.05;
+/// Factor the temperature is multiplied by after every iteration.
+const COOLING_RATE: f64 = 0.9995;
+/// Temperature at which the transition has converged, as a fraction of the initial temperature.
+const FINAL_TEMPERATURE: f64 = 1e-4;
+/// Largest random increase of the cost of an edge when proposing a route.
+const PROPOSAL_NOISE: f64 = 0.5;
+
+pub(super) struct AnnealingState {
+    routes: TransitionRoutes,
+    iterations: u64,
+    /// Iterations of the transition before simulated annealing started on it, which the cooling
+    /// schedule does not count.
+    start_iterations: u64,
+    initial_temperature: f64,
+    best_routes: Vec<Vec<usize>>,
+    best_objective: f64,
+}
+
+impl AnnealingState {
+    pub(super) fn new(routes: TransitionRoutes, iterations: u64, start_iterations: u64) -> Self {
+        Self {
+            iterations,
+            start_iterations,
+            initial_temperature: (INITIAL_TEMPERATURE * routes.objective) /
+            (routes.routes.len().max(1) as f64),
+            best_routes: routes.nodes(),
+            best_objective: routes.objective,
+            routes,
+        }
+    }
+
+    fn temperature(&self) -> f64 {
+        self.initial_temperature * COOLING_RATE.powf((self.iterations - self.start_iterations) as f64)
+    }
+
+    fn iterate(&mut self, context: &OptimizationContext<'_>) {
+        let index = self.routes.random.below(self.routes.routes.len());
+        let proposal = self.routes.marginal_cost_path(context, index, PROPOSAL_NOISE);
+        if
+            let Some(new_nodes) = proposal.filter(
+                |nodes| *nodes != self.routes.routes[index].nodes
+            )
+        {
+            let delta = self.routes.reroute_delta(context, index, &new_nodes);
+            let accepted =
+                delta <= 0.0 || self.routes.random.next_f64() < (-delta / self.temperature()).exp();
+            if
+                accepted &&
+                context.allow_reroute(self.routes.day, &self.routes.routes[index], &new_nodes)
+            {
+                self.routes.reroute(context, index, new_nodes);
+            }
+        }
+        self.iterations += 1;
+    }
+}
+
+impl RouteOptimizer for AnnealingState {
+    fn routes(&self) -> &TransitionRoutes {
+        &self.routes
+    }
+
+    fn iterations(&self) -> u64 {
+        self.iterations
+    }
+
+    fn is_converged(&self) -> bool {
+        self.routes.routes.is_empty() ||
+            self.temperature() <= FINAL_TEMPERATURE * self.initial_temperature
+    }
+
+    fn run_batch(&mut self, context: &OptimizationContext<'_>) {
+        for _ in 0..context.params.batch_size {
+            if self.is_converged() {
+                break;
+            }
+            self.iterate(context);
+        }
+        self.routes.update_loads(context);
+        if self.routes.objective < self.best_objective {
+            self.best_routes = self.routes.nodes();
+            self.best_objective = self.routes.objective;
+        } else if self.is_converged() {
+            let best_routes = std::mem::take(&mut self.best_routes);
+            self.routes.restore(context, &best_routes);
+            self.best_routes = best_routes;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{
+        stopping::StopReason,
+        tests::{
+            assert_keeps_detour_limits,
+            assert_keeps_pinning_rules,
+            assert_same_seed_same_routes,
+            batch_performance_indices,
+            fixture_store,
+            optimize,
+            params,
+        },
+        Checkpoint,
+        OptimizationStrategy,
+        RouteStore,
+    };
+
+    /// Iterations of the transition from `A101` to `C101`.
+    fn iterations(store: &RouteStore) -> u64 {
+        store.progress(1, 1).unwrap().iterations
+    }
+
+    #[test]
+    fn resuming_keeps_the_cooling_schedule() {
+        let params = params(OptimizationStrategy::SimulatedAnnealing);
+        let store = fixture_store();
+        assert_eq!(optimize(&store, params), StopReason::Converged);
+        let uninterrupted = iterations(&store);
+
+        let mut paused = params;
+        paused.stopping.max_iterations = Some(uninterrupted / 2);
+        let store = fixture_store();
+        assert_eq!(optimize(&store, paused), StopReason::MaxIterations);
+        let checkpoint: Checkpoint = serde_json
+            ::from_str(&serde_json::to_string(&store.checkpoint(paused)).unwrap())
+            .unwrap();
+        let store = RouteStore::from_checkpoint(checkpoint);
+        assert_eq!(optimize(&store, params), StopReason::Converged);
+        assert_eq!(iterations(&store), uninterrupted);
+
+        // A converged transition is not reheated either.
+        assert_eq!(optimize(&store, params), StopReason::Converged);
+        assert_eq!(iterations(&store), uninterrupted);
+    }
+
+    #[test]
+    fn ends_with_the_best_routes() {
+        let performance_indices = batch_performance_indices(
+            OptimizationStrategy::SimulatedAnnealing
+        );
+        let best = performance_indices.iter().copied().fold(f64::INFINITY, f64::min);
+        assert!(best < performance_indices[0]);
+        assert_eq!(*performance_indices.last().unwrap(), best);
+    }
+
+    #[test]
+    fn keeps_pinning_rules() {
+        assert_keeps_pinning_rules(OptimizationStrategy::SimulatedAnnealing);
+    }
+
+    #[test]
+    fn keeps_detour_limits() {
+        assert_keeps_detour_limits(OptimizationStrategy::SimulatedAnnealing);
+    }
+
+    #[test]
+    fn same_seed_gives_same_routes() {
+        assert_same_seed_same_routes(OptimizationStrategy::SimulatedAnnealing);
+    }
+}
@@ -0,0 +1,201 @@
+//! Checkpoints of the route optimization.
+//!
+//! A checkpoint extends the `routes.json` format of the C++ optimizer with the progress of every
+//! transition and the parameters of the run, so that either optimizer can resume from it. Files are
+//! written next to their destination first and then moved over it, so a crash while saving leaves
+//! the previous checkpoint intact. Every checkpoint is also kept in a history, from which an
+//! earlier checkpoint with a better performance index can be restored.
+
+use std::{
+    collections::HashMap,
+    fs::{ self, File },
+    io::{ self, Write },
+    path::{ Path, PathBuf },
+};
+
+use crate::{ pinning::PinningRule, routing::{ EditedRoutes, Routes } };
+
+use super::{ stopping::StopReason, OptimizationParams, TransitionProgress };
+
+/// Number of checkpoints kept in the history, besides the one with the best performance index.
+pub const HISTORY_LENGTH: usize = 20;
+
+#[derive(Clone, Default, serde::Deserialize, serde::Serialize)]
+pub struct Checkpoint {
+    /// Iterations of each day.
+    pub iter: [u64; 5],
+    /// Day → transition → performance index.
+    pub indices: HashMap<u32, HashMap<usize, u128>>,
+    pub routes: Routes,
+    /// Day → transition → progress. Not saved by the C++ optimizer.
+    #[serde(default)]
+    pub progress: HashMap<u32, HashMap<usize, TransitionProgress>>,
+    #[serde(default)]
+    pub params: Option<OptimizationParams>,
+    /// Why the optimization stopped, or `None` if it was still running.
+    #[serde(default)]
+    pub stop_reason: Option<StopReason>,
+    /// Routes edited by hand, which the optimizer leaves untouched.
+    #[serde(default)]
+    pub edited: EditedRoutes,
+    /// Rules the routes keep to.
+    #[serde(default)]
+    pub pinning_rules: Vec<PinningRule>,
+    /// Milliseconds since the Unix epoch.
+    #[serde(default)]
+    pub saved_at: u64,
+}
+
+impl Checkpoint {
+    pub fn iterations(&self) -> u64 {
+        self.iter.iter().sum()
+    }
+
+    pub fn performance_index(&self) -> u128 {
+        self.indices
+            .values()
+            .flat_map(|day_indices| day_indices.values())
+            .sum()
+    }
+
+    pub fn load(path: &Path) -> io::Result<Self> {
+        Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        write_atomically(path, serde_json::to_string(self)?.as_bytes())
+    }
+}
+
+/// Writes `content` to a temporary file and moves it over `path` once it is complete.
+pub fn write_atomically(path: &Path, content: &[u8]) -> io::Result<()> {
+    let mut temporary_path = path.as_os_str().to_owned();
+    temporary_path.push(".tmp");
+    let mut file = File::create(&temporary_path)?;
+    file.write_all(content)?;
+    file.sync_all()?;
+    fs::rename(&temporary_path, path)
+}
+
+#[derive(Clone, serde::Deserialize, serde::Serialize)]
+pub struct CheckpointSummary {
+    pub file_name: String,
+    pub iterations: u64,
+    pub performance_index: u128,
+    /// Milliseconds since the Unix epoch.
+    pub saved_at: u64,
+}
+
+/// Checkpoints saved in a directory, together with a `history.json` index of their summaries.
+pub struct CheckpointHistory {
+    directory: PathBuf,
+}
+
+impl CheckpointHistory {
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        Self { directory: directory.into() }
+    }
+
+    fn index_path(&self) -> PathBuf {
+        self.directory.join("history.json")
+    }
+
+    /// Summaries of the kept checkpoints, oldest first. A missing or unreadable index gives an
+    /// empty history.
+    pub fn summaries(&self) -> Vec<CheckpointSummary> {
+        fs::read_to_string(self.index_path())
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn load(&self, summary: &CheckpointSummary) -> io::Result<Checkpoint> {
+        Checkpoint::load(&self.directory.join(&summary.file_name))
+    }
+
+    /// Adds `checkpoint` to the history, dropping the oldest checkpoints beyond `HISTORY_LENGTH`
+    /// except the best one. Returns the summaries of the kept checkpoints.
+    pub fn save(&self, checkpoint: &Checkpoint) -> io::Result<Vec<CheckpointSummary>> {
+        fs::create_dir_all(&self.directory)?;
+        let summary = CheckpointSummary {
+            file_name: format!(
+                "checkpoint_{}_{}.json",
+                checkpoint.saved_at,
+                checkpoint.iterations()
+            ),
+            iterations: checkpoint.iterations(),
+            performance_index: checkpoint.performance_index(),
+            saved_at: checkpoint.saved_at,
+        };
+        checkpoint.save(&self.directory.join(&summary.file_name))?;
+        let mut summaries = self.summaries();
+        summaries.push(summary);
+        while summaries.len() > HISTORY_LENGTH + 1 {
+            let best = summaries
+                .iter()
+                .enumerate()
+                .min_by_key(|(_, summary)| summary.performance_index)
+                .map(|(index, _)| index);
+            let oldest = if best == Some(0) { 1 } else { 0 };
+            let removed = summaries.remove(oldest);
+            fs::remove_file(self.directory.join(&removed.file_name)).ok();
+        }
+        write_atomically(&self.index_path(), serde_json::to_string(&summaries)?.as_bytes())?;
+        Ok(summaries)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn checkpoint(saved_at: u64, performance_index: u128) -> Checkpoint {
+        Checkpoint {
+            iter: [saved_at, 0, 0, 0, 0],
+            indices: HashMap::from([(1, HashMap::from([(0, performance_index)]))]),
+            saved_at,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn history_keeps_the_latest_checkpoints_and_the_best() {
+        let directory = std::env
+            ::temp_dir()
+            .join(format!("optiway_checkpoint_history_{}", std::process::id()));
+        fs::remove_dir_all(&directory).ok();
+        let history = CheckpointHistory::new(&directory);
+
+        history.save(&checkpoint(1, 10)).unwrap();
+        let mut summaries = vec![];
+        for saved_at in 2..=40 {
+            summaries = history.save(&checkpoint(saved_at, 1000 - (saved_at as u128))).unwrap();
+        }
+
+        assert_eq!(summaries.len(), HISTORY_LENGTH + 1);
+        assert_eq!(summaries[0].saved_at, 1);
+        assert_eq!(summaries[0].performance_index, 10);
+        let latest: Vec<u64> = summaries[1..]
+            .iter()
+            .map(|summary| summary.saved_at)
+            .collect();
+        assert_eq!(latest, (21..=40).collect::<Vec<u64>>());
+        assert_eq!(
+            history
+                .summaries()
+                .iter()
+                .map(|summary| summary.saved_at)
+                .collect::<Vec<u64>>(),
+            summaries
+                .iter()
+                .map(|summary| summary.saved_at)
+                .collect::<Vec<u64>>()
+        );
+        // the files of the dropped checkpoints are removed, and the kept ones can be loaded
+        assert_eq!(fs::read_dir(&directory).unwrap().count(), HISTORY_LENGTH + 2);
+        assert_eq!(history.load(&summaries[0]).unwrap().performance_index(), 10);
+        assert_eq!(history.load(&summaries[HISTORY_LENGTH]).unwrap().iterations(), 40);
+
+        fs::remove_dir_all(&directory).ok();
+    }
+}
@@ -0,0 +1,338 @@
+//! Min-cost multicommodity flow relaxation.
+//!
+//! Students walking between the same two nodes under the same pinning rules form a commodity, and
+//! the routes of a transition are a flow of every commodity through the building graph in which the
+//! cost of an edge is its performance index. The relaxation lets a commodity split over its paths
+//! in any fraction, and is solved with the Frank–Wolfe algorithm: every iteration sends each
+//! commodity along its cheapest path where an edge costs the derivative of its performance index at
+//! the current flow, and moves the flow towards that assignment by the step that lowers the total
+//! cost the most. After every batch the fractional flow is rounded to whole students, keeping
+//! students on their current path where possible, and the rounded routes are kept if they lower
+//! the performance index.
+
+use std::collections::HashMap;
+
+use crate::evaluation::edge_performance;
+
+use super::{ OptimizationContext, RouteOptimizer, TransitionRoutes };
+
+/// Relative gap between the cost of the flow and its lower bound below which the flow is optimal.
+const CONVERGENCE_GAP: f64 = 1e-3;
+/// Number of iterations of a run after which the flow is taken as optimal.
+const MAX_ITERATIONS: u64 = 1000;
+/// Steps of the golden-section line search.
+const LINE_SEARCH_STEPS: usize = 40;
+/// Flow below which a path is dropped from its commodity.
+const MIN_PATH_FLOW: f64 = 1e-6;
+
+/// Students walking between the same two nodes.
+struct Commodity {
+    start: usize,
+    end: usize,
+    /// Constraint of the pinning rules matching the students.
+    constraint: Option<usize>,
+    /// Route indices.
+    students: Vec<usize>,
+    /// Paths with the number of students on them, which need not be whole.
+    paths: Vec<(Vec<usize>, f64)>,
+}
+
+pub(super) struct FlowState {
+    routes: TransitionRoutes,
+    iterations: u64,
+    /// Iterations of the transition before the flow relaxation started on it, which the iteration
+    /// limit does not count.
+    start_iterations: u64,
+    commodities: Vec<Commodity>,
+    /// Edge id → number of students walking the edge in the fractional flow, including the routes
+    /// that cannot be changed.
+    flows: Vec<f64>,
+    converged: bool,
+}
+
+/// Performance index of an edge walked by `flow` students, which need not be whole.
+fn flow_cost(distance: f64, flow: f64) -> f64 {
+    flow * edge_performance(distance, flow)
+}
+
+/// Derivative of `flow_cost` with respect to the flow.
+fn flow_marginal_cost(distance: f64, flow: f64) -> f64 {
+    let congestion = ((flow - 300.0) / 200.0).tanh();
+    distance * (2.0 + congestion) + (flow * distance * (1.0 - congestion * congestion)) / 200.0
+}
+
+impl FlowState {
+    pub(super) fn new(
+        context: &OptimizationContext<'_>,
+        routes: TransitionRoutes,
+        iterations: u64,
+        start_iterations: u64
+    ) -> Self {
+        let mut commodities: Vec<Commodity> = vec![];
+        let mut commodity_indices = HashMap::new();
+        for (index, route) in routes.routes.iter().enumerate() {
+            let (start, end) = route.ends();
+            let constraint = route.constraint;
+            let commodity_index = *commodity_indices
+                .entry((start, end, constraint))
+                .or_insert_with(|| {
+                    commodities.push(Commodity {
+                        start,
+                        end,
+                        constraint,
+                        students: vec![],
+                        paths: vec![],
+                    });
+                    commodities.len() - 1
+                });
+            let commodity = &mut commodities[commodity_index];
+            commodity.students.push(index);
+            match commodity.paths.iter_mut().find(|(nodes, _)| *nodes == route.nodes) {
+                Some((_, flow)) => {
+                    *flow += 1.0;
+                }
+                None => commodity.paths.push((route.nodes.clone(), 1.0)),
+            }
+        }
+        let mut state = Self {
+            iterations,
+            start_iterations,
+            commodities,
+            flows: vec![0.0; routes.loads.len()],
+            converged: routes.routes.is_empty(),
+            routes,
+        };
+        state.update_flows(context);
+        state
+    }
+
+    fn update_flows(&mut self, context: &OptimizationContext<'_>) {
+        for (flow, fixed_load) in self.flows.iter_mut().zip(&self.routes.fixed_loads) {
+            *flow = *fixed_load as f64;
+        }
+        for (nodes, flow) in self.commodities.iter().flat_map(|commodity| &commodity.paths) {
+            for edge in context.graph.counted_edges(nodes) {
+                self.flows[edge.id] += flow;
+            }
+        }
+    }
+
+    fn total_cost(
+        &self,
+        context: &OptimizationContext<'_>,
+        flows: impl Iterator<Item = f64>
+    ) -> f64 {
+        flows
+            .enumerate()
+            .map(|(edge, flow)| flow_cost(context.edge_distances[edge] as f64, flow))
+            .sum()
+    }
+
+    /// Step towards `target` that lowers the total cost the most, by golden-section search.
+    fn line_search(&self, context: &OptimizationContext<'_>, target: &[f64]) -> f64 {
+        let cost = |step: f64| {
+            self.total_cost(
+                context,
+                self.flows
+                    .iter()
+                    .zip(target)
+                    .map(|(flow, target_flow)| flow + step * (target_flow - flow))
+            )
+        };
+        let ratio = (5f64.sqrt() - 1.0) / 2.0;
+        let (mut low, mut high) = (0.0, 1.0);
+        for _ in 0..LINE_SEARCH_STEPS {
+            let step1 = high - ratio * (high - low);
+            let step2 = low + ratio * (high - low);
+            if cost(step1) < cost(step2) {
+                high = step2;
+            } else {
+                low = step1;
+            }
+        }
+        (low + high) / 2.0
+    }
+
+    fn iterate(&mut self, context: &OptimizationContext<'_>) {
+        self.iterations += 1;
+        let costs: Vec<f64> = self.flows
+            .iter()
+            .enumerate()
+            .map(|(edge, flow)| flow_marginal_cost(context.edge_distances[edge] as f64, *flow))
+            .collect();
+        let mut target: Vec<f64> = self.routes.fixed_loads
+            .iter()
+            .map(|load| *load as f64)
+            .collect();
+        let mut cheapest_paths = Vec::with_capacity(self.commodities.len());
+        for commodity in &self.commodities {
+            let path = context
+                .cheapest_path(commodity.start, commodity.end, commodity.constraint, |node, edge| {
+                    if context.graph.is_ground(node) || context.graph.is_ground(edge.to) {
+                        edge.distance as f64
+                    } else {
+                        costs[edge.id]
+                    }
+                })
+                .unwrap_or_else(|| commodity.paths[0].0.clone());
+            for edge in context.graph.counted_edges(&path) {
+                target[edge.id] += commodity.students.len() as f64;
+            }
+            cheapest_paths.push(path);
+        }
+        let current_cost: f64 = costs
+            .iter()
+            .zip(&self.flows)
+            .map(|(cost, flow)| cost * flow)
+            .sum();
+        let target_cost: f64 = costs
+            .iter()
+            .zip(&target)
+            .map(|(cost, flow)| cost * flow)
+            .sum();
+        if
+            current_cost - target_cost <= CONVERGENCE_GAP * current_cost ||
+            self.iterations - self.start_iterations >= MAX_ITERATIONS
+        {
+            self.converged = true;
+            return;
+        }
+        let step = self.line_search(context, &target);
+        for (commodity, path) in self.commodities.iter_mut().zip(cheapest_paths) {
+            let demand = commodity.students.len() as f64;
+            for (_, flow) in &mut commodity.paths {
+                *flow *= 1.0 - step;
+            }
+            match commodity.paths.iter_mut().find(|(nodes, _)| *nodes == path) {
+                Some((_, flow)) => {
+                    *flow += step * demand;
+                }
+                None => commodity.paths.push((path, step * demand)),
+            }
+            commodity.paths.retain(|(_, flow)| *flow > MIN_PATH_FLOW);
+        }
+        self.update_flows(context);
+    }
+
+    /// Number of students on each path of a commodity, rounded so that they add up to the number
+    /// of students of the commodity.
+    fn rounded_paths(commodity: &Commodity) -> Vec<(&[usize], usize)> {
+        let mut counts: Vec<(&[usize], usize, f64)> = commodity.paths
+            .iter()
+            .map(|(nodes, flow)| (nodes.as_slice(), flow.floor() as usize, flow.fract()))
+            .collect();
+        let rounded: usize = counts
+            .iter()
+            .map(|(_, count, _)| count)
+            .sum();
+        counts.sort_by(|count1, count2| count2.2.total_cmp(&count1.2));
+        let remainder = commodity.students.len().saturating_sub(rounded);
+        if !counts.is_empty() {
+            let path_count = counts.len();
+            for index in 0..remainder {
+                counts[index % path_count].1 += 1;
+            }
+        }
+        counts
+            .into_iter()
+            .map(|(nodes, count, _)| (nodes, count))
+            .collect()
+    }
+
+    /// Moves students to the rounded flow, keeping the routes if they lower the objective.
+    fn round(&mut self, context: &OptimizationContext<'_>) {
+        let previous_routes = self.routes.nodes();
+        let previous_objective = self.routes.objective;
+        for commodity in &self.commodities {
+            let mut counts = Self::rounded_paths(commodity);
+            let mut unassigned = vec![];
+            for &index in &commodity.students {
+                let nodes = &self.routes.routes[index].nodes;
+                match counts.iter_mut().find(|(path, count)| *count > 0 && *path == nodes) {
+                    Some((_, count)) => {
+                        *count -= 1;
+                    }
+                    None => unassigned.push(index),
+                }
+            }
+            for index in unassigned {
+                let route = &self.routes.routes[index];
+                if
+                    let Some((path, count)) = counts
+                        .iter_mut()
+                        .find(|(path, count)| {
+                            *count > 0 && context.allow_reroute(self.routes.day, route, path)
+                        })
+                {
+                    *count -= 1;
+                    self.routes.reroute(context, index, path.to_vec());
+                }
+            }
+        }
+        self.routes.update_loads(context);
+        if self.routes.objective > previous_objective {
+            self.routes.restore(context, &previous_routes);
+        }
+    }
+}
+
+impl RouteOptimizer for FlowState {
+    fn routes(&self) -> &TransitionRoutes {
+        &self.routes
+    }
+
+    fn iterations(&self) -> u64 {
+        self.iterations
+    }
+
+    fn is_converged(&self) -> bool {
+        self.converged
+    }
+
+    fn run_batch(&mut self, context: &OptimizationContext<'_>) {
+        for _ in 0..context.params.batch_size {
+            if self.converged {
+                break;
+            }
+            self.iterate(context);
+        }
+        self.round(context);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{
+        tests::{
+            assert_keeps_detour_limits,
+            assert_keeps_pinning_rules,
+            assert_same_seed_same_routes,
+            batch_performance_indices,
+        },
+        OptimizationStrategy,
+    };
+
+    #[test]
+    fn no_batch_raises_the_performance_index() {
+        let performance_indices = batch_performance_indices(OptimizationStrategy::FlowRelaxation);
+        assert!(performance_indices.last() < performance_indices.first());
+        for pair in performance_indices.windows(2) {
+            assert!(pair[1] <= pair[0], "{:?}", pair);
+        }
+    }
+
+    #[test]
+    fn keeps_pinning_rules() {
+        assert_keeps_pinning_rules(OptimizationStrategy::FlowRelaxation);
+    }
+
+    #[test]
+    fn keeps_detour_limits() {
+        assert_keeps_detour_limits(OptimizationStrategy::FlowRelaxation);
+    }
+
+    #[test]
+    fn same_seed_gives_same_routes() {
+        assert_same_seed_same_routes(OptimizationStrategy::FlowRelaxation);
+    }
+}
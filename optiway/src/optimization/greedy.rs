@@ -0,0 +1,219 @@
+//! Greedy worst-first rerouting, as in `multi_agent_path_finding/multi-objective-agent.cpp`.
+//!
+//! Every iteration reroutes the student whose route has the highest performance index along the
+//! cheapest path, where every student on an edge adds a large penalty to its cost, and keeps the
+//! new route if it has a lower performance index. The congestion is only recalculated after each
+//! batch, and the whole batch is undone if it made the performance index of the transition worse.
+
+use std::{ cmp::Ordering, collections::BinaryHeap };
+
+use crate::evaluation::edge_performance;
+
+use super::{ OptimizationContext, RouteOptimizer, TransitionRoutes };
+
+/// Cost added to an edge for every student walking it when rerouting.
+const CONGESTION_PENALTY: f64 = 10000.0;
+
+/// Entry of the route queue, ordered so that `BinaryHeap` pops the highest performance index first.
+struct QueueEntry(f64, usize);
+
+impl PartialEq for QueueEntry {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for QueueEntry {}
+
+impl PartialOrd for QueueEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for QueueEntry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0).then_with(|| self.1.cmp(&other.1))
+    }
+}
+
+pub(super) struct GreedyState {
+    routes: TransitionRoutes,
+    iterations: u64,
+    /// Routes that may still be rerouted, by performance index with the loads of the last batch.
+    queue: BinaryHeap<QueueEntry>,
+    /// Routes that are not rerouted any more because rerouting them did not help.
+    frozen: Vec<bool>,
+    /// Ends of the last route whose reroute was rejected. Routes between the same nodes are frozen
+    /// without trying, as they would be rejected too.
+    last_rejected: Option<(usize, usize)>,
+    /// Routes at the end of the last batch that did not make the objective worse.
+    best_routes: Vec<Vec<usize>>,
+    best_objective: f64,
+}
+
+impl GreedyState {
+    pub(super) fn new(
+        context: &OptimizationContext<'_>,
+        routes: TransitionRoutes,
+        iterations: u64
+    ) -> Self {
+        let mut state = Self {
+            iterations,
+            queue: BinaryHeap::new(),
+            frozen: vec![false; routes.routes.len()],
+            last_rejected: None,
+            best_routes: routes.nodes(),
+            best_objective: routes.objective,
+            routes,
+        };
+        state.fill_queue(context);
+        state
+    }
+
+    fn route_performance(&self, context: &OptimizationContext<'_>, nodes: &[usize]) -> f64 {
+        context.graph
+            .counted_edges(nodes)
+            .map(|edge| {
+                edge_performance(edge.distance as f64, self.routes.loads[edge.id] as f64)
+            })
+            .sum()
+    }
+
+    fn fill_queue(&mut self, context: &OptimizationContext<'_>) {
+        self.queue = self.routes.routes
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| !self.frozen[*index])
+            .map(|(index, route)| QueueEntry(self.route_performance(context, &route.nodes), index))
+            .collect();
+    }
+
+    fn iterate(&mut self, context: &OptimizationContext<'_>) {
+        while let Some(QueueEntry(performance, index)) = self.queue.pop() {
+            let route = &self.routes.routes[index];
+            if Some(route.ends()) == self.last_rejected {
+                self.frozen[index] = true;
+                continue;
+            }
+            let (start, end) = route.ends();
+            let new_nodes = context.cheapest_path(start, end, route.constraint, |node, edge| {
+                if context.graph.is_ground(node) || context.graph.is_ground(edge.to) {
+                    edge.distance as f64
+                } else {
+                    (edge.distance as f64) +
+                        CONGESTION_PENALTY * (self.routes.loads[edge.id] as f64)
+                }
+            });
+            match new_nodes {
+                Some(new_nodes) if
+                    self.route_performance(context, &new_nodes) < performance &&
+                    context.allow_reroute(self.routes.day, route, &new_nodes)
+                => {
+                    let new_performance = self.route_performance(context, &new_nodes);
+                    self.routes.routes[index].nodes = new_nodes;
+                    self.queue.push(QueueEntry(new_performance, index));
+                }
+                _ => {
+                    self.frozen[index] = true;
+                    self.last_rejected = Some(route.ends());
+                }
+            }
+            break;
+        }
+        self.iterations += 1;
+    }
+
+    /// Recalculates the congestion, undoing the batch if it made the objective worse.
+    fn finish_batch(&mut self, context: &OptimizationContext<'_>) {
+        self.routes.update_loads(context);
+        if self.routes.objective > self.best_objective {
+            let mut worst = None;
+            for (index, best_nodes) in self.best_routes.iter().enumerate() {
+                let route = &self.routes.routes[index];
+                if
+                    route.nodes != *best_nodes &&
+                    context.allow_reroute(self.routes.day, route, best_nodes)
+                {
+                    self.routes.routes[index].nodes = best_nodes.clone();
+                }
+                if !self.frozen[index] {
+                    let performance = self.route_performance(context, best_nodes);
+                    if worst.is_none_or(|(_, worst_performance)| performance > worst_performance) {
+                        worst = Some((index, performance));
+                    }
+                }
+            }
+            // As in the C++ optimizer, the route that would be rerouted first is given up on so
+            // that the next batch does not repeat this one.
+            if let Some((index, _)) = worst {
+                self.frozen[index] = true;
+            }
+            self.routes.update_loads(context);
+        }
+        self.best_routes = self.routes.nodes();
+        self.best_objective = self.routes.objective;
+        self.fill_queue(context);
+    }
+}
+
+impl RouteOptimizer for GreedyState {
+    fn routes(&self) -> &TransitionRoutes {
+        &self.routes
+    }
+
+    fn iterations(&self) -> u64 {
+        self.iterations
+    }
+
+    fn is_converged(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    fn run_batch(&mut self, context: &OptimizationContext<'_>) {
+        for _ in 0..context.params.batch_size {
+            if self.queue.is_empty() {
+                break;
+            }
+            self.iterate(context);
+        }
+        self.finish_batch(context);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{
+        tests::{
+            assert_keeps_detour_limits,
+            assert_keeps_pinning_rules,
+            assert_same_seed_same_routes,
+            batch_performance_indices,
+        },
+        OptimizationStrategy,
+    };
+
+    #[test]
+    fn no_batch_raises_the_performance_index() {
+        let performance_indices = batch_performance_indices(OptimizationStrategy::Greedy);
+        assert!(performance_indices.last() < performance_indices.first());
+        for pair in performance_indices.windows(2) {
+            assert!(pair[1] <= pair[0], "{:?}", pair);
+        }
+    }
+
+    #[test]
+    fn keeps_pinning_rules() {
+        assert_keeps_pinning_rules(OptimizationStrategy::Greedy);
+    }
+
+    #[test]
+    fn keeps_detour_limits() {
+        assert_keeps_detour_limits(OptimizationStrategy::Greedy);
+    }
+
+    #[test]
+    fn same_seed_gives_same_routes() {
+        assert_same_seed_same_routes(OptimizationStrategy::Greedy);
+    }
+}
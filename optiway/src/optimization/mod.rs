@@ -0,0 +1,1363 @@
+//! In-process route optimization.
+//!
+//! Transitions are optimized independently of each other, so they are shared out to a pool of
+//! worker threads. A worker takes a transition that no other worker holds, runs one batch of
+//! iterations on it and writes its routes back to the `RouteStore` before queueing it again. The
+//! store therefore holds the latest routes of the whole week, which can be read at any time while
+//! the optimization is running, along with a congestion index that follows every route the workers
+//! change.
+//!
+//! The strategies minimise the objective of every transition, which is its performance index unless
+//! `OptimizationParams::robust_objective` asks for the mean or the worst performance index over
+//! the scenarios of the store (see `robustness`).
+
+mod annealing;
+pub mod checkpoint;
+mod flow;
+mod greedy;
+pub(crate) mod random;
+pub mod stopping;
+mod tabu;
+
+use std::{
+    collections::{ HashMap, VecDeque },
+    fmt::Display,
+    sync::{ atomic::{ AtomicBool, AtomicUsize, Ordering }, Mutex },
+    thread,
+    time::{ Duration, SystemTime, UNIX_EPOCH },
+};
+
+use crate::{
+    congestion::CongestionIndex,
+    evaluation::edge_total_performance,
+    fairness::{ DetourLimit, DetourLimits },
+    graph::{ BuildingGraph, GraphEdge },
+    pinning::{
+        CompiledRules,
+        PinningReport,
+        PinningRule,
+        RouteConstraint,
+        RouteRule,
+        RuleOutcome,
+    },
+    robustness::{ PerturbationParams, Scenario },
+    routing::{ shortest_path, EditedRoutes, Routes, ShortestPaths },
+};
+
+use annealing::AnnealingState;
+use checkpoint::Checkpoint;
+use flow::FlowState;
+use greedy::GreedyState;
+use random::Random;
+use stopping::{ StopCheck, StopReason, StoppingCriteria };
+use tabu::TabuState;
+
+/// How often `optimize_routes` reports on the progress of its workers.
+const MONITOR_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Clone, Copy, Default, serde::Deserialize, serde::Serialize)]
+pub struct TransitionProgress {
+    pub iterations: u64,
+    pub initial_performance_index: f64,
+    pub performance_index: f64,
+    /// Whether no route of the transition can be improved any further.
+    pub converged: bool,
+    /// State of the random number generator of the transition.
+    pub random_state: u64,
+    /// Strategy the transition was last optimized with.
+    #[serde(default)]
+    pub strategy: Option<OptimizationStrategy>,
+    /// Iterations of the transition when `strategy` started on it, from which the schedules of
+    /// simulated annealing and the flow relaxation are counted, so that a paused run resumes them
+    /// where it stopped.
+    #[serde(default)]
+    pub strategy_start: u64,
+}
+
+/// Routes of the whole week shared by the optimization workers.
+#[derive(Default)]
+pub struct RouteStore {
+    routes: Mutex<Routes>,
+    /// Day → transition → progress.
+    progress: Mutex<HashMap<u32, HashMap<usize, TransitionProgress>>>,
+    /// Iterations of each day made before the routes were loaded, e.g. by an earlier run whose
+    /// routes were saved to a file.
+    previous_iterations: [u64; 5],
+    /// Day → the transition a batch was last finished on.
+    latest_transitions: Mutex<[usize; 5]>,
+    /// Why the last run stopped, or `None` while it is running.
+    stop_reason: Mutex<Option<StopReason>>,
+    /// Congestion of the routes, from the start of the first run on.
+    congestion: Mutex<Option<CongestionIndex>>,
+    edited: Mutex<EditedRoutes>,
+    pinning_rules: Mutex<Vec<PinningRule>>,
+    /// Perturbed timetables of the robust objectives.
+    scenarios: Mutex<Vec<Scenario>>,
+}
+
+impl RouteStore {
+    pub fn new(routes: Routes, previous_iterations: [u64; 5]) -> Self {
+        Self {
+            routes: Mutex::new(routes),
+            previous_iterations,
+            ..Default::default()
+        }
+    }
+
+    /// A store with the routes and progress of `checkpoint`.
+    pub fn from_checkpoint(checkpoint: Checkpoint) -> Self {
+        let mut previous_iterations = checkpoint.iter;
+        for (day, day_progress) in &checkpoint.progress {
+            if let Some(iterations) = previous_iterations.get_mut((day - 1) as usize) {
+                *iterations = iterations.saturating_sub(
+                    day_progress
+                        .values()
+                        .map(|progress| progress.iterations)
+                        .sum()
+                );
+            }
+        }
+        Self {
+            routes: Mutex::new(checkpoint.routes),
+            progress: Mutex::new(checkpoint.progress),
+            previous_iterations,
+            stop_reason: Mutex::new(checkpoint.stop_reason),
+            edited: Mutex::new(checkpoint.edited),
+            pinning_rules: Mutex::new(checkpoint.pinning_rules),
+            ..Default::default()
+        }
+    }
+
+    pub fn checkpoint(&self, params: OptimizationParams) -> Checkpoint {
+        Checkpoint {
+            iter: self.iterations(),
+            indices: self.performance_indices(),
+            routes: self.routes(),
+            progress: self.progress.lock().unwrap().clone(),
+            params: Some(params),
+            stop_reason: self.stop_reason(),
+            edited: self.edited_routes(),
+            pinning_rules: self.pinning_rules(),
+            saved_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |duration| duration.as_millis() as u64),
+        }
+    }
+
+    pub fn routes(&self) -> Routes {
+        self.routes.lock().unwrap().clone()
+    }
+
+    pub fn progress(&self, day: u32, transition: usize) -> Option<TransitionProgress> {
+        self.progress
+            .lock()
+            .unwrap()
+            .get(&day)
+            .and_then(|day_progress| day_progress.get(&transition))
+            .copied()
+    }
+
+    /// Total number of iterations of each day.
+    pub fn iterations(&self) -> [u64; 5] {
+        let mut iterations = self.previous_iterations;
+        for (day, day_progress) in self.progress.lock().unwrap().iter() {
+            iterations[(day - 1) as usize] += day_progress
+                .values()
+                .map(|progress| progress.iterations)
+                .sum::<u64>();
+        }
+        iterations
+    }
+
+    /// Day → transition → performance index, in the format of `routes.json`.
+    pub fn performance_indices(&self) -> HashMap<u32, HashMap<usize, u128>> {
+        self.progress
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(day, day_progress)| {
+                (
+                    *day,
+                    day_progress
+                        .iter()
+                        .map(|(transition, progress)| {
+                            (*transition, progress.performance_index as u128)
+                        })
+                        .collect(),
+                )
+            })
+            .collect()
+    }
+
+    pub fn total_performance_index(&self) -> f64 {
+        self.progress
+            .lock()
+            .unwrap()
+            .values()
+            .flat_map(|day_progress| day_progress.values())
+            .map(|progress| progress.performance_index)
+            .sum()
+    }
+
+    pub fn stop_reason(&self) -> Option<StopReason> {
+        *self.stop_reason.lock().unwrap()
+    }
+
+    pub fn latest_transitions(&self) -> [usize; 5] {
+        *self.latest_transitions.lock().unwrap()
+    }
+
+    pub fn edited_routes(&self) -> EditedRoutes {
+        self.edited.lock().unwrap().clone()
+    }
+
+    /// Replaces a route by one edited by hand, which later runs leave untouched. The congestion
+    /// index and the performance index of the transition follow the change. Must not be called
+    /// while the routes are being optimized.
+    pub fn set_route(
+        &self,
+        graph: &BuildingGraph,
+        student_number: &str,
+        day: u32,
+        transition: usize,
+        route: String
+    ) {
+        if self.replace_route(graph, student_number, day, transition, route) {
+            self.edited.lock().unwrap().insert((student_number.to_owned(), day, transition));
+        }
+    }
+
+    /// Replaces a route, keeping the congestion index and the performance index of the
+    /// transition up to date. Returns whether the student has routes on the day.
+    fn replace_route(
+        &self,
+        graph: &BuildingGraph,
+        student_number: &str,
+        day: u32,
+        transition: usize,
+        route: String
+    ) -> bool {
+        let old_route = {
+            let mut routes = self.routes.lock().unwrap();
+            let Some(day_routes) = routes
+                .get_mut(student_number)
+                .and_then(|student_routes| student_routes.get_mut(&day)) else {
+                return false;
+            };
+            day_routes.insert(transition, route.clone()).unwrap_or_default()
+        };
+        let performance_index = self.with_congestion(|congestion| {
+            if
+                let (Some(old_nodes), Some(new_nodes)) = (
+                    graph.route_ids(&old_route),
+                    graph.route_ids(&route),
+                )
+            {
+                congestion.replace_route(graph, day, transition, &old_nodes, &new_nodes);
+            }
+            congestion.transition(day, transition).map(|congestion| congestion.performance_index)
+        });
+        if
+            let Some(progress) = self.progress
+                .lock()
+                .unwrap()
+                .get_mut(&day)
+                .and_then(|day_progress| day_progress.get_mut(&transition))
+        {
+            if let Some(Some(performance_index)) = performance_index {
+                progress.performance_index = performance_index;
+            }
+            // the route may open up improvements to the others
+            progress.converged = false;
+        }
+        true
+    }
+
+    pub fn pinning_rules(&self) -> Vec<PinningRule> {
+        self.pinning_rules.lock().unwrap().clone()
+    }
+
+    /// Makes the routes keep to `rules` in this and later runs. Pinned routes are set to the
+    /// shortest route, and routes that break a constraint are replaced by the shortest route that
+    /// keeps to it. Routes edited by hand are left untouched. Must not be called while the routes
+    /// are being optimized.
+    pub fn apply_rules(
+        &self,
+        graph: &BuildingGraph,
+        shortest_paths: &ShortestPaths,
+        rules: Vec<PinningRule>
+    ) -> PinningReport {
+        let mut report: PinningReport = rules
+            .iter()
+            .map(|rule| RuleOutcome { name: rule.name.clone(), ..Default::default() })
+            .collect();
+        let mut changes = vec![];
+        {
+            let routes = self.routes.lock().unwrap();
+            let edited = self.edited.lock().unwrap();
+            let mut compiled_rules = CompiledRules::new(graph, &rules);
+            for (student_number, student_routes) in routes.iter() {
+                for (&day, day_routes) in student_routes {
+                    for (&transition, route) in day_routes {
+                        if edited.contains(&(student_number.clone(), day, transition)) {
+                            continue;
+                        }
+                        let Some(nodes) = graph.route_ids(route) else {
+                            continue;
+                        };
+                        if nodes.len() < 2 || nodes.first() == nodes.last() {
+                            continue;
+                        }
+                        let (start, end) = (nodes[0], *nodes.last().unwrap());
+                        let (from, to) = (graph.node_name(start), graph.node_name(end));
+                        let matching_rules = compiled_rules.matching_rules(
+                            student_number,
+                            day,
+                            transition,
+                            from,
+                            to
+                        );
+                        let new_route = match compiled_rules.route_rule(&matching_rules) {
+                            RouteRule::Free => {
+                                continue;
+                            }
+                            RouteRule::Pinned => {
+                                Some(shortest_path(shortest_paths, from, to)).filter(
+                                    |shortest_route| !shortest_route.is_empty()
+                                )
+                            }
+                            RouteRule::Constrained(index) => {
+                                let constraint = compiled_rules.constraint(index);
+                                if constraint.allows(graph, &nodes) {
+                                    Some(route.clone())
+                                } else {
+                                    constraint
+                                        .cheapest_path(graph, start, end, |_, edge| {
+                                            edge.distance as f64
+                                        })
+                                        .map(|path| graph.route_string(&path))
+                                }
+                            }
+                        };
+                        for &index in &matching_rules {
+                            // a route changes because of the rules it breaks on its own
+                            let breaks_rule = new_route.as_ref().is_some_and(|new_route| {
+                                new_route != route &&
+                                    match compiled_rules.route_rule(&[index]) {
+                                        RouteRule::Constrained(constraint) => {
+                                            !compiled_rules
+                                                .constraint(constraint)
+                                                .allows(graph, &nodes)
+                                        }
+                                        _ => true,
+                                    }
+                            });
+                            let outcome = &mut report[index];
+                            outcome.matched += 1;
+                            outcome.changed += breaks_rule as usize;
+                            outcome.unsatisfiable += new_route.is_none() as usize;
+                        }
+                        if let Some(new_route) = new_route.filter(|new_route| new_route != route) {
+                            changes.push((student_number.clone(), day, transition, new_route));
+                        }
+                    }
+                }
+            }
+        }
+        for (student_number, day, transition, route) in changes {
+            self.replace_route(graph, &student_number, day, transition, route);
+        }
+        let mut pinning_rules = self.pinning_rules.lock().unwrap();
+        if *pinning_rules != rules {
+            // the rules change which routes the optimizer may take
+            for progress in self.progress
+                .lock()
+                .unwrap()
+                .values_mut()
+                .flat_map(|day_progress| day_progress.values_mut()) {
+                progress.converged = false;
+            }
+            *pinning_rules = rules;
+        }
+        report
+    }
+
+    /// Sets the perturbed timetables that later runs with a robust objective optimize over.
+    pub fn set_scenarios(&self, scenarios: Vec<Scenario>) {
+        *self.scenarios.lock().unwrap() = scenarios;
+        // the objective may have changed
+        for progress in self.progress
+            .lock()
+            .unwrap()
+            .values_mut()
+            .flat_map(|day_progress| day_progress.values_mut()) {
+            progress.converged = false;
+        }
+    }
+
+    /// Hands a route edited by hand back to the optimizer.
+    pub fn release_route(&self, student_number: &str, day: u32, transition: usize) {
+        self.edited.lock().unwrap().remove(&(student_number.to_owned(), day, transition));
+        if
+            let Some(progress) = self.progress
+                .lock()
+                .unwrap()
+                .get_mut(&day)
+                .and_then(|day_progress| day_progress.get_mut(&transition))
+        {
+            progress.converged = false;
+        }
+    }
+
+    /// Calls `f` with the congestion index of the routes, unless the store has not been optimized
+    /// yet.
+    pub fn with_congestion<T>(&self, f: impl FnOnce(&mut CongestionIndex) -> T) -> Option<T> {
+        self.congestion.lock().unwrap().as_mut().map(f)
+    }
+
+    pub fn is_converged(&self) -> bool {
+        let progress = self.progress.lock().unwrap();
+        !progress.is_empty() &&
+            progress
+                .values()
+                .flat_map(|day_progress| day_progress.values())
+                .all(|progress| progress.converged)
+    }
+
+    fn publish(
+        &self,
+        context: &OptimizationContext<'_>,
+        routes: &TransitionRoutes,
+        progress: TransitionProgress
+    ) {
+        {
+            let mut store_routes = self.routes.lock().unwrap();
+            let mut congestion = self.congestion.lock().unwrap();
+            for route in &routes.routes {
+                let Some(day_routes) = store_routes
+                    .get_mut(&context.students[route.student])
+                    .and_then(|student_routes| student_routes.get_mut(&routes.day)) else {
+                    continue;
+                };
+                let route_string = context.graph.route_string(&route.nodes);
+                let Some(old_route) = day_routes.insert(routes.transition, route_string) else {
+                    continue;
+                };
+                if
+                    let (Some(congestion), Some(old_nodes)) = (
+                        congestion.as_mut(),
+                        context.graph.route_ids(&old_route),
+                    )
+                {
+                    if old_nodes != route.nodes {
+                        congestion.replace_route(
+                            context.graph,
+                            routes.day,
+                            routes.transition,
+                            &old_nodes,
+                            &route.nodes
+                        );
+                    }
+                }
+            }
+        }
+        self.progress
+            .lock()
+            .unwrap()
+            .entry(routes.day)
+            .or_default()
+            .insert(routes.transition, progress);
+        self.latest_transitions.lock().unwrap()[(routes.day - 1) as usize] = routes.transition;
+    }
+}
+
+#[derive(Clone, Copy, serde::Deserialize, serde::Serialize)]
+pub struct OptimizationParams {
+    /// Number of iterations on a transition before its congestion is recalculated.
+    pub batch_size: u32,
+    pub thread_count: usize,
+    pub detour_limits: DetourLimits,
+    /// Seed of the random number generators of the transitions.
+    pub seed: u64,
+    #[serde(default)]
+    pub stopping: StoppingCriteria,
+    #[serde(default)]
+    pub strategy: OptimizationStrategy,
+    #[serde(default)]
+    pub robust_objective: RobustObjective,
+    /// How the scenarios of a robust objective perturb the timetable.
+    #[serde(default)]
+    pub perturbation: PerturbationParams,
+}
+
+/// Algorithm that optimizes the routes of each transition.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug, serde::Deserialize, serde::Serialize)]
+pub enum OptimizationStrategy {
+    /// Reroutes the worst route first, as the C++ optimizer does.
+    #[default]
+    Greedy,
+    SimulatedAnnealing,
+    TabuSearch,
+    /// Rounds a min-cost multicommodity flow relaxation to whole students.
+    FlowRelaxation,
+}
+
+impl OptimizationStrategy {
+    pub const ALL: [Self; 4] = [
+        Self::Greedy,
+        Self::SimulatedAnnealing,
+        Self::TabuSearch,
+        Self::FlowRelaxation,
+    ];
+}
+
+impl Display for OptimizationStrategy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OptimizationStrategy::Greedy => write!(f, "Greedy worst-first"),
+            OptimizationStrategy::SimulatedAnnealing => write!(f, "Simulated annealing"),
+            OptimizationStrategy::TabuSearch => write!(f, "Tabu search"),
+            OptimizationStrategy::FlowRelaxation => write!(f, "Multicommodity flow relaxation"),
+        }
+    }
+}
+
+/// What the optimizer minimises in every transition.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug, serde::Deserialize, serde::Serialize)]
+pub enum RobustObjective {
+    /// The performance index with the timetable as given.
+    #[default]
+    Nominal,
+    /// The mean performance index over the scenarios of the store.
+    Expected,
+    /// The highest performance index of the scenarios of the store.
+    WorstCase,
+}
+
+impl RobustObjective {
+    pub const ALL: [Self; 3] = [Self::Nominal, Self::Expected, Self::WorstCase];
+}
+
+impl Display for RobustObjective {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RobustObjective::Nominal => write!(f, "Timetable as given"),
+            RobustObjective::Expected => write!(f, "Expected over perturbations"),
+            RobustObjective::WorstCase => write!(f, "Worst case over perturbations"),
+        }
+    }
+}
+
+/// A student's route in one transition.
+#[derive(Clone)]
+struct OptimizedRoute {
+    student: usize,
+    nodes: Vec<usize>,
+    shortest_distance: u32,
+    /// Index of the constraint of the pinning rules matching the route in the context.
+    constraint: Option<usize>,
+    /// Scenarios in which the student does not walk the route.
+    replaced_in: Vec<usize>,
+}
+
+impl OptimizedRoute {
+    fn ends(&self) -> (usize, usize) {
+        (self.nodes[0], *self.nodes.last().unwrap())
+    }
+
+    fn is_walked_in(&self, scenario: usize) -> bool {
+        !self.replaced_in.contains(&scenario)
+    }
+}
+
+fn loads_performance_index(context: &OptimizationContext<'_>, loads: &[u32]) -> f64 {
+    loads
+        .iter()
+        .enumerate()
+        .map(|(edge, load)| edge_total_performance(context.edge_distances[edge] as f64, *load))
+        .sum()
+}
+
+/// Net change of the load of every edge when a student walks `new_nodes` instead of `old_nodes`,
+/// by edge id.
+fn load_changes(
+    context: &OptimizationContext<'_>,
+    old_nodes: &[usize],
+    new_nodes: &[usize]
+) -> Vec<(usize, i64)> {
+    let mut changes: Vec<(usize, i64)> = context.graph
+        .counted_edges(old_nodes)
+        .map(|edge| (edge.id, -1))
+        .chain(context.graph.counted_edges(new_nodes).map(|edge| (edge.id, 1)))
+        .collect();
+    changes.sort_unstable_by_key(|(edge, _)| *edge);
+    changes
+        .chunk_by(|change1, change2| change1.0 == change2.0)
+        .map(|edge_changes| {
+            (edge_changes[0].0, edge_changes.iter().map(|(_, change)| change).sum())
+        })
+        .collect()
+}
+
+/// Change of the performance index of `loads` by `changes`.
+fn performance_index_delta(
+    context: &OptimizationContext<'_>,
+    loads: &[u32],
+    changes: &[(usize, i64)]
+) -> f64 {
+    changes
+        .iter()
+        .map(|&(edge, change)| {
+            let distance = context.edge_distances[edge] as f64;
+            let load = loads[edge];
+            edge_total_performance(distance, ((load as i64) + change) as u32) -
+                edge_total_performance(distance, load)
+        })
+        .sum()
+}
+
+/// Routes of one transition that can be changed, i.e. that do not start and end at the same node
+/// and were neither edited by hand nor pinned by a rule.
+struct TransitionRoutes {
+    day: u32,
+    transition: usize,
+    routes: Vec<OptimizedRoute>,
+    /// Edge id → number of students walking the edge on a route that cannot be changed.
+    fixed_loads: Vec<u32>,
+    /// Edge id → number of students walking the edge.
+    loads: Vec<u32>,
+    performance_index: f64,
+    /// Scenario → edge id → number of students walking the edge in the scenario on a route that
+    /// cannot be changed there, including every route that differs from the plan. Empty unless
+    /// the objective is robust.
+    scenario_fixed_loads: Vec<Vec<u32>>,
+    /// Scenario → edge id → number of students walking the edge in the scenario.
+    scenario_loads: Vec<Vec<u32>>,
+    scenario_performance_indices: Vec<f64>,
+    /// What the strategies minimise: the performance index, or its mean or maximum over the
+    /// scenarios.
+    objective: f64,
+    random: Random,
+}
+
+impl TransitionRoutes {
+    /// Counts a route the optimizer cannot change, in every scenario it is not replaced in.
+    fn add_fixed_load(&mut self, graph: &BuildingGraph, nodes: &[usize], replaced_in: &[usize]) {
+        graph.add_load(&mut self.fixed_loads, nodes);
+        for (scenario, loads) in self.scenario_fixed_loads.iter_mut().enumerate() {
+            if !replaced_in.contains(&scenario) {
+                graph.add_load(loads, nodes);
+            }
+        }
+    }
+
+    fn update_loads(&mut self, context: &OptimizationContext<'_>) {
+        self.loads.copy_from_slice(&self.fixed_loads);
+        for route in &self.routes {
+            context.graph.add_load(&mut self.loads, &route.nodes);
+        }
+        self.performance_index = loads_performance_index(context, &self.loads);
+        for (scenario, loads) in self.scenario_loads.iter_mut().enumerate() {
+            loads.copy_from_slice(&self.scenario_fixed_loads[scenario]);
+            for route in self.routes.iter().filter(|route| route.is_walked_in(scenario)) {
+                context.graph.add_load(loads, &route.nodes);
+            }
+            self.scenario_performance_indices[scenario] = loads_performance_index(context, loads);
+        }
+        self.objective = self.objective(
+            context,
+            self.performance_index,
+            &self.scenario_performance_indices
+        );
+    }
+
+    /// The objective of the given performance indices of the timetable and the scenarios.
+    fn objective(
+        &self,
+        context: &OptimizationContext<'_>,
+        performance_index: f64,
+        scenario_performance_indices: &[f64]
+    ) -> f64 {
+        if scenario_performance_indices.is_empty() {
+            return performance_index;
+        }
+        match context.params.robust_objective {
+            RobustObjective::Nominal => performance_index,
+            RobustObjective::Expected => {
+                scenario_performance_indices.iter().sum::<f64>() /
+                    (scenario_performance_indices.len() as f64)
+            }
+            RobustObjective::WorstCase => {
+                scenario_performance_indices.iter().copied().fold(f64::MIN, f64::max)
+            }
+        }
+    }
+
+    /// Change of the objective if route `index` was replaced by `new_nodes`.
+    fn reroute_delta(
+        &self,
+        context: &OptimizationContext<'_>,
+        index: usize,
+        new_nodes: &[usize]
+    ) -> f64 {
+        let route = &self.routes[index];
+        let changes = load_changes(context, &route.nodes, new_nodes);
+        if self.scenario_loads.is_empty() {
+            return performance_index_delta(context, &self.loads, &changes);
+        }
+        let scenario_performance_indices: Vec<f64> = self.scenario_loads
+            .iter()
+            .zip(&self.scenario_performance_indices)
+            .enumerate()
+            .map(|(scenario, (loads, performance_index))| {
+                if route.is_walked_in(scenario) {
+                    performance_index + performance_index_delta(context, loads, &changes)
+                } else {
+                    *performance_index
+                }
+            })
+            .collect();
+        self.objective(context, self.performance_index, &scenario_performance_indices) -
+            self.objective
+    }
+
+    /// Replaces route `index` by `new_nodes`, keeping the loads, performance indices and objective
+    /// up to date. The detour limits are not checked.
+    fn reroute(&mut self, context: &OptimizationContext<'_>, index: usize, new_nodes: Vec<usize>) {
+        let route = &self.routes[index];
+        let changes = load_changes(context, &route.nodes, &new_nodes);
+        self.performance_index += performance_index_delta(context, &self.loads, &changes);
+        context.graph.remove_load(&mut self.loads, &route.nodes);
+        context.graph.add_load(&mut self.loads, &new_nodes);
+        for (scenario, loads) in self.scenario_loads.iter_mut().enumerate() {
+            if route.is_walked_in(scenario) {
+                self.scenario_performance_indices[scenario] += performance_index_delta(
+                    context,
+                    loads,
+                    &changes
+                );
+                context.graph.remove_load(loads, &route.nodes);
+                context.graph.add_load(loads, &new_nodes);
+            }
+        }
+        self.routes[index].nodes = new_nodes;
+        self.objective = self.objective(
+            context,
+            self.performance_index,
+            &self.scenario_performance_indices
+        );
+    }
+
+    /// Edge id → increase of the objective when the student of route `index` walks the edge, not
+    /// counting the route itself, or `None` for the performance index of the timetable as given.
+    fn robust_marginal_costs(
+        &mut self,
+        context: &OptimizationContext<'_>,
+        index: usize
+    ) -> Option<Vec<f64>> {
+        if self.scenario_loads.is_empty() {
+            return None;
+        }
+        let route = &self.routes[index];
+        let scenarios: Vec<usize> = (0..self.scenario_loads.len())
+            .filter(|scenario| route.is_walked_in(*scenario))
+            .collect();
+        // the cost of the worst scenario, unless the student is absent from it
+        let worst = (context.params.robust_objective == RobustObjective::WorstCase)
+            .then(|| {
+                (0..self.scenario_performance_indices.len()).max_by(|scenario1, scenario2| {
+                    self.scenario_performance_indices[*scenario1].total_cmp(
+                        &self.scenario_performance_indices[*scenario2]
+                    )
+                })
+            })
+            .flatten()
+            .filter(|scenario| scenarios.contains(scenario));
+        for &scenario in &scenarios {
+            context.graph.remove_load(&mut self.scenario_loads[scenario], &route.nodes);
+        }
+        let costs = (0..self.loads.len())
+            .map(|edge| {
+                let distance = context.edge_distances[edge] as f64;
+                match worst {
+                    Some(scenario) => marginal_cost(distance, self.scenario_loads[scenario][edge]),
+                    None => {
+                        scenarios
+                            .iter()
+                            .map(|scenario| {
+                                marginal_cost(distance, self.scenario_loads[*scenario][edge])
+                            })
+                            .sum::<f64>() / (self.scenario_loads.len() as f64)
+                    }
+                }
+            })
+            .collect();
+        for &scenario in &scenarios {
+            context.graph.add_load(&mut self.scenario_loads[scenario], &route.nodes);
+        }
+        Some(costs)
+    }
+
+    /// Cheapest path between the ends of route `index` where walking an edge costs the increase of
+    /// its performance index, or of the objective, not counting the route itself. With `noise`
+    /// above zero, the cost of every edge is scaled by a random factor between 1 and 1 + `noise`.
+    fn marginal_cost_path(
+        &mut self,
+        context: &OptimizationContext<'_>,
+        index: usize,
+        noise: f64
+    ) -> Option<Vec<usize>> {
+        let factors: Vec<f64> = (0..self.loads.len())
+            .map(|_| if noise > 0.0 { 1.0 + noise * self.random.next_f64() } else { 1.0 })
+            .collect();
+        let robust_costs = self.robust_marginal_costs(context, index);
+        let (start, end) = self.routes[index].ends();
+        context.graph.remove_load(&mut self.loads, &self.routes[index].nodes);
+        let path = context.cheapest_path(start, end, self.routes[index].constraint, |node, edge| {
+            if context.graph.is_ground(node) || context.graph.is_ground(edge.to) {
+                edge.distance as f64
+            } else if let Some(robust_costs) = &robust_costs {
+                factors[edge.id] * robust_costs[edge.id]
+            } else {
+                factors[edge.id] *
+                    marginal_cost(edge.distance as f64, self.loads[edge.id])
+            }
+        });
+        context.graph.add_load(&mut self.loads, &self.routes[index].nodes);
+        path
+    }
+
+    fn nodes(&self) -> Vec<Vec<usize>> {
+        self.routes
+            .iter()
+            .map(|route| route.nodes.clone())
+            .collect()
+    }
+
+    /// Puts every student back on their route in `nodes` where the detour limits allow it, and
+    /// recalculates the loads.
+    fn restore(&mut self, context: &OptimizationContext<'_>, nodes: &[Vec<usize>]) {
+        for (route, nodes) in self.routes.iter_mut().zip(nodes) {
+            if route.nodes != *nodes && context.allow_reroute(self.day, route, nodes) {
+                route.nodes = nodes.clone();
+            }
+        }
+        self.update_loads(context);
+    }
+}
+
+/// Increase of the performance index of an edge when one more student walks it.
+fn marginal_cost(distance: f64, load: u32) -> f64 {
+    edge_total_performance(distance, load + 1) - edge_total_performance(distance, load)
+}
+
+/// Optimizes the routes of one transition, one batch of iterations at a time. All strategies
+/// report their progress in the same way, so that they can be compared.
+trait RouteOptimizer: Send {
+    fn routes(&self) -> &TransitionRoutes;
+
+    fn iterations(&self) -> u64;
+
+    /// Whether more batches would not change the routes any more.
+    fn is_converged(&self) -> bool;
+
+    /// Runs up to `params.batch_size` iterations. The loads and performance index of the routes
+    /// are up to date afterwards.
+    fn run_batch(&mut self, context: &OptimizationContext<'_>);
+
+    fn progress(&self) -> TransitionProgress {
+        let routes = self.routes();
+        TransitionProgress {
+            iterations: self.iterations(),
+            initial_performance_index: routes.performance_index,
+            performance_index: routes.performance_index,
+            converged: self.is_converged(),
+            random_state: routes.random.state(),
+            ..Default::default()
+        }
+    }
+}
+
+fn route_optimizer(
+    context: &OptimizationContext<'_>,
+    routes: TransitionRoutes,
+    iterations: u64,
+    strategy_start: u64
+) -> Box<dyn RouteOptimizer> {
+    match context.params.strategy {
+        OptimizationStrategy::Greedy => Box::new(GreedyState::new(context, routes, iterations)),
+        OptimizationStrategy::SimulatedAnnealing => {
+            Box::new(AnnealingState::new(routes, iterations, strategy_start))
+        }
+        OptimizationStrategy::TabuSearch => Box::new(TabuState::new(routes, iterations)),
+        OptimizationStrategy::FlowRelaxation => {
+            Box::new(FlowState::new(context, routes, iterations, strategy_start))
+        }
+    }
+}
+
+/// Detours of every student in every day. The transitions of a day are optimized by different
+/// workers, so a change to the detour of a student is checked and recorded in one step.
+struct DayDetours {
+    limit: DetourLimit,
+    /// Student → day → sum of the shortest distances of the student's routes.
+    shortest_distances: Vec<[u32; 5]>,
+    /// Student → day → sum of the detours of the student's routes.
+    detours: Mutex<Vec<[u32; 5]>>,
+}
+
+impl DayDetours {
+    /// Replaces `old_detour` by `new_detour` in the day detour of a student, unless the new day
+    /// detour exceeds the limit. Returns whether the detour was replaced.
+    fn replace(&self, student: usize, day: u32, old_detour: u32, new_detour: u32) -> bool {
+        let day_index = (day - 1) as usize;
+        let mut detours = self.detours.lock().unwrap();
+        let new_day_detour = detours[student][day_index] - old_detour + new_detour;
+        if !self.limit.allows(new_day_detour, self.shortest_distances[student][day_index]) {
+            return false;
+        }
+        detours[student][day_index] = new_day_detour;
+        true
+    }
+}
+
+/// Data shared by the workers.
+struct OptimizationContext<'a> {
+    graph: &'a BuildingGraph,
+    /// Edge id → distance.
+    edge_distances: Vec<u32>,
+    students: Vec<String>,
+    params: OptimizationParams,
+    day_detours: DayDetours,
+    /// Constraints of the pinning rules, shared by the routes matching the same rules.
+    constraints: Vec<RouteConstraint>,
+}
+
+impl OptimizationContext<'_> {
+    fn detour(&self, nodes: &[usize], shortest_distance: u32) -> u32 {
+        self.graph.route_distance(nodes).saturating_sub(shortest_distance)
+    }
+
+    /// Whether a student may take `new_nodes` instead of `route` within the detour limits and the
+    /// pinning rules. If so, the new detour is recorded in the day detours.
+    fn allow_reroute(&self, day: u32, route: &OptimizedRoute, new_nodes: &[usize]) -> bool {
+        let new_detour = self.detour(new_nodes, route.shortest_distance);
+        route.constraint.is_none_or(|index| {
+            self.constraints[index].allows(self.graph, new_nodes)
+        }) &&
+            self.params.detour_limits.transition.allows(new_detour, route.shortest_distance) &&
+            self.day_detours.replace(
+                route.student,
+                day,
+                self.detour(&route.nodes, route.shortest_distance),
+                new_detour
+            )
+    }
+
+    /// Cheapest path from `start` to `end` that keeps to a constraint of the pinning rules, where
+    /// `cost` gives the cost of walking an edge.
+    fn cheapest_path(
+        &self,
+        start: usize,
+        end: usize,
+        constraint: Option<usize>,
+        cost: impl Fn(usize, &GraphEdge) -> f64
+    ) -> Option<Vec<usize>> {
+        match constraint {
+            Some(index) => self.constraints[index].cheapest_path(self.graph, start, end, cost),
+            None => self.graph.cheapest_path(start, end, cost),
+        }
+    }
+}
+
+/// Splits the routes of the store into transitions, and indexes their congestion in the store.
+fn transitions<'a>(
+    store: &RouteStore,
+    shortest_paths: &ShortestPaths,
+    graph: &'a BuildingGraph,
+    params: OptimizationParams
+) -> (OptimizationContext<'a>, Vec<TransitionRoutes>) {
+    let routes = store.routes();
+    let edited = store.edited_routes();
+    let pinning_rules = store.pinning_rules();
+    let scenarios = store.scenarios.lock().unwrap();
+    let scenarios: &[Scenario] = match params.robust_objective {
+        RobustObjective::Nominal => &[],
+        RobustObjective::Expected | RobustObjective::WorstCase => &scenarios,
+    };
+    let mut compiled_rules = CompiledRules::new(graph, &pinning_rules);
+    let mut congestion = CongestionIndex::new(graph);
+    for student_routes in routes.values() {
+        for (day, day_routes) in student_routes {
+            for (transition, route) in day_routes {
+                match graph.route_ids(route) {
+                    Some(nodes) => congestion.add_route(graph, *day, *transition, &nodes),
+                    None => {
+                        let steps = route.split(' ').filter(|node| !node.is_empty()).count();
+                        congestion.add_uncounted_steps(steps.saturating_sub(1));
+                    }
+                }
+            }
+        }
+    }
+    *store.congestion.lock().unwrap() = Some(congestion);
+    let mut students: Vec<String> = routes.keys().cloned().collect();
+    students.sort();
+    let mut shortest_distances = vec![[0; 5]; students.len()];
+    let mut detours = vec![[0; 5]; students.len()];
+    let mut transitions = vec![];
+    for day in 1..=5 {
+        for transition in 0..=11 {
+            let mut transition_routes = TransitionRoutes {
+                day,
+                transition,
+                routes: vec![],
+                fixed_loads: vec![0; graph.edge_count()],
+                loads: vec![0; graph.edge_count()],
+                performance_index: 0.0,
+                scenario_fixed_loads: vec![vec![0; graph.edge_count()]; scenarios.len()],
+                scenario_loads: vec![vec![0; graph.edge_count()]; scenarios.len()],
+                scenario_performance_indices: vec![0.0; scenarios.len()],
+                objective: 0.0,
+                random: store
+                    .progress(day, transition)
+                    .map_or_else(
+                        || Random::for_transition(params.seed, day, transition),
+                        |progress| Random::new(progress.random_state)
+                    ),
+            };
+            for (student, student_number) in students.iter().enumerate() {
+                let Some(nodes) = routes[student_number]
+                    .get(&day)
+                    .and_then(|day_routes| day_routes.get(&transition))
+                    .and_then(|route| graph.route_ids(route)) else {
+                    continue;
+                };
+                let mut replaced_in = vec![];
+                for (scenario_index, scenario) in scenarios.iter().enumerate() {
+                    if let Some(route) = scenario.route(student_number, day, transition) {
+                        if let Some(scenario_nodes) = graph.route_ids(route) {
+                            graph.add_load(
+                                &mut transition_routes.scenario_fixed_loads[scenario_index],
+                                &scenario_nodes
+                            );
+                        }
+                        replaced_in.push(scenario_index);
+                    }
+                }
+                if edited.contains(&(student_number.clone(), day, transition)) {
+                    transition_routes.add_fixed_load(graph, &nodes, &replaced_in);
+                    continue;
+                }
+                if nodes.len() < 2 || nodes.first() == nodes.last() {
+                    continue;
+                }
+                let matching_rules = compiled_rules.matching_rules(
+                    student_number,
+                    day,
+                    transition,
+                    graph.node_name(nodes[0]),
+                    graph.node_name(*nodes.last().unwrap())
+                );
+                let constraint = match compiled_rules.route_rule(&matching_rules) {
+                    RouteRule::Free => None,
+                    RouteRule::Pinned => {
+                        transition_routes.add_fixed_load(graph, &nodes, &replaced_in);
+                        continue;
+                    }
+                    RouteRule::Constrained(index) => Some(index),
+                };
+                let shortest_distance = graph.shortest_distance(&nodes, shortest_paths);
+                shortest_distances[student][(day - 1) as usize] += shortest_distance;
+                detours[student][(day - 1) as usize] += graph
+                    .route_distance(&nodes)
+                    .saturating_sub(shortest_distance);
+                transition_routes.routes.push(OptimizedRoute {
+                    student,
+                    nodes,
+                    shortest_distance,
+                    constraint,
+                    replaced_in,
+                });
+            }
+            transitions.push(transition_routes);
+        }
+    }
+    let context = OptimizationContext {
+        graph,
+        edge_distances: graph.edge_distances(),
+        students,
+        params,
+        day_detours: DayDetours {
+            limit: params.detour_limits.day,
+            shortest_distances,
+            detours: Mutex::new(detours),
+        },
+        constraints: compiled_rules.into_constraints(),
+    };
+    for transition_routes in &mut transitions {
+        transition_routes.update_loads(&context);
+    }
+    (context, transitions)
+}
+
+/// Optimizes the routes of `store` on `params.thread_count` worker threads until every transition
+/// has converged, a stopping criterion is met or `monitor` returns `false`. Returns why it stopped,
+/// which is also kept in the store.
+///
+/// `monitor` is called on the calling thread every 100 ms while the workers are running, and once
+/// more after they have stopped.
+pub fn optimize_routes(
+    store: &RouteStore,
+    shortest_paths: &ShortestPaths,
+    graph: &BuildingGraph,
+    params: OptimizationParams,
+    mut monitor: impl FnMut(&RouteStore) -> bool
+) -> StopReason {
+    *store.stop_reason.lock().unwrap() = None;
+    let (context, transitions) = transitions(store, shortest_paths, graph, params);
+    let queue = Mutex::new(VecDeque::new());
+    for routes in transitions {
+        let previous = store.progress(routes.day, routes.transition);
+        let iterations = previous.map_or(0, |progress| progress.iterations);
+        // A schedule of the same strategy is resumed where an earlier run stopped it.
+        let strategy_start = previous
+            .filter(|progress| progress.strategy == Some(params.strategy))
+            .map_or(iterations, |progress| progress.strategy_start);
+        let state = route_optimizer(&context, routes, iterations, strategy_start);
+        let progress = TransitionProgress {
+            initial_performance_index: previous.map_or(
+                state.routes().performance_index,
+                |progress| progress.initial_performance_index
+            ),
+            strategy: Some(params.strategy),
+            strategy_start,
+            ..state.progress()
+        };
+        store.publish(&context, state.routes(), progress);
+        if !state.is_converged() {
+            queue.lock().unwrap().push_back((state, progress));
+        }
+    }
+
+    let stop_check = Mutex::new(
+        StopCheck::new(
+            params.stopping,
+            store.iterations().iter().sum(),
+            store.total_performance_index()
+        )
+    );
+    let stop_reason = Mutex::new(None);
+    let stop = AtomicBool::new(false);
+    let running = AtomicUsize::new(params.thread_count.max(1));
+    thread::scope(|scope| {
+        for _ in 0..params.thread_count.max(1) {
+            scope.spawn(|| {
+                while !stop.load(Ordering::Relaxed) {
+                    let Some((mut state, start)) = queue.lock().unwrap().pop_front() else {
+                        break;
+                    };
+                    state.run_batch(&context);
+                    store.publish(&context, state.routes(), TransitionProgress {
+                        initial_performance_index: start.initial_performance_index,
+                        strategy: start.strategy,
+                        strategy_start: start.strategy_start,
+                        ..state.progress()
+                    });
+                    if !state.is_converged() {
+                        queue.lock().unwrap().push_back((state, start));
+                    }
+                    let reason = stop_check
+                        .lock()
+                        .unwrap()
+                        .check(store.iterations().iter().sum(), store.total_performance_index());
+                    if let Some(reason) = reason {
+                        stop_reason.lock().unwrap().get_or_insert(reason);
+                        stop.store(true, Ordering::Relaxed);
+                    }
+                }
+                running.fetch_sub(1, Ordering::Relaxed);
+            });
+        }
+        while running.load(Ordering::Relaxed) > 0 {
+            if !stop.load(Ordering::Relaxed) && !monitor(store) {
+                stop_reason.lock().unwrap().get_or_insert(StopReason::Interrupted);
+                stop.store(true, Ordering::Relaxed);
+            }
+            thread::sleep(MONITOR_INTERVAL);
+        }
+    });
+    let stop_reason = stop_reason.into_inner().unwrap().unwrap_or(StopReason::Converged);
+    *store.stop_reason.lock().unwrap() = Some(stop_reason);
+    monitor(store);
+    stop_reason
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A corridor from `A1` to `C1` through `B1`, a slightly longer one through `D1`, and the
+    /// entrances `A1` and `C1` joined to the ground node.
+    pub(super) const FIXTURE_PATHS: &str = "\
+A101 A1 50 0
+A1 B1 100 0
+B1 C1 100 0
+C1 C101 50 0
+A1 D1 110 0
+D1 C1 110 0
+A1 G 999999 2
+C1 G 999999 2
+";
+
+    /// Number of students walking from `A101` to `C101` in the first transition of Monday.
+    pub(super) const FIXTURE_STUDENTS: usize = 400;
+
+    /// The fixture graph, its shortest paths, and routes in which every student walks from `A101`
+    /// to `C101` through `B1` in transition 1 of Monday, after arriving from `G` in transition 0.
+    pub(super) fn fixture() -> (BuildingGraph, ShortestPaths, Routes) {
+        let graph = BuildingGraph::parse(FIXTURE_PATHS);
+        let shortest_paths = ShortestPaths::from([
+            ("A101C101".to_owned(), "A101 A1 B1 C1 C101".to_owned()),
+            ("GA101".to_owned(), "G A1 A101".to_owned()),
+        ]);
+        let routes = (0..FIXTURE_STUDENTS)
+            .map(|student| {
+                let day_routes = HashMap::from([
+                    (0, "G A1 A101".to_owned()),
+                    (1, "A101 A1 B1 C1 C101".to_owned()),
+                ]);
+                (format!("{:05}", student), HashMap::from([(1, day_routes)]))
+            })
+            .collect();
+        (graph, shortest_paths, routes)
+    }
+
+    pub(super) fn params(strategy: OptimizationStrategy) -> OptimizationParams {
+        OptimizationParams {
+            batch_size: 100,
+            thread_count: 1,
+            detour_limits: Default::default(),
+            seed: 7,
+            stopping: Default::default(),
+            strategy,
+            robust_objective: Default::default(),
+            perturbation: Default::default(),
+        }
+    }
+
+    /// Optimizes the fixture routes until they converge or a stopping criterion is met.
+    pub(super) fn optimize(store: &RouteStore, params: OptimizationParams) -> StopReason {
+        let (graph, shortest_paths, _) = fixture();
+        optimize_routes(store, &shortest_paths, &graph, params, |_| true)
+    }
+
+    pub(super) fn fixture_store() -> RouteStore {
+        RouteStore::new(fixture().2, [0; 5])
+    }
+
+    /// Performance index of transition 1 of Monday before its first batch and after each batch,
+    /// until it converges.
+    pub(super) fn batch_performance_indices(strategy: OptimizationStrategy) -> Vec<f64> {
+        let (graph, shortest_paths, routes) = fixture();
+        let store = RouteStore::new(routes, [0; 5]);
+        let (context, transitions) = transitions(&store, &shortest_paths, &graph, params(strategy));
+        let routes = transitions
+            .into_iter()
+            .find(|routes| routes.day == 1 && routes.transition == 1)
+            .unwrap();
+        let mut state = route_optimizer(&context, routes, 0, 0);
+        let mut performance_indices = vec![state.routes().performance_index];
+        while !state.is_converged() {
+            state.run_batch(&context);
+            performance_indices.push(state.routes().performance_index);
+        }
+        performance_indices
+    }
+
+    /// Optimizes the fixture routes with students `0000*` forbidden from `B1`, students `0001*`
+    /// pinned to the shortest route and students `0002*` required to pass `B1`, and checks that
+    /// the optimized routes keep to the rules.
+    pub(super) fn assert_keeps_pinning_rules(strategy: OptimizationStrategy) {
+        let (graph, shortest_paths, _) = fixture();
+        let rules: Vec<PinningRule> = serde_yaml
+            ::from_str(
+                "\
+- { name: forbid, students: ['0000*'], transitions: [1], forbid: [B1] }
+- { name: pin, students: ['0001*'], transitions: [1], pin_shortest: true }
+- { name: require, students: ['0002*'], transitions: [1], require: [B1] }
+"
+            )
+            .unwrap();
+        let store = fixture_store();
+        store.apply_rules(&graph, &shortest_paths, rules);
+        optimize(&store, params(strategy));
+        let routes = store.routes();
+        let moved = routes
+            .values()
+            .filter(|student_routes| student_routes[&1][&1].contains("D1"))
+            .count();
+        assert!(moved > 0, "{}", strategy);
+        for (student, student_routes) in &routes {
+            let route = &student_routes[&1][&1];
+            if student.starts_with("0000") {
+                assert_eq!(route, "A101 A1 D1 C1 C101", "{}: {}", strategy, student);
+            } else if student.starts_with("0001") || student.starts_with("0002") {
+                assert_eq!(route, "A101 A1 B1 C1 C101", "{}: {}", strategy, student);
+            }
+        }
+    }
+
+    /// Optimizes the fixture routes with a detour limit shorter than the detour through `D1`, and
+    /// checks that every student keeps the shortest route.
+    pub(super) fn assert_keeps_detour_limits(strategy: OptimizationStrategy) {
+        let store = fixture_store();
+        let mut params = params(strategy);
+        params.detour_limits.transition.absolute = Some(10);
+        optimize(&store, params);
+        assert_eq!(store.routes(), fixture().2, "{}", strategy);
+    }
+
+    /// Optimizes the fixture routes twice with the same seed and checks that the routes are the
+    /// same.
+    pub(super) fn assert_same_seed_same_routes(strategy: OptimizationStrategy) {
+        let (store1, store2) = (fixture_store(), fixture_store());
+        optimize(&store1, params(strategy));
+        optimize(&store2, params(strategy));
+        assert!(store1.routes() != fixture().2, "{}", strategy);
+        assert_eq!(store1.routes(), store2.routes(), "{}", strategy);
+    }
+
+    #[test]
+    fn optimized_routes_only_visit_ground_at_their_ends() {
+        let graph = fixture().0;
+        for strategy in OptimizationStrategy::ALL {
+            let store = fixture_store();
+            let mut params = params(strategy);
+            params.stopping.max_iterations = Some(5000);
+            optimize(&store, params);
+            let performance_index = store.total_performance_index();
+            let initial_performance_index: f64 = store.progress
+                .lock()
+                .unwrap()
+                .values()
+                .flat_map(|day_progress| day_progress.values())
+                .map(|progress| progress.initial_performance_index)
+                .sum();
+            assert!(performance_index < initial_performance_index, "{}", strategy);
+            for route in store
+                .routes()
+                .values()
+                .flat_map(|student_routes| student_routes.values())
+                .flat_map(|day_routes| day_routes.values()) {
+                let nodes = graph.route_ids(route).unwrap();
+                assert!(
+                    nodes[1..nodes.len() - 1].iter().all(|node| !graph.is_ground(*node)),
+                    "{}: {}",
+                    strategy,
+                    route
+                );
+            }
+        }
+    }
+}
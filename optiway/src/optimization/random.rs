@@ -0,0 +1,78 @@
+//! A small seedable random number generator (SplitMix64) whose whole state is a single number, so
+//! that it can be saved in checkpoints and resumed.
+
+/// Increment of the state of SplitMix64.
+const GOLDEN_GAMMA: u64 = 0x9e37_79b9_7f4a_7c15;
+
+/// Output function of SplitMix64, which spreads every bit of `value` over the whole result.
+fn mix(mut value: u64) -> u64 {
+    value = (value ^ (value >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    value = (value ^ (value >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    value ^ (value >> 31)
+}
+
+#[derive(Clone, Copy)]
+pub(crate) struct Random {
+    state: u64,
+}
+
+impl Random {
+    pub(crate) fn new(state: u64) -> Self {
+        Self { state }
+    }
+
+    /// A generator whose state is a hash of `seed` and `key`, so that generators of nearby keys or
+    /// seeds draw unrelated sequences. A state of `seed ^ key` would not do: SplitMix64 advances
+    /// its state by a constant, so such generators can draw the same sequence shifted by a few
+    /// numbers.
+    pub(crate) fn keyed(seed: u64, key: u64) -> Self {
+        Self::new(mix(mix(seed).wrapping_add(key)))
+    }
+
+    /// A generator for one transition, so that every transition draws its own sequence.
+    pub(crate) fn for_transition(seed: u64, day: u32, transition: usize) -> Self {
+        Self::keyed(seed, (day as u64) * 12 + (transition as u64))
+    }
+
+    pub(crate) fn state(&self) -> u64 {
+        self.state
+    }
+
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(GOLDEN_GAMMA);
+        mix(self.state)
+    }
+
+    /// A number in `[0, 1)`.
+    pub(crate) fn next_f64(&mut self) -> f64 {
+        ((self.next_u64() >> 11) as f64) / ((1u64 << 53) as f64)
+    }
+
+    /// A number in `[0, bound)`.
+    pub(crate) fn below(&mut self, bound: usize) -> usize {
+        (self.next_u64() % (bound as u64)) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transitions_draw_unrelated_sequences() {
+        let draws: Vec<Vec<u64>> = (0..12)
+            .map(|transition| {
+                let mut random = Random::for_transition(0, 1, transition);
+                (0..8).map(|_| random.next_u64()).collect()
+            })
+            .collect();
+        for (transition1, draws1) in draws.iter().enumerate() {
+            for draws2 in &draws[transition1 + 1..] {
+                assert!(draws1.iter().all(|draw| !draws2.contains(draw)));
+            }
+        }
+        let mut random = Random::for_transition(0, 1, 0);
+        let mut same = Random::for_transition(0, 1, 0);
+        assert_eq!(random.next_u64(), same.next_u64());
+    }
+}
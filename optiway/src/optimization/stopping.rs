@@ -0,0 +1,165 @@
+//! Rules for stopping the route optimization before every transition has converged.
+
+use std::{ collections::VecDeque, fmt::Display, time::{ Duration, Instant } };
+
+/// Stop once the total performance index improved by less than `min_improvement` over the last
+/// `iterations` iterations.
+#[derive(Clone, Copy, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct ImprovementWindow {
+    pub iterations: u64,
+    /// Fraction of the total performance index at the start of the window.
+    pub min_improvement: f64,
+}
+
+/// Rules that stop a run, all optional. Iterations and time are counted from the start of the run,
+/// not from the start of the optimization when resuming.
+#[derive(Clone, Copy, Default, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct StoppingCriteria {
+    pub max_iterations: Option<u64>,
+    /// Wall-clock time, in seconds.
+    pub time_limit: Option<u64>,
+    pub improvement_window: Option<ImprovementWindow>,
+    /// Stop once the total performance index of the week is at most this.
+    pub target_performance_index: Option<u64>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, serde::Deserialize, serde::Serialize)]
+pub enum StopReason {
+    /// No route of any transition can be improved any further.
+    Converged,
+    MaxIterations,
+    TimeLimit,
+    NoImprovement,
+    TargetReached,
+    /// Stopped from outside, e.g. by pausing in the optimization window.
+    Interrupted,
+}
+
+impl Display for StopReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StopReason::Converged => write!(f, "No route can be improved any further"),
+            StopReason::MaxIterations => write!(f, "Maximum number of iterations reached"),
+            StopReason::TimeLimit => write!(f, "Time limit reached"),
+            StopReason::NoImprovement => write!(f, "Performance index stopped improving"),
+            StopReason::TargetReached => write!(f, "Target performance index reached"),
+            StopReason::Interrupted => write!(f, "Paused"),
+        }
+    }
+}
+
+/// Progress of a run, checked against the stopping criteria.
+pub(super) struct StopCheck {
+    criteria: StoppingCriteria,
+    started: Instant,
+    initial_iterations: u64,
+    /// (iterations, total performance index) samples, the first of which is the latest sample at
+    /// least one improvement window old.
+    samples: VecDeque<(u64, f64)>,
+}
+
+impl StopCheck {
+    pub(super) fn new(
+        criteria: StoppingCriteria,
+        initial_iterations: u64,
+        initial_performance_index: f64
+    ) -> Self {
+        Self {
+            criteria,
+            started: Instant::now(),
+            initial_iterations,
+            samples: VecDeque::from([(0, initial_performance_index)]),
+        }
+    }
+
+    /// The criterion met with `iterations` iterations in total and a total performance index of
+    /// `performance_index`, if any.
+    pub(super) fn check(&mut self, iterations: u64, performance_index: f64) -> Option<StopReason> {
+        let iterations = iterations.saturating_sub(self.initial_iterations);
+        if
+            self.criteria.target_performance_index.is_some_and(
+                |target| performance_index <= (target as f64)
+            )
+        {
+            return Some(StopReason::TargetReached);
+        }
+        if self.criteria.max_iterations.is_some_and(|max_iterations| iterations >= max_iterations) {
+            return Some(StopReason::MaxIterations);
+        }
+        if
+            self.criteria.time_limit.is_some_and(
+                |time_limit| self.started.elapsed() >= Duration::from_secs(time_limit)
+            )
+        {
+            return Some(StopReason::TimeLimit);
+        }
+        let window = self.criteria.improvement_window?;
+        self.samples.push_back((iterations, performance_index));
+        while
+            self.samples.len() > 1 &&
+            self.samples[1].0 + window.iterations <= iterations
+        {
+            self.samples.pop_front();
+        }
+        let (window_iterations, window_performance_index) = self.samples[0];
+        if
+            window_iterations + window.iterations <= iterations &&
+            window_performance_index - performance_index <
+                window.min_improvement * window_performance_index
+        {
+            return Some(StopReason::NoImprovement);
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A check of a run resumed after 1000 iterations at a total performance index of 100.
+    fn stop_check(criteria: StoppingCriteria) -> StopCheck {
+        StopCheck::new(criteria, 1000, 100.0)
+    }
+
+    #[test]
+    fn every_rule_stops_the_run() {
+        let mut check = stop_check(StoppingCriteria::default());
+        assert_eq!(check.check(1_000_000, 0.0), None);
+
+        let mut check = stop_check(StoppingCriteria {
+            max_iterations: Some(500),
+            ..Default::default()
+        });
+        assert_eq!(check.check(1499, 90.0), None);
+        assert_eq!(check.check(1500, 90.0), Some(StopReason::MaxIterations));
+
+        let mut check = stop_check(StoppingCriteria {
+            time_limit: Some(3600),
+            ..Default::default()
+        });
+        assert_eq!(check.check(1100, 90.0), None);
+        let mut check = stop_check(StoppingCriteria {
+            time_limit: Some(0),
+            ..Default::default()
+        });
+        assert_eq!(check.check(1100, 90.0), Some(StopReason::TimeLimit));
+
+        let mut check = stop_check(StoppingCriteria {
+            target_performance_index: Some(50),
+            ..Default::default()
+        });
+        assert_eq!(check.check(1100, 60.0), None);
+        assert_eq!(check.check(1200, 50.0), Some(StopReason::TargetReached));
+
+        let mut check = stop_check(StoppingCriteria {
+            improvement_window: Some(ImprovementWindow { iterations: 100, min_improvement: 0.1 }),
+            ..Default::default()
+        });
+        // 15% and 11.6% better than 100 iterations before, then only 5.9% better
+        assert_eq!(check.check(1050, 95.0), None);
+        assert_eq!(check.check(1100, 85.0), None);
+        assert_eq!(check.check(1150, 84.0), None);
+        assert_eq!(check.check(1200, 80.0), Some(StopReason::NoImprovement));
+    }
+}
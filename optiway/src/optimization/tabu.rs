@@ -0,0 +1,161 @@
+//! Tabu search.
+//!
+//! Every iteration looks at the students with the most congested routes that are not tabu, proposes
+//! for each the cheapest path between the ends of their route where walking an edge costs the
+//! increase of its performance index, and makes the move that lowers the performance index of the
+//! transition the most, or raises it the least if none lowers it. The student is then tabu for a
+//! number of iterations, so that the search can leave a local optimum without moving them straight
+//! back. Once the best routes have not improved for a number of iterations, they are restored and
+//! the transition has converged.
+
+use crate::evaluation::edge_performance;
+
+use super::{ OptimizationContext, RouteOptimizer, TransitionRoutes };
+
+/// Number of routes a move is looked for in every iteration.
+const CANDIDATES: usize = 8;
+/// Number of iterations a student is not moved for after being moved.
+const TABU_TENURE: u64 = 50;
+/// Number of iterations without a new best objective after which the search stops.
+const PATIENCE: u64 = 500;
+
+pub(super) struct TabuState {
+    routes: TransitionRoutes,
+    iterations: u64,
+    /// Route index → iteration from which the student may be moved again.
+    tabu_until: Vec<u64>,
+    best_routes: Vec<Vec<usize>>,
+    best_objective: f64,
+    /// Iteration at which the best objective was last improved.
+    last_improvement: u64,
+    converged: bool,
+}
+
+impl TabuState {
+    pub(super) fn new(routes: TransitionRoutes, iterations: u64) -> Self {
+        Self {
+            iterations,
+            tabu_until: vec![0; routes.routes.len()],
+            best_routes: routes.nodes(),
+            best_objective: routes.objective,
+            last_improvement: iterations,
+            converged: routes.routes.is_empty(),
+            routes,
+        }
+    }
+
+    fn route_performance(&self, context: &OptimizationContext<'_>, nodes: &[usize]) -> f64 {
+        context.graph
+            .counted_edges(nodes)
+            .map(|edge| {
+                edge_performance(edge.distance as f64, self.routes.loads[edge.id] as f64)
+            })
+            .sum()
+    }
+
+    fn iterate(&mut self, context: &OptimizationContext<'_>) {
+        let mut candidates: Vec<(f64, usize)> = self.routes.routes
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| self.tabu_until[*index] <= self.iterations)
+            .map(|(index, route)| (self.route_performance(context, &route.nodes), index))
+            .collect();
+        candidates.sort_unstable_by(|candidate1, candidate2| {
+            candidate2.0.total_cmp(&candidate1.0)
+        });
+        let mut best_move: Option<(f64, usize, Vec<usize>)> = None;
+        for (_, index) in candidates.into_iter().take(CANDIDATES) {
+            let Some(new_nodes) = self.routes
+                .marginal_cost_path(context, index, 0.0)
+                .filter(|nodes| *nodes != self.routes.routes[index].nodes) else {
+                // Nothing to move this student to until the routes around them change.
+                self.tabu_until[index] = self.iterations + TABU_TENURE;
+                continue;
+            };
+            let delta = self.routes.reroute_delta(context, index, &new_nodes);
+            if best_move.as_ref().is_none_or(|(best_delta, _, _)| delta < *best_delta) {
+                best_move = Some((delta, index, new_nodes));
+            }
+        }
+        if let Some((_, index, new_nodes)) = best_move {
+            if context.allow_reroute(self.routes.day, &self.routes.routes[index], &new_nodes) {
+                self.routes.reroute(context, index, new_nodes);
+            }
+            self.tabu_until[index] = self.iterations + TABU_TENURE;
+        }
+        self.iterations += 1;
+        if self.routes.objective < self.best_objective {
+            self.best_routes = self.routes.nodes();
+            self.best_objective = self.routes.objective;
+            self.last_improvement = self.iterations;
+        } else if self.iterations - self.last_improvement >= PATIENCE {
+            self.converged = true;
+        }
+    }
+}
+
+impl RouteOptimizer for TabuState {
+    fn routes(&self) -> &TransitionRoutes {
+        &self.routes
+    }
+
+    fn iterations(&self) -> u64 {
+        self.iterations
+    }
+
+    fn is_converged(&self) -> bool {
+        self.converged
+    }
+
+    fn run_batch(&mut self, context: &OptimizationContext<'_>) {
+        for _ in 0..context.params.batch_size {
+            if self.converged {
+                break;
+            }
+            self.iterate(context);
+        }
+        if self.converged {
+            let best_routes = std::mem::take(&mut self.best_routes);
+            self.routes.restore(context, &best_routes);
+            self.best_routes = best_routes;
+        } else {
+            self.routes.update_loads(context);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{
+        tests::{
+            assert_keeps_detour_limits,
+            assert_keeps_pinning_rules,
+            assert_same_seed_same_routes,
+            batch_performance_indices,
+        },
+        OptimizationStrategy,
+    };
+
+    #[test]
+    fn ends_with_the_best_routes() {
+        let performance_indices = batch_performance_indices(OptimizationStrategy::TabuSearch);
+        let best = performance_indices.iter().copied().fold(f64::INFINITY, f64::min);
+        assert!(best < performance_indices[0]);
+        assert_eq!(*performance_indices.last().unwrap(), best);
+    }
+
+    #[test]
+    fn keeps_pinning_rules() {
+        assert_keeps_pinning_rules(OptimizationStrategy::TabuSearch);
+    }
+
+    #[test]
+    fn keeps_detour_limits() {
+        assert_keeps_detour_limits(OptimizationStrategy::TabuSearch);
+    }
+
+    #[test]
+    fn same_seed_gives_same_routes() {
+        assert_same_seed_same_routes(OptimizationStrategy::TabuSearch);
+    }
+}
@@ -0,0 +1,400 @@
+//! Multi-objective route optimization.
+//!
+//! Route plans are compared on three separate objectives instead of the performance index: the
+//! total walking distance, the peak edge load and the largest detour of a single student. Plans are
+//! generated by rerouting students worst-first on the building graph, where every student already
+//! walking an edge makes it more expensive. Sweeping the strength of this congestion penalty trades
+//! distance for congestion, and the plans that no other plan beats on every objective are kept in a
+//! Pareto archive. Reroutes that would exceed the detour limits of `fairness` are not made.
+
+use std::collections::HashMap;
+
+use crate::{
+    evaluation::edge_total_performance,
+    fairness::DetourLimits,
+    graph::BuildingGraph,
+    routing::{ Routes, ShortestPaths },
+};
+
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+pub struct PlanObjectives {
+    pub total_distance: u64,
+    /// Highest number of students walking the same edge in any transition.
+    pub peak_load: u32,
+    /// Largest extra distance walked by a student in a transition, compared to the shortest route.
+    pub max_detour: u32,
+}
+
+impl PlanObjectives {
+    /// Whether these objectives are at least as good as `other` on every objective and better on
+    /// at least one.
+    pub fn dominates(&self, other: &PlanObjectives) -> bool {
+        self.total_distance <= other.total_distance &&
+            self.peak_load <= other.peak_load &&
+            self.max_detour <= other.max_detour &&
+            self != other
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Objective {
+    TotalDistance,
+    PeakLoad,
+    MaxDetour,
+}
+
+impl Objective {
+    pub fn value(&self, objectives: &PlanObjectives) -> f64 {
+        match self {
+            Objective::TotalDistance => objectives.total_distance as f64,
+            Objective::PeakLoad => objectives.peak_load as f64,
+            Objective::MaxDetour => objectives.max_detour as f64,
+        }
+    }
+}
+
+impl std::fmt::Display for Objective {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Objective::TotalDistance => write!(f, "Total distance"),
+            Objective::PeakLoad => write!(f, "Peak edge load"),
+            Objective::MaxDetour => write!(f, "Maximum detour"),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct RoutePlan {
+    /// Congestion penalty the plan was generated with.
+    pub penalty: f64,
+    /// Number of rerouting passes the plan was generated with.
+    pub passes: usize,
+    pub objectives: PlanObjectives,
+    pub performance_index: f64,
+    /// Routes that differ from the base routes: (student number, day, transition) → route.
+    pub changes: HashMap<(String, u32, usize), String>,
+}
+
+impl RoutePlan {
+    pub fn apply(&self, base: &Routes) -> Routes {
+        let mut routes = base.clone();
+        for ((student_number, day, transition), route) in &self.changes {
+            if
+                let Some(day_routes) = routes
+                    .get_mut(student_number)
+                    .and_then(|student_routes| student_routes.get_mut(day))
+            {
+                day_routes.insert(*transition, route.to_owned());
+            }
+        }
+        routes
+    }
+}
+
+/// Non-dominated route plans.
+#[derive(Clone, Default)]
+pub struct ParetoArchive {
+    plans: Vec<RoutePlan>,
+}
+
+impl ParetoArchive {
+    /// Adds `plan` unless an archived plan dominates it or has the same objectives, removing the
+    /// plans it dominates. Returns whether the plan was added.
+    pub fn insert(&mut self, plan: RoutePlan) -> bool {
+        if
+            self.plans
+                .iter()
+                .any(|archived| {
+                    archived.objectives.dominates(&plan.objectives) ||
+                        archived.objectives == plan.objectives
+                })
+        {
+            return false;
+        }
+        self.plans.retain(|archived| !plan.objectives.dominates(&archived.objectives));
+        self.plans.push(plan);
+        self.plans.sort_by_key(|plan| plan.objectives.total_distance);
+        true
+    }
+
+    /// Archived plans, by increasing total distance.
+    pub fn plans(&self) -> &[RoutePlan] {
+        &self.plans
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct ParetoParams {
+    /// Number of congestion penalties tried between zero and `max_penalty`.
+    pub penalty_levels: usize,
+    /// Relative increase in the cost of an edge for every 100 students already walking it.
+    pub max_penalty: f64,
+    /// Rerouting passes over all students for each penalty.
+    pub passes: usize,
+    pub detour_limits: DetourLimits,
+}
+
+/// A student's route in one transition.
+#[derive(Clone)]
+struct PlanRoute {
+    student: usize,
+    nodes: Vec<usize>,
+    shortest_distance: u32,
+}
+
+#[derive(Clone)]
+struct TransitionPlan {
+    day: u32,
+    transition: usize,
+    routes: Vec<PlanRoute>,
+    /// Edge id → number of students walking the edge.
+    loads: Vec<u32>,
+}
+
+struct PlanSearch<'a> {
+    graph: &'a BuildingGraph,
+    students: Vec<String>,
+    base: Vec<TransitionPlan>,
+    /// Student → day → sum of the shortest distances of the student's routes.
+    day_shortest_distances: Vec<[u32; 5]>,
+    /// Student → day → sum of the detours of the student's base routes.
+    base_day_detours: Vec<[u32; 5]>,
+}
+
+impl<'a> PlanSearch<'a> {
+    fn new(base: &Routes, shortest_paths: &ShortestPaths, graph: &'a BuildingGraph) -> Self {
+        let mut students: Vec<String> = base.keys().cloned().collect();
+        students.sort();
+        let mut search = Self {
+            graph,
+            base: vec![],
+            day_shortest_distances: vec![[0; 5]; students.len()],
+            base_day_detours: vec![[0; 5]; students.len()],
+            students,
+        };
+        for day in 1..=5 {
+            for transition in 0..=11 {
+                let mut plan = TransitionPlan {
+                    day,
+                    transition,
+                    routes: vec![],
+                    loads: vec![0; graph.edge_count()],
+                };
+                for (student, student_number) in search.students.iter().enumerate() {
+                    let Some(route) = base[student_number]
+                        .get(&day)
+                        .and_then(|day_routes| day_routes.get(&transition)) else {
+                        continue;
+                    };
+                    let Some(nodes) = graph.route_ids(route) else {
+                        continue;
+                    };
+                    if nodes.len() < 2 || nodes.first() == nodes.last() {
+                        continue;
+                    }
+                    let shortest_distance = graph.shortest_distance(&nodes, shortest_paths);
+                    let route = PlanRoute { student, nodes, shortest_distance };
+                    search.day_shortest_distances[student][(day - 1) as usize] += shortest_distance;
+                    search.base_day_detours[student][(day - 1) as usize] += search.detour(&route);
+                    graph.add_load(&mut plan.loads, &route.nodes);
+                    plan.routes.push(route);
+                }
+                search.base.push(plan);
+            }
+        }
+        search
+    }
+
+    fn detour(&self, route: &PlanRoute) -> u32 {
+        self.graph.route_distance(&route.nodes).saturating_sub(route.shortest_distance)
+    }
+
+    fn edge_cost(&self, penalty: f64, loads: &[u32], distance: u32, edge: usize) -> f64 {
+        (distance as f64) * (1.0 + (penalty * (loads[edge] as f64)) / 100.0)
+    }
+
+    /// Reroutes every student of a transition once, starting with the most expensive route. A
+    /// student keeps their route if the new one would exceed the detour limits, where
+    /// `day_detours` holds the current detour of every student in every day.
+    fn reroute(
+        &self,
+        plan: &mut TransitionPlan,
+        penalty: f64,
+        limits: DetourLimits,
+        day_detours: &mut [[u32; 5]]
+    ) {
+        let day_index = (plan.day - 1) as usize;
+        let mut order: Vec<(f64, usize)> = plan.routes
+            .iter()
+            .enumerate()
+            .map(|(index, route)| {
+                let cost = self.graph
+                    .counted_edges(&route.nodes)
+                    .map(|edge| self.edge_cost(penalty, &plan.loads, edge.distance, edge.id))
+                    .sum();
+                (cost, index)
+            })
+            .collect();
+        order.sort_by(|a, b| b.0.total_cmp(&a.0));
+        for (_, index) in order {
+            let route = &plan.routes[index];
+            self.graph.remove_load(&mut plan.loads, &route.nodes);
+            let (start, end) = (route.nodes[0], *route.nodes.last().unwrap());
+            let new_nodes = self.graph.cheapest_path(start, end, |node, edge| {
+                if self.graph.is_ground(node) || self.graph.is_ground(edge.to) {
+                    edge.distance as f64
+                } else {
+                    self.edge_cost(penalty, &plan.loads, edge.distance, edge.id)
+                }
+            });
+            if let Some(new_nodes) = new_nodes {
+                let student = route.student;
+                let new_detour = self.graph
+                    .route_distance(&new_nodes)
+                    .saturating_sub(route.shortest_distance);
+                let new_day_detour =
+                    day_detours[student][day_index] - self.detour(route) + new_detour;
+                if
+                    limits.transition.allows(new_detour, route.shortest_distance) &&
+                    limits.day.allows(
+                        new_day_detour,
+                        self.day_shortest_distances[student][day_index]
+                    )
+                {
+                    day_detours[student][day_index] = new_day_detour;
+                    plan.routes[index].nodes = new_nodes;
+                }
+            }
+            self.graph.add_load(&mut plan.loads, &plan.routes[index].nodes);
+        }
+    }
+
+    fn objectives(&self, plans: &[TransitionPlan]) -> PlanObjectives {
+        let mut objectives = PlanObjectives::default();
+        for plan in plans {
+            for route in &plan.routes {
+                let distance = self.graph.route_distance(&route.nodes);
+                objectives.total_distance += distance as u64;
+                objectives.max_detour = objectives.max_detour.max(
+                    distance.saturating_sub(route.shortest_distance)
+                );
+            }
+            objectives.peak_load = objectives.peak_load.max(
+                plan.loads.iter().copied().max().unwrap_or(0)
+            );
+        }
+        objectives
+    }
+
+    fn performance_index(&self, plans: &[TransitionPlan]) -> f64 {
+        let edge_distances = self.graph.edge_distances();
+        plans
+            .iter()
+            .flat_map(|plan| plan.loads.iter().enumerate())
+            .map(|(edge, load)| edge_total_performance(edge_distances[edge] as f64, *load))
+            .sum()
+    }
+
+    fn route_plan(&self, plans: &[TransitionPlan], penalty: f64, passes: usize) -> RoutePlan {
+        let mut changes = HashMap::new();
+        for (plan, base_plan) in plans.iter().zip(&self.base) {
+            for (route, base_route) in plan.routes.iter().zip(&base_plan.routes) {
+                if route.nodes != base_route.nodes {
+                    changes.insert(
+                        (self.students[route.student].to_owned(), plan.day, plan.transition),
+                        self.graph.route_string(&route.nodes)
+                    );
+                }
+            }
+        }
+        RoutePlan {
+            penalty,
+            passes,
+            objectives: self.objectives(plans),
+            performance_index: self.performance_index(plans),
+            changes,
+        }
+    }
+}
+
+/// Generates route plans from `base` for a range of congestion penalties and returns the
+/// non-dominated ones, including `base` itself unless another plan dominates it.
+///
+/// `progress` is called with a percentage as the search advances.
+pub fn pareto_optimize(
+    base: &Routes,
+    shortest_paths: &ShortestPaths,
+    graph: &BuildingGraph,
+    params: ParetoParams,
+    mut progress: impl FnMut(i32)
+) -> ParetoArchive {
+    let search = PlanSearch::new(base, shortest_paths, graph);
+    let mut archive = ParetoArchive::default();
+    archive.insert(search.route_plan(&search.base, 0.0, 0));
+
+    let step_count = params.penalty_levels * params.passes;
+    for level in 1..=params.penalty_levels {
+        let penalty = (params.max_penalty * (level as f64)) / (params.penalty_levels as f64);
+        let mut plans = search.base.clone();
+        let mut day_detours = search.base_day_detours.clone();
+        for pass in 1..=params.passes {
+            progress(
+                ((((level - 1) * params.passes + pass - 1) as f32) / (step_count as f32) *
+                    100.0) as i32
+            );
+            for plan in &mut plans {
+                search.reroute(plan, penalty, params.detour_limits, &mut day_detours);
+            }
+            archive.insert(search.route_plan(&plans, penalty, pass));
+        }
+    }
+    archive
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn plan(total_distance: u64, peak_load: u32, max_detour: u32) -> RoutePlan {
+        RoutePlan {
+            penalty: 0.0,
+            passes: 0,
+            objectives: PlanObjectives { total_distance, peak_load, max_detour },
+            performance_index: 0.0,
+            changes: HashMap::new(),
+        }
+    }
+
+    fn archived(archive: &ParetoArchive) -> Vec<(u64, u32, u32)> {
+        archive
+            .plans()
+            .iter()
+            .map(|plan| {
+                let objectives = plan.objectives;
+                (objectives.total_distance, objectives.peak_load, objectives.max_detour)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn archive_keeps_only_non_dominated_plans() {
+        let mut archive = ParetoArchive::default();
+        assert!(archive.insert(plan(1000, 50, 0)));
+        assert!(archive.insert(plan(1200, 30, 40)));
+        // worse on every objective than the first plan
+        assert!(!archive.insert(plan(1100, 60, 10)));
+        // the same objectives as an archived plan
+        assert!(!archive.insert(plan(1200, 30, 40)));
+        // worse than the second plan on one objective and equal on the others
+        assert!(!archive.insert(plan(1200, 31, 40)));
+        assert_eq!(archived(&archive), vec![(1000, 50, 0), (1200, 30, 40)]);
+
+        // dominates the second plan, which is removed
+        assert!(archive.insert(plan(1150, 30, 20)));
+        assert_eq!(archived(&archive), vec![(1000, 50, 0), (1150, 30, 20)]);
+
+        // dominates every archived plan
+        assert!(archive.insert(plan(900, 20, 0)));
+        assert_eq!(archived(&archive), vec![(900, 20, 0)]);
+    }
+}
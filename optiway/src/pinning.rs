@@ -0,0 +1,522 @@
+//! Rules that pin, forbid or prefer edges for the routes of matching students and transitions.
+//!
+//! Rules are written as a YAML list, for example:
+//!
+//! ```yaml
+//! - name: Floor 8 by staircase S8
+//!   transitions: [3]
+//!   to: ["?8??"]
+//!   require: ["S8-*"]
+//! - name: Grade 11 keeps the shortest routes
+//!   students: ["21*"]
+//!   pin_shortest: true
+//! - name: Bridge closed
+//!   forbid: ["X2A1 X2B1"]
+//! ```
+//!
+//! A rule matches the routes of the students, days (1–5), transitions (0–11, where 0 is before
+//! P1 and 3 is P3–P4) and start and end rooms it lists, and every route if a list is left out.
+//! Student numbers and rooms are matched by patterns where `*` stands for any number of characters
+//! and `?` for one character. A rule either pins the matching routes to the shortest route, or
+//! constrains them:
+//!
+//! - `require`: node patterns, at least one of which the route must pass;
+//! - `forbid`: node or edge patterns the route must not use, where an edge is written as the
+//!   patterns of its two nodes separated by a space and a node stands for all of its edges;
+//! - `prefer`: node or edge patterns whose walking cost is multiplied by `prefer_factor`
+//!   (default 0.5) when the optimizer looks for new routes.
+//!
+//! A route matched by several rules keeps to all of them, unless one of them pins it.
+
+use std::{
+    collections::HashMap,
+    fmt::{ self, Display },
+    fs,
+    io,
+    path::Path,
+};
+
+use crate::graph::{ BuildingGraph, GraphEdge };
+
+fn default_prefer_factor() -> f64 {
+    0.5
+}
+
+#[derive(Clone, PartialEq, Debug, serde::Deserialize, serde::Serialize)]
+pub struct PinningRule {
+    pub name: String,
+    /// Student number patterns. Empty matches every student.
+    #[serde(default)]
+    pub students: Vec<String>,
+    /// Empty matches every day.
+    #[serde(default)]
+    pub days: Vec<u32>,
+    /// Empty matches every transition.
+    #[serde(default)]
+    pub transitions: Vec<usize>,
+    /// Patterns of the room the route starts at. Empty matches every room.
+    #[serde(default)]
+    pub from: Vec<String>,
+    /// Patterns of the room the route ends at. Empty matches every room.
+    #[serde(default)]
+    pub to: Vec<String>,
+    #[serde(default)]
+    pub pin_shortest: bool,
+    #[serde(default)]
+    pub require: Vec<String>,
+    #[serde(default)]
+    pub forbid: Vec<String>,
+    #[serde(default)]
+    pub prefer: Vec<String>,
+    #[serde(default = "default_prefer_factor")]
+    pub prefer_factor: f64,
+}
+
+impl PinningRule {
+    pub fn matches(
+        &self,
+        student_number: &str,
+        day: u32,
+        transition: usize,
+        from: &str,
+        to: &str
+    ) -> bool {
+        let matches_any = |patterns: &[String], text: &str| {
+            patterns.is_empty() || patterns.iter().any(|pattern| matches_pattern(pattern, text))
+        };
+        matches_any(&self.students, student_number) &&
+            (self.days.is_empty() || self.days.contains(&day)) &&
+            (self.transitions.is_empty() || self.transitions.contains(&transition)) &&
+            matches_any(&self.from, from) &&
+            matches_any(&self.to, to)
+    }
+
+    /// The routes the rule matches, e.g. `students 21*; days 1, 2`.
+    pub fn scope(&self) -> String {
+        let mut scope = vec![];
+        if !self.students.is_empty() {
+            scope.push(format!("students {}", join(&self.students)));
+        }
+        if !self.days.is_empty() {
+            scope.push(format!("days {}", join(&self.days)));
+        }
+        if !self.transitions.is_empty() {
+            scope.push(format!("transitions {}", join(&self.transitions)));
+        }
+        if !self.from.is_empty() {
+            scope.push(format!("from {}", join(&self.from)));
+        }
+        if !self.to.is_empty() {
+            scope.push(format!("to {}", join(&self.to)));
+        }
+        if scope.is_empty() { "all routes".to_owned() } else { scope.join("; ") }
+    }
+
+    /// What the rule does to the routes it matches, e.g. `forbid X2A1 X2B1`.
+    pub fn action(&self) -> String {
+        if self.pin_shortest {
+            return "pin to shortest".to_owned();
+        }
+        let mut actions = vec![];
+        if !self.require.is_empty() {
+            actions.push(format!("require {}", join(&self.require)));
+        }
+        if !self.forbid.is_empty() {
+            actions.push(format!("forbid {}", join(&self.forbid)));
+        }
+        if !self.prefer.is_empty() {
+            actions.push(format!("prefer {} (×{})", join(&self.prefer), self.prefer_factor));
+        }
+        actions.join("; ")
+    }
+}
+
+fn join<T: ToString>(items: &[T]) -> String {
+    items
+        .iter()
+        .map(|item| item.to_string())
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+#[derive(Debug)]
+pub enum PinningError {
+    Io(io::Error),
+    Format(serde_yaml::Error),
+    /// A rule that neither pins nor constrains routes.
+    NoAction(String),
+    /// A preference factor that is not a positive number.
+    InvalidFactor(String),
+    /// A pattern that matches no node of the building graph, with the name of its rule.
+    UnknownNode(String, String),
+    /// An edge pattern in the required nodes of a rule.
+    RequiredEdge(String, String),
+}
+
+impl Display for PinningError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PinningError::Io(error) => write!(f, "Failed to read pinning rules: {}", error),
+            PinningError::Format(error) => write!(f, "Invalid pinning rules: {}", error),
+            PinningError::NoAction(rule) => {
+                write!(f, "Rule \"{}\" neither pins nor constrains routes.", rule)
+            }
+            PinningError::InvalidFactor(rule) => {
+                write!(f, "The preference factor of rule \"{}\" must be positive.", rule)
+            }
+            PinningError::UnknownNode(rule, pattern) => {
+                write!(f, "\"{}\" of rule \"{}\" matches no node.", pattern, rule)
+            }
+            PinningError::RequiredEdge(rule, pattern) => {
+                write!(f, "Rule \"{}\" can only require nodes, not \"{}\".", rule, pattern)
+            }
+        }
+    }
+}
+
+impl std::error::Error for PinningError {}
+
+/// Reads the rules of a YAML file and checks them against the building graph.
+pub fn load_rules(path: &Path, graph: &BuildingGraph) -> Result<Vec<PinningRule>, PinningError> {
+    let content = fs::read_to_string(path).map_err(PinningError::Io)?;
+    let rules: Vec<PinningRule> = serde_yaml::from_str(&content).map_err(PinningError::Format)?;
+    check_rules(&rules, graph)?;
+    Ok(rules)
+}
+
+pub fn check_rules(rules: &[PinningRule], graph: &BuildingGraph) -> Result<(), PinningError> {
+    for rule in rules {
+        if
+            !rule.pin_shortest &&
+            rule.require.is_empty() &&
+            rule.forbid.is_empty() &&
+            rule.prefer.is_empty()
+        {
+            return Err(PinningError::NoAction(rule.name.clone()));
+        }
+        if rule.prefer_factor.is_nan() || rule.prefer_factor <= 0.0 {
+            return Err(PinningError::InvalidFactor(rule.name.clone()));
+        }
+        for pattern in &rule.require {
+            if pattern.contains(' ') {
+                return Err(PinningError::RequiredEdge(rule.name.clone(), pattern.clone()));
+            }
+        }
+        for pattern in rule.require.iter().chain(&rule.forbid).chain(&rule.prefer) {
+            let matches_node = pattern
+                .split(' ')
+                .all(|node_pattern| {
+                    (0..graph.node_count()).any(|node| {
+                        matches_pattern(node_pattern, graph.node_name(node))
+                    })
+                });
+            if !matches_node {
+                return Err(PinningError::UnknownNode(rule.name.clone(), pattern.clone()));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Whether `text` matches `pattern`, where `*` stands for any number of characters and `?` for
+/// one character.
+pub fn matches_pattern(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    // position after the last `*`, and the text position it was matched up to
+    let mut star = None;
+    let (mut p, mut t) = (0, 0);
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p + 1, t));
+            p += 1;
+        } else if let Some((star_p, star_t)) = star {
+            p = star_p;
+            t = star_t + 1;
+            star = Some((star_p, t));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+/// Edges of the graph matching a node or edge pattern.
+fn matching_edges(graph: &BuildingGraph, pattern: &str) -> Vec<usize> {
+    let mut edges = vec![];
+    for node in 0..graph.node_count() {
+        for edge in graph.edges(node) {
+            let (name1, name2) = (graph.node_name(node), graph.node_name(edge.to));
+            let matches = match pattern.split_once(' ') {
+                Some((pattern1, pattern2)) => {
+                    matches_pattern(pattern1, name1) && matches_pattern(pattern2, name2)
+                }
+                None => matches_pattern(pattern, name1) || matches_pattern(pattern, name2),
+            };
+            if matches {
+                edges.push(edge.id);
+            }
+        }
+    }
+    edges
+}
+
+/// How the rules matching a route constrain it.
+pub struct RouteConstraint {
+    /// Edge id → factor the cost of walking the edge is multiplied by, infinite if it is
+    /// forbidden.
+    edge_factors: Vec<f64>,
+    /// Nodes at least one of which the route must pass, or empty.
+    required_nodes: Vec<usize>,
+}
+
+impl RouteConstraint {
+    fn new(graph: &BuildingGraph, rules: &[&PinningRule]) -> Self {
+        let mut edge_factors = vec![1.0_f64; graph.edge_count()];
+        let mut required_nodes = vec![];
+        for rule in rules {
+            for pattern in &rule.prefer {
+                for edge in matching_edges(graph, pattern) {
+                    edge_factors[edge] = edge_factors[edge].min(rule.prefer_factor);
+                }
+            }
+            for pattern in &rule.require {
+                required_nodes.extend(
+                    (0..graph.node_count()).filter(|node| {
+                        matches_pattern(pattern, graph.node_name(*node))
+                    })
+                );
+            }
+        }
+        // forbidding wins over preferring, whichever rule comes first
+        for rule in rules {
+            for pattern in &rule.forbid {
+                for edge in matching_edges(graph, pattern) {
+                    edge_factors[edge] = f64::INFINITY;
+                }
+            }
+        }
+        required_nodes.sort_unstable();
+        required_nodes.dedup();
+        Self { edge_factors, required_nodes }
+    }
+
+    /// Whether a route uses no forbidden edge and passes a required node.
+    pub fn allows(&self, graph: &BuildingGraph, nodes: &[usize]) -> bool {
+        let uses_no_forbidden_edge = nodes.windows(2).all(|pair| {
+            graph
+                .edge_between(pair[0], pair[1])
+                .is_some_and(|edge| self.edge_factors[edge.id].is_finite())
+        });
+        uses_no_forbidden_edge &&
+            (self.required_nodes.is_empty() ||
+                nodes.iter().any(|node| self.required_nodes.binary_search(node).is_ok()))
+    }
+
+    /// Cheapest path from `start` to `end` that keeps to the constraint, where `cost` gives the
+    /// cost of walking an edge before it is multiplied by the factor of the edge.
+    pub fn cheapest_path(
+        &self,
+        graph: &BuildingGraph,
+        start: usize,
+        end: usize,
+        cost: impl Fn(usize, &GraphEdge) -> f64
+    ) -> Option<Vec<usize>> {
+        let factored_cost = |node: usize, edge: &GraphEdge| {
+            let factor = self.edge_factors[edge.id];
+            if factor.is_finite() { factor * cost(node, edge) } else { f64::INFINITY }
+        };
+        let passes_required = self.required_nodes.is_empty() || [start, end]
+            .iter()
+            .any(|node| self.required_nodes.binary_search(node).is_ok());
+        if passes_required {
+            return graph.cheapest_path(start, end, factored_cost);
+        }
+        let path_cost = |path: &[usize]| -> f64 {
+            path.windows(2)
+                .map(|pair| {
+                    graph
+                        .edge_between(pair[0], pair[1])
+                        .map_or(f64::INFINITY, |edge| factored_cost(pair[0], edge))
+                })
+                .sum()
+        };
+        self.required_nodes
+            .iter()
+            .filter_map(|&via| {
+                let mut path = graph.cheapest_path(start, via, factored_cost)?;
+                let second_half = graph.cheapest_path(via, end, factored_cost)?;
+                path.extend_from_slice(&second_half[1..]);
+                Some((path_cost(&path), path))
+            })
+            .min_by(|(cost1, _), (cost2, _)| cost1.total_cmp(cost2))
+            .map(|(_, path)| path)
+    }
+}
+
+/// What the rules do to one route.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum RouteRule {
+    /// No rule matches the route.
+    Free,
+    /// The route is fixed to the shortest route.
+    Pinned,
+    /// The route keeps to a constraint, by index.
+    Constrained(usize),
+}
+
+/// Rules prepared for the routes of one building graph. Routes matched by the same rules share a
+/// constraint.
+pub struct CompiledRules<'a> {
+    graph: &'a BuildingGraph,
+    rules: &'a [PinningRule],
+    constraints: Vec<RouteConstraint>,
+    /// Indices of matching rules → constraint index.
+    constraint_indices: HashMap<Vec<usize>, usize>,
+}
+
+impl<'a> CompiledRules<'a> {
+    pub fn new(graph: &'a BuildingGraph, rules: &'a [PinningRule]) -> Self {
+        Self { graph, rules, constraints: vec![], constraint_indices: HashMap::new() }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Indices of the rules matching a route.
+    pub fn matching_rules(
+        &self,
+        student_number: &str,
+        day: u32,
+        transition: usize,
+        from: &str,
+        to: &str
+    ) -> Vec<usize> {
+        (0..self.rules.len())
+            .filter(|index| self.rules[*index].matches(student_number, day, transition, from, to))
+            .collect()
+    }
+
+    /// What the rules of `matching_rules` do to a route.
+    pub fn route_rule(&mut self, matching_rules: &[usize]) -> RouteRule {
+        if matching_rules.is_empty() {
+            return RouteRule::Free;
+        }
+        if matching_rules.iter().any(|index| self.rules[*index].pin_shortest) {
+            return RouteRule::Pinned;
+        }
+        let index = match self.constraint_indices.get(matching_rules) {
+            Some(index) => *index,
+            None => {
+                let rules: Vec<&PinningRule> = matching_rules
+                    .iter()
+                    .map(|index| &self.rules[*index])
+                    .collect();
+                self.constraints.push(RouteConstraint::new(self.graph, &rules));
+                self.constraint_indices.insert(matching_rules.to_vec(), self.constraints.len() - 1);
+                self.constraints.len() - 1
+            }
+        };
+        RouteRule::Constrained(index)
+    }
+
+    pub fn constraint(&self, index: usize) -> &RouteConstraint {
+        &self.constraints[index]
+    }
+
+    pub fn into_constraints(self) -> Vec<RouteConstraint> {
+        self.constraints
+    }
+}
+
+/// How many routes a rule affected when the rules were applied.
+#[derive(Clone, Default, PartialEq, Eq, Debug)]
+pub struct RuleOutcome {
+    pub name: String,
+    /// Routes the rule matches.
+    pub matched: usize,
+    /// Routes that broke the rule and were changed to keep to the rules.
+    pub changed: usize,
+    /// Routes no path of the building graph can keep to the rules, which are left as they are.
+    pub unsatisfiable: usize,
+}
+
+pub type PinningReport = Vec<RuleOutcome>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Three corridors from `A1` to `C1`: through `B1`, the shortest, then `D1`, then `E1`.
+    const PATHS: &str = "\
+A101 A1 50 0
+A1 B1 100 0
+B1 C1 100 0
+C1 C101 50 0
+A1 D1 110 0
+D1 C1 110 0
+A1 E1 150 0
+E1 C1 150 0
+";
+
+    const RULES: &str = "\
+- { name: pin, students: ['2100*'], pin_shortest: true }
+- { name: require, students: ['2101*'], require: [E1] }
+- { name: forbid, students: ['2102*'], forbid: [B1] }
+";
+
+    #[test]
+    fn pin_require_and_forbid_rules_constrain_their_routes() {
+        let graph = BuildingGraph::parse(PATHS);
+        let rules: Vec<PinningRule> = serde_yaml::from_str(RULES).unwrap();
+        check_rules(&rules, &graph).unwrap();
+        let mut compiled_rules = CompiledRules::new(&graph, &rules);
+        let (start, end) = (graph.node_id("A101").unwrap(), graph.node_id("C101").unwrap());
+        let mut route_rule = |student_number: &str| {
+            let matching_rules = compiled_rules.matching_rules(
+                student_number,
+                1,
+                1,
+                "A101",
+                "C101"
+            );
+            compiled_rules.route_rule(&matching_rules)
+        };
+        assert_eq!(route_rule("21001"), RouteRule::Pinned);
+        assert_eq!(route_rule("21031"), RouteRule::Free);
+        let (RouteRule::Constrained(require), RouteRule::Constrained(forbid)) = (
+            route_rule("21011"),
+            route_rule("21021"),
+        ) else {
+            panic!("the require and forbid rules do not constrain their routes");
+        };
+
+        let shortest = graph.route_ids("A101 A1 B1 C1 C101").unwrap();
+        let cheapest_path = |constraint: &RouteConstraint| {
+            let path = constraint.cheapest_path(&graph, start, end, |_, edge| edge.distance as f64);
+            graph.route_string(&path.unwrap())
+        };
+        let require = compiled_rules.constraint(require);
+        assert!(!require.allows(&graph, &shortest));
+        assert_eq!(cheapest_path(require), "A101 A1 E1 C1 C101");
+        let forbid = compiled_rules.constraint(forbid);
+        assert!(!forbid.allows(&graph, &shortest));
+        assert_eq!(cheapest_path(forbid), "A101 A1 D1 C1 C101");
+    }
+
+    #[test]
+    fn rules_with_unknown_nodes_or_required_edges_are_rejected() {
+        let graph = BuildingGraph::parse(PATHS);
+        let check = |rules: &str| {
+            let rules: Vec<PinningRule> = serde_yaml::from_str(rules).unwrap();
+            check_rules(&rules, &graph).unwrap_err().to_string()
+        };
+        assert!(check("- { name: unknown, forbid: [Z9] }").contains("matches no node"));
+        assert!(check("- { name: edge, require: [A1 B1] }").contains("only require nodes"));
+        assert!(check("- { name: nothing, students: ['21*'] }").contains("neither pins"));
+    }
+}
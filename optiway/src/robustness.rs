@@ -0,0 +1,390 @@
+//! Robustness of route plans against changes to the timetable.
+//!
+//! A scenario perturbs the timetable: every student is absent on a day with the absence rate, and
+//! every session (the students in a room in a period) moves to a random free room with the room
+//! change rate. An absent student is in `G` for the whole day and does not walk. Wherever the
+//! rooms a student walks between change, the student leaves the planned route and walks the
+//! shortest route between the new rooms; everyone else keeps to the plan. The rooms of arrivals,
+//! lunch and dismissals follow the destinations of the students (see `destinations`).
+
+use std::collections::{ BTreeMap, BTreeSet, HashMap };
+
+use crate::{
+    compact_routes::CompactRoutes,
+    destinations::DestinationAssignment,
+    evaluation::edge_total_performance,
+    graph::BuildingGraph,
+    monte_carlo::ConfidenceInterval,
+    optimization::random::Random,
+    routing::{ shortest_path, ShortestPaths, TRANSITION_COUNT },
+    timetable::{ Day, Period, Timetable, DAY_COUNT },
+};
+
+#[derive(Clone, Copy, PartialEq, Debug, serde::Deserialize, serde::Serialize)]
+pub struct PerturbationParams {
+    /// Probability that a student is absent on a day.
+    pub absence_rate: f64,
+    /// Probability that a session moves to another room.
+    pub room_change_rate: f64,
+    pub samples: usize,
+    pub seed: u64,
+}
+
+impl Default for PerturbationParams {
+    fn default() -> Self {
+        Self {
+            absence_rate: 0.1,
+            room_change_rate: 0.05,
+            samples: 16,
+            seed: 0,
+        }
+    }
+}
+
+/// A perturbed timetable, as the routes that differ from the routes of the timetable as given.
+#[derive(Clone, Default, Debug)]
+pub struct Scenario {
+    /// Number of days a student is absent.
+    pub absences: usize,
+    /// Number of sessions moved to another room.
+    pub room_changes: usize,
+    /// Student number → (day, transition) → route walked instead of the planned route, empty if
+    /// the student does not walk.
+    routes: HashMap<String, BTreeMap<(u32, usize), String>>,
+}
+
+impl Scenario {
+    /// Route walked instead of the planned route, or `None` if the student keeps to the plan.
+    pub fn route(&self, student_number: &str, day: u32, transition: usize) -> Option<&str> {
+        self.routes
+            .get(student_number)
+            .and_then(|routes| routes.get(&(day, transition)))
+            .map(String::as_str)
+    }
+
+    /// Number of routes that differ from the plan.
+    pub fn changed_route_count(&self) -> usize {
+        self.routes.values().map(BTreeMap::len).sum()
+    }
+}
+
+/// Perturbs `timetable` at random, and returns the perturbed timetable with the number of absences
+/// and room changes.
+fn perturb(
+    timetable: &Timetable,
+    params: PerturbationParams,
+    random: &mut Random
+) -> (Timetable, usize, usize) {
+    let mut perturbed = timetable.clone();
+    // sessions and the rooms in use, by day and period
+    let mut sessions: BTreeSet<(Day, Period, &str)> = BTreeSet::new();
+    let mut rooms: BTreeSet<&str> = BTreeSet::new();
+    for (_, week_timetable) in timetable.students() {
+        for (day, day_timetable) in week_timetable.days() {
+            for (period, room) in day_timetable.rooms().filter(|(_, room)| *room != "G") {
+                sessions.insert((day, period, room));
+                rooms.insert(room);
+            }
+        }
+    }
+
+    let mut moves: HashMap<(Day, Period, &str), &str> = HashMap::new();
+    let mut used: HashMap<(Day, Period), BTreeSet<&str>> = HashMap::new();
+    for (day, period, room) in &sessions {
+        used.entry((*day, *period)).or_default().insert(room);
+    }
+    for &(day, period, room) in &sessions {
+        if random.next_f64() >= params.room_change_rate {
+            continue;
+        }
+        let used = used.get_mut(&(day, period)).unwrap();
+        let free: Vec<&str> = rooms
+            .iter()
+            .copied()
+            .filter(|room| !used.contains(room))
+            .collect();
+        if free.is_empty() {
+            continue;
+        }
+        let new_room = free[random.below(free.len())];
+        used.insert(new_room);
+        moves.insert((day, period, room), new_room);
+    }
+
+    let mut absences = 0;
+    let student_numbers: Vec<String> = timetable.student_numbers().map(str::to_owned).collect();
+    for student_number in &student_numbers {
+        let week_timetable = perturbed.student_mut(student_number).unwrap();
+        for day in Day::all() {
+            let is_absent = random.next_f64() < params.absence_rate;
+            absences += is_absent as usize;
+            let day_timetable = week_timetable.day_mut(day);
+            for period in Period::all() {
+                let room = timetable.room(student_number, day, period).unwrap();
+                if is_absent {
+                    day_timetable.set_room(period, "G".to_owned());
+                } else if let Some(new_room) = moves.get(&(day, period, room)) {
+                    day_timetable.set_room(period, (*new_room).to_owned());
+                }
+            }
+        }
+    }
+    (perturbed, absences, moves.len())
+}
+
+/// Samples `params.samples` scenarios of `timetable`. The same parameters always give the same
+/// scenarios.
+pub fn scenarios(
+    timetable: &Timetable,
+    destinations: &DestinationAssignment,
+    shortest_paths: &ShortestPaths,
+    params: PerturbationParams
+) -> Vec<Scenario> {
+    // the seeds of the samples are drawn from one generator, so that no two samples of nearby
+    // seeds share a seed
+    let mut seeds = Random::new(params.seed);
+    (0..params.samples)
+        .map(|_| {
+            let mut random = Random::new(seeds.next_u64());
+            let (perturbed, absences, room_changes) = perturb(timetable, params, &mut random);
+            let mut routes: HashMap<String, BTreeMap<(u32, usize), String>> = HashMap::new();
+            for (student_number, week_timetable) in timetable.students() {
+                let perturbed_week = perturbed.student(student_number).unwrap();
+                for (day, day_timetable) in week_timetable.days() {
+                    let perturbed_day = perturbed_week.day(day);
+                    for transition in 0..TRANSITION_COUNT {
+                        let ends = destinations.transition_ends(
+                            student_number,
+                            day,
+                            day_timetable,
+                            transition
+                        );
+                        let perturbed_ends = destinations.transition_ends(
+                            student_number,
+                            day,
+                            perturbed_day,
+                            transition
+                        );
+                        if ends == perturbed_ends {
+                            continue;
+                        }
+                        let route = perturbed_ends.map_or_else(String::new, |(from, to)| {
+                            shortest_path(shortest_paths, from, to)
+                        });
+                        routes
+                            .entry(student_number.to_owned())
+                            .or_default()
+                            .insert((day.number(), transition), route);
+                    }
+                }
+            }
+            Scenario { absences, room_changes, routes }
+        })
+        .collect()
+}
+
+/// Performance indices of a route plan in the timetable as given and in every scenario.
+pub struct RobustnessReport {
+    /// Transition → performance index of the plan, ordered by day, then transition.
+    nominal: Vec<f64>,
+    /// Scenario → transition → performance index.
+    samples: Vec<Vec<f64>>,
+}
+
+impl RobustnessReport {
+    fn transition_index(day: u32, transition: usize) -> Option<usize> {
+        ((1..=DAY_COUNT as u32).contains(&day) && transition < TRANSITION_COUNT).then(|| {
+            ((day - 1) as usize) * TRANSITION_COUNT + transition
+        })
+    }
+
+    pub fn nominal_performance_index(&self) -> f64 {
+        self.nominal.iter().sum()
+    }
+
+    /// Performance index of the week in every scenario.
+    pub fn sample_performance_indices(&self) -> Vec<f64> {
+        self.samples
+            .iter()
+            .map(|sample| sample.iter().sum())
+            .collect()
+    }
+
+    pub fn performance_index(&self) -> ConfidenceInterval {
+        ConfidenceInterval::new(&self.sample_performance_indices())
+    }
+
+    pub fn worst_performance_index(&self) -> f64 {
+        self.sample_performance_indices().into_iter().fold(0.0, f64::max)
+    }
+
+    /// Performance index of a transition as given, over the scenarios, and in the worst scenario.
+    pub fn transition(
+        &self,
+        day: u32,
+        transition: usize
+    ) -> Option<(f64, ConfidenceInterval, f64)> {
+        let index = Self::transition_index(day, transition)?;
+        let samples: Vec<f64> = self.samples
+            .iter()
+            .map(|sample| sample[index])
+            .collect();
+        let worst = samples.iter().copied().fold(0.0, f64::max);
+        Some((self.nominal[index], ConfidenceInterval::new(&samples), worst))
+    }
+}
+
+/// Scores the fixed route plan `routes` in the timetable as given and in every scenario.
+pub fn evaluate(
+    graph: &BuildingGraph,
+    routes: &CompactRoutes,
+    scenarios: &[Scenario]
+) -> RobustnessReport {
+    let graph_ids: Vec<Option<usize>> = routes
+        .nodes()
+        .map(|(_, name)| graph.node_id(name))
+        .collect();
+    let edge_distances = graph.edge_distances();
+    let performance_index = |loads: &[u32]| -> f64 {
+        loads
+            .iter()
+            .zip(&edge_distances)
+            .filter(|(load, _)| **load > 0)
+            .map(|(load, distance)| edge_total_performance(*distance as f64, *load))
+            .sum()
+    };
+    let student_numbers: Vec<&str> = routes.students().collect();
+    let score = |scenario: Option<&Scenario>| -> Vec<f64> {
+        let mut performance_indices = Vec::with_capacity(DAY_COUNT * TRANSITION_COUNT);
+        let mut loads = vec![0; graph.edge_count()];
+        for day in 1..=DAY_COUNT as u32 {
+            for transition in 0..TRANSITION_COUNT {
+                loads.fill(0);
+                for (student_index, student_number) in student_numbers.iter().enumerate() {
+                    let changed_route = scenario.and_then(|scenario| {
+                        scenario.route(student_number, day, transition)
+                    });
+                    let nodes = match changed_route {
+                        Some(route) => graph.route_ids(route),
+                        None =>
+                            routes
+                                .route(student_index, day, transition)
+                                .iter()
+                                .map(|node| graph_ids[node.index()])
+                                .collect(),
+                    };
+                    if let Some(nodes) = nodes {
+                        graph.add_load(&mut loads, &nodes);
+                    }
+                }
+                performance_indices.push(performance_index(&loads));
+            }
+        }
+        performance_indices
+    };
+    RobustnessReport {
+        nominal: score(None),
+        samples: scenarios
+            .iter()
+            .map(|scenario| score(Some(scenario)))
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::routing::routes_from_timetable;
+
+    use super::*;
+
+    /// Rooms `A101` and `A102` next to the corridor node `A1`, and rooms `B101` and `B102` next to
+    /// `B1`. Both corridor nodes are entrances.
+    const PATHS: &str = "\
+A101 A1 10 0
+A102 A1 10 0
+A1 B1 200 0
+B101 B1 10 0
+B102 B1 10 0
+A1 G 999999 2
+B1 G 999999 2
+";
+
+    const ROOMS: [&str; 4] = ["A101", "A102", "B101", "B102"];
+
+    fn corridor_node(room: &str) -> &str {
+        if room.starts_with('A') { "A1" } else { "B1" }
+    }
+
+    fn shortest_paths() -> ShortestPaths {
+        let mut shortest_paths = ShortestPaths::new();
+        for from in ROOMS {
+            let node = corridor_node(from);
+            shortest_paths.insert(format!("G{}", from), format!("G {} {}", node, from));
+            shortest_paths.insert(format!("{}G", from), format!("{} {} G", from, node));
+            for to in ROOMS.iter().filter(|to| **to != from) {
+                let route = if node == corridor_node(to) {
+                    format!("{} {} {}", from, node, to)
+                } else {
+                    format!("{} {} {} {}", from, node, corridor_node(to), to)
+                };
+                shortest_paths.insert(format!("{}{}", from, to), route);
+            }
+        }
+        shortest_paths
+    }
+
+    /// Students `21001` to `21030` in `A101` in odd periods and in `B101` in even periods, and
+    /// students `21031` to `21060` the other way round.
+    fn timetable() -> Timetable {
+        let week = |odd_room: &str, even_room: &str| {
+            let day: serde_json::Map<String, serde_json::Value> = Period::all()
+                .map(|period| {
+                    let room = if period.number() % 2 == 1 { odd_room } else { even_room };
+                    (period.to_string(), room.into())
+                })
+                .collect();
+            serde_json::Value::Object(
+                Day::all()
+                    .map(|day_of_week| (day_of_week.to_string(), day.clone().into()))
+                    .collect()
+            )
+        };
+        let students: serde_json::Map<String, serde_json::Value> = (21001..=21060)
+            .map(|student| {
+                let week = if student <= 21030 {
+                    week("A101", "B101")
+                } else {
+                    week("B101", "A101")
+                };
+                (student.to_string(), week)
+            })
+            .collect();
+        let json = serde_json::Value::Object(students).to_string();
+        Timetable::from_reader(json.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn worst_case_is_at_least_the_expected_performance_index() {
+        let graph = BuildingGraph::parse(PATHS);
+        let (timetable, shortest_paths) = (timetable(), shortest_paths());
+        let routes = CompactRoutes::from_routes(
+            &routes_from_timetable(&timetable, &shortest_paths)
+        ).unwrap();
+        let params = PerturbationParams {
+            absence_rate: 0.2,
+            room_change_rate: 0.3,
+            samples: 8,
+            seed: 3,
+        };
+        let scenarios = scenarios(&timetable, &Default::default(), &shortest_paths, params);
+        assert!(scenarios.iter().all(|scenario| scenario.changed_route_count() > 0));
+        let report = evaluate(&graph, &routes, &scenarios);
+        let performance_indices = report.sample_performance_indices();
+        assert!(performance_indices.iter().any(|index| *index != performance_indices[0]));
+        assert!(report.worst_performance_index() >= report.performance_index().mean);
+        for transition in 0..TRANSITION_COUNT {
+            let (_, expected, worst) = report.transition(1, transition).unwrap();
+            assert!(worst >= expected.mean, "transition {}", transition);
+        }
+    }
+}
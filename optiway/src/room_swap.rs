@@ -0,0 +1,469 @@
+//! Room reassignment suggestions.
+//!
+//! A suggestion either swaps the rooms of two sessions taking place in the same period, or moves a
+//! session to a room which the timetable leaves free in that period. Only rooms that appear in the
+//! timetable are considered, and candidates are limited to the rooms nearest to the original one.
+
+use std::collections::{ HashMap, HashSet };
+
+use crate::{
+    evaluation::{
+        add_route,
+        edge_load,
+        edge_total_performance,
+        known_path_distance,
+        performance_index,
+        remove_route,
+        route_distance,
+        route_edges,
+        EdgeLoad,
+        MissingEdge,
+        PathDistances,
+    },
+    routing::{ day_routes, routes_from_timetable, Routes, ShortestPaths },
+    timetable::{ Day, Period, Timetable },
+};
+
+#[derive(Clone)]
+pub struct RoomSwap {
+    pub day: Day,
+    pub period: Period,
+    pub room: String,
+    pub target_room: String,
+    /// Number of students in the session held in `room`.
+    pub student_count: usize,
+    /// Number of students in the session held in `target_room`, zero if the room is free.
+    pub target_student_count: usize,
+    /// Estimated reduction of the total performance index if this is the only change applied.
+    pub gain: f64,
+}
+
+#[derive(Clone, Default)]
+pub struct RoomSwapReport {
+    /// Suggestions with a positive gain, best first.
+    pub suggestions: Vec<RoomSwap>,
+    /// Number of suggestions applied to `patched_timetable`.
+    pub applied_count: usize,
+    pub baseline_pi: f64,
+    pub patched_pi: f64,
+    pub patched_timetable: Timetable,
+}
+
+/// Students of a session, and the room each of them will move to.
+type Reassignment = Vec<(String, String)>;
+
+struct SwapSearch<'a> {
+    timetable: Timetable,
+    routes: Routes,
+    loads: HashMap<(u32, usize), EdgeLoad>,
+    /// (day, period) → room → students in that room.
+    sessions: HashMap<(Day, Period), HashMap<String, Vec<String>>>,
+    shortest_paths: &'a ShortestPaths,
+    distances: &'a PathDistances,
+}
+
+impl<'a> SwapSearch<'a> {
+    fn new(
+        timetable: &Timetable,
+        shortest_paths: &'a ShortestPaths,
+        distances: &'a PathDistances
+    ) -> Self {
+        let routes = routes_from_timetable(timetable, shortest_paths);
+        let mut loads = HashMap::new();
+        for day in 1..=5 {
+            for transition in 0..=11 {
+                loads.insert((day, transition), edge_load(&routes, day, transition));
+            }
+        }
+        let mut sessions: HashMap<(Day, Period), HashMap<String, Vec<String>>> = HashMap::new();
+        for (student_number, week_timetable) in timetable.students() {
+            for (day, day_timetable) in week_timetable.days() {
+                for (period, room) in day_timetable.rooms() {
+                    if room == "G" {
+                        continue;
+                    }
+                    sessions
+                        .entry((day, period))
+                        .or_default()
+                        .entry(room.to_owned())
+                        .or_default()
+                        .push(student_number.to_owned());
+                }
+            }
+        }
+        Self {
+            timetable: timetable.clone(),
+            routes,
+            loads,
+            sessions,
+            shortest_paths,
+            distances,
+        }
+    }
+
+    fn total_pi(&self) -> Result<f64, MissingEdge> {
+        self.loads
+            .values()
+            .map(|load| performance_index(load, self.distances))
+            .sum()
+    }
+
+    fn session_students(&self, day: Day, period: Period, room: &str) -> Vec<String> {
+        self.sessions
+            .get(&(day, period))
+            .and_then(|rooms| rooms.get(room))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    fn reassignment(
+        &self,
+        day: Day,
+        period: Period,
+        room: &str,
+        target_room: &str
+    ) -> Reassignment {
+        let mut reassignment: Reassignment = self
+            .session_students(day, period, room)
+            .into_iter()
+            .map(|student_number| (student_number, target_room.to_owned()))
+            .collect();
+        reassignment.extend(
+            self
+                .session_students(day, period, target_room)
+                .into_iter()
+                .map(|student_number| (student_number, room.to_owned()))
+        );
+        reassignment
+    }
+
+    /// New routes of the affected students, as (student, transition, route).
+    fn rerouted(
+        &self,
+        day: Day,
+        period: Period,
+        reassignment: &Reassignment
+    ) -> Vec<(String, usize, String)> {
+        let mut rerouted = vec![];
+        for (student_number, new_room) in reassignment {
+            let Some(week_timetable) = self.timetable.student(student_number) else {
+                continue;
+            };
+            let mut day_timetable = week_timetable.day(day).clone();
+            day_timetable.set_room(period, new_room.to_owned());
+            let new_routes = day_routes(student_number, &day_timetable, self.shortest_paths);
+            let old_routes = &self.routes[student_number][&day.number()];
+            for (transition, route) in new_routes {
+                if old_routes.get(&transition) != Some(&route) {
+                    rerouted.push((student_number.to_owned(), transition, route));
+                }
+            }
+        }
+        rerouted
+    }
+
+    /// Reduction of the total performance index if the routes were replaced.
+    fn gain(&self, day: Day, rerouted: &[(String, usize, String)]) -> Result<f64, MissingEdge> {
+        let mut delta: HashMap<(usize, (String, String)), i64> = HashMap::new();
+        for (student_number, transition, route) in rerouted {
+            for edge in route_edges(&self.routes[student_number][&day.number()][transition]) {
+                *delta.entry((*transition, edge)).or_insert(0) -= 1;
+            }
+            for edge in route_edges(route) {
+                *delta.entry((*transition, edge)).or_insert(0) += 1;
+            }
+        }
+        delta
+            .iter()
+            .filter(|(_, change)| **change != 0)
+            .map(|((transition, edge), change)| {
+                let distance = known_path_distance(self.distances, &edge.0, &edge.1)? as f64;
+                let congestion = self.loads[&(day.number(), *transition)]
+                    .get(edge)
+                    .copied()
+                    .unwrap_or(0);
+                let new_congestion = ((congestion as i64) + change).max(0) as u32;
+                Ok(
+                    edge_total_performance(distance, congestion) -
+                        edge_total_performance(distance, new_congestion)
+                )
+            })
+            .sum()
+    }
+
+    fn apply(
+        &mut self,
+        day: Day,
+        period: Period,
+        room: &str,
+        target_room: &str,
+        reassignment: &Reassignment,
+        rerouted: Vec<(String, usize, String)>
+    ) {
+        for (student_number, new_room) in reassignment {
+            if let Some(week_timetable) = self.timetable.student_mut(student_number) {
+                week_timetable.day_mut(day).set_room(period, new_room.to_owned());
+            }
+        }
+        for (student_number, transition, route) in rerouted {
+            let load = self.loads.get_mut(&(day.number(), transition)).unwrap();
+            let old_route = self.routes
+                .get_mut(&student_number)
+                .unwrap()
+                .get_mut(&day.number())
+                .unwrap()
+                .insert(transition, route.clone())
+                .unwrap_or_default();
+            remove_route(load, &old_route);
+            add_route(load, &route);
+        }
+        let rooms = self.sessions.get_mut(&(day, period)).unwrap();
+        let students = rooms.remove(room);
+        let target_students = rooms.remove(target_room);
+        if let Some(students) = students {
+            rooms.insert(target_room.to_owned(), students);
+        }
+        if let Some(target_students) = target_students {
+            rooms.insert(room.to_owned(), target_students);
+        }
+    }
+}
+
+/// For each room in the timetable, the `count` other rooms closest to it by walking distance.
+fn nearest_rooms(
+    rooms: &[String],
+    shortest_paths: &ShortestPaths,
+    distances: &PathDistances,
+    count: usize
+) -> HashMap<String, Vec<String>> {
+    rooms
+        .iter()
+        .map(|room| {
+            let mut candidates: Vec<(u32, &String)> = rooms
+                .iter()
+                .filter(|other| *other != room)
+                .filter_map(|other| {
+                    let route = shortest_paths.get(&format!("{}{}", room, other))?;
+                    Some((route_distance(distances, route).ok()?, other))
+                })
+                .collect();
+            candidates.sort();
+            (
+                room.to_owned(),
+                candidates
+                    .into_iter()
+                    .take(count)
+                    .map(|(_, other)| other.to_owned())
+                    .collect(),
+            )
+        })
+        .collect()
+}
+
+/// Searches for room swaps that reduce the total performance index of the shortest routes.
+///
+/// `progress` is called with a percentage as the search advances. Fails if a route walks an edge
+/// missing from `distances`.
+pub fn suggest_room_swaps(
+    timetable: &Timetable,
+    shortest_paths: &ShortestPaths,
+    distances: &PathDistances,
+    candidate_count: usize,
+    mut progress: impl FnMut(i32)
+) -> Result<RoomSwapReport, MissingEdge> {
+    let mut search = SwapSearch::new(timetable, shortest_paths, distances);
+    let baseline_pi = search.total_pi()?;
+
+    let mut rooms: Vec<String> = search.sessions
+        .values()
+        .flat_map(|rooms| rooms.keys().cloned())
+        .collect::<HashSet<String>>()
+        .into_iter()
+        .collect();
+    rooms.sort();
+    let nearest_rooms = nearest_rooms(&rooms, shortest_paths, distances, candidate_count);
+
+    let mut slots: Vec<(Day, Period)> = search.sessions.keys().copied().collect();
+    slots.sort();
+    let mut suggestions = vec![];
+    for (index, (day, period)) in slots.iter().enumerate() {
+        progress(((index as f32) / (slots.len() as f32) * 90.0) as i32);
+        let mut session_rooms: Vec<String> = search.sessions[&(*day, *period)]
+            .keys()
+            .cloned()
+            .collect();
+        session_rooms.sort();
+        let mut evaluated = HashSet::new();
+        for room in &session_rooms {
+            for target_room in &nearest_rooms[room] {
+                let pair = if room < target_room {
+                    (room.to_owned(), target_room.to_owned())
+                } else {
+                    (target_room.to_owned(), room.to_owned())
+                };
+                if !evaluated.insert(pair) {
+                    continue;
+                }
+                let reassignment = search.reassignment(*day, *period, room, target_room);
+                let rerouted = search.rerouted(*day, *period, &reassignment);
+                let gain = search.gain(*day, &rerouted)?;
+                if gain > 0.0 {
+                    suggestions.push(RoomSwap {
+                        day: *day,
+                        period: *period,
+                        room: room.to_owned(),
+                        target_room: target_room.to_owned(),
+                        student_count: search.session_students(*day, *period, room).len(),
+                        target_student_count: search
+                            .session_students(*day, *period, target_room)
+                            .len(),
+                        gain,
+                    });
+                }
+            }
+        }
+    }
+    suggestions.sort_by(|a, b| b.gain.total_cmp(&a.gain));
+
+    // Apply the suggestions greedily, re-evaluating each one against the already patched
+    // timetable so that interacting swaps never make the result worse.
+    let mut changed_sessions = HashSet::new();
+    let mut applied_count = 0;
+    for (index, suggestion) in suggestions.iter().enumerate() {
+        if index % 100 == 0 {
+            progress(90 + (((index as f32) / (suggestions.len() as f32)) * 10.0) as i32);
+        }
+        let room_key = (suggestion.day, suggestion.period, suggestion.room.to_owned());
+        let target_key = (suggestion.day, suggestion.period, suggestion.target_room.to_owned());
+        if changed_sessions.contains(&room_key) || changed_sessions.contains(&target_key) {
+            continue;
+        }
+        let reassignment = search.reassignment(
+            suggestion.day,
+            suggestion.period,
+            &suggestion.room,
+            &suggestion.target_room
+        );
+        let rerouted = search.rerouted(suggestion.day, suggestion.period, &reassignment);
+        if search.gain(suggestion.day, &rerouted)? <= 0.0 {
+            continue;
+        }
+        search.apply(
+            suggestion.day,
+            suggestion.period,
+            &suggestion.room,
+            &suggestion.target_room,
+            &reassignment,
+            rerouted
+        );
+        changed_sessions.insert(room_key);
+        changed_sessions.insert(target_key);
+        applied_count += 1;
+    }
+
+    Ok(RoomSwapReport {
+        suggestions,
+        applied_count,
+        baseline_pi,
+        patched_pi: search.total_pi()?,
+        patched_timetable: search.timetable,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ evaluation::parse_path_distances, routing::TRANSITION_COUNT };
+
+    use super::*;
+
+    /// Rooms `A101` and `A102` next to the corridor node `A1`, rooms `B101` and `B102` next to
+    /// `B1`, and a long corridor from `A1` to `B1`.
+    const PATHS: &str = "\
+A101 A1 10 0
+A102 A1 10 0
+A1 M 100 0
+M B1 100 0
+B101 B1 10 0
+B102 B1 10 0
+";
+
+    const ROOMS: [&str; 4] = ["A101", "A102", "B101", "B102"];
+
+    fn corridor_node(room: &str) -> &str {
+        if room.starts_with('A') { "A1" } else { "B1" }
+    }
+
+    fn shortest_paths() -> ShortestPaths {
+        let mut shortest_paths = ShortestPaths::new();
+        for from in ROOMS {
+            let node = corridor_node(from);
+            shortest_paths.insert(format!("G{}", from), format!("G {} {}", node, from));
+            shortest_paths.insert(format!("{}G", from), format!("{} {} G", from, node));
+            for to in ROOMS.iter().filter(|to| **to != from) {
+                let route = if node == corridor_node(to) {
+                    format!("{} {} {}", from, node, to)
+                } else {
+                    format!("{} {} M {} {}", from, node, corridor_node(to), to)
+                };
+                shortest_paths.insert(format!("{}{}", from, to), route);
+            }
+        }
+        shortest_paths
+    }
+
+    /// Students `21001` to `21030` in `A101` in period 1 and in `B101` afterwards, and students
+    /// `21031` to `21060` in `B102` in period 1 and in `A102` afterwards, so that both classes
+    /// walk the whole corridor after period 1.
+    fn timetable() -> Timetable {
+        let week = |first_room: &str, room: &str| {
+            let day: serde_json::Map<String, serde_json::Value> = Period::all()
+                .map(|period| {
+                    let room = if period.number() == 1 { first_room } else { room };
+                    (period.to_string(), room.into())
+                })
+                .collect();
+            serde_json::Value::Object(
+                Day::all()
+                    .map(|day_of_week| (day_of_week.to_string(), day.clone().into()))
+                    .collect()
+            )
+        };
+        let students: serde_json::Map<String, serde_json::Value> = (21001..=21060)
+            .map(|student| {
+                let week = if student <= 21030 {
+                    week("A101", "B101")
+                } else {
+                    week("B102", "A102")
+                };
+                (student.to_string(), week)
+            })
+            .collect();
+        let json = serde_json::Value::Object(students).to_string();
+        Timetable::from_reader(json.as_bytes()).unwrap()
+    }
+
+    /// Highest number of students walking an edge in a transition of `day`.
+    fn peak_load(timetable: &Timetable, day: Day) -> u32 {
+        let routes = routes_from_timetable(timetable, &shortest_paths());
+        (0..TRANSITION_COUNT)
+            .flat_map(|transition| edge_load(&routes, day.number(), transition).into_values())
+            .max()
+            .unwrap_or(0)
+    }
+
+    #[test]
+    fn suggested_swaps_lower_the_peak_load() {
+        let timetable = timetable();
+        let distances = parse_path_distances(PATHS);
+        let report = suggest_room_swaps(&timetable, &shortest_paths(), &distances, 3, |_| ());
+        let report = report.unwrap();
+        let best = &report.suggestions[0];
+        assert_eq!(best.period, Period::new(1).unwrap());
+        assert_eq!((best.room.as_str(), best.target_room.as_str()), ("A101", "B102"));
+        assert_eq!((best.student_count, best.target_student_count), (30, 30));
+        assert!(report.applied_count > 0);
+        assert!(report.patched_pi < report.baseline_pi);
+        assert_eq!(peak_load(&timetable, best.day), 60);
+        assert_eq!(peak_load(&report.patched_timetable, best.day), 30);
+    }
+}
@@ -0,0 +1,175 @@
+//! Editing the route of one student in one transition by hand.
+//!
+//! An edited route is described by its waypoints, the first and last of which are the ends of the
+//! original route. Between two consecutive waypoints the route takes the edge joining them if
+//! there is one, and the shortest path through the building graph otherwise, so it always follows
+//! the edges of the graph. The ground node `G` is only entered as an end of the route.
+
+use std::fmt::{ self, Display };
+
+use crate::graph::BuildingGraph;
+
+#[derive(Debug)]
+pub enum RouteEditError {
+    /// The original route has a node missing from the building graph.
+    UnknownNode(String),
+    /// The student does not move in the transition, so there is nothing to edit.
+    Stationary,
+    /// No path of the building graph joins two consecutive waypoints.
+    Unreachable(String, String),
+}
+
+impl Display for RouteEditError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RouteEditError::UnknownNode(node) => {
+                write!(f, "The route passes {}, which is not in the building graph.", node)
+            }
+            RouteEditError::Stationary => {
+                write!(f, "The student stays in the same room in this transition.")
+            }
+            RouteEditError::Unreachable(from, to) => {
+                write!(f, "There is no path from {} to {}.", from, to)
+            }
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct RouteEditor {
+    pub student_number: String,
+    pub day: u32,
+    pub transition: usize,
+    /// Graph node ids, starting and ending at the ends of the original route.
+    waypoints: Vec<usize>,
+    original: Vec<usize>,
+    /// Earlier waypoints, the latest last.
+    history: Vec<Vec<usize>>,
+}
+
+impl RouteEditor {
+    /// Starts editing `route`, every node of which becomes a waypoint.
+    pub fn new(
+        graph: &BuildingGraph,
+        student_number: &str,
+        day: u32,
+        transition: usize,
+        route: &str
+    ) -> Result<Self, RouteEditError> {
+        let original = route
+            .split_whitespace()
+            .map(|name| graph.node_id(name).ok_or_else(|| RouteEditError::UnknownNode(name.into())))
+            .collect::<Result<Vec<_>, _>>()?;
+        if original.len() < 2 || original.first() == original.last() {
+            return Err(RouteEditError::Stationary);
+        }
+        Ok(Self {
+            student_number: student_number.to_owned(),
+            day,
+            transition,
+            waypoints: original.clone(),
+            original,
+            history: vec![],
+        })
+    }
+
+    pub fn waypoints(&self) -> &[usize] {
+        &self.waypoints
+    }
+
+    fn change(&mut self, waypoints: Vec<usize>) {
+        if waypoints != self.waypoints {
+            self.history.push(std::mem::replace(&mut self.waypoints, waypoints));
+        }
+    }
+
+    /// Adds a waypoint just before the end of the route.
+    pub fn add_waypoint(&mut self, node: usize) {
+        let mut waypoints = self.waypoints.clone();
+        waypoints.insert(waypoints.len() - 1, node);
+        self.change(waypoints);
+    }
+
+    /// Moves a waypoint to another node. The ends of the route cannot be moved.
+    pub fn move_waypoint(&mut self, index: usize, node: usize) {
+        if self.is_movable(index) {
+            let mut waypoints = self.waypoints.clone();
+            waypoints[index] = node;
+            self.change(waypoints);
+        }
+    }
+
+    /// Removes a waypoint. The ends of the route cannot be removed.
+    pub fn remove_waypoint(&mut self, index: usize) {
+        if self.is_movable(index) {
+            let mut waypoints = self.waypoints.clone();
+            waypoints.remove(index);
+            self.change(waypoints);
+        }
+    }
+
+    /// Whether a waypoint is neither the start nor the end of the route.
+    pub fn is_movable(&self, index: usize) -> bool {
+        index > 0 && index + 1 < self.waypoints.len()
+    }
+
+    /// Keeps only the ends of the route, which then follows the shortest path.
+    pub fn clear(&mut self) {
+        self.change(vec![self.original[0], *self.original.last().unwrap()]);
+    }
+
+    /// Goes back to the route the editor was opened with.
+    pub fn reset(&mut self) {
+        self.change(self.original.clone());
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.history.is_empty()
+    }
+
+    pub fn undo(&mut self) {
+        if let Some(waypoints) = self.history.pop() {
+            self.waypoints = waypoints;
+        }
+    }
+
+    /// Nodes of the route through every waypoint.
+    pub fn path(&self, graph: &BuildingGraph) -> Result<Vec<usize>, RouteEditError> {
+        let start = self.waypoints[0];
+        let end = *self.waypoints.last().unwrap();
+        let mut path = vec![start];
+        for pair in self.waypoints.windows(2) {
+            let (from, to) = (pair[0], pair[1]);
+            if from == to {
+                continue;
+            }
+            if graph.edge_between(from, to).is_some() {
+                path.push(to);
+                continue;
+            }
+            let segment = graph
+                .cheapest_path(from, to, |node, edge| {
+                    let leaves_ground = graph.is_ground(node) && node != start;
+                    let enters_ground = graph.is_ground(edge.to) && edge.to != end;
+                    if leaves_ground || enters_ground {
+                        f64::INFINITY
+                    } else {
+                        edge.distance as f64
+                    }
+                })
+                .ok_or_else(|| {
+                    RouteEditError::Unreachable(
+                        graph.node_name(from).to_owned(),
+                        graph.node_name(to).to_owned()
+                    )
+                })?;
+            path.extend_from_slice(&segment[1..]);
+        }
+        Ok(path)
+    }
+
+    /// The route through every waypoint in the format of `routes.json`.
+    pub fn route_string(&self, graph: &BuildingGraph) -> Result<String, RouteEditError> {
+        self.path(graph).map(|path| graph.route_string(&path))
+    }
+}
@@ -0,0 +1,295 @@
+//! Printable route sheets, one HTML page per student with the weekly timetable and the directions
+//! and a thumbnail of the projection for every transition the student moves in.
+//!
+//! The thumbnails are inline SVG drawn with the projection of the app. The edges of the building
+//! are drawn once per page and referenced by every thumbnail, which only adds its route.
+
+use std::{ collections::HashMap, f32::consts::PI, fmt::Write as _, fs, io, path::Path };
+
+use crate::{
+    compact_routes::CompactRoutes,
+    destinations::DestinationAssignment,
+    directions::{ instructions, route_steps, Language },
+    graph::BuildingGraph,
+    routing::TRANSITION_NAMES,
+    timetable::{ Day, Period, Timetable, WeekTimetable },
+};
+
+/// Width of a thumbnail on the page, in CSS pixels.
+const THUMBNAIL_WIDTH: u32 = 260;
+/// Margin around the building in a thumbnail, in units of projection-coords.yaml.
+const THUMBNAIL_MARGIN: f32 = 20.0;
+
+const STYLE: &str = "\
+body { font-family: \"Inter\", \"Source Han Sans SC\", sans-serif; margin: 2em; color: #212121; }
+h1 { font-size: 1.6em; margin-bottom: 0.2em; }
+h2 { font-size: 1.2em; margin-top: 1.5em; page-break-after: avoid; }
+table { border-collapse: collapse; }
+th, td { border: 1px solid #bdbdbd; padding: 0.3em 0.6em; text-align: left; vertical-align: top; }
+th { background: #eeeeee; }
+.transition { page-break-inside: avoid; }
+.transition ol { margin: 0; padding-left: 1.2em; }
+.route { fill: none; stroke: #007af5; stroke-width: 6; stroke-linejoin: round; }
+.start { fill: #14ae52; }
+.end { fill: #e43748; }
+@media print { body { margin: 0; } .sheet { page-break-after: always; } }
+";
+
+/// Escapes text for HTML.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+/// Position of 3D coordinates on the projection of the app, at a scale of 1.
+fn project(coords: &[i32; 3]) -> [f32; 2] {
+    const ANGLE: f32 = PI / 6.0;
+    let [x, y, z] = coords.map(|value| value as f32);
+    [(x + y) * ANGLE.cos(), (x - y) * ANGLE.sin() - z]
+}
+
+/// Writes route sheets for students of the building.
+pub struct RouteSheets<'a> {
+    graph: &'a BuildingGraph,
+    coords: &'a HashMap<String, [i32; 3]>,
+    /// `viewBox` of the thumbnails.
+    view_box: [f32; 4],
+    /// SVG lines of the edges of the building.
+    building: String,
+}
+
+impl<'a> RouteSheets<'a> {
+    pub fn new(graph: &'a BuildingGraph, coords: &'a HashMap<String, [i32; 3]>) -> Self {
+        let points: Vec<[f32; 2]> = coords.values().map(project).collect();
+        let min_x = points.iter().map(|point| point[0]).fold(f32::INFINITY, f32::min);
+        let max_x = points.iter().map(|point| point[0]).fold(f32::NEG_INFINITY, f32::max);
+        let min_y = points.iter().map(|point| point[1]).fold(f32::INFINITY, f32::min);
+        let max_y = points.iter().map(|point| point[1]).fold(f32::NEG_INFINITY, f32::max);
+        let view_box = [
+            min_x - THUMBNAIL_MARGIN,
+            min_y - THUMBNAIL_MARGIN,
+            max_x - min_x + 2.0 * THUMBNAIL_MARGIN,
+            max_y - min_y + 2.0 * THUMBNAIL_MARGIN,
+        ];
+
+        let mut building = String::new();
+        for node in 0..graph.node_count() {
+            for edge in graph.edges(node).iter().filter(|edge| edge.to > node) {
+                let from = coords.get(graph.node_name(node));
+                let to = coords.get(graph.node_name(edge.to));
+                if let (Some(from), Some(to)) = (from, to) {
+                    let ([x1, y1], [x2, y2]) = (project(from), project(to));
+                    write!(
+                        building,
+                        "<line x1=\"{:.0}\" y1=\"{:.0}\" x2=\"{:.0}\" y2=\"{:.0}\"/>",
+                        x1,
+                        y1,
+                        x2,
+                        y2
+                    ).unwrap();
+                }
+            }
+        }
+        Self { graph, coords, view_box, building }
+    }
+
+    /// Thumbnail of a route given by graph node ids, marking where it starts and ends.
+    fn thumbnail(&self, route: &[usize]) -> String {
+        let points: Vec<[f32; 2]> = route
+            .iter()
+            .filter_map(|node| self.coords.get(self.graph.node_name(*node)))
+            .map(project)
+            .collect();
+        let [x, y, width, height] = self.view_box;
+        let mut svg = format!(
+            "<svg viewBox=\"{:.0} {:.0} {:.0} {:.0}\" width=\"{}\"><use href=\"#building\"/>",
+            x,
+            y,
+            width,
+            height,
+            THUMBNAIL_WIDTH
+        );
+        if let (Some(first), Some(last)) = (points.first(), points.last()) {
+            svg.push_str("<polyline class=\"route\" points=\"");
+            for [x, y] in &points {
+                write!(svg, "{:.0},{:.0} ", x, y).unwrap();
+            }
+            svg.push_str("\"/>");
+            for (class, [x, y]) in [("start", first), ("end", last)] {
+                write!(
+                    svg,
+                    "<circle class=\"{}\" cx=\"{:.0}\" cy=\"{:.0}\" r=\"14\"/>",
+                    class,
+                    x,
+                    y
+                ).unwrap();
+            }
+        }
+        svg.push_str("</svg>");
+        svg
+    }
+
+    /// Route sheet of a student, with the routes of the student in `routes` under the heading
+    /// `plan`, such as "Optimized routes", and the destinations of the student for the outings.
+    pub fn sheet(
+        &self,
+        student_number: &str,
+        week_timetable: &WeekTimetable,
+        routes: &CompactRoutes,
+        destinations: &DestinationAssignment,
+        plan: &str
+    ) -> String {
+        let student_number_html = escape(student_number);
+        let mut html = String::new();
+        write!(
+            html,
+            "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n<title>Routes of {}</title>\n<style>\n{}</style>\n</head>\n<body>\n",
+            student_number_html,
+            STYLE
+        ).unwrap();
+        writeln!(
+            html,
+            "<svg width=\"0\" height=\"0\" style=\"position: absolute\"><defs><g id=\"building\" fill=\"none\" stroke=\"#bdbdbd\" stroke-width=\"2\">{}</g></defs></svg>",
+            self.building
+        ).unwrap();
+        write!(html, "<div class=\"sheet\">\n<h1>Routes of {}</h1>\n", student_number_html)
+            .unwrap();
+        writeln!(html, "<p>{}</p>", escape(plan)).unwrap();
+
+        html.push_str("<h2>Timetable</h2>\n<table>\n<tr><th>Period</th>");
+        for day in Day::all() {
+            write!(html, "<th>{}</th>", day.name()).unwrap();
+        }
+        html.push_str("</tr>\n");
+        for period in Period::all() {
+            write!(html, "<tr><th>Period {}</th>", period).unwrap();
+            for day in Day::all() {
+                let room = week_timetable.room(day, period);
+                let room = if room == "G" { String::new() } else { escape(room) };
+                write!(html, "<td>{}</td>", room).unwrap();
+            }
+            html.push_str("</tr>\n");
+        }
+        html.push_str("</table>\n");
+
+        let student_index = routes.student_index(student_number);
+        for (day, day_timetable) in week_timetable.days() {
+            write!(html, "<h2>{}</h2>\n<table>\n", day.name()).unwrap();
+            html.push_str("<tr><th>Transition</th><th>Directions</th><th>Route</th></tr>\n");
+            for (transition, transition_name) in TRANSITION_NAMES.iter().enumerate() {
+                let rooms = destinations.transition_ends(
+                    student_number,
+                    day,
+                    day_timetable,
+                    transition
+                );
+                let Some((from, to)) = rooms else {
+                    continue;
+                };
+                let route = student_index
+                    .and_then(|index| {
+                        self.graph.route_ids(&routes.route_string(index, day.number(), transition))
+                    })
+                    .filter(|route| route.len() > 1);
+                write!(
+                    html,
+                    "<tr class=\"transition\"><td><b>{}</b><br>{} → {}</td><td>",
+                    transition_name,
+                    escape(from),
+                    escape(to)
+                ).unwrap();
+                match &route {
+                    Some(route) => {
+                        let steps = route_steps(self.graph, self.coords, route);
+                        let instructions = instructions(&steps, Language::English);
+                        html.push_str("<ol>");
+                        for instruction in &instructions {
+                            write!(html, "<li>{}", escape(&instruction.text)).unwrap();
+                            if instruction.distance > 0 {
+                                write!(html, " ({})", instruction.distance).unwrap();
+                            }
+                            html.push_str("</li>");
+                        }
+                        html.push_str("</ol>");
+                        let distance: u32 = instructions
+                            .iter()
+                            .map(|instruction| instruction.distance)
+                            .sum();
+                        write!(html, "Distance {}", distance).unwrap();
+                    }
+                    None => html.push_str("No route."),
+                }
+                html.push_str("</td><td>");
+                if let Some(route) = &route {
+                    html.push_str(&self.thumbnail(route));
+                }
+                html.push_str("</td></tr>\n");
+            }
+            html.push_str("</table>\n");
+        }
+        html.push_str("</div>\n</body>\n</html>\n");
+        html
+    }
+}
+
+/// Whether `<student number>.html` is a file of its own in the output directory: a name of
+/// letters, digits, `-`, `_` and `.` that is not hidden and is not taken by the index.
+fn is_plain_file_name(student_number: &str) -> bool {
+    !student_number.is_empty() &&
+        !student_number.starts_with('.') &&
+        !student_number.eq_ignore_ascii_case("index") &&
+        student_number
+            .chars()
+            .all(|character| character.is_alphanumeric() || "-_.".contains(character))
+}
+
+/// Writes the route sheet of every student of the timetable `include` accepts into `directory`,
+/// as `<student number>.html`, with an `index.html` linking them. Returns the number of sheets.
+///
+/// Fails without writing anything if a student number cannot be used as a file name.
+pub fn write_route_sheets(
+    directory: &Path,
+    sheets: &RouteSheets<'_>,
+    timetable: &Timetable,
+    routes: &CompactRoutes,
+    destinations: &DestinationAssignment,
+    plan: &str,
+    include: impl Fn(&str) -> bool
+) -> io::Result<usize> {
+    let mut student_numbers: Vec<&str> = timetable
+        .student_numbers()
+        .filter(|student_number| include(student_number))
+        .collect();
+    student_numbers.sort();
+    let invalid = student_numbers.iter().find(|student_number| !is_plain_file_name(student_number));
+    if let Some(student_number) = invalid {
+        return Err(
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Student number {:?} cannot be used as a file name", student_number)
+            )
+        );
+    }
+    fs::create_dir_all(directory)?;
+    let mut index = format!(
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n<title>Route sheets</title>\n<style>\n{}</style>\n</head>\n<body>\n<h1>Route sheets</h1>\n<p>{}</p>\n<ul>\n",
+        STYLE,
+        escape(plan)
+    );
+    for student_number in &student_numbers {
+        let week_timetable = timetable.student(student_number).unwrap();
+        let file_name = format!("{}.html", student_number);
+        fs::write(
+            directory.join(&file_name),
+            sheets.sheet(student_number, week_timetable, routes, destinations, plan)
+        )?;
+        writeln!(
+            index,
+            "<li><a href=\"{}\">{}</a></li>",
+            escape(&file_name),
+            escape(student_number)
+        ).unwrap();
+    }
+    index.push_str("</ul>\n</body>\n</html>\n");
+    fs::write(directory.join("index.html"), index)?;
+    Ok(student_numbers.len())
+}
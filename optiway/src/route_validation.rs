@@ -0,0 +1,293 @@
+//! Validation of imported routes against the building graph and the timetable.
+//!
+//! Every route must be a walk along the edges of `paths.txt`. With a timetable, the route of a
+//! transition must also lead from the room the student is in before the transition to the room
+//! after it (see `transition_rooms`), and be empty if the student does not move. Arrivals, lunch
+//! and dismissals lead to or from the destination of the student instead of `G` (see
+//! `destinations`).
+
+use std::fmt::{ self, Display };
+
+use crate::{
+    destinations::DestinationAssignment,
+    graph::BuildingGraph,
+    routing::{ Routes, TRANSITION_COUNT },
+    timetable::{ Day, Timetable },
+};
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum RouteProblem {
+    /// A student of the routes who is not in the timetable.
+    UnknownStudent,
+    /// A student of the timetable without routes.
+    MissingStudent,
+    /// A day that is not a school day.
+    InvalidDay,
+    /// A school day of the timetable without routes.
+    MissingDay,
+    /// A transition number out of range.
+    InvalidTransition,
+    /// A node missing from the building graph.
+    UnknownNode(String),
+    /// Consecutive nodes of the route that no edge joins.
+    MissingEdge(String, String),
+    /// A route that does not lead between the given rooms of the timetable.
+    WrongEnds(String, String),
+    /// An empty route where the student moves between the given rooms.
+    MissingRoute(String, String),
+    /// A route where the student stays in the same room.
+    UnexpectedRoute,
+}
+
+/// A problem with the routes of a student, or of one day or transition of a student.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct RouteIssue {
+    pub student_number: String,
+    pub day: Option<u32>,
+    pub transition: Option<usize>,
+    pub problem: RouteProblem,
+}
+
+impl Display for RouteIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Student {}", self.student_number)?;
+        if let Some(day) = self.day {
+            write!(f, ", day {}", day)?;
+        }
+        if let Some(transition) = self.transition {
+            write!(f, ", transition {}", transition)?;
+        }
+        write!(f, ": ")?;
+        match &self.problem {
+            RouteProblem::UnknownStudent => write!(f, "not in the timetable"),
+            RouteProblem::MissingStudent => write!(f, "no routes"),
+            RouteProblem::InvalidDay => write!(f, "not a school day"),
+            RouteProblem::MissingDay => write!(f, "no routes on this day"),
+            RouteProblem::InvalidTransition => write!(f, "not a transition"),
+            RouteProblem::UnknownNode(node) => write!(f, "{} is not in the building graph", node),
+            RouteProblem::MissingEdge(node1, node2) => {
+                write!(f, "no edge joins {} and {}", node1, node2)
+            }
+            RouteProblem::WrongEnds(from, to) => {
+                write!(f, "the route does not lead from {} to {}", from, to)
+            }
+            RouteProblem::MissingRoute(from, to) => write!(f, "no route from {} to {}", from, to),
+            RouteProblem::UnexpectedRoute => write!(f, "a route where the student stays in place"),
+        }
+    }
+}
+
+/// Every issue of `routes`, ordered by student, day and transition. Without a timetable, only
+/// the days, the transitions and the walks along the graph are checked.
+pub fn validate_routes(
+    routes: &Routes,
+    graph: &BuildingGraph,
+    timetable: Option<&Timetable>,
+    destinations: &DestinationAssignment
+) -> Vec<RouteIssue> {
+    let mut issues = vec![];
+    let issue = |
+        student_number: &str,
+        day: Option<u32>,
+        transition: Option<usize>,
+        problem: RouteProblem
+    | RouteIssue {
+        student_number: student_number.to_owned(),
+        day,
+        transition,
+        problem,
+    };
+
+    let mut student_numbers: Vec<&String> = routes.keys().collect();
+    student_numbers.sort();
+    for student_number in student_numbers {
+        let week_timetable = match timetable.map(|timetable| timetable.student(student_number)) {
+            Some(None) => {
+                issues.push(issue(student_number, None, None, RouteProblem::UnknownStudent));
+                continue;
+            }
+            Some(Some(week_timetable)) => Some(week_timetable),
+            None => None,
+        };
+        let week_routes = &routes[student_number];
+        let mut days: Vec<u32> = week_routes.keys().copied().collect();
+        days.sort();
+        for &day in &days {
+            if Day::new(day).is_none() {
+                issues.push(issue(student_number, Some(day), None, RouteProblem::InvalidDay));
+            }
+        }
+        if let Some(week_timetable) = week_timetable {
+            for (day, _) in week_timetable.days() {
+                if !week_routes.contains_key(&day.number()) {
+                    issues.push(
+                        issue(student_number, Some(day.number()), None, RouteProblem::MissingDay)
+                    );
+                }
+            }
+        }
+
+        for day in days.into_iter().filter(|day| Day::new(*day).is_some()) {
+            let day_routes = &week_routes[&day];
+            let day_timetable = week_timetable.map(|week_timetable| {
+                week_timetable.day(Day::new(day).unwrap())
+            });
+            let mut invalid_transitions: Vec<usize> = day_routes
+                .keys()
+                .copied()
+                .filter(|&transition| transition >= TRANSITION_COUNT)
+                .collect();
+            invalid_transitions.sort();
+            for transition in invalid_transitions {
+                issues.push(
+                    issue(
+                        student_number,
+                        Some(day),
+                        Some(transition),
+                        RouteProblem::InvalidTransition
+                    )
+                );
+            }
+
+            for transition in 0..TRANSITION_COUNT {
+                let route = day_routes.get(&transition).map_or("", |route| route.as_str());
+                let nodes: Vec<&str> = route.split_whitespace().collect();
+                let mut problem = None;
+                if let Some(unknown) = nodes.iter().find(|node| graph.node_id(node).is_none()) {
+                    problem = Some(RouteProblem::UnknownNode((*unknown).to_owned()));
+                } else if
+                    let Some(pair) = nodes.windows(2).find(|pair| {
+                        let node1 = graph.node_id(pair[0]).unwrap();
+                        let node2 = graph.node_id(pair[1]).unwrap();
+                        graph.edge_between(node1, node2).is_none()
+                    })
+                {
+                    problem = Some(
+                        RouteProblem::MissingEdge(pair[0].to_owned(), pair[1].to_owned())
+                    );
+                } else if let Some(day_timetable) = day_timetable {
+                    let rooms = destinations.transition_ends(
+                        student_number,
+                        Day::new(day).unwrap(),
+                        day_timetable,
+                        transition
+                    );
+                    problem = match (rooms, nodes.first().zip(nodes.last())) {
+                        (None, None) => None,
+                        (None, Some(_)) => Some(RouteProblem::UnexpectedRoute),
+                        (Some((from, to)), None) => {
+                            Some(RouteProblem::MissingRoute(from.to_owned(), to.to_owned()))
+                        }
+                        (Some((from, to)), Some((first, last))) => {
+                            if *first == from && *last == to && nodes.len() > 1 {
+                                None
+                            } else {
+                                Some(RouteProblem::WrongEnds(from.to_owned(), to.to_owned()))
+                            }
+                        }
+                    };
+                }
+                if let Some(problem) = problem {
+                    issues.push(issue(student_number, Some(day), Some(transition), problem));
+                }
+            }
+        }
+    }
+
+    if let Some(timetable) = timetable {
+        let mut missing: Vec<&str> = timetable
+            .student_numbers()
+            .filter(|student_number| !routes.contains_key(*student_number))
+            .collect();
+        missing.sort();
+        issues.extend(
+            missing
+                .into_iter()
+                .map(|student_number| {
+                    issue(student_number, None, None, RouteProblem::MissingStudent)
+                })
+        );
+    }
+    issues
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::routing::{ routes_from_timetable, ShortestPaths };
+
+    use super::*;
+
+    const PATHS: &str = "\
+A101 A1 50 0
+A1 B1 100 0
+B1 C1 100 0
+C1 C101 50 0
+A1 G 999999 2
+C1 G 999999 2
+";
+
+    /// Student `21001`, in `C101` in period 1 and in `A101` afterwards.
+    fn timetable() -> Timetable {
+        let day: String = (1..=10)
+            .map(|period| {
+                let room = if period == 1 { "C101" } else { "A101" };
+                format!("\"{}\": \"{}\"", period, room)
+            })
+            .collect::<Vec<String>>()
+            .join(", ");
+        let week: String = (1..=5)
+            .map(|day_of_week| format!("\"{}\": {{ {} }}", day_of_week, day))
+            .collect::<Vec<String>>()
+            .join(", ");
+        Timetable::from_reader(format!("{{ \"21001\": {{ {} }} }}", week).as_bytes()).unwrap()
+    }
+
+    fn shortest_paths() -> ShortestPaths {
+        ShortestPaths::from(
+            [
+                ("GC101", "G C1 C101"),
+                ("C101A101", "C101 C1 B1 A1 A101"),
+                ("A101G", "A101 A1 G"),
+                ("GA101", "G A1 A101"),
+            ].map(|(key, route)| (key.to_owned(), route.to_owned()))
+        )
+    }
+
+    #[test]
+    fn bad_routes_are_rejected() {
+        let graph = BuildingGraph::parse(PATHS);
+        let timetable = timetable();
+        let destinations = DestinationAssignment::default();
+        let mut routes = routes_from_timetable(&timetable, &shortest_paths());
+        assert_eq!(validate_routes(&routes, &graph, Some(&timetable), &destinations), []);
+
+        let week_routes = routes.get_mut("21001").unwrap();
+        let monday = week_routes.get_mut(&1).unwrap();
+        monday.insert(0, "G C1 Z9".to_owned());
+        monday.insert(1, "C101 C1 A1 A101".to_owned());
+        monday.insert(2, "A101 A1 A101".to_owned());
+        week_routes.get_mut(&2).unwrap().insert(1, "C101 C1 B1".to_owned());
+        week_routes.get_mut(&3).unwrap().insert(0, String::new());
+        week_routes.remove(&5);
+        week_routes.insert(6, Default::default());
+        routes.insert("21002".to_owned(), Default::default());
+
+        let issue = |student_number: &str, day, transition, problem| RouteIssue {
+            student_number: student_number.to_owned(),
+            day,
+            transition,
+            problem,
+        };
+        let node = |name: &str| name.to_owned();
+        assert_eq!(validate_routes(&routes, &graph, Some(&timetable), &destinations), [
+            issue("21001", Some(6), None, RouteProblem::InvalidDay),
+            issue("21001", Some(5), None, RouteProblem::MissingDay),
+            issue("21001", Some(1), Some(0), RouteProblem::UnknownNode(node("Z9"))),
+            issue("21001", Some(1), Some(1), RouteProblem::MissingEdge(node("C1"), node("A1"))),
+            issue("21001", Some(1), Some(2), RouteProblem::UnexpectedRoute),
+            issue("21001", Some(2), Some(1), RouteProblem::WrongEnds(node("C101"), node("A101"))),
+            issue("21001", Some(3), Some(0), RouteProblem::MissingRoute(node("G"), node("C101"))),
+            issue("21002", None, None, RouteProblem::UnknownStudent),
+        ]);
+    }
+}
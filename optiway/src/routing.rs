@@ -0,0 +1,112 @@
+//! Shortest route assignment from a timetable, mirroring `getRoutesfromTimetable` in
+//! `multi_agent_path_finding/floyd.cpp`.
+
+use std::collections::{ BTreeSet, HashMap };
+
+use crate::timetable::{ DayTimetable, Period, Timetable };
+
+/// Student number → day (1–5) → transition (0–11) → space-separated route.
+pub type Routes = HashMap<String, HashMap<u32, HashMap<usize, String>>>;
+
+/// Routes edited by hand, which the optimizer leaves untouched, as (student number, day,
+/// transition).
+pub type EditedRoutes = BTreeSet<(String, u32, usize)>;
+
+/// Concatenated room pair (e.g. `A201B305`) → space-separated shortest route.
+pub type ShortestPaths = HashMap<String, String>;
+
+/// Number of transitions in a day: arriving, between consecutive periods, leaving for and coming
+/// back from lunch, and leaving at the end of the day.
+pub const TRANSITION_COUNT: usize = 12;
+
+/// Short names of the transitions, such as `P1–P2`.
+pub const TRANSITION_NAMES: [&str; TRANSITION_COUNT] = [
+    "Before P1",
+    "P1–P2",
+    "P2–P3",
+    "P3–P4",
+    "P4–P5",
+    "P5–P6",
+    "P6–Lunch",
+    "Lunch–P7",
+    "P7–P8",
+    "P8–P9",
+    "P9–P10",
+    "After P10",
+];
+
+/// Student numbers below this are AS & A Level students, who leave for lunch after P6.
+const G_LEVEL_STUDENT_NUMBER: u32 = 22000;
+
+/// The timetable periods a transition moves between, where `None` is the ground floor.
+pub fn transition_periods(transition: usize) -> (Option<usize>, Option<usize>) {
+    match transition {
+        0 => (None, Some(1)),
+        1..=5 => (Some(transition), Some(transition + 1)),
+        6 => (Some(6), None),
+        7 => (None, Some(7)),
+        8..=10 => (Some(transition - 1), Some(transition)),
+        _ => (Some(10), None),
+    }
+}
+
+/// Shortest route between two rooms, or an empty route if there is none.
+pub fn shortest_path(shortest_paths: &ShortestPaths, from: &str, to: &str) -> String {
+    shortest_paths
+        .get(&format!("{}{}", from, to))
+        .cloned()
+        .unwrap_or_default()
+}
+
+/// The rooms a student moves between in a transition, where `G` is the ground floor, or `None` if
+/// the student does not move.
+pub fn transition_rooms<'a>(
+    student_number: &str,
+    day_timetable: &'a DayTimetable,
+    transition: usize
+) -> Option<(&'a str, &'a str)> {
+    let room = |period: usize| Period::new(period).map_or("G", |period| day_timetable.room(period));
+    let is_g_level = student_number.parse::<u32>().unwrap_or(0) >= G_LEVEL_STUDENT_NUMBER;
+    let (from, to) = match transition_periods(transition) {
+        // G Level students have lunch in school
+        (Some(6), None) if is_g_level => {
+            return None;
+        }
+        (from, to) => (from.map_or("G", room), to.map_or("G", room)),
+    };
+    (from != to).then_some((from, to))
+}
+
+/// Shortest routes of one student for every transition of a day.
+pub fn day_routes(
+    student_number: &str,
+    day_timetable: &DayTimetable,
+    shortest_paths: &ShortestPaths
+) -> HashMap<usize, String> {
+    (0..TRANSITION_COUNT)
+        .map(|transition| {
+            let route = transition_rooms(student_number, day_timetable, transition).map_or_else(
+                String::new,
+                |(from, to)| shortest_path(shortest_paths, from, to)
+            );
+            (transition, route)
+        })
+        .collect()
+}
+
+pub fn routes_from_timetable(timetable: &Timetable, shortest_paths: &ShortestPaths) -> Routes {
+    timetable
+        .students()
+        .map(|(student_number, week_timetable)| {
+            (
+                student_number.to_owned(),
+                week_timetable
+                    .days()
+                    .map(|(day, day_timetable)| {
+                        (day.number(), day_routes(student_number, day_timetable, shortest_paths))
+                    })
+                    .collect(),
+            )
+        })
+        .collect()
+}
@@ -0,0 +1,611 @@
+//! Typed student timetables, as written by `timetable_generation`.
+//!
+//! A timetable file maps student numbers to days (`"1"` to `"5"`) to periods (`"1"` to `"10"`) to
+//! rooms, where `G` is the ground floor. Every student has a room in every period of every day.
+//! Files are deserialized as they are read, without holding the whole file or a `serde_json::Value`
+//! of it in memory, and a malformed file is rejected with an error naming the student, day and
+//! period at fault and the position in the file.
+
+use std::{
+    collections::BTreeMap,
+    fmt::{ self, Display },
+    fs::File,
+    io::{ self, BufReader, Read },
+    path::Path,
+};
+
+use serde::{
+    de::{ self, DeserializeSeed, MapAccess, Unexpected, Visitor },
+    ser::SerializeMap,
+    Deserialize,
+    Deserializer,
+    Serialize,
+    Serializer,
+};
+
+pub const DAY_COUNT: usize = 5;
+pub const PERIOD_COUNT: usize = 10;
+
+/// A school day, from 1 (Monday) to 5 (Friday).
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct Day(u32);
+
+impl Day {
+    pub fn new(number: u32) -> Option<Self> {
+        (1..=DAY_COUNT as u32).contains(&number).then_some(Self(number))
+    }
+
+    pub fn all() -> impl Iterator<Item = Self> {
+        (1..=DAY_COUNT as u32).map(Self)
+    }
+
+    pub fn number(self) -> u32 {
+        self.0
+    }
+
+    /// English name of the day, such as `Monday`.
+    pub fn name(self) -> &'static str {
+        ["Monday", "Tuesday", "Wednesday", "Thursday", "Friday"][self.index()]
+    }
+
+    fn index(self) -> usize {
+        (self.0 - 1) as usize
+    }
+
+    /// Parses a day key of a timetable file, e.g. `"1"`.
+    fn from_key(key: &str) -> Option<Self> {
+        key.parse().ok().and_then(Self::new)
+    }
+}
+
+impl Display for Day {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// A lesson period of a day, from 1 to 10. Periods 1 to 6 are before lunch.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct Period(usize);
+
+impl Period {
+    pub fn new(number: usize) -> Option<Self> {
+        (1..=PERIOD_COUNT).contains(&number).then_some(Self(number))
+    }
+
+    pub fn all() -> impl Iterator<Item = Self> {
+        (1..=PERIOD_COUNT).map(Self)
+    }
+
+    pub fn number(self) -> usize {
+        self.0
+    }
+
+    fn index(self) -> usize {
+        self.0 - 1
+    }
+
+    fn from_key(key: &str) -> Option<Self> {
+        key.parse().ok().and_then(Self::new)
+    }
+}
+
+impl Display for Period {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Rooms of one student in every period of a day.
+#[derive(Clone, Default, PartialEq, Eq, Debug)]
+pub struct DayTimetable([String; PERIOD_COUNT]);
+
+impl DayTimetable {
+    pub fn room(&self, period: Period) -> &str {
+        &self.0[period.index()]
+    }
+
+    pub fn set_room(&mut self, period: Period, room: String) {
+        self.0[period.index()] = room;
+    }
+
+    pub fn rooms(&self) -> impl Iterator<Item = (Period, &str)> {
+        Period::all().zip(self.0.iter().map(String::as_str))
+    }
+}
+
+/// Rooms of one student in every period of the week.
+#[derive(Clone, Default, PartialEq, Eq, Debug)]
+pub struct WeekTimetable([DayTimetable; DAY_COUNT]);
+
+impl WeekTimetable {
+    pub fn day(&self, day: Day) -> &DayTimetable {
+        &self.0[day.index()]
+    }
+
+    pub fn day_mut(&mut self, day: Day) -> &mut DayTimetable {
+        &mut self.0[day.index()]
+    }
+
+    pub fn room(&self, day: Day, period: Period) -> &str {
+        self.day(day).room(period)
+    }
+
+    pub fn days(&self) -> impl Iterator<Item = (Day, &DayTimetable)> {
+        Day::all().zip(self.0.iter())
+    }
+}
+
+/// Timetables of all students, ordered by student number.
+#[derive(Clone, Default, PartialEq, Eq, Debug)]
+pub struct Timetable {
+    students: BTreeMap<String, WeekTimetable>,
+}
+
+#[derive(Debug)]
+pub enum TimetableError {
+    Io(io::Error),
+    /// The file is not valid JSON or not a timetable. The message names the student, day and
+    /// period at fault where there is one.
+    Format(serde_json::Error),
+    UnknownRoom {
+        student_number: String,
+        day: Day,
+        period: Period,
+        room: String,
+    },
+}
+
+impl Display for TimetableError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TimetableError::Io(error) => write!(f, "Failed to read timetable file: {}", error),
+            TimetableError::Format(error) if error.is_data() => {
+                write!(f, "Invalid timetable: {}", error)
+            }
+            TimetableError::Format(error) => {
+                write!(f, "Invalid JSON format in timetable file: {}", error)
+            }
+            TimetableError::UnknownRoom { student_number, day, period, room } =>
+                write!(
+                    f,
+                    "Student {} has an invalid classroom on day {} period {}: {}",
+                    student_number,
+                    day,
+                    period,
+                    room
+                ),
+        }
+    }
+}
+
+impl std::error::Error for TimetableError {}
+
+impl From<io::Error> for TimetableError {
+    fn from(error: io::Error) -> Self {
+        TimetableError::Io(error)
+    }
+}
+
+impl From<serde_json::Error> for TimetableError {
+    fn from(error: serde_json::Error) -> Self {
+        if error.is_io() {
+            TimetableError::Io(error.into())
+        } else {
+            TimetableError::Format(error)
+        }
+    }
+}
+
+/// Reports the fraction of `total` bytes read so far.
+struct ProgressReader<R, F> {
+    inner: R,
+    read: u64,
+    total: u64,
+    progress: F,
+}
+
+impl<R: Read, F: FnMut(f32)> Read for ProgressReader<R, F> {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buffer)?;
+        self.read += read as u64;
+        (self.progress)((self.read as f32) / (self.total.max(1) as f32));
+        Ok(read)
+    }
+}
+
+impl Timetable {
+    pub fn from_reader(reader: impl Read) -> Result<Self, TimetableError> {
+        Ok(serde_json::from_reader(BufReader::new(reader))?)
+    }
+
+    /// Reads a timetable file, calling `progress` with the fraction of the file read so far.
+    pub fn load(path: &Path, progress: impl FnMut(f32)) -> Result<Self, TimetableError> {
+        let file = File::open(path)?;
+        let total = file.metadata()?.len();
+        Self::from_reader(ProgressReader { inner: file, read: 0, total, progress })
+    }
+
+    pub fn student(&self, student_number: &str) -> Option<&WeekTimetable> {
+        self.students.get(student_number)
+    }
+
+    pub fn student_mut(&mut self, student_number: &str) -> Option<&mut WeekTimetable> {
+        self.students.get_mut(student_number)
+    }
+
+    pub fn students(&self) -> impl Iterator<Item = (&str, &WeekTimetable)> {
+        self.students.iter().map(|(student_number, week)| (student_number.as_str(), week))
+    }
+
+    pub fn student_numbers(&self) -> impl Iterator<Item = &str> {
+        self.students.keys().map(String::as_str)
+    }
+
+    pub fn student_count(&self) -> usize {
+        self.students.len()
+    }
+
+    pub fn session_count(&self) -> usize {
+        self.students.len() * DAY_COUNT * PERIOD_COUNT
+    }
+
+    pub fn room(&self, student_number: &str, day: Day, period: Period) -> Option<&str> {
+        self.student(student_number).map(|week| week.room(day, period))
+    }
+
+    /// Checks that every room is either the ground floor `G` or accepted by `is_room`.
+    pub fn check_rooms(&self, is_room: impl Fn(&str) -> bool) -> Result<(), TimetableError> {
+        for (student_number, week) in self.students() {
+            for (day, day_timetable) in week.days() {
+                for (period, room) in day_timetable.rooms() {
+                    if room != "G" && !is_room(room) {
+                        return Err(TimetableError::UnknownRoom {
+                            student_number: student_number.to_owned(),
+                            day,
+                            period,
+                            room: room.to_owned(),
+                        });
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Serialize for DayTimetable {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(PERIOD_COUNT))?;
+        for (period, room) in self.rooms() {
+            map.serialize_entry(&period.to_string(), room)?;
+        }
+        map.end()
+    }
+}
+
+impl Serialize for WeekTimetable {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(DAY_COUNT))?;
+        for (day, day_timetable) in self.days() {
+            map.serialize_entry(&day.to_string(), day_timetable)?;
+        }
+        map.end()
+    }
+}
+
+impl Serialize for Timetable {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.students.serialize(serializer)
+    }
+}
+
+fn missing_list<T>(entries: &[Option<T>]) -> String {
+    entries
+        .iter()
+        .enumerate()
+        .filter(|(_, entry)| entry.is_none())
+        .map(|(index, _)| (index + 1).to_string())
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+struct TimetableVisitor;
+
+impl<'de> Visitor<'de> for TimetableVisitor {
+    type Value = Timetable;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "a map from student numbers to timetables")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Timetable, A::Error> {
+        let mut students = BTreeMap::new();
+        while let Some(student_number) = map.next_key::<String>()? {
+            if
+                !student_number.chars().all(|c| c.is_ascii_digit()) ||
+                !(4..=5).contains(&student_number.len())
+            {
+                return Err(
+                    de::Error::custom(format!("Invalid student number: \"{}\"", student_number))
+                );
+            }
+            let week = map.next_value_seed(WeekSeed { student_number: &student_number })?;
+            if students.insert(student_number.clone(), week).is_some() {
+                return Err(
+                    de::Error::custom(format!("Student {} appears more than once", student_number))
+                );
+            }
+        }
+        Ok(Timetable { students })
+    }
+}
+
+impl<'de> Deserialize<'de> for Timetable {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_map(TimetableVisitor)
+    }
+}
+
+struct WeekSeed<'a> {
+    student_number: &'a str,
+}
+
+impl<'de> DeserializeSeed<'de> for WeekSeed<'_> {
+    type Value = WeekTimetable;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de> Visitor<'de> for WeekSeed<'_> {
+    type Value = WeekTimetable;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "a map from days to the timetable of student {}", self.student_number)
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<WeekTimetable, A::Error> {
+        let mut days: [Option<DayTimetable>; DAY_COUNT] = Default::default();
+        while let Some(key) = map.next_key::<String>()? {
+            let Some(day) = Day::from_key(&key) else {
+                return Err(
+                    de::Error::custom(
+                        format!(
+                            "Student {} has an invalid day of week: \"{}\"",
+                            self.student_number,
+                            key
+                        )
+                    )
+                );
+            };
+            let day_timetable = map.next_value_seed(DaySeed {
+                student_number: self.student_number,
+                day,
+            })?;
+            if days[day.index()].replace(day_timetable).is_some() {
+                return Err(
+                    de::Error::custom(
+                        format!("Student {} has day {} more than once", self.student_number, day)
+                    )
+                );
+            }
+        }
+        if days.iter().any(Option::is_none) {
+            return Err(
+                de::Error::custom(
+                    format!(
+                        "Student {} has an incomplete timetable: missing day {}",
+                        self.student_number,
+                        missing_list(&days)
+                    )
+                )
+            );
+        }
+        Ok(WeekTimetable(days.map(Option::unwrap_or_default)))
+    }
+}
+
+struct DaySeed<'a> {
+    student_number: &'a str,
+    day: Day,
+}
+
+impl<'de> DeserializeSeed<'de> for DaySeed<'_> {
+    type Value = DayTimetable;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de> Visitor<'de> for DaySeed<'_> {
+    type Value = DayTimetable;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "a map from periods to rooms for student {} on day {}",
+            self.student_number,
+            self.day
+        )
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<DayTimetable, A::Error> {
+        let mut periods: [Option<String>; PERIOD_COUNT] = Default::default();
+        while let Some(key) = map.next_key::<String>()? {
+            let Some(period) = Period::from_key(&key) else {
+                return Err(
+                    de::Error::custom(
+                        format!(
+                            "Student {} has an invalid period on day {}: \"{}\"",
+                            self.student_number,
+                            self.day,
+                            key
+                        )
+                    )
+                );
+            };
+            let room = map.next_value_seed(RoomSeed {
+                student_number: self.student_number,
+                day: self.day,
+                period,
+            })?;
+            if periods[period.index()].replace(room).is_some() {
+                return Err(
+                    de::Error::custom(
+                        format!(
+                            "Student {} has period {} on day {} more than once",
+                            self.student_number,
+                            period,
+                            self.day
+                        )
+                    )
+                );
+            }
+        }
+        if periods.iter().any(Option::is_none) {
+            return Err(
+                de::Error::custom(
+                    format!(
+                        "Student {} has an incomplete timetable on day {}: missing periods {}",
+                        self.student_number,
+                        self.day,
+                        missing_list(&periods)
+                    )
+                )
+            );
+        }
+        Ok(DayTimetable(periods.map(Option::unwrap_or_default)))
+    }
+}
+
+struct RoomSeed<'a> {
+    student_number: &'a str,
+    day: Day,
+    period: Period,
+}
+
+impl<'de> DeserializeSeed<'de> for RoomSeed<'_> {
+    type Value = String;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_str(self)
+    }
+}
+
+impl<'de> Visitor<'de> for RoomSeed<'_> {
+    type Value = String;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "the room of student {} on day {} period {}",
+            self.student_number,
+            self.day,
+            self.period
+        )
+    }
+
+    fn visit_str<E: de::Error>(self, room: &str) -> Result<String, E> {
+        if room.is_empty() || room.contains(' ') {
+            return Err(E::invalid_value(Unexpected::Str(room), &self));
+        }
+        Ok(room.to_owned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A timetable of one student who is in `A101` in every period, with `edit` applied to the
+    /// map of that student's week.
+    fn timetable_json(edit: impl FnOnce(&mut serde_json::Value)) -> String {
+        let day: serde_json::Map<String, serde_json::Value> = Period::all()
+            .map(|period| (period.to_string(), "A101".into()))
+            .collect();
+        let mut week = serde_json::Value::Object(
+            Day::all()
+                .map(|day_of_week| (day_of_week.to_string(), day.clone().into()))
+                .collect()
+        );
+        edit(&mut week);
+        serde_json::json!({ "21001": week }).to_string()
+    }
+
+    fn parse(json: &str) -> Result<Timetable, TimetableError> {
+        Timetable::from_reader(json.as_bytes())
+    }
+
+    fn format_error(json: &str) -> String {
+        match parse(json) {
+            Err(error @ TimetableError::Format(_)) => error.to_string(),
+            Err(error) => panic!("unexpected error: {}", error),
+            Ok(_) => panic!("accepted {}", json),
+        }
+    }
+
+    #[test]
+    fn parses_and_writes_timetables() {
+        let timetable = parse(&timetable_json(|_| ())).unwrap();
+        assert_eq!(timetable.student_count(), 1);
+        let room = timetable.room("21001", Day::new(3).unwrap(), Period::new(10).unwrap());
+        assert_eq!(room, Some("A101"));
+        let written = serde_json::to_string(&timetable).unwrap();
+        assert_eq!(parse(&written).unwrap(), timetable);
+    }
+
+    #[test]
+    fn rejects_truncated_timetables() {
+        let json = timetable_json(|_| ());
+        for length in 0..json.len() {
+            assert!(parse(&json[..length]).is_err(), "length {}", length);
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_timetables() {
+        let error = format_error(&timetable_json(|_| ()).replace("21001", "21a01"));
+        assert!(error.contains("Invalid student number"), "{}", error);
+
+        let error = format_error(&timetable_json(|week| {
+            week["6"] = week["1"].clone();
+        }));
+        assert!(error.contains("invalid day of week: \"6\""), "{}", error);
+
+        let error = format_error(&timetable_json(|week| {
+            week["2"]["11"] = "A101".into();
+        }));
+        assert!(error.contains("invalid period on day 2: \"11\""), "{}", error);
+
+        let error = format_error(&timetable_json(|week| {
+            week["4"].as_object_mut().unwrap().remove("7");
+        }));
+        assert!(error.contains("on day 4: missing periods 7"), "{}", error);
+
+        let error = format_error(&timetable_json(|week| {
+            week.as_object_mut().unwrap().remove("5");
+        }));
+        assert!(error.contains("missing day 5"), "{}", error);
+
+        let error = format_error(&timetable_json(|week| {
+            week["1"]["3"] = "A 101".into();
+        }));
+        assert!(error.contains("student 21001 on day 1 period 3"), "{}", error);
+
+        let error = format_error(&timetable_json(|week| {
+            week["1"]["3"] = 101.into();
+        }));
+        assert!(error.contains("student 21001 on day 1 period 3"), "{}", error);
+
+        let duplicate = timetable_json(|_| ());
+        let duplicate = format!("{},{}", &duplicate[..duplicate.len() - 1], &duplicate[1..]);
+        let error = format_error(&duplicate);
+        assert!(error.contains("appears more than once"), "{}", error);
+
+        assert!(format_error("[]").contains("Invalid timetable"));
+        assert!(format_error("{ \"21001\": ").contains("Invalid JSON format"));
+    }
+}